@@ -1,29 +1,54 @@
-use borsh::BorshDeserialize;
 use clap::{
-    crate_description, crate_name, crate_version, value_t, App, AppSettings, Arg, SubCommand,
+    crate_description, crate_name, crate_version, value_t, App, AppSettings, Arg, ArgMatches,
+    SubCommand,
 };
+use num_traits::FromPrimitive;
 use simple_token_pool::{
-    find_authority_bump_seed,
-    instruction::{initialize, swap},
-    state::PoolData,
+    amm::ConstantProduct,
+    curve::CurveType,
+    error::PoolError,
+    find_authority_bump_seed, find_bank_address, find_deposit_record_address, find_pool_address,
+    find_pool_mint_address, find_reserve_address,
+    instruction::{
+        accept_manager, add_liquidity, close_deposit_record, close_pool, deposit_rewards,
+        initialize, initialize_reserve, migrate, pause, propose_manager, redeem, refresh,
+        remove_liquidity, set_apr, set_deposit_limit, set_oracle, set_permit_key, set_sale,
+        set_swap_limits, swap_exact_out, swap_reserves, swap_with_minimum, swap_with_permit,
+        swap_with_proof, unpause, update_allowlist_root, withdraw_proceeds, withdraw_rewards,
+        DepositRecordKeys,
+    },
+    math::{self, Rounding},
+    merkle::MerkleTree,
+    permit::{ed25519_instruction, Permit},
+    state::{DepositPeriod, DepositRecord, ExchangeRate, PoolData, PoolDataV1, PriceData},
 };
 use solana_clap_utils::{
     fee_payer::fee_payer_arg,
     input_parsers::{pubkey_of, value_of},
-    input_validators::{is_amount, is_pubkey, is_url_or_moniker, is_valid_signer},
+    input_validators::{is_amount, is_parsable, is_pubkey, is_url_or_moniker, is_valid_signer},
     keypair::signer_from_path,
 };
-use solana_client::rpc_client::RpcClient;
-use solana_program::{borsh::get_packed_len, program_pack::Pack, pubkey::Pubkey};
+use solana_client::{
+    client_error::{ClientError, ClientErrorKind},
+    rpc_client::RpcClient,
+    rpc_request::{RpcError, RpcResponseErrorData},
+};
+use solana_program::{program_pack::Pack, pubkey::Pubkey};
 use solana_sdk::{
     commitment_config::CommitmentConfig,
+    hash::Hash,
+    instruction::InstructionError,
     native_token::*,
-    signature::{Keypair, Signer},
+    signature::{Keypair, Signature, Signer},
     system_instruction,
-    transaction::Transaction,
+    transaction::{Transaction, TransactionError},
 };
 use spl_token as token;
-use std::{env, process::exit};
+use std::{
+    env, fs,
+    process::exit,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 #[allow(dead_code)]
 struct Config {
@@ -43,6 +68,25 @@ macro_rules! unique_signers {
     };
 }
 
+/// Decodes the pool error of the failed transaction
+fn get_pool_error(err: &ClientError) -> Option<PoolError> {
+    let tx_error = match err.kind() {
+        ClientErrorKind::TransactionError(tx_error) => Some(tx_error),
+        ClientErrorKind::RpcError(RpcError::RpcResponseError {
+            data: RpcResponseErrorData::SendTransactionPreflightFailure(result),
+            ..
+        }) => result.err.as_ref(),
+        _ => None,
+    }?;
+
+    match tx_error {
+        TransactionError::InstructionError(_, InstructionError::Custom(code)) => {
+            PoolError::from_u32(*code)
+        }
+        _ => None,
+    }
+}
+
 fn check_fee_payer_balance(config: &Config, required_balance: u64) -> Result<(), Error> {
     let balance = config.rpc_client.get_balance(&config.fee_payer.pubkey())?;
     if balance < required_balance {
@@ -58,19 +102,236 @@ fn check_fee_payer_balance(config: &Config, required_balance: u64) -> Result<(),
     }
 }
 
-fn command_create_pool(config: &Config, bank_mint_pubkey: &Pubkey) -> CommandResult {
-    let pool = Keypair::new();
-    println!("Creating pool {}", pool.pubkey());
+/// Parses a rate in the `NUMERATOR/DENOMINATOR` or `NUMERATOR` form
+fn parse_rate(rate: &str) -> Result<(u64, u64), String> {
+    let mut parts = rate.splitn(2, '/');
+    let numerator = parts
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .parse::<u64>()
+        .map_err(|e| format!("Invalid rate numerator: {}", e))?;
+    let denominator = match parts.next() {
+        Some(denominator) => denominator
+            .trim()
+            .parse::<u64>()
+            .map_err(|e| format!("Invalid rate denominator: {}", e))?,
+        None => 1,
+    };
+
+    if numerator == 0 || denominator == 0 {
+        return Err("Rate numerator and denominator must be non-zero".to_string());
+    }
+
+    Ok((numerator, denominator))
+}
+
+fn is_rate(rate: String) -> Result<(), String> {
+    parse_rate(&rate).map(|_| ())
+}
+
+fn parse_price(price: &str) -> Result<u64, String> {
+    let price = price
+        .trim()
+        .parse::<f64>()
+        .map_err(|e| format!("Invalid price: {}", e))?;
+    if price.is_nan() || price < 0.0 || price * CurveType::PRICE_SCALE as f64 > u64::MAX as f64 {
+        return Err("Price out of range".to_string());
+    }
+    Ok((price * CurveType::PRICE_SCALE as f64).round() as u64)
+}
+
+/// Parses `constant`, `linear:BASE:SLOPE`, `exponential:BASE:GROWTH_BPS:STEP`
+/// or `step:BASE:STEP:INCREMENT`
+fn parse_curve(curve: &str) -> Result<CurveType, String> {
+    let parts: Vec<&str> = curve.split(':').collect();
+    let parse_amount = |amount: &str| {
+        amount
+            .trim()
+            .parse::<u64>()
+            .map_err(|e| format!("Invalid amount: {}", e))
+    };
+
+    let curve = match parts.as_slice() {
+        ["constant"] => CurveType::Constant,
+        ["linear", base_price, slope] => CurveType::Linear {
+            base_price: parse_price(base_price)?,
+            slope: parse_price(slope)?,
+        },
+        ["exponential", base_price, growth_bps, step_size] => CurveType::Exponential {
+            base_price: parse_price(base_price)?,
+            growth_bps: parse_amount(growth_bps)?,
+            step_size: parse_amount(step_size)?,
+        },
+        ["step", base_price, step_size, increment] => CurveType::Step {
+            base_price: parse_price(base_price)?,
+            step_size: parse_amount(step_size)?,
+            increment: parse_price(increment)?,
+        },
+        _ => return Err(format!("Unknown curve: {}", curve)),
+    };
+
+    if !curve.is_valid() {
+        return Err("Curve must have a base price and a step size".to_string());
+    }
+
+    Ok(curve)
+}
+
+fn is_curve(curve: String) -> Result<(), String> {
+    parse_curve(&curve).map(|_| ())
+}
+
+/// Parses a permit printed by `sign-permit`, `MAX_AMOUNT:EXPIRY_SLOT:NONCE:SIGNATURE`
+fn parse_permit(permit: &str) -> Result<(u64, u64, u64, Signature), String> {
+    let parts: Vec<&str> = permit.split(':').collect();
+    let parse_number = |number: &str| {
+        number
+            .trim()
+            .parse::<u64>()
+            .map_err(|e| format!("Invalid permit number: {}", e))
+    };
+
+    match parts.as_slice() {
+        [max_amount, expiry_slot, nonce, signature] => Ok((
+            parse_number(max_amount)?,
+            parse_number(expiry_slot)?,
+            parse_number(nonce)?,
+            signature
+                .trim()
+                .parse::<Signature>()
+                .map_err(|e| format!("Invalid permit signature: {}", e))?,
+        )),
+        _ => Err(format!("Unknown permit: {}", permit)),
+    }
+}
+
+fn is_permit(permit: String) -> Result<(), String> {
+    parse_permit(&permit).map(|_| ())
+}
+
+fn is_fee_bps(fee_bps: String) -> Result<(), String> {
+    let fee_bps = fee_bps
+        .parse::<u64>()
+        .map_err(|e| format!("Invalid basis points: {}", e))?;
+    if fee_bps >= PoolData::BPS_DENOMINATOR {
+        return Err(format!(
+            "Fee must be below {} basis points",
+            PoolData::BPS_DENOMINATOR
+        ));
+    }
+    Ok(())
+}
+
+fn is_bps(bps: String) -> Result<(), String> {
+    let bps = bps
+        .parse::<u64>()
+        .map_err(|e| format!("Invalid basis points: {}", e))?;
+    if bps > PoolData::BPS_DENOMINATOR {
+        return Err(format!(
+            "Basis points must not exceed {}",
+            PoolData::BPS_DENOMINATOR
+        ));
+    }
+    Ok(())
+}
+
+/// Reads the allowlist addresses from the first column of a CSV file, skipping a header
+fn read_allowlist(path: &str) -> Result<Vec<Pubkey>, Error> {
+    let csv = fs::read_to_string(path).map_err(|e| format!("Can't read {}: {}", path, e))?;
+
+    let mut users = vec![];
+    for (line_number, line) in csv.lines().enumerate() {
+        let address = line.split(',').next().unwrap_or_default().trim();
+        if address.is_empty() {
+            continue;
+        }
+
+        match address.parse::<Pubkey>() {
+            Ok(user) => users.push(user),
+            Err(_) if line_number == 0 => continue,
+            Err(_) => {
+                return Err(
+                    format!("Invalid address {} on line {}", address, line_number + 1).into(),
+                )
+            }
+        }
+    }
+
+    if users.is_empty() {
+        return Err(format!("No addresses in {}", path).into());
+    }
+
+    Ok(users)
+}
+
+/// Builds the allowlist tree of the CSV file, which must match the pool root
+fn allowlist_proof(
+    path: &str,
+    pool_data: &PoolData,
+    user: &Pubkey,
+) -> Result<Vec<[u8; 32]>, Error> {
+    let tree = MerkleTree::new(&read_allowlist(path)?);
+    if tree.root() != pool_data.allowlist_root {
+        return Err(format!(
+            "Allowlist root {} does not match the pool root {}",
+            Hash::new_from_array(tree.root()),
+            Hash::new_from_array(pool_data.allowlist_root)
+        )
+        .into());
+    }
+
+    tree.proof(user)
+        .ok_or_else(|| format!("{} is not on the allowlist", user).into())
+}
+
+/// Returns the pool given by address or by bank mint and index
+fn pool_of(matches: &ArgMatches<'_>) -> Pubkey {
+    match pubkey_of(matches, "bank_mint") {
+        Some(bank_mint) => {
+            let index = value_of::<u64>(matches, "index").unwrap();
+            find_pool_address(&simple_token_pool::id(), &bank_mint, index).0
+        }
+        None => pubkey_of(matches, "pool").unwrap(),
+    }
+}
+
+fn command_create_pool(
+    config: &Config,
+    bank_mint_pubkey: &Pubkey,
+    index: u64,
+    rate_numerator: u64,
+    rate_denominator: u64,
+    fee_bps: u16,
+    fee_receiver_pubkey: Option<Pubkey>,
+    curve: CurveType,
+) -> CommandResult {
+    let (pool, _) = find_pool_address(&simple_token_pool::id(), bank_mint_pubkey, index);
+    println!("Creating pool {} with index {}", pool, index);
+
+    let (pool_mint, _) = find_pool_mint_address(&simple_token_pool::id(), &pool);
+    println!("Creating pool mint {}", pool_mint);
 
-    let pool_mint = Keypair::new();
-    println!("Creating pool mint {}", pool_mint.pubkey());
+    let (bank, _) = find_bank_address(&simple_token_pool::id(), &pool);
+    println!("Creating bank account {}", bank);
+    println!("Manager: {}", config.owner.pubkey());
+    println!("Rate: {}/{}", rate_numerator, rate_denominator);
+    if curve != CurveType::Constant {
+        println!("Curve: {:?}", curve);
+    }
 
-    let bank = Keypair::new();
-    println!("Creating bank account {}", bank.pubkey());
+    // Fee receiver is created for the owner if it's not provided
+    let fee_receiver = match fee_receiver_pubkey {
+        Some(_) => None,
+        None => Some(Keypair::new()),
+    };
+    let fee_receiver_pubkey =
+        fee_receiver_pubkey.unwrap_or_else(|| fee_receiver.as_ref().unwrap().pubkey());
+    println!("Fee: {} bps to {}", fee_bps, fee_receiver_pubkey);
 
     let pool_balance = config
         .rpc_client
-        .get_minimum_balance_for_rent_exemption(get_packed_len::<PoolData>())?;
+        .get_minimum_balance_for_rent_exemption(PoolData::LEN)?;
     let pool_mint_balance = config
         .rpc_client
         .get_minimum_balance_for_rent_exemption(token::state::Mint::LEN)?;
@@ -78,48 +339,52 @@ fn command_create_pool(config: &Config, bank_mint_pubkey: &Pubkey) -> CommandRes
         .rpc_client
         .get_minimum_balance_for_rent_exemption(token::state::Account::LEN)?;
 
-    let total_required_balance = pool_balance + pool_mint_balance + bank_balance;
+    let mut total_required_balance = pool_balance + pool_mint_balance + bank_balance;
 
-    let (authority, _) = find_authority_bump_seed(&simple_token_pool::id(), &pool.pubkey());
+    let (authority, _) = find_authority_bump_seed(&simple_token_pool::id(), &pool);
 
-    let mut tx = Transaction::new_with_payer(
-        &[
-            // Pool account
-            system_instruction::create_account(
-                &config.fee_payer.pubkey(),
-                &pool.pubkey(),
-                pool_balance,
-                get_packed_len::<PoolData>() as u64,
-                &simple_token_pool::id(),
-            ),
-            // Pool mint account
-            system_instruction::create_account(
-                &config.fee_payer.pubkey(),
-                &pool_mint.pubkey(),
-                pool_mint_balance,
-                token::state::Mint::LEN as u64,
-                &token::id(),
-            ),
-            // Account for the bank
+    let mut instructions = vec![];
+
+    if let Some(fee_receiver) = fee_receiver.as_ref() {
+        println!("Creating fee receiver account {}", fee_receiver.pubkey());
+
+        total_required_balance += bank_balance;
+        instructions.extend(vec![
             system_instruction::create_account(
                 &config.fee_payer.pubkey(),
-                &bank.pubkey(),
+                &fee_receiver.pubkey(),
                 bank_balance,
                 token::state::Account::LEN as u64,
                 &token::id(),
             ),
-            // Initialize pool account
-            initialize(
-                &simple_token_pool::id(),
-                &pool.pubkey(),
-                &authority,
+            token::instruction::initialize_account(
+                &token::id(),
+                &fee_receiver.pubkey(),
                 &bank_mint_pubkey,
-                &pool_mint.pubkey(),
-                &bank.pubkey(),
-            ),
-        ],
-        Some(&config.fee_payer.pubkey()),
-    );
+                &config.owner.pubkey(),
+            )?,
+        ]);
+    }
+
+    // Pool, pool mint and bank accounts are created by the program
+    instructions.push(initialize(
+        &simple_token_pool::id(),
+        &pool,
+        &authority,
+        &config.owner.pubkey(),
+        &bank_mint_pubkey,
+        &pool_mint,
+        &bank,
+        &fee_receiver_pubkey,
+        &config.fee_payer.pubkey(),
+        index,
+        rate_numerator,
+        rate_denominator,
+        fee_bps,
+        curve,
+    ));
+
+    let mut tx = Transaction::new_with_payer(&instructions, Some(&config.fee_payer.pubkey()));
 
     let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
     check_fee_payer_balance(
@@ -127,7 +392,10 @@ fn command_create_pool(config: &Config, bank_mint_pubkey: &Pubkey) -> CommandRes
         total_required_balance + fee_calculator.calculate_fee(&tx.message()),
     )?;
 
-    let mut signers = vec![config.fee_payer.as_ref(), &pool, &pool_mint, &bank];
+    let mut signers = vec![config.fee_payer.as_ref(), config.owner.as_ref()];
+    if let Some(fee_receiver) = fee_receiver.as_ref() {
+        signers.push(fee_receiver);
+    }
 
     unique_signers!(signers);
     tx.sign(&signers, recent_blockhash);
@@ -135,7 +403,313 @@ fn command_create_pool(config: &Config, bank_mint_pubkey: &Pubkey) -> CommandRes
     Ok(Some(tx))
 }
 
+/// Reads the exchange rate from the pool mint supply and the bank balance,
+/// with interest accrued up to the current slot
+fn get_exchange_rate(config: &Config, pool_data: &PoolData) -> Result<ExchangeRate, Error> {
+    let pool_supply = config
+        .rpc_client
+        .get_token_supply(&pool_data.pool_mint)?
+        .amount
+        .parse::<u64>()?;
+    let bank_balance = config
+        .rpc_client
+        .get_token_account_balance(&pool_data.bank)?
+        .amount
+        .parse::<u64>()?;
+
+    let mut pool_data = pool_data.clone();
+    pool_data.accrue_interest(bank_balance, config.rpc_client.get_slot()?)?;
+
+    Ok(pool_data.exchange_rate(pool_supply, bank_balance)?)
+}
+
+/// Reads the rate of swaps and redeems, the oracle price if the pool has an oracle
+fn get_swap_rate(config: &Config, pool_data: &PoolData) -> Result<ExchangeRate, Error> {
+    let rate = get_exchange_rate(config, pool_data)?;
+    if pool_data.oracle == Pubkey::default() {
+        return Ok(rate);
+    }
+
+    let oracle = config.rpc_client.get_account(&pool_data.oracle)?;
+    let price = PriceData::try_from_slice_unchecked(&oracle.data)?;
+    let age = config
+        .rpc_client
+        .get_slot()?
+        .saturating_sub(price.last_update_slot);
+    if age > pool_data.oracle_max_age {
+        return Err(format!(
+            "Oracle price is {} slots old, the pool accepts at most {}",
+            age, pool_data.oracle_max_age
+        )
+        .into());
+    }
+
+    Ok(price.exchange_rate(rate.pool_supply, rate.bank_balance)?)
+}
+
+/// Oracle account passed to swaps and redeems, if the pool has one
+fn oracle_of(pool_data: &PoolData) -> Option<&Pubkey> {
+    if pool_data.oracle == Pubkey::default() {
+        None
+    } else {
+        Some(&pool_data.oracle)
+    }
+}
+
+/// Reads the balances of a constant product pool and the decimals of both mints
+fn get_constant_product(
+    config: &Config,
+    pool_data: &PoolData,
+) -> Result<(ConstantProduct, u8, u8), Error> {
+    if !pool_data.is_constant_product() {
+        return Err("Pool has no reserve, initialize it with initialize-reserve".into());
+    }
+
+    let pool_supply = config
+        .rpc_client
+        .get_token_supply(&pool_data.pool_mint)?
+        .amount
+        .parse::<u64>()?;
+    let bank_balance = config
+        .rpc_client
+        .get_token_account_balance(&pool_data.bank)?;
+    let reserve_balance = config
+        .rpc_client
+        .get_token_account_balance(&pool_data.reserve)?;
+
+    Ok((
+        ConstantProduct {
+            pool_supply,
+            bank_balance: bank_balance.amount.parse::<u64>()?,
+            reserve_balance: reserve_balance.amount.parse::<u64>()?,
+        },
+        bank_balance.decimals,
+        reserve_balance.decimals,
+    ))
+}
+
 fn command_swap(
+    config: &Config,
+    pool_pubkey: &Pubkey,
+    sender: &Pubkey,
+    recipient: &Pubkey,
+    amount: f64,
+    exact_out: bool,
+    slippage_bps: u16,
+    allowlist: Option<&str>,
+    permit: Option<&str>,
+) -> CommandResult {
+    let pool = config.rpc_client.get_account(&pool_pubkey)?;
+    let pool_data = PoolData::try_from_slice_unchecked(&pool.data)?;
+
+    // SwapExactOut takes no permit, so the Ed25519 instruction would verify it for nothing
+    if exact_out && (permit.is_some() || pool_data.has_permit_key()) {
+        return Err("Pools with a permit key don't swap with --exact-out".into());
+    }
+    let permit = match permit {
+        Some(permit) => {
+            let (max_amount, expiry_slot, nonce, signature) = parse_permit(permit)?;
+            let permit = Permit {
+                pool: *pool_pubkey,
+                user: config.owner.pubkey(),
+                max_amount,
+                expiry_slot,
+                nonce,
+            };
+            if !signature.verify(pool_data.permit_key.as_ref(), &permit.message()?) {
+                return Err(format!("Permit is not signed by {}", pool_data.permit_key).into());
+            }
+            Some((permit, signature))
+        }
+        None if pool_data.has_permit_key() => {
+            return Err("Pool swaps only with a permit, pass it with --permit".into())
+        }
+        None => None,
+    };
+
+    // SwapExactOut takes no proof, so it would fail or swap past the allowlist
+    if exact_out && (allowlist.is_some() || pool_data.has_allowlist()) {
+        return Err("Allowlisted pools don't swap with --exact-out".into());
+    }
+    let proof = match allowlist {
+        Some(path) => Some(allowlist_proof(path, &pool_data, &config.owner.pubkey())?),
+        None if pool_data.has_allowlist() => {
+            return Err("Pool has an allowlist, pass it with --allowlist".into())
+        }
+        None => None,
+    };
+
+    println!("{:?}", pool_data);
+    println!("Amount: {}", amount);
+
+    // The deposits count for the owner of the sender, and the fee payer funds the
+    // deposit record of the first swap
+    let deposit_record = if pool_data.has_deposit_limit() {
+        let sender_account = config
+            .rpc_client
+            .get_token_account(&sender)?
+            .ok_or_else(|| format!("Could not find token account {}", sender))?;
+        Some(DepositRecordKeys {
+            user: sender_account.owner.parse()?,
+            payer: config.fee_payer.pubkey(),
+        })
+    } else {
+        None
+    };
+
+    let (pool_authority, _) = find_authority_bump_seed(&simple_token_pool::id(), &pool_pubkey);
+    let bank_mint_account = config
+        .rpc_client
+        .get_token_account(&pool_data.bank)?
+        .ok_or_else(|| format!("Could not find token account {}", &pool_data.bank))?;
+    // The pool mint has the bank mint decimals
+    let decimals = bank_mint_account.token_amount.decimals;
+    let amount = token::ui_amount_to_amount(amount, decimals);
+
+    // Quote with the current pool state
+    let rate = get_swap_rate(config, &pool_data)?;
+    if let Some(remaining_supply) = pool_data.remaining_supply(rate.pool_supply) {
+        println!(
+            "Remaining capacity: {} pool tokens",
+            token::amount_to_ui_amount(remaining_supply, decimals)
+        );
+    }
+    if pool_data.has_sale() {
+        println!(
+            "Sale runs from {} to {}",
+            pool_data.sale_start, pool_data.sale_end
+        );
+    }
+    let ix = if exact_out {
+        let amounts = pool_data.calc_swap_exact_out(&rate, amount)?;
+        let maximum_amount_in = math::mul_div(
+            amounts.amount_in,
+            PoolData::BPS_DENOMINATOR + slippage_bps as u64,
+            PoolData::BPS_DENOMINATOR,
+            Rounding::Up,
+        )?;
+
+        println!(
+            "Expected amount in: {}",
+            token::amount_to_ui_amount(amounts.amount_in, decimals)
+        );
+        println!(
+            "Maximum amount in: {} ({} bps slippage)",
+            token::amount_to_ui_amount(maximum_amount_in, decimals),
+            slippage_bps
+        );
+
+        swap_exact_out(
+            &simple_token_pool::id(),
+            &pool_pubkey,
+            &pool_authority,
+            &config.owner.pubkey(),
+            &pool_data.pool_mint,
+            &pool_data.bank,
+            &pool_data.fee_receiver,
+            &sender,
+            &recipient,
+            oracle_of(&pool_data),
+            deposit_record.as_ref(),
+            amount,
+            maximum_amount_in,
+        )
+    } else {
+        let amounts = pool_data.calc_swap_exact_in(&rate, amount)?;
+        let minimum_amount_out = math::mul_div(
+            amounts.amount_out,
+            PoolData::BPS_DENOMINATOR - slippage_bps as u64,
+            PoolData::BPS_DENOMINATOR,
+            Rounding::Down,
+        )?;
+
+        println!(
+            "Expected amount out: {}",
+            token::amount_to_ui_amount(amounts.amount_out, decimals)
+        );
+        println!(
+            "Minimum amount out: {} ({} bps slippage)",
+            token::amount_to_ui_amount(minimum_amount_out, decimals),
+            slippage_bps
+        );
+
+        match (permit, proof) {
+            (Some((permit, _)), _) => swap_with_permit(
+                &simple_token_pool::id(),
+                &pool_pubkey,
+                &pool_authority,
+                &config.owner.pubkey(),
+                &pool_data.pool_mint,
+                &pool_data.bank,
+                &pool_data.fee_receiver,
+                &sender,
+                &recipient,
+                oracle_of(&pool_data),
+                &config.fee_payer.pubkey(),
+                deposit_record.as_ref(),
+                amount,
+                minimum_amount_out,
+                &permit,
+            ),
+            (None, Some(proof)) => swap_with_proof(
+                &simple_token_pool::id(),
+                &pool_pubkey,
+                &pool_authority,
+                &config.owner.pubkey(),
+                &pool_data.pool_mint,
+                &pool_data.bank,
+                &pool_data.fee_receiver,
+                &sender,
+                &recipient,
+                oracle_of(&pool_data),
+                deposit_record.as_ref(),
+                amount,
+                minimum_amount_out,
+                proof,
+            ),
+            (None, None) => swap_with_minimum(
+                &simple_token_pool::id(),
+                &pool_pubkey,
+                &pool_authority,
+                &config.owner.pubkey(),
+                &pool_data.pool_mint,
+                &pool_data.bank,
+                &pool_data.fee_receiver,
+                &sender,
+                &recipient,
+                oracle_of(&pool_data),
+                deposit_record.as_ref(),
+                amount,
+                minimum_amount_out,
+            ),
+        }
+    };
+
+    // The Ed25519 program verifies the permit before the swap reads it
+    let mut instructions = vec![];
+    if let Some((permit, signature)) = permit {
+        instructions.push(ed25519_instruction(
+            &pool_data.permit_key,
+            signature.as_ref(),
+            &permit.message()?,
+        ));
+    }
+    instructions.push(ix);
+
+    let mut tx = Transaction::new_with_payer(&instructions, Some(&config.fee_payer.pubkey()));
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(config, fee_calculator.calculate_fee(&tx.message()))?;
+
+    let mut signers = vec![config.fee_payer.as_ref(), config.owner.as_ref()];
+
+    unique_signers!(signers);
+    tx.sign(&signers, recent_blockhash);
+
+    Ok(Some(tx))
+}
+
+fn command_redeem(
     config: &Config,
     pool_pubkey: &Pubkey,
     sender: &Pubkey,
@@ -143,7 +717,7 @@ fn command_swap(
     amount_in: f64,
 ) -> CommandResult {
     let pool = config.rpc_client.get_account(&pool_pubkey)?;
-    let pool_data = PoolData::try_from_slice(&pool.data)?;
+    let pool_data = PoolData::try_from_slice_unchecked(&pool.data)?;
 
     println!("{:?}", pool_data);
     println!("Amount: {}", amount_in);
@@ -153,9 +727,18 @@ fn command_swap(
         .rpc_client
         .get_token_account(&pool_data.bank)?
         .ok_or_else(|| format!("Could not find token account {}", &pool_data.bank))?;
+    // Pool mint has the same decimals as the bank mint
+    let decimals = bank_mint_account.token_amount.decimals;
+    let amount_in = token::ui_amount_to_amount(amount_in, decimals);
+
+    let rate = get_swap_rate(config, &pool_data)?;
+    println!(
+        "Expected amount out: {}",
+        token::amount_to_ui_amount(rate.calc_bank_amount(amount_in)?, decimals)
+    );
 
     let mut tx = Transaction::new_with_payer(
-        &[swap(
+        &[redeem(
             &simple_token_pool::id(),
             &pool_pubkey,
             &pool_authority,
@@ -164,7 +747,8 @@ fn command_swap(
             &pool_data.bank,
             &sender,
             &recipient,
-            token::ui_amount_to_amount(amount_in, bank_mint_account.token_amount.decimals),
+            oracle_of(&pool_data),
+            amount_in,
         )],
         Some(&config.fee_payer.pubkey()),
     );
@@ -180,112 +764,2148 @@ fn command_swap(
     Ok(Some(tx))
 }
 
-fn main() {
-    let matches = App::new(crate_name!())
-        .about(crate_description!())
-        .version(crate_version!())
-        .setting(AppSettings::SubcommandRequiredElseHelp)
-        .arg({
-            let arg = Arg::with_name("config_file")
-                .short("C")
-                .long("config")
-                .value_name("PATH")
-                .takes_value(true)
-                .global(true)
-                .help("Configuration file to use");
-            if let Some(ref config_file) = *solana_cli_config::CONFIG_FILE {
-                arg.default_value(&config_file)
-            } else {
-                arg
-            }
-        })
-        .arg(
-            Arg::with_name("verbose")
-                .short("v")
-                .long("verbose")
-                .takes_value(false)
-                .global(true)
-                .help("Show additional information"),
-        )
-        .arg(
-            Arg::with_name("json_rpc_url")
-                .short("u")
-                .long("url")
-                .value_name("URL_OR_MONIKER")
-                .takes_value(true)
-                .global(true)
-                .validator(is_url_or_moniker)
-                .help(
-                    "URL for Solana's JSON RPC or moniker (or their first letter): \
-                       [mainnet-beta, testnet, devnet, localhost] \
-                    Default from the configuration file.",
-                ),
-        )
-        .arg(
-            Arg::with_name("owner")
-                .long("owner")
-                .value_name("KEYPAIR")
-                .validator(is_valid_signer)
-                .takes_value(true)
-                .global(true)
-                .help(
-                    "Specify the token owner account. \
-                     This may be a keypair file, the ASK keyword. \
-                     Defaults to the client keypair.",
-                ),
-        )
-        .arg(fee_payer_arg().global(true))
-        .subcommand(
-            SubCommand::with_name("create-pool")
-                .about("Create a new pool")
-                .arg(
-                    Arg::with_name("bank_mint")
-                        .validator(is_pubkey)
-                        .value_name("ADDRESS")
-                        .takes_value(true)
-                        .required(true)
-                        .index(1)
+fn command_propose_manager(
+    config: &Config,
+    pool_pubkey: &Pubkey,
+    new_manager: &Pubkey,
+) -> CommandResult {
+    println!("Proposing manager {}", new_manager);
+
+    let mut tx = Transaction::new_with_payer(
+        &[propose_manager(
+            &simple_token_pool::id(),
+            &pool_pubkey,
+            &config.owner.pubkey(),
+            &new_manager,
+        )],
+        Some(&config.fee_payer.pubkey()),
+    );
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(config, fee_calculator.calculate_fee(&tx.message()))?;
+
+    let mut signers = vec![config.fee_payer.as_ref(), config.owner.as_ref()];
+
+    unique_signers!(signers);
+    tx.sign(&signers, recent_blockhash);
+
+    Ok(Some(tx))
+}
+
+fn command_accept_manager(config: &Config, pool_pubkey: &Pubkey) -> CommandResult {
+    println!("Accepting manager {}", config.owner.pubkey());
+
+    let mut tx = Transaction::new_with_payer(
+        &[accept_manager(
+            &simple_token_pool::id(),
+            &pool_pubkey,
+            &config.owner.pubkey(),
+        )],
+        Some(&config.fee_payer.pubkey()),
+    );
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(config, fee_calculator.calculate_fee(&tx.message()))?;
+
+    let mut signers = vec![config.fee_payer.as_ref(), config.owner.as_ref()];
+
+    unique_signers!(signers);
+    tx.sign(&signers, recent_blockhash);
+
+    Ok(Some(tx))
+}
+
+fn command_set_paused(config: &Config, pool_pubkey: &Pubkey, paused: bool) -> CommandResult {
+    let ix = if paused {
+        println!("Pausing pool {}", pool_pubkey);
+        pause(
+            &simple_token_pool::id(),
+            &pool_pubkey,
+            &config.owner.pubkey(),
+        )
+    } else {
+        println!("Unpausing pool {}", pool_pubkey);
+        unpause(
+            &simple_token_pool::id(),
+            &pool_pubkey,
+            &config.owner.pubkey(),
+        )
+    };
+
+    let mut tx = Transaction::new_with_payer(&[ix], Some(&config.fee_payer.pubkey()));
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(config, fee_calculator.calculate_fee(&tx.message()))?;
+
+    let mut signers = vec![config.fee_payer.as_ref(), config.owner.as_ref()];
+
+    unique_signers!(signers);
+    tx.sign(&signers, recent_blockhash);
+
+    Ok(Some(tx))
+}
+
+fn command_migrate(config: &Config, pool_pubkey: &Pubkey) -> CommandResult {
+    let pool = config.rpc_client.get_account(&pool_pubkey)?;
+    let version = *pool
+        .data
+        .first()
+        .ok_or_else(|| format!("Pool {} has no data", pool_pubkey))?;
+
+    if version == PoolData::CURRENT_VERSION {
+        println!("Pool {} is already at version {}", pool_pubkey, version);
+        return Ok(None);
+    }
+    if version != PoolDataV1::VERSION || pool.data.len() != PoolDataV1::LEN {
+        return Err(format!("Pool {} has an unsupported layout", pool_pubkey).into());
+    }
+    let pool_data = PoolDataV1::try_from_slice_unchecked(&pool.data)?;
+
+    // The old account can't grow, the pool moves to a new account
+    let new_pool = Keypair::new();
+    println!(
+        "Migrating pool {} from version {} to {} into {}",
+        pool_pubkey,
+        version,
+        PoolData::CURRENT_VERSION,
+        new_pool.pubkey()
+    );
+
+    let (new_pool_authority, _) =
+        find_authority_bump_seed(&simple_token_pool::id(), &new_pool.pubkey());
+    let pool_balance = config
+        .rpc_client
+        .get_minimum_balance_for_rent_exemption(PoolData::LEN)?;
+
+    let mut tx = Transaction::new_with_payer(
+        &[
+            system_instruction::create_account(
+                &config.fee_payer.pubkey(),
+                &new_pool.pubkey(),
+                pool_balance,
+                PoolData::LEN as u64,
+                &simple_token_pool::id(),
+            ),
+            migrate(
+                &simple_token_pool::id(),
+                &pool_pubkey,
+                &new_pool.pubkey(),
+                &pool_data.authority,
+                &new_pool_authority,
+                &pool_data.pool_mint,
+                &pool_data.bank,
+            ),
+        ],
+        Some(&config.fee_payer.pubkey()),
+    );
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(
+        config,
+        pool_balance + fee_calculator.calculate_fee(&tx.message()),
+    )?;
+
+    let mut signers = vec![config.fee_payer.as_ref()];
+    signers.push(&new_pool);
+
+    unique_signers!(signers);
+    tx.sign(&signers, recent_blockhash);
+
+    Ok(Some(tx))
+}
+
+fn command_close_pool(
+    config: &Config,
+    pool_pubkey: &Pubkey,
+    destination: Option<Pubkey>,
+) -> CommandResult {
+    let pool = config.rpc_client.get_account(&pool_pubkey)?;
+    let pool_data = PoolData::try_from_slice_unchecked(&pool.data)?;
+    let destination = destination.unwrap_or_else(|| config.owner.pubkey());
+
+    let supply = config.rpc_client.get_token_supply(&pool_data.pool_mint)?;
+    let bank_balance = config
+        .rpc_client
+        .get_token_account_balance(&pool_data.bank)?;
+    if supply.amount != "0" || bank_balance.amount != "0" {
+        return Err(format!(
+            "Pool is not empty: pool mint supply {}, bank balance {}",
+            supply.real_number_string_trimmed(),
+            bank_balance.real_number_string_trimmed()
+        )
+        .into());
+    }
+
+    let reserve = if pool_data.is_constant_product() {
+        let reserve_balance = config
+            .rpc_client
+            .get_token_account_balance(&pool_data.reserve)?;
+        if reserve_balance.amount != "0" {
+            return Err(format!(
+                "Pool is not empty: reserve balance {}",
+                reserve_balance.real_number_string_trimmed()
+            )
+            .into());
+        }
+        Some(pool_data.reserve)
+    } else {
+        None
+    };
+
+    println!("Closing pool {}", pool_pubkey);
+    println!("Closing bank account {}", pool_data.bank);
+    if let Some(reserve) = reserve {
+        println!("Closing reserve account {}", reserve);
+    }
+    println!("Removing mint authority of {}", pool_data.pool_mint);
+    println!("Rent goes to {}", destination);
+
+    let (pool_authority, _) = find_authority_bump_seed(&simple_token_pool::id(), &pool_pubkey);
+
+    let mut tx = Transaction::new_with_payer(
+        &[close_pool(
+            &simple_token_pool::id(),
+            &pool_pubkey,
+            &pool_authority,
+            &config.owner.pubkey(),
+            &pool_data.pool_mint,
+            &pool_data.bank,
+            &destination,
+            reserve.as_ref(),
+        )],
+        Some(&config.fee_payer.pubkey()),
+    );
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(config, fee_calculator.calculate_fee(&tx.message()))?;
+
+    let mut signers = vec![config.fee_payer.as_ref(), config.owner.as_ref()];
+
+    unique_signers!(signers);
+    tx.sign(&signers, recent_blockhash);
+
+    Ok(Some(tx))
+}
+
+fn command_deposit_rewards(
+    config: &Config,
+    pool_pubkey: &Pubkey,
+    source: &Pubkey,
+    amount: f64,
+) -> CommandResult {
+    let pool = config.rpc_client.get_account(&pool_pubkey)?;
+    let pool_data = PoolData::try_from_slice_unchecked(&pool.data)?;
+
+    let bank_mint_account = config
+        .rpc_client
+        .get_token_account(&pool_data.bank)?
+        .ok_or_else(|| format!("Could not find token account {}", &pool_data.bank))?;
+    let decimals = bank_mint_account.token_amount.decimals;
+
+    let rate = get_exchange_rate(config, &pool_data)?;
+    let amount = token::ui_amount_to_amount(amount, decimals);
+    println!(
+        "Depositing {} rewards for {} pool tokens",
+        token::amount_to_ui_amount(amount, decimals),
+        token::amount_to_ui_amount(rate.pool_supply, decimals)
+    );
+    if pool_data.apr_bps > 0 {
+        println!("Rewards are paid out at {} bps a year", pool_data.apr_bps);
+    }
+
+    let mut tx = Transaction::new_with_payer(
+        &[deposit_rewards(
+            &simple_token_pool::id(),
+            &pool_pubkey,
+            &config.owner.pubkey(),
+            &pool_data.pool_mint,
+            &pool_data.bank,
+            &source,
+            amount,
+        )],
+        Some(&config.fee_payer.pubkey()),
+    );
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(config, fee_calculator.calculate_fee(&tx.message()))?;
+
+    let mut signers = vec![config.fee_payer.as_ref(), config.owner.as_ref()];
+
+    unique_signers!(signers);
+    tx.sign(&signers, recent_blockhash);
+
+    Ok(Some(tx))
+}
+
+fn command_refresh(config: &Config, pool_pubkey: &Pubkey) -> CommandResult {
+    let pool = config.rpc_client.get_account(&pool_pubkey)?;
+    let pool_data = PoolData::try_from_slice_unchecked(&pool.data)?;
+
+    println!(
+        "Accruing interest since slot {}, {} undistributed rewards left",
+        pool_data.last_update_slot, pool_data.undistributed_rewards
+    );
+
+    let mut tx = Transaction::new_with_payer(
+        &[refresh(
+            &simple_token_pool::id(),
+            &pool_pubkey,
+            &pool_data.bank,
+        )],
+        Some(&config.fee_payer.pubkey()),
+    );
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(config, fee_calculator.calculate_fee(&tx.message()))?;
+
+    let mut signers = vec![config.fee_payer.as_ref()];
+
+    unique_signers!(signers);
+    tx.sign(&signers, recent_blockhash);
+
+    Ok(Some(tx))
+}
+
+fn command_set_apr(config: &Config, pool_pubkey: &Pubkey, apr_bps: u16) -> CommandResult {
+    let pool = config.rpc_client.get_account(&pool_pubkey)?;
+    let pool_data = PoolData::try_from_slice_unchecked(&pool.data)?;
+
+    println!(
+        "Setting APR of pool {} from {} to {} bps",
+        pool_pubkey, pool_data.apr_bps, apr_bps
+    );
+
+    let mut tx = Transaction::new_with_payer(
+        &[set_apr(
+            &simple_token_pool::id(),
+            &pool_pubkey,
+            &config.owner.pubkey(),
+            &pool_data.bank,
+            apr_bps,
+        )],
+        Some(&config.fee_payer.pubkey()),
+    );
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(config, fee_calculator.calculate_fee(&tx.message()))?;
+
+    let mut signers = vec![config.fee_payer.as_ref(), config.owner.as_ref()];
+
+    unique_signers!(signers);
+    tx.sign(&signers, recent_blockhash);
+
+    Ok(Some(tx))
+}
+
+fn command_set_oracle(
+    config: &Config,
+    pool_pubkey: &Pubkey,
+    oracle: Option<Pubkey>,
+    max_age: u64,
+) -> CommandResult {
+    let pool = config.rpc_client.get_account(&pool_pubkey)?;
+    let pool_data = PoolData::try_from_slice_unchecked(&pool.data)?;
+
+    let supply = config.rpc_client.get_token_supply(&pool_data.pool_mint)?;
+    if supply.amount != "0" {
+        return Err(format!(
+            "Pool {} has {} pool tokens, only empty pools change the price source",
+            pool_pubkey,
+            supply.real_number_string_trimmed()
+        )
+        .into());
+    }
+
+    let oracle = oracle.unwrap_or_default();
+    if oracle == Pubkey::default() {
+        println!("Pool {} swaps at the share price", pool_pubkey);
+    } else {
+        let oracle_account = config.rpc_client.get_account(&oracle)?;
+        let price = PriceData::try_from_slice_unchecked(&oracle_account.data)?;
+        println!(
+            "Pool {} swaps at {} * 10^{} from {}, at most {} slots old",
+            pool_pubkey, price.price, price.exponent, oracle, max_age
+        );
+    }
+
+    let mut tx = Transaction::new_with_payer(
+        &[set_oracle(
+            &simple_token_pool::id(),
+            &pool_pubkey,
+            &config.owner.pubkey(),
+            &pool_data.pool_mint,
+            &oracle,
+            max_age,
+        )],
+        Some(&config.fee_payer.pubkey()),
+    );
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(config, fee_calculator.calculate_fee(&tx.message()))?;
+
+    let mut signers = vec![config.fee_payer.as_ref(), config.owner.as_ref()];
+
+    unique_signers!(signers);
+    tx.sign(&signers, recent_blockhash);
+
+    Ok(Some(tx))
+}
+
+fn command_initialize_reserve(
+    config: &Config,
+    pool_pubkey: &Pubkey,
+    reserve_mint: &Pubkey,
+) -> CommandResult {
+    let pool = config.rpc_client.get_account(&pool_pubkey)?;
+    let pool_data = PoolData::try_from_slice_unchecked(&pool.data)?;
+
+    let (pool_authority, _) = find_authority_bump_seed(&simple_token_pool::id(), &pool_pubkey);
+    let (reserve, _) = find_reserve_address(&simple_token_pool::id(), &pool_pubkey);
+    println!("Creating reserve {} of mint {}", reserve, reserve_mint);
+
+    let mut tx = Transaction::new_with_payer(
+        &[initialize_reserve(
+            &simple_token_pool::id(),
+            &pool_pubkey,
+            &pool_authority,
+            &config.owner.pubkey(),
+            &reserve_mint,
+            &reserve,
+            &pool_data.pool_mint,
+            &config.fee_payer.pubkey(),
+        )],
+        Some(&config.fee_payer.pubkey()),
+    );
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(
+        config,
+        config
+            .rpc_client
+            .get_minimum_balance_for_rent_exemption(token::state::Account::LEN)?
+            + fee_calculator.calculate_fee(&tx.message()),
+    )?;
+
+    let mut signers = vec![config.fee_payer.as_ref(), config.owner.as_ref()];
+
+    unique_signers!(signers);
+    tx.sign(&signers, recent_blockhash);
+
+    Ok(Some(tx))
+}
+
+fn command_add_liquidity(
+    config: &Config,
+    pool_pubkey: &Pubkey,
+    bank_source: &Pubkey,
+    reserve_source: &Pubkey,
+    recipient: &Pubkey,
+    bank_amount: f64,
+    reserve_amount: f64,
+    slippage_bps: u16,
+) -> CommandResult {
+    let pool = config.rpc_client.get_account(&pool_pubkey)?;
+    let pool_data = PoolData::try_from_slice_unchecked(&pool.data)?;
+
+    let (pool, bank_decimals, reserve_decimals) = get_constant_product(config, &pool_data)?;
+    let bank_amount = token::ui_amount_to_amount(bank_amount, bank_decimals);
+    let reserve_amount = token::ui_amount_to_amount(reserve_amount, reserve_decimals);
+
+    let amounts = pool.calc_deposit(
+        bank_amount,
+        reserve_amount,
+        pool_data.rate_numerator,
+        pool_data.rate_denominator,
+    )?;
+    let minimum_pool_amount = math::mul_div(
+        amounts.pool_amount,
+        PoolData::BPS_DENOMINATOR - slippage_bps as u64,
+        PoolData::BPS_DENOMINATOR,
+        Rounding::Down,
+    )?;
+    println!(
+        "Depositing {} bank and {} reserve tokens",
+        token::amount_to_ui_amount(amounts.bank_amount, bank_decimals),
+        token::amount_to_ui_amount(amounts.reserve_amount, reserve_decimals)
+    );
+    println!(
+        "Expected pool tokens: {}, minimum {} ({} bps slippage)",
+        token::amount_to_ui_amount(amounts.pool_amount, bank_decimals),
+        token::amount_to_ui_amount(minimum_pool_amount, bank_decimals),
+        slippage_bps
+    );
+
+    let (pool_authority, _) = find_authority_bump_seed(&simple_token_pool::id(), &pool_pubkey);
+    let mut tx = Transaction::new_with_payer(
+        &[add_liquidity(
+            &simple_token_pool::id(),
+            &pool_pubkey,
+            &pool_authority,
+            &config.owner.pubkey(),
+            &pool_data.pool_mint,
+            &pool_data.bank,
+            &pool_data.reserve,
+            &bank_source,
+            &reserve_source,
+            &recipient,
+            bank_amount,
+            reserve_amount,
+            minimum_pool_amount,
+        )],
+        Some(&config.fee_payer.pubkey()),
+    );
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(config, fee_calculator.calculate_fee(&tx.message()))?;
+
+    let mut signers = vec![config.fee_payer.as_ref(), config.owner.as_ref()];
+
+    unique_signers!(signers);
+    tx.sign(&signers, recent_blockhash);
+
+    Ok(Some(tx))
+}
+
+fn command_remove_liquidity(
+    config: &Config,
+    pool_pubkey: &Pubkey,
+    sender: &Pubkey,
+    bank_recipient: &Pubkey,
+    reserve_recipient: &Pubkey,
+    pool_amount: f64,
+    slippage_bps: u16,
+) -> CommandResult {
+    let pool = config.rpc_client.get_account(&pool_pubkey)?;
+    let pool_data = PoolData::try_from_slice_unchecked(&pool.data)?;
+
+    let (pool, bank_decimals, reserve_decimals) = get_constant_product(config, &pool_data)?;
+    // The pool mint has the bank mint decimals
+    let pool_amount = token::ui_amount_to_amount(pool_amount, bank_decimals);
+
+    let amounts = pool.calc_withdraw(pool_amount)?;
+    let minimum = |amount| {
+        math::mul_div(
+            amount,
+            PoolData::BPS_DENOMINATOR - slippage_bps as u64,
+            PoolData::BPS_DENOMINATOR,
+            Rounding::Down,
+        )
+    };
+    let minimum_bank_amount = minimum(amounts.bank_amount)?;
+    let minimum_reserve_amount = minimum(amounts.reserve_amount)?;
+    println!(
+        "Expected bank tokens: {}, minimum {}",
+        token::amount_to_ui_amount(amounts.bank_amount, bank_decimals),
+        token::amount_to_ui_amount(minimum_bank_amount, bank_decimals)
+    );
+    println!(
+        "Expected reserve tokens: {}, minimum {}",
+        token::amount_to_ui_amount(amounts.reserve_amount, reserve_decimals),
+        token::amount_to_ui_amount(minimum_reserve_amount, reserve_decimals)
+    );
+
+    let (pool_authority, _) = find_authority_bump_seed(&simple_token_pool::id(), &pool_pubkey);
+    let mut tx = Transaction::new_with_payer(
+        &[remove_liquidity(
+            &simple_token_pool::id(),
+            &pool_pubkey,
+            &pool_authority,
+            &config.owner.pubkey(),
+            &pool_data.pool_mint,
+            &pool_data.bank,
+            &pool_data.reserve,
+            &sender,
+            &bank_recipient,
+            &reserve_recipient,
+            pool_amount,
+            minimum_bank_amount,
+            minimum_reserve_amount,
+        )],
+        Some(&config.fee_payer.pubkey()),
+    );
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(config, fee_calculator.calculate_fee(&tx.message()))?;
+
+    let mut signers = vec![config.fee_payer.as_ref(), config.owner.as_ref()];
+
+    unique_signers!(signers);
+    tx.sign(&signers, recent_blockhash);
+
+    Ok(Some(tx))
+}
+
+fn command_swap_reserves(
+    config: &Config,
+    pool_pubkey: &Pubkey,
+    source: &Pubkey,
+    destination: &Pubkey,
+    amount_in: f64,
+    slippage_bps: u16,
+) -> CommandResult {
+    let pool = config.rpc_client.get_account(&pool_pubkey)?;
+    let pool_data = PoolData::try_from_slice_unchecked(&pool.data)?;
+
+    let (pool, bank_decimals, reserve_decimals) = get_constant_product(config, &pool_data)?;
+    let source_account = config
+        .rpc_client
+        .get_token_account(&source)?
+        .ok_or_else(|| format!("Could not find token account {}", source))?;
+
+    // The mint of the source picks the direction
+    let (swap_source, swap_destination, balance_in, balance_out, decimals_in, decimals_out) =
+        if source_account.mint == pool_data.bank_mint.to_string() {
+            (
+                &pool_data.bank,
+                &pool_data.reserve,
+                pool.bank_balance,
+                pool.reserve_balance,
+                bank_decimals,
+                reserve_decimals,
+            )
+        } else {
+            (
+                &pool_data.reserve,
+                &pool_data.bank,
+                pool.reserve_balance,
+                pool.bank_balance,
+                reserve_decimals,
+                bank_decimals,
+            )
+        };
+
+    let amount_in = token::ui_amount_to_amount(amount_in, decimals_in);
+    let amounts =
+        ConstantProduct::calc_trade(balance_in, balance_out, amount_in, pool_data.fee_bps)?;
+    let minimum_amount_out = math::mul_div(
+        amounts.amount_out,
+        PoolData::BPS_DENOMINATOR - slippage_bps as u64,
+        PoolData::BPS_DENOMINATOR,
+        Rounding::Down,
+    )?;
+    println!(
+        "Expected amount out: {}",
+        token::amount_to_ui_amount(amounts.amount_out, decimals_out)
+    );
+    println!(
+        "Minimum amount out: {} ({} bps slippage)",
+        token::amount_to_ui_amount(minimum_amount_out, decimals_out),
+        slippage_bps
+    );
+
+    let (pool_authority, _) = find_authority_bump_seed(&simple_token_pool::id(), &pool_pubkey);
+    let mut tx = Transaction::new_with_payer(
+        &[swap_reserves(
+            &simple_token_pool::id(),
+            &pool_pubkey,
+            &pool_authority,
+            &config.owner.pubkey(),
+            swap_source,
+            swap_destination,
+            &source,
+            &destination,
+            amount_in,
+            minimum_amount_out,
+        )],
+        Some(&config.fee_payer.pubkey()),
+    );
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(config, fee_calculator.calculate_fee(&tx.message()))?;
+
+    let mut signers = vec![config.fee_payer.as_ref(), config.owner.as_ref()];
+
+    unique_signers!(signers);
+    tx.sign(&signers, recent_blockhash);
+
+    Ok(Some(tx))
+}
+
+fn command_allowlist(path: &str, address: Option<Pubkey>) -> CommandResult {
+    let users = read_allowlist(path)?;
+    let tree = MerkleTree::new(&users);
+
+    println!("Users: {}", users.len());
+    println!("Root: {}", Hash::new_from_array(tree.root()));
+
+    if let Some(address) = address {
+        let proof = tree
+            .proof(&address)
+            .ok_or_else(|| format!("{} is not on the allowlist", address))?;
+        let proof: Vec<String> = proof
+            .iter()
+            .map(|hash| Hash::new_from_array(*hash).to_string())
+            .collect();
+        println!("Proof of {}: {}", address, proof.join(","));
+    }
+
+    Ok(None)
+}
+
+fn command_set_allowlist(
+    config: &Config,
+    pool_pubkey: &Pubkey,
+    path: Option<&str>,
+) -> CommandResult {
+    let root = match path {
+        Some(path) => {
+            let users = read_allowlist(path)?;
+            let root = MerkleTree::new(&users).root();
+            println!(
+                "Pool {} swaps only for {} users, root {}",
+                pool_pubkey,
+                users.len(),
+                Hash::new_from_array(root)
+            );
+            root
+        }
+        None => {
+            println!("Anyone can swap with pool {}", pool_pubkey);
+            [0; 32]
+        }
+    };
+
+    let mut tx = Transaction::new_with_payer(
+        &[update_allowlist_root(
+            &simple_token_pool::id(),
+            &pool_pubkey,
+            &config.owner.pubkey(),
+            root,
+        )],
+        Some(&config.fee_payer.pubkey()),
+    );
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(config, fee_calculator.calculate_fee(&tx.message()))?;
+
+    let mut signers = vec![config.fee_payer.as_ref(), config.owner.as_ref()];
+
+    unique_signers!(signers);
+    tx.sign(&signers, recent_blockhash);
+
+    Ok(Some(tx))
+}
+
+fn command_sign_permit(
+    config: &Config,
+    pool_pubkey: &Pubkey,
+    user: &Pubkey,
+    max_amount: f64,
+    valid_for: u64,
+    nonce: Option<u64>,
+) -> CommandResult {
+    let pool = config.rpc_client.get_account(&pool_pubkey)?;
+    let pool_data = PoolData::try_from_slice_unchecked(&pool.data)?;
+    if pool_data.permit_key != config.owner.pubkey() {
+        return Err(format!("Permits of the pool are signed by {}", pool_data.permit_key).into());
+    }
+
+    let bank_account = config
+        .rpc_client
+        .get_token_account(&pool_data.bank)?
+        .ok_or_else(|| format!("Could not find token account {}", &pool_data.bank))?;
+    let decimals = bank_account.token_amount.decimals;
+
+    let nonce = match nonce {
+        Some(nonce) => nonce,
+        None => SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos() as u64,
+    };
+    let permit = Permit {
+        pool: *pool_pubkey,
+        user: *user,
+        max_amount: token::ui_amount_to_amount(max_amount, decimals),
+        expiry_slot: config.rpc_client.get_slot()? + valid_for,
+        nonce,
+    };
+    let signature = config.owner.try_sign_message(&permit.message()?)?;
+
+    println!(
+        "{} can swap up to {} until slot {}",
+        user, max_amount, permit.expiry_slot
+    );
+    println!(
+        "Permit: {}:{}:{}:{}",
+        permit.max_amount, permit.expiry_slot, permit.nonce, signature
+    );
+
+    Ok(None)
+}
+
+fn command_set_permit_key(
+    config: &Config,
+    pool_pubkey: &Pubkey,
+    permit_key: Option<Pubkey>,
+) -> CommandResult {
+    let permit_key = permit_key.unwrap_or_default();
+    if permit_key == Pubkey::default() {
+        println!("Pool {} swaps without a permit", pool_pubkey);
+    } else {
+        println!(
+            "Pool {} swaps only with a permit signed by {}",
+            pool_pubkey, permit_key
+        );
+    }
+
+    let mut tx = Transaction::new_with_payer(
+        &[set_permit_key(
+            &simple_token_pool::id(),
+            &pool_pubkey,
+            &config.owner.pubkey(),
+            &permit_key,
+        )],
+        Some(&config.fee_payer.pubkey()),
+    );
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(config, fee_calculator.calculate_fee(&tx.message()))?;
+
+    let mut signers = vec![config.fee_payer.as_ref(), config.owner.as_ref()];
+
+    unique_signers!(signers);
+    tx.sign(&signers, recent_blockhash);
+
+    Ok(Some(tx))
+}
+
+fn command_set_deposit_limit(
+    config: &Config,
+    pool_pubkey: &Pubkey,
+    deposit_limit: f64,
+    period: DepositPeriod,
+) -> CommandResult {
+    let pool = config.rpc_client.get_account(&pool_pubkey)?;
+    let pool_data = PoolData::try_from_slice_unchecked(&pool.data)?;
+
+    let bank_account = config
+        .rpc_client
+        .get_token_account(&pool_data.bank)?
+        .ok_or_else(|| format!("Could not find token account {}", &pool_data.bank))?;
+    let deposit_limit =
+        token::ui_amount_to_amount(deposit_limit, bank_account.token_amount.decimals);
+
+    if deposit_limit == 0 {
+        println!("Users of pool {} have no deposit limit", pool_pubkey);
+    } else {
+        println!(
+            "Users of pool {} can swap in up to {} {}",
+            pool_pubkey,
+            token::amount_to_ui_amount(deposit_limit, bank_account.token_amount.decimals),
+            match period {
+                DepositPeriod::Lifetime => "in total",
+                DepositPeriod::Epoch => "per epoch",
+            }
+        );
+    }
+
+    let mut tx = Transaction::new_with_payer(
+        &[set_deposit_limit(
+            &simple_token_pool::id(),
+            &pool_pubkey,
+            &config.owner.pubkey(),
+            deposit_limit,
+            period,
+        )],
+        Some(&config.fee_payer.pubkey()),
+    );
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(config, fee_calculator.calculate_fee(&tx.message()))?;
+
+    let mut signers = vec![config.fee_payer.as_ref(), config.owner.as_ref()];
+
+    unique_signers!(signers);
+    tx.sign(&signers, recent_blockhash);
+
+    Ok(Some(tx))
+}
+
+fn command_close_deposit_record(
+    config: &Config,
+    pool_pubkey: &Pubkey,
+    destination: Option<Pubkey>,
+) -> CommandResult {
+    let destination = destination.unwrap_or_else(|| config.owner.pubkey());
+    let (deposit_record_pubkey, _) = find_deposit_record_address(
+        &simple_token_pool::id(),
+        pool_pubkey,
+        &config.owner.pubkey(),
+    );
+
+    let deposit_record_account = config.rpc_client.get_account(&deposit_record_pubkey)?;
+    let deposit_record = DepositRecord::try_from_slice_unchecked(&deposit_record_account.data)?;
+    println!("{:?}", deposit_record);
+    println!(
+        "Closing deposit record {}, {} lamports go to {}",
+        deposit_record_pubkey, deposit_record_account.lamports, destination
+    );
+
+    let mut tx = Transaction::new_with_payer(
+        &[close_deposit_record(
+            &simple_token_pool::id(),
+            &pool_pubkey,
+            &config.owner.pubkey(),
+            &destination,
+        )],
+        Some(&config.fee_payer.pubkey()),
+    );
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(config, fee_calculator.calculate_fee(&tx.message()))?;
+
+    let mut signers = vec![config.fee_payer.as_ref(), config.owner.as_ref()];
+
+    unique_signers!(signers);
+    tx.sign(&signers, recent_blockhash);
+
+    Ok(Some(tx))
+}
+
+fn command_set_swap_limits(
+    config: &Config,
+    pool_pubkey: &Pubkey,
+    supply_cap: f64,
+    min_amount_in: f64,
+    max_amount_in: f64,
+) -> CommandResult {
+    let pool = config.rpc_client.get_account(&pool_pubkey)?;
+    let pool_data = PoolData::try_from_slice_unchecked(&pool.data)?;
+
+    let bank_account = config
+        .rpc_client
+        .get_token_account(&pool_data.bank)?
+        .ok_or_else(|| format!("Could not find token account {}", &pool_data.bank))?;
+    // The pool mint has the bank mint decimals
+    let decimals = bank_account.token_amount.decimals;
+    let supply_cap = token::ui_amount_to_amount(supply_cap, decimals);
+    let min_amount_in = token::ui_amount_to_amount(min_amount_in, decimals);
+    let max_amount_in = token::ui_amount_to_amount(max_amount_in, decimals);
+    if max_amount_in != 0 && min_amount_in > max_amount_in {
+        return Err("Minimum amount in must not exceed the maximum".into());
+    }
+
+    let pool_supply = config
+        .rpc_client
+        .get_token_supply(&pool_data.pool_mint)?
+        .amount
+        .parse::<u64>()?;
+    if supply_cap == 0 {
+        println!("Pool {} has no supply cap", pool_pubkey);
+    } else {
+        println!(
+            "Pool {} mints up to {} pool tokens, {} left",
+            pool_pubkey,
+            token::amount_to_ui_amount(supply_cap, decimals),
+            token::amount_to_ui_amount(supply_cap.saturating_sub(pool_supply), decimals)
+        );
+    }
+    println!(
+        "Swaps take at least {} bank tokens",
+        token::amount_to_ui_amount(min_amount_in, decimals)
+    );
+    if max_amount_in != 0 {
+        println!(
+            "Swaps take at most {} bank tokens",
+            token::amount_to_ui_amount(max_amount_in, decimals)
+        );
+    }
+
+    let mut tx = Transaction::new_with_payer(
+        &[set_swap_limits(
+            &simple_token_pool::id(),
+            &pool_pubkey,
+            &config.owner.pubkey(),
+            supply_cap,
+            min_amount_in,
+            max_amount_in,
+        )],
+        Some(&config.fee_payer.pubkey()),
+    );
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(config, fee_calculator.calculate_fee(&tx.message()))?;
+
+    let mut signers = vec![config.fee_payer.as_ref(), config.owner.as_ref()];
+
+    unique_signers!(signers);
+    tx.sign(&signers, recent_blockhash);
+
+    Ok(Some(tx))
+}
+
+fn command_set_sale(
+    config: &Config,
+    pool_pubkey: &Pubkey,
+    start: Option<i64>,
+    end: i64,
+    soft_cap_bps: u16,
+    hard_cap: f64,
+) -> CommandResult {
+    let pool = config.rpc_client.get_account(&pool_pubkey)?;
+    let pool_data = PoolData::try_from_slice_unchecked(&pool.data)?;
+
+    let bank_account = config
+        .rpc_client
+        .get_token_account(&pool_data.bank)?
+        .ok_or_else(|| format!("Could not find token account {}", &pool_data.bank))?;
+    // The pool mint has the bank mint decimals
+    let decimals = bank_account.token_amount.decimals;
+    let hard_cap = token::ui_amount_to_amount(hard_cap, decimals);
+
+    let start = match start {
+        Some(start) => start,
+        None => SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64,
+    };
+
+    if end == 0 {
+        println!("Removing the sale of pool {}", pool_pubkey);
+    } else {
+        if start >= end {
+            return Err("Sale start must be before the end".into());
+        }
+        if !pool_data.has_sale() && pool_data.supply_cap != 0 {
+            return Err("Pool has a supply cap, lift it with set-swap-limits first".into());
+        }
+        if soft_cap_bps != 0 && hard_cap == 0 {
+            return Err("Soft cap needs a hard cap".into());
+        }
+
+        let supply = config.rpc_client.get_token_supply(&pool_data.pool_mint)?;
+        if supply.amount != "0" {
+            return Err(format!(
+                "Pool is not empty: pool mint supply {}",
+                supply.real_number_string_trimmed()
+            )
+            .into());
+        }
+
+        let soft_cap = math::mul_div(
+            hard_cap,
+            soft_cap_bps as u64,
+            PoolData::BPS_DENOMINATOR,
+            Rounding::Up,
+        )?;
+        println!(
+            "Sale of pool {} runs from {} to {}",
+            pool_pubkey, start, end
+        );
+        println!(
+            "Hard cap: {} pool tokens",
+            token::amount_to_ui_amount(hard_cap, decimals)
+        );
+        println!(
+            "Soft cap: {} pool tokens ({} bps)",
+            token::amount_to_ui_amount(soft_cap, decimals),
+            soft_cap_bps
+        );
+        if pool_data.fee_bps > 0 {
+            println!(
+                "Swaps pay no fee, the {} bps fee is taken out of the proceeds",
+                pool_data.fee_bps
+            );
+        }
+    }
+
+    let mut tx = Transaction::new_with_payer(
+        &[set_sale(
+            &simple_token_pool::id(),
+            &pool_pubkey,
+            &config.owner.pubkey(),
+            &pool_data.pool_mint,
+            start,
+            end,
+            soft_cap_bps,
+            hard_cap,
+        )],
+        Some(&config.fee_payer.pubkey()),
+    );
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(config, fee_calculator.calculate_fee(&tx.message()))?;
+
+    let mut signers = vec![config.fee_payer.as_ref(), config.owner.as_ref()];
+
+    unique_signers!(signers);
+    tx.sign(&signers, recent_blockhash);
+
+    Ok(Some(tx))
+}
+
+fn command_withdraw_proceeds(
+    config: &Config,
+    pool_pubkey: &Pubkey,
+    destination: &Pubkey,
+) -> CommandResult {
+    let pool = config.rpc_client.get_account(&pool_pubkey)?;
+    let pool_data = PoolData::try_from_slice_unchecked(&pool.data)?;
+
+    if !pool_data.has_sale() {
+        return Err(format!("Pool {} runs no sale", pool_pubkey).into());
+    }
+
+    let supply = config
+        .rpc_client
+        .get_token_supply(&pool_data.pool_mint)?
+        .amount
+        .parse::<u64>()?;
+    if !pool_data.is_soft_cap_met(supply)? {
+        return Err(format!(
+            "Sale sold {} of the {} soft cap, depositors redeem their pool tokens for refunds",
+            supply,
+            pool_data.soft_cap()?
+        )
+        .into());
+    }
+
+    let bank_balance = config
+        .rpc_client
+        .get_token_account_balance(&pool_data.bank)?;
+    let amount = bank_balance.amount.parse::<u64>()?;
+    let fee = pool_data.calc_fee(amount)?;
+    println!(
+        "Withdrawing {} bank tokens to {}, {} of them to the fee receiver {}",
+        token::amount_to_ui_amount(amount, bank_balance.decimals),
+        destination,
+        token::amount_to_ui_amount(fee, bank_balance.decimals),
+        pool_data.fee_receiver
+    );
+
+    let (pool_authority, _) = find_authority_bump_seed(&simple_token_pool::id(), &pool_pubkey);
+
+    let mut tx = Transaction::new_with_payer(
+        &[withdraw_proceeds(
+            &simple_token_pool::id(),
+            &pool_pubkey,
+            &pool_authority,
+            &config.owner.pubkey(),
+            &pool_data.pool_mint,
+            &pool_data.bank,
+            destination,
+            &pool_data.fee_receiver,
+        )],
+        Some(&config.fee_payer.pubkey()),
+    );
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(config, fee_calculator.calculate_fee(&tx.message()))?;
+
+    let mut signers = vec![config.fee_payer.as_ref(), config.owner.as_ref()];
+
+    unique_signers!(signers);
+    tx.sign(&signers, recent_blockhash);
+
+    Ok(Some(tx))
+}
+
+fn command_withdraw_rewards(
+    config: &Config,
+    pool_pubkey: &Pubkey,
+    destination: &Pubkey,
+) -> CommandResult {
+    let pool = config.rpc_client.get_account(&pool_pubkey)?;
+    let pool_data = PoolData::try_from_slice_unchecked(&pool.data)?;
+
+    let bank_mint_account = config
+        .rpc_client
+        .get_token_account(&pool_data.bank)?
+        .ok_or_else(|| format!("Could not find token account {}", &pool_data.bank))?;
+    let decimals = bank_mint_account.token_amount.decimals;
+
+    // A pool without pool tokens gives up everything left in the bank
+    let supply = config
+        .rpc_client
+        .get_token_supply(&pool_data.pool_mint)?
+        .amount
+        .parse::<u64>()?;
+    if supply == 0 {
+        let bank_balance = bank_mint_account.token_amount.amount.parse::<u64>()?;
+        println!(
+            "Withdrawing {} left in the bank to {}",
+            token::amount_to_ui_amount(bank_balance, decimals),
+            destination
+        );
+    } else if pool_data.apr_bps > 0 {
+        return Err(format!(
+            "Rewards are paid out at {} bps a year, set the APR to 0 first",
+            pool_data.apr_bps
+        )
+        .into());
+    } else {
+        println!(
+            "Withdrawing {} undistributed rewards to {}",
+            token::amount_to_ui_amount(pool_data.undistributed_rewards, decimals),
+            destination
+        );
+    }
+
+    let (pool_authority, _) = find_authority_bump_seed(&simple_token_pool::id(), &pool_pubkey);
+
+    let mut tx = Transaction::new_with_payer(
+        &[withdraw_rewards(
+            &simple_token_pool::id(),
+            &pool_pubkey,
+            &pool_authority,
+            &config.owner.pubkey(),
+            &pool_data.pool_mint,
+            &pool_data.bank,
+            destination,
+        )],
+        Some(&config.fee_payer.pubkey()),
+    );
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(config, fee_calculator.calculate_fee(&tx.message()))?;
+
+    let mut signers = vec![config.fee_payer.as_ref(), config.owner.as_ref()];
+
+    unique_signers!(signers);
+    tx.sign(&signers, recent_blockhash);
+
+    Ok(Some(tx))
+}
+
+fn main() {
+    let matches = App::new(crate_name!())
+        .about(crate_description!())
+        .version(crate_version!())
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .arg({
+            let arg = Arg::with_name("config_file")
+                .short("C")
+                .long("config")
+                .value_name("PATH")
+                .takes_value(true)
+                .global(true)
+                .help("Configuration file to use");
+            if let Some(ref config_file) = *solana_cli_config::CONFIG_FILE {
+                arg.default_value(&config_file)
+            } else {
+                arg
+            }
+        })
+        .arg(
+            Arg::with_name("verbose")
+                .short("v")
+                .long("verbose")
+                .takes_value(false)
+                .global(true)
+                .help("Show additional information"),
+        )
+        .arg(
+            Arg::with_name("json_rpc_url")
+                .short("u")
+                .long("url")
+                .value_name("URL_OR_MONIKER")
+                .takes_value(true)
+                .global(true)
+                .validator(is_url_or_moniker)
+                .help(
+                    "URL for Solana's JSON RPC or moniker (or their first letter): \
+                       [mainnet-beta, testnet, devnet, localhost] \
+                    Default from the configuration file.",
+                ),
+        )
+        .arg(
+            Arg::with_name("owner")
+                .long("owner")
+                .value_name("KEYPAIR")
+                .validator(is_valid_signer)
+                .takes_value(true)
+                .global(true)
+                .help(
+                    "Specify the token owner account. \
+                     This may be a keypair file, the ASK keyword. \
+                     Defaults to the client keypair.",
+                ),
+        )
+        .arg(fee_payer_arg().global(true))
+        .subcommand(
+            SubCommand::with_name("create-pool")
+                .about("Create a new pool")
+                .arg(
+                    Arg::with_name("bank_mint")
+                        .validator(is_pubkey)
+                        .value_name("ADDRESS")
+                        .takes_value(true)
+                        .required(true)
+                        .index(1)
                         .help("Mint for bank."),
+                )
+                .arg(
+                    Arg::with_name("index")
+                        .long("index")
+                        .validator(is_parsable::<u64>)
+                        .value_name("INDEX")
+                        .takes_value(true)
+                        .default_value("0")
+                        .help("Index of the pool among the pools for the bank mint."),
+                )
+                .arg(
+                    Arg::with_name("rate")
+                        .long("rate")
+                        .validator(is_rate)
+                        .value_name("NUMERATOR/DENOMINATOR")
+                        .takes_value(true)
+                        .default_value("1")
+                        .help("Pool tokens minted per bank token by the empty pool, e.g. 3/2."),
+                )
+                .arg(
+                    Arg::with_name("fee_bps")
+                        .long("fee-bps")
+                        .validator(is_fee_bps)
+                        .value_name("BPS")
+                        .takes_value(true)
+                        .default_value("0")
+                        .help("Swap fee in basis points."),
+                )
+                .arg(
+                    Arg::with_name("fee_receiver")
+                        .long("fee-receiver")
+                        .validator(is_pubkey)
+                        .value_name("ADDRESS")
+                        .takes_value(true)
+                        .help(
+                            "Bank mint token account receiving swap fees. \
+                             Defaults to a new account of the owner.",
+                        ),
+                )
+                .arg(
+                    Arg::with_name("curve")
+                        .long("curve")
+                        .validator(is_curve)
+                        .value_name("CURVE")
+                        .takes_value(true)
+                        .default_value("constant")
+                        .help(
+                            "Price curve of pool tokens by their supply: constant, \
+                             linear:BASE:SLOPE, exponential:BASE:GROWTH_BPS:STEP or \
+                             step:BASE:STEP:INCREMENT. Prices are in bank tokens per \
+                             pool token, slopes and steps in the smallest pool token units.",
+                        ),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("swap")
+                .about("Swap to pool tokens")
+                .arg(
+                    Arg::with_name("sender")
+                        .validator(is_pubkey)
+                        .value_name("SENDER")
+                        .takes_value(true)
+                        .required(true)
+                        .index(1)
+                        .help("Sender token public key."),
+                )
+                .arg(
+                    Arg::with_name("recipient")
+                        .validator(is_pubkey)
+                        .value_name("RECIPIENT")
+                        .takes_value(true)
+                        .required(true)
+                        .index(2)
+                        .help("Recipient token public key."),
+                )
+                .arg(
+                    Arg::with_name("amount_in")
+                        .validator(is_amount)
+                        .value_name("AMOUNT_IN")
+                        .takes_value(true)
+                        .required(true)
+                        .index(3)
+                        .help("Amount of bank tokens to swap, or pool tokens with --exact-out."),
+                )
+                .arg(
+                    Arg::with_name("pool")
+                        .validator(is_pubkey)
+                        .value_name("POOL")
+                        .takes_value(true)
+                        .required_unless("bank_mint")
+                        .index(4)
+                        .help("Pool public key."),
+                )
+                .arg(
+                    Arg::with_name("bank_mint")
+                        .long("bank-mint")
+                        .validator(is_pubkey)
+                        .value_name("ADDRESS")
+                        .takes_value(true)
+                        .conflicts_with("pool")
+                        .help("Find the pool by its bank mint instead of the address."),
+                )
+                .arg(
+                    Arg::with_name("index")
+                        .long("index")
+                        .validator(is_parsable::<u64>)
+                        .value_name("INDEX")
+                        .takes_value(true)
+                        .default_value("0")
+                        .help("Index of the pool for the bank mint."),
+                )
+                .arg(
+                    Arg::with_name("slippage_bps")
+                        .long("slippage-bps")
+                        .validator(is_bps)
+                        .value_name("BPS")
+                        .takes_value(true)
+                        .default_value("50")
+                        .help("Accepted slippage from the quoted amount, in basis points."),
+                )
+                .arg(
+                    Arg::with_name("exact_out")
+                        .long("exact-out")
+                        .takes_value(false)
+                        .help("Swap for exactly AMOUNT pool tokens instead of AMOUNT bank tokens."),
+                )
+                .arg(
+                    Arg::with_name("allowlist")
+                        .long("allowlist")
+                        .value_name("CSV")
+                        .takes_value(true)
+                        .conflicts_with("exact_out")
+                        .help("Allowlist of the pool, proving the owner is on it."),
+                )
+                .arg(
+                    Arg::with_name("permit")
+                        .long("permit")
+                        .validator(is_permit)
+                        .value_name("PERMIT")
+                        .takes_value(true)
+                        .conflicts_with_all(&["exact_out", "allowlist"])
+                        .help("Permit of the owner printed by sign-permit."),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("redeem")
+                .about("Redeem pool tokens for bank tokens")
+                .arg(
+                    Arg::with_name("sender")
+                        .validator(is_pubkey)
+                        .value_name("SENDER")
+                        .takes_value(true)
+                        .required(true)
+                        .index(1)
+                        .help("Sender pool token public key."),
+                )
+                .arg(
+                    Arg::with_name("recipient")
+                        .validator(is_pubkey)
+                        .value_name("RECIPIENT")
+                        .takes_value(true)
+                        .required(true)
+                        .index(2)
+                        .help("Recipient bank token public key."),
+                )
+                .arg(
+                    Arg::with_name("amount_in")
+                        .validator(is_amount)
+                        .value_name("AMOUNT_IN")
+                        .takes_value(true)
+                        .required(true)
+                        .index(3)
+                        .help("Amount of pool tokens for redeem."),
+                )
+                .arg(
+                    Arg::with_name("pool")
+                        .validator(is_pubkey)
+                        .value_name("POOL")
+                        .takes_value(true)
+                        .required_unless("bank_mint")
+                        .index(4)
+                        .help("Pool public key."),
+                )
+                .arg(
+                    Arg::with_name("bank_mint")
+                        .long("bank-mint")
+                        .validator(is_pubkey)
+                        .value_name("ADDRESS")
+                        .takes_value(true)
+                        .conflicts_with("pool")
+                        .help("Find the pool by its bank mint instead of the address."),
+                )
+                .arg(
+                    Arg::with_name("index")
+                        .long("index")
+                        .validator(is_parsable::<u64>)
+                        .value_name("INDEX")
+                        .takes_value(true)
+                        .default_value("0")
+                        .help("Index of the pool for the bank mint."),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("propose-manager")
+                .about("Propose a new pool manager, signed by the current one")
+                .arg(
+                    Arg::with_name("pool")
+                        .validator(is_pubkey)
+                        .value_name("POOL")
+                        .takes_value(true)
+                        .required(true)
+                        .index(1)
+                        .help("Pool public key."),
+                )
+                .arg(
+                    Arg::with_name("new_manager")
+                        .validator(is_pubkey)
+                        .value_name("NEW_MANAGER")
+                        .takes_value(true)
+                        .required(true)
+                        .index(2)
+                        .help("New manager public key."),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("accept-manager")
+                .about("Accept the pool manager role, signed by the proposed manager")
+                .arg(
+                    Arg::with_name("pool")
+                        .validator(is_pubkey)
+                        .value_name("POOL")
+                        .takes_value(true)
+                        .required(true)
+                        .index(1)
+                        .help("Pool public key."),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("pause")
+                .about("Pause swaps and redeems, signed by the manager")
+                .arg(
+                    Arg::with_name("pool")
+                        .validator(is_pubkey)
+                        .value_name("POOL")
+                        .takes_value(true)
+                        .required(true)
+                        .index(1)
+                        .help("Pool public key."),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("unpause")
+                .about("Resume swaps and redeems, signed by the manager")
+                .arg(
+                    Arg::with_name("pool")
+                        .validator(is_pubkey)
+                        .value_name("POOL")
+                        .takes_value(true)
+                        .required(true)
+                        .index(1)
+                        .help("Pool public key."),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("migrate")
+                .about("Upgrade the pool account to the current layout")
+                .arg(
+                    Arg::with_name("pool")
+                        .validator(is_pubkey)
+                        .value_name("POOL")
+                        .takes_value(true)
+                        .required(true)
+                        .index(1)
+                        .help("Pool public key."),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("close-pool")
+                .about("Close an empty pool and reclaim its rent, signed by the manager")
+                .arg(
+                    Arg::with_name("pool")
+                        .validator(is_pubkey)
+                        .value_name("POOL")
+                        .takes_value(true)
+                        .required(true)
+                        .index(1)
+                        .help("Pool public key."),
+                )
+                .arg(
+                    Arg::with_name("destination")
+                        .long("destination")
+                        .validator(is_pubkey)
+                        .value_name("ADDRESS")
+                        .takes_value(true)
+                        .help("Account receiving the rent. Defaults to the owner."),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("deposit-rewards")
+                .about("Add bank tokens to the pool without minting, signed by the manager")
+                .arg(
+                    Arg::with_name("pool")
+                        .validator(is_pubkey)
+                        .value_name("POOL")
+                        .takes_value(true)
+                        .required(true)
+                        .index(1)
+                        .help("Pool public key."),
+                )
+                .arg(
+                    Arg::with_name("source")
+                        .validator(is_pubkey)
+                        .value_name("SOURCE")
+                        .takes_value(true)
+                        .required(true)
+                        .index(2)
+                        .help("Manager bank token public key."),
+                )
+                .arg(
+                    Arg::with_name("amount")
+                        .validator(is_amount)
+                        .value_name("AMOUNT")
+                        .takes_value(true)
+                        .required(true)
+                        .index(3)
+                        .help("Amount of bank tokens."),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("refresh")
+                .about("Accrue the pool interest up to the current slot")
+                .arg(
+                    Arg::with_name("pool")
+                        .validator(is_pubkey)
+                        .value_name("POOL")
+                        .takes_value(true)
+                        .required(true)
+                        .index(1)
+                        .help("Pool public key."),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("set-apr")
+                .about("Set the annual interest paid out of the rewards, signed by the manager")
+                .arg(
+                    Arg::with_name("pool")
+                        .validator(is_pubkey)
+                        .value_name("POOL")
+                        .takes_value(true)
+                        .required(true)
+                        .index(1)
+                        .help("Pool public key."),
+                )
+                .arg(
+                    Arg::with_name("apr_bps")
+                        .validator(is_parsable::<u16>)
+                        .value_name("BPS")
+                        .takes_value(true)
+                        .required(true)
+                        .index(2)
+                        .help("Annual interest in basis points, 0 stops the interest."),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("set-oracle")
+                .about("Price swaps and redeems of an empty pool with an oracle, by the manager")
+                .arg(
+                    Arg::with_name("pool")
+                        .validator(is_pubkey)
+                        .value_name("POOL")
+                        .takes_value(true)
+                        .required(true)
+                        .index(1)
+                        .help("Pool public key."),
+                )
+                .arg(
+                    Arg::with_name("oracle")
+                        .validator(is_pubkey)
+                        .value_name("ORACLE")
+                        .takes_value(true)
+                        .index(2)
+                        .help("Price account. Swaps go back to the share price without it."),
+                )
+                .arg(
+                    Arg::with_name("max_age")
+                        .long("max-age")
+                        .validator(is_parsable::<u64>)
+                        .value_name("SLOTS")
+                        .takes_value(true)
+                        .default_value("150")
+                        .help("Most slots since the last price update accepted by swaps."),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("initialize-reserve")
+                .about("Turn an empty pool into a constant product pool, signed by the manager")
+                .arg(
+                    Arg::with_name("pool")
+                        .validator(is_pubkey)
+                        .value_name("POOL")
+                        .takes_value(true)
+                        .required(true)
+                        .index(1)
+                        .help("Pool public key."),
+                )
+                .arg(
+                    Arg::with_name("reserve_mint")
+                        .validator(is_pubkey)
+                        .value_name("RESERVE_MINT")
+                        .takes_value(true)
+                        .required(true)
+                        .index(2)
+                        .help("Mint traded against the bank mint."),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("add-liquidity")
+                .about("Deposit bank and reserve tokens for pool tokens")
+                .arg(
+                    Arg::with_name("pool")
+                        .validator(is_pubkey)
+                        .value_name("POOL")
+                        .takes_value(true)
+                        .required(true)
+                        .index(1)
+                        .help("Pool public key."),
+                )
+                .arg(
+                    Arg::with_name("bank_source")
+                        .validator(is_pubkey)
+                        .value_name("BANK_SOURCE")
+                        .takes_value(true)
+                        .required(true)
+                        .index(2)
+                        .help("Bank token account to deposit from."),
+                )
+                .arg(
+                    Arg::with_name("reserve_source")
+                        .validator(is_pubkey)
+                        .value_name("RESERVE_SOURCE")
+                        .takes_value(true)
+                        .required(true)
+                        .index(3)
+                        .help("Reserve token account to deposit from."),
+                )
+                .arg(
+                    Arg::with_name("recipient")
+                        .validator(is_pubkey)
+                        .value_name("RECIPIENT")
+                        .takes_value(true)
+                        .required(true)
+                        .index(4)
+                        .help("Pool token account receiving the pool tokens."),
+                )
+                .arg(
+                    Arg::with_name("bank_amount")
+                        .validator(is_amount)
+                        .value_name("BANK_AMOUNT")
+                        .takes_value(true)
+                        .required(true)
+                        .index(5)
+                        .help("Most bank tokens to deposit."),
+                )
+                .arg(
+                    Arg::with_name("reserve_amount")
+                        .validator(is_amount)
+                        .value_name("RESERVE_AMOUNT")
+                        .takes_value(true)
+                        .required(true)
+                        .index(6)
+                        .help("Most reserve tokens to deposit."),
+                )
+                .arg(
+                    Arg::with_name("slippage_bps")
+                        .long("slippage-bps")
+                        .validator(is_bps)
+                        .value_name("BPS")
+                        .takes_value(true)
+                        .default_value("50")
+                        .help("Accepted slippage from the quoted amount, in basis points."),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("remove-liquidity")
+                .about("Burn pool tokens for bank and reserve tokens")
+                .arg(
+                    Arg::with_name("pool")
+                        .validator(is_pubkey)
+                        .value_name("POOL")
+                        .takes_value(true)
+                        .required(true)
+                        .index(1)
+                        .help("Pool public key."),
+                )
+                .arg(
+                    Arg::with_name("sender")
+                        .validator(is_pubkey)
+                        .value_name("SENDER")
+                        .takes_value(true)
+                        .required(true)
+                        .index(2)
+                        .help("Pool token account to burn from."),
+                )
+                .arg(
+                    Arg::with_name("bank_recipient")
+                        .validator(is_pubkey)
+                        .value_name("BANK_RECIPIENT")
+                        .takes_value(true)
+                        .required(true)
+                        .index(3)
+                        .help("Bank token account receiving the bank tokens."),
+                )
+                .arg(
+                    Arg::with_name("reserve_recipient")
+                        .validator(is_pubkey)
+                        .value_name("RESERVE_RECIPIENT")
+                        .takes_value(true)
+                        .required(true)
+                        .index(4)
+                        .help("Reserve token account receiving the reserve tokens."),
+                )
+                .arg(
+                    Arg::with_name("pool_amount")
+                        .validator(is_amount)
+                        .value_name("POOL_AMOUNT")
+                        .takes_value(true)
+                        .required(true)
+                        .index(5)
+                        .help("Amount of pool tokens."),
+                )
+                .arg(
+                    Arg::with_name("slippage_bps")
+                        .long("slippage-bps")
+                        .validator(is_bps)
+                        .value_name("BPS")
+                        .takes_value(true)
+                        .default_value("50")
+                        .help("Accepted slippage from the quoted amounts, in basis points."),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("swap-reserves")
+                .about("Swap between the bank and reserve tokens of a constant product pool")
+                .arg(
+                    Arg::with_name("pool")
+                        .validator(is_pubkey)
+                        .value_name("POOL")
+                        .takes_value(true)
+                        .required(true)
+                        .index(1)
+                        .help("Pool public key."),
+                )
+                .arg(
+                    Arg::with_name("source")
+                        .validator(is_pubkey)
+                        .value_name("SOURCE")
+                        .takes_value(true)
+                        .required(true)
+                        .index(2)
+                        .help("Bank or reserve token account to swap from."),
+                )
+                .arg(
+                    Arg::with_name("destination")
+                        .validator(is_pubkey)
+                        .value_name("DESTINATION")
+                        .takes_value(true)
+                        .required(true)
+                        .index(3)
+                        .help("Token account of the other mint receiving the swap."),
+                )
+                .arg(
+                    Arg::with_name("amount_in")
+                        .validator(is_amount)
+                        .value_name("AMOUNT")
+                        .takes_value(true)
+                        .required(true)
+                        .index(4)
+                        .help("Amount of source tokens."),
+                )
+                .arg(
+                    Arg::with_name("slippage_bps")
+                        .long("slippage-bps")
+                        .validator(is_bps)
+                        .value_name("BPS")
+                        .takes_value(true)
+                        .default_value("50")
+                        .help("Accepted slippage from the quoted amount, in basis points."),
                 ),
         )
         .subcommand(
-            SubCommand::with_name("swap")
-                .about("Swap to pool tokens")
+            SubCommand::with_name("allowlist")
+                .about("Print the Merkle root of a CSV of addresses and the proof of an address")
                 .arg(
-                    Arg::with_name("sender")
+                    Arg::with_name("csv")
+                        .value_name("CSV")
+                        .takes_value(true)
+                        .required(true)
+                        .index(1)
+                        .help("File with an address in the first column of every line."),
+                )
+                .arg(
+                    Arg::with_name("address")
                         .validator(is_pubkey)
-                        .value_name("SENDER")
+                        .value_name("ADDRESS")
+                        .takes_value(true)
+                        .index(2)
+                        .help("Address to print the proof of."),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("set-allowlist")
+                .about("Limit swaps to the addresses of a CSV, signed by the manager")
+                .arg(
+                    Arg::with_name("pool")
+                        .validator(is_pubkey)
+                        .value_name("POOL")
                         .takes_value(true)
                         .required(true)
                         .index(1)
-                        .help("Sender token public key."),
+                        .help("Pool public key."),
                 )
                 .arg(
-                    Arg::with_name("recipient")
+                    Arg::with_name("csv")
+                        .value_name("CSV")
+                        .takes_value(true)
+                        .index(2)
+                        .help("Addresses in the first column. Anyone can swap without it."),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("sign-permit")
+                .about("Print a permit letting a user swap, signed by the permit key as owner")
+                .arg(
+                    Arg::with_name("pool")
                         .validator(is_pubkey)
-                        .value_name("RECIPIENT")
+                        .value_name("POOL")
+                        .takes_value(true)
+                        .required(true)
+                        .index(1)
+                        .help("Pool public key."),
+                )
+                .arg(
+                    Arg::with_name("user")
+                        .validator(is_pubkey)
+                        .value_name("USER")
                         .takes_value(true)
                         .required(true)
                         .index(2)
-                        .help("Recipient token public key."),
+                        .help("Owner of the tokens swapped with the permit."),
                 )
                 .arg(
-                    Arg::with_name("amount_in")
+                    Arg::with_name("max_amount")
                         .validator(is_amount)
-                        .value_name("AMOUNT_IN")
+                        .value_name("MAX_AMOUNT")
                         .takes_value(true)
                         .required(true)
                         .index(3)
-                        .help("Amount of tokens for swap."),
+                        .help("Most bank tokens the swap can take."),
+                )
+                .arg(
+                    Arg::with_name("valid_for")
+                        .long("valid-for")
+                        .validator(is_parsable::<u64>)
+                        .value_name("SLOTS")
+                        .takes_value(true)
+                        .default_value("150")
+                        .help("Slots from now the permit can be used in."),
                 )
+                .arg(
+                    Arg::with_name("nonce")
+                        .long("nonce")
+                        .validator(is_parsable::<u64>)
+                        .value_name("NONCE")
+                        .takes_value(true)
+                        .help("Number used once per pool, the current time by default."),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("set-permit-key")
+                .about("Require permits signed by a key for swaps, signed by the manager")
                 .arg(
                     Arg::with_name("pool")
                         .validator(is_pubkey)
                         .value_name("POOL")
                         .takes_value(true)
                         .required(true)
-                        .index(4)
+                        .index(1)
+                        .help("Pool public key."),
+                )
+                .arg(
+                    Arg::with_name("permit_key")
+                        .validator(is_pubkey)
+                        .value_name("KEY")
+                        .takes_value(true)
+                        .index(2)
+                        .help("Key signing the permits. Anyone can swap without it."),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("set-deposit-limit")
+                .about("Limit the bank tokens each user can swap in, signed by the manager")
+                .arg(
+                    Arg::with_name("pool")
+                        .validator(is_pubkey)
+                        .value_name("POOL")
+                        .takes_value(true)
+                        .required(true)
+                        .index(1)
+                        .help("Pool public key."),
+                )
+                .arg(
+                    Arg::with_name("deposit_limit")
+                        .validator(is_amount)
+                        .value_name("AMOUNT")
+                        .takes_value(true)
+                        .required(true)
+                        .index(2)
+                        .help("Most bank tokens a user can swap in with fees. 0 lifts the limit."),
+                )
+                .arg(
+                    Arg::with_name("per_epoch")
+                        .long("per-epoch")
+                        .takes_value(false)
+                        .help("Start the limit over every epoch instead of the pool lifetime."),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("close-deposit-record")
+                .about("Close the deposit record of the owner once it no longer counts")
+                .arg(
+                    Arg::with_name("pool")
+                        .validator(is_pubkey)
+                        .value_name("POOL")
+                        .takes_value(true)
+                        .required(true)
+                        .index(1)
+                        .help("Pool public key."),
+                )
+                .arg(
+                    Arg::with_name("destination")
+                        .long("destination")
+                        .validator(is_pubkey)
+                        .value_name("ADDRESS")
+                        .takes_value(true)
+                        .help("Account receiving the rent. Defaults to the owner."),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("set-swap-limits")
+                .about("Cap the pool token supply and the amount of a swap, signed by the manager")
+                .arg(
+                    Arg::with_name("pool")
+                        .validator(is_pubkey)
+                        .value_name("POOL")
+                        .takes_value(true)
+                        .required(true)
+                        .index(1)
+                        .help("Pool public key."),
+                )
+                .arg(
+                    Arg::with_name("supply_cap")
+                        .long("supply-cap")
+                        .validator(is_amount)
+                        .value_name("AMOUNT")
+                        .takes_value(true)
+                        .default_value("0")
+                        .help("Most pool tokens in supply after a swap. 0 for no cap."),
+                )
+                .arg(
+                    Arg::with_name("min_amount_in")
+                        .long("min-amount-in")
+                        .validator(is_amount)
+                        .value_name("AMOUNT")
+                        .takes_value(true)
+                        .default_value("0")
+                        .help("Least bank tokens a swap takes, fee included."),
+                )
+                .arg(
+                    Arg::with_name("max_amount_in")
+                        .long("max-amount-in")
+                        .validator(is_amount)
+                        .value_name("AMOUNT")
+                        .takes_value(true)
+                        .default_value("0")
+                        .help("Most bank tokens a swap takes, fee included. 0 for no maximum."),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("set-sale")
+                .about("Limit swaps of an empty pool to a sale window, signed by the manager")
+                .arg(
+                    Arg::with_name("pool")
+                        .validator(is_pubkey)
+                        .value_name("POOL")
+                        .takes_value(true)
+                        .required(true)
+                        .index(1)
+                        .help("Pool public key."),
+                )
+                .arg(
+                    Arg::with_name("end")
+                        .validator(is_parsable::<i64>)
+                        .value_name("UNIX_TIMESTAMP")
+                        .takes_value(true)
+                        .required(true)
+                        .index(2)
+                        .help("Time swaps close at. 0 removes a sale that has not started."),
+                )
+                .arg(
+                    Arg::with_name("start")
+                        .long("start")
+                        .validator(is_parsable::<i64>)
+                        .value_name("UNIX_TIMESTAMP")
+                        .takes_value(true)
+                        .help("Time swaps open at. Defaults to now."),
+                )
+                .arg(
+                    Arg::with_name("hard_cap")
+                        .long("hard-cap")
+                        .validator(is_amount)
+                        .value_name("AMOUNT")
+                        .takes_value(true)
+                        .default_value("0")
+                        .help("Most pool tokens the sale sells, set as the supply cap."),
+                )
+                .arg(
+                    Arg::with_name("soft_cap_bps")
+                        .long("soft-cap-bps")
+                        .validator(is_bps)
+                        .value_name("BPS")
+                        .takes_value(true)
+                        .default_value("0")
+                        .help("Share of the hard cap the sale has to sell, or it refunds."),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("withdraw-proceeds")
+                .about("Withdraw the bank of a sale that met its soft cap, signed by the manager")
+                .arg(
+                    Arg::with_name("pool")
+                        .validator(is_pubkey)
+                        .value_name("POOL")
+                        .takes_value(true)
+                        .required(true)
+                        .index(1)
+                        .help("Pool public key."),
+                )
+                .arg(
+                    Arg::with_name("destination")
+                        .validator(is_pubkey)
+                        .value_name("TOKEN_ACCOUNT")
+                        .takes_value(true)
+                        .required(true)
+                        .index(2)
+                        .help("Bank mint token account receiving the proceeds."),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("withdraw-rewards")
+                .about("Withdraw the rewards left once the APR is 0, signed by the manager")
+                .arg(
+                    Arg::with_name("pool")
+                        .validator(is_pubkey)
+                        .value_name("POOL")
+                        .takes_value(true)
+                        .required(true)
+                        .index(1)
                         .help("Pool public key."),
+                )
+                .arg(
+                    Arg::with_name("destination")
+                        .validator(is_pubkey)
+                        .value_name("TOKEN_ACCOUNT")
+                        .takes_value(true)
+                        .required(true)
+                        .index(2)
+                        .help("Bank mint token account receiving the rewards."),
                 ),
         )
         .get_matches();
@@ -339,14 +2959,213 @@ fn main() {
     let _ = match matches.subcommand() {
         ("create-pool", Some(arg_matches)) => {
             let bank_mint = pubkey_of(arg_matches, "bank_mint").unwrap();
-            command_create_pool(&config, &bank_mint)
+            let index = value_of::<u64>(arg_matches, "index").unwrap();
+            let (rate_numerator, rate_denominator) =
+                parse_rate(arg_matches.value_of("rate").unwrap()).unwrap();
+            let fee_bps = value_of::<u16>(arg_matches, "fee_bps").unwrap();
+            let fee_receiver = pubkey_of(arg_matches, "fee_receiver");
+            let curve = parse_curve(arg_matches.value_of("curve").unwrap()).unwrap();
+            command_create_pool(
+                &config,
+                &bank_mint,
+                index,
+                rate_numerator,
+                rate_denominator,
+                fee_bps,
+                fee_receiver,
+                curve,
+            )
         }
         ("swap", Some(arg_matches)) => {
             let sender = pubkey_of(arg_matches, "sender").unwrap();
             let recipient = pubkey_of(arg_matches, "recipient").unwrap();
             let amount_in = value_of::<f64>(arg_matches, "amount_in").unwrap();
+            let pool = pool_of(arg_matches);
+            let exact_out = arg_matches.is_present("exact_out");
+            let slippage_bps = value_of::<u16>(arg_matches, "slippage_bps").unwrap();
+            command_swap(
+                &config,
+                &pool,
+                &sender,
+                &recipient,
+                amount_in,
+                exact_out,
+                slippage_bps,
+                arg_matches.value_of("allowlist"),
+                arg_matches.value_of("permit"),
+            )
+        }
+        ("redeem", Some(arg_matches)) => {
+            let sender = pubkey_of(arg_matches, "sender").unwrap();
+            let recipient = pubkey_of(arg_matches, "recipient").unwrap();
+            let amount_in = value_of::<f64>(arg_matches, "amount_in").unwrap();
+            let pool = pool_of(arg_matches);
+            command_redeem(&config, &pool, &sender, &recipient, amount_in)
+        }
+        ("propose-manager", Some(arg_matches)) => {
+            let pool = pubkey_of(arg_matches, "pool").unwrap();
+            let new_manager = pubkey_of(arg_matches, "new_manager").unwrap();
+            command_propose_manager(&config, &pool, &new_manager)
+        }
+        ("accept-manager", Some(arg_matches)) => {
+            let pool = pubkey_of(arg_matches, "pool").unwrap();
+            command_accept_manager(&config, &pool)
+        }
+        ("pause", Some(arg_matches)) => {
+            let pool = pubkey_of(arg_matches, "pool").unwrap();
+            command_set_paused(&config, &pool, true)
+        }
+        ("unpause", Some(arg_matches)) => {
+            let pool = pubkey_of(arg_matches, "pool").unwrap();
+            command_set_paused(&config, &pool, false)
+        }
+        ("migrate", Some(arg_matches)) => {
+            let pool = pubkey_of(arg_matches, "pool").unwrap();
+            command_migrate(&config, &pool)
+        }
+        ("close-pool", Some(arg_matches)) => {
+            let pool = pubkey_of(arg_matches, "pool").unwrap();
+            let destination = pubkey_of(arg_matches, "destination");
+            command_close_pool(&config, &pool, destination)
+        }
+        ("deposit-rewards", Some(arg_matches)) => {
+            let pool = pubkey_of(arg_matches, "pool").unwrap();
+            let source = pubkey_of(arg_matches, "source").unwrap();
+            let amount = value_of::<f64>(arg_matches, "amount").unwrap();
+            command_deposit_rewards(&config, &pool, &source, amount)
+        }
+        ("refresh", Some(arg_matches)) => {
+            let pool = pubkey_of(arg_matches, "pool").unwrap();
+            command_refresh(&config, &pool)
+        }
+        ("set-apr", Some(arg_matches)) => {
+            let pool = pubkey_of(arg_matches, "pool").unwrap();
+            let apr_bps = value_of::<u16>(arg_matches, "apr_bps").unwrap();
+            command_set_apr(&config, &pool, apr_bps)
+        }
+        ("set-oracle", Some(arg_matches)) => {
+            let pool = pubkey_of(arg_matches, "pool").unwrap();
+            let oracle = pubkey_of(arg_matches, "oracle");
+            let max_age = value_of::<u64>(arg_matches, "max_age").unwrap();
+            command_set_oracle(&config, &pool, oracle, max_age)
+        }
+        ("initialize-reserve", Some(arg_matches)) => {
+            let pool = pubkey_of(arg_matches, "pool").unwrap();
+            let reserve_mint = pubkey_of(arg_matches, "reserve_mint").unwrap();
+            command_initialize_reserve(&config, &pool, &reserve_mint)
+        }
+        ("add-liquidity", Some(arg_matches)) => {
+            let pool = pubkey_of(arg_matches, "pool").unwrap();
+            let bank_source = pubkey_of(arg_matches, "bank_source").unwrap();
+            let reserve_source = pubkey_of(arg_matches, "reserve_source").unwrap();
+            let recipient = pubkey_of(arg_matches, "recipient").unwrap();
+            let bank_amount = value_of::<f64>(arg_matches, "bank_amount").unwrap();
+            let reserve_amount = value_of::<f64>(arg_matches, "reserve_amount").unwrap();
+            let slippage_bps = value_of::<u16>(arg_matches, "slippage_bps").unwrap();
+            command_add_liquidity(
+                &config,
+                &pool,
+                &bank_source,
+                &reserve_source,
+                &recipient,
+                bank_amount,
+                reserve_amount,
+                slippage_bps,
+            )
+        }
+        ("remove-liquidity", Some(arg_matches)) => {
+            let pool = pubkey_of(arg_matches, "pool").unwrap();
+            let sender = pubkey_of(arg_matches, "sender").unwrap();
+            let bank_recipient = pubkey_of(arg_matches, "bank_recipient").unwrap();
+            let reserve_recipient = pubkey_of(arg_matches, "reserve_recipient").unwrap();
+            let pool_amount = value_of::<f64>(arg_matches, "pool_amount").unwrap();
+            let slippage_bps = value_of::<u16>(arg_matches, "slippage_bps").unwrap();
+            command_remove_liquidity(
+                &config,
+                &pool,
+                &sender,
+                &bank_recipient,
+                &reserve_recipient,
+                pool_amount,
+                slippage_bps,
+            )
+        }
+        ("swap-reserves", Some(arg_matches)) => {
+            let pool = pubkey_of(arg_matches, "pool").unwrap();
+            let source = pubkey_of(arg_matches, "source").unwrap();
+            let destination = pubkey_of(arg_matches, "destination").unwrap();
+            let amount_in = value_of::<f64>(arg_matches, "amount_in").unwrap();
+            let slippage_bps = value_of::<u16>(arg_matches, "slippage_bps").unwrap();
+            command_swap_reserves(
+                &config,
+                &pool,
+                &source,
+                &destination,
+                amount_in,
+                slippage_bps,
+            )
+        }
+        ("allowlist", Some(arg_matches)) => {
+            let csv = arg_matches.value_of("csv").unwrap();
+            let address = pubkey_of(arg_matches, "address");
+            command_allowlist(csv, address)
+        }
+        ("set-allowlist", Some(arg_matches)) => {
+            let pool = pubkey_of(arg_matches, "pool").unwrap();
+            command_set_allowlist(&config, &pool, arg_matches.value_of("csv"))
+        }
+        ("sign-permit", Some(arg_matches)) => {
+            let pool = pubkey_of(arg_matches, "pool").unwrap();
+            let user = pubkey_of(arg_matches, "user").unwrap();
+            let max_amount = value_of::<f64>(arg_matches, "max_amount").unwrap();
+            let valid_for = value_of::<u64>(arg_matches, "valid_for").unwrap();
+            let nonce = value_of::<u64>(arg_matches, "nonce");
+            command_sign_permit(&config, &pool, &user, max_amount, valid_for, nonce)
+        }
+        ("set-permit-key", Some(arg_matches)) => {
+            let pool = pubkey_of(arg_matches, "pool").unwrap();
+            let permit_key = pubkey_of(arg_matches, "permit_key");
+            command_set_permit_key(&config, &pool, permit_key)
+        }
+        ("set-deposit-limit", Some(arg_matches)) => {
+            let pool = pubkey_of(arg_matches, "pool").unwrap();
+            let deposit_limit = value_of::<f64>(arg_matches, "deposit_limit").unwrap();
+            let period = if arg_matches.is_present("per_epoch") {
+                DepositPeriod::Epoch
+            } else {
+                DepositPeriod::Lifetime
+            };
+            command_set_deposit_limit(&config, &pool, deposit_limit, period)
+        }
+        ("close-deposit-record", Some(arg_matches)) => {
+            let pool = pubkey_of(arg_matches, "pool").unwrap();
+            let destination = pubkey_of(arg_matches, "destination");
+            command_close_deposit_record(&config, &pool, destination)
+        }
+        ("set-swap-limits", Some(arg_matches)) => {
+            let pool = pubkey_of(arg_matches, "pool").unwrap();
+            let supply_cap = value_of::<f64>(arg_matches, "supply_cap").unwrap();
+            let min_amount_in = value_of::<f64>(arg_matches, "min_amount_in").unwrap();
+            let max_amount_in = value_of::<f64>(arg_matches, "max_amount_in").unwrap();
+            command_set_swap_limits(&config, &pool, supply_cap, min_amount_in, max_amount_in)
+        }
+        ("set-sale", Some(arg_matches)) => {
+            let pool = pubkey_of(arg_matches, "pool").unwrap();
+            let start = value_of::<i64>(arg_matches, "start");
+            let end = value_of::<i64>(arg_matches, "end").unwrap();
+            let hard_cap = value_of::<f64>(arg_matches, "hard_cap").unwrap();
+            let soft_cap_bps = value_of::<u16>(arg_matches, "soft_cap_bps").unwrap();
+            command_set_sale(&config, &pool, start, end, soft_cap_bps, hard_cap)
+        }
+        ("withdraw-proceeds", Some(arg_matches)) => {
+            let pool = pubkey_of(arg_matches, "pool").unwrap();
+            let destination = pubkey_of(arg_matches, "destination").unwrap();
+            command_withdraw_proceeds(&config, &pool, &destination)
+        }
+        ("withdraw-rewards", Some(arg_matches)) => {
             let pool = pubkey_of(arg_matches, "pool").unwrap();
-            command_swap(&config, &pool, &sender, &recipient, amount_in)
+            let destination = pubkey_of(arg_matches, "destination").unwrap();
+            command_withdraw_rewards(&config, &pool, &destination)
         }
         _ => unreachable!(),
     }
@@ -354,7 +3173,13 @@ fn main() {
         if let Some(tx) = tx {
             let signature = config
                 .rpc_client
-                .send_and_confirm_transaction_with_spinner(&tx)?;
+                .send_and_confirm_transaction_with_spinner(&tx)
+                .map_err(|err| -> Error {
+                    match get_pool_error(&err) {
+                        Some(pool_error) => format!("Pool error: {}", pool_error).into(),
+                        None => err.into(),
+                    }
+                })?;
             println!("Signature: {}", signature);
         }
         Ok(())
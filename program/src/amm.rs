@@ -0,0 +1,157 @@
+//! Constant product math of two-sided pools
+
+use crate::{
+    error::PoolError,
+    math::{checked_add, checked_sub, mul_div, Rounding},
+    state::PoolData,
+};
+
+/// Balances of a pool holding reserves of the bank mint and of the reserve mint
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ConstantProduct {
+    /// Supply of the pool mint, shares of both reserves
+    pub pool_supply: u64,
+
+    /// Bank tokens held by the pool
+    pub bank_balance: u64,
+
+    /// Reserve mint tokens held by the pool
+    pub reserve_balance: u64,
+}
+
+/// Token amounts moved by adding or removing liquidity
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LiquidityAmounts {
+    /// Pool tokens minted or burned
+    pub pool_amount: u64,
+
+    /// Bank tokens deposited or paid out
+    pub bank_amount: u64,
+
+    /// Reserve mint tokens deposited or paid out
+    pub reserve_amount: u64,
+}
+
+/// Token amounts moved by a swap between the reserves
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TradeAmounts {
+    /// Part of the amount in kept by the pool for the liquidity providers
+    pub fee: u64,
+
+    /// Tokens paid out of the other reserve
+    pub amount_out: u64,
+}
+
+impl ConstantProduct {
+    /// Calculates the pool tokens minted for depositing at most the given amounts
+    ///
+    /// The first deposit sets the price and mints pool tokens for the bank tokens at
+    /// `rate_numerator / rate_denominator`. Later deposits mint the smaller share of
+    /// the two sides, rounded down, and take the amounts it is worth, rounded up
+    pub fn calc_deposit(
+        &self,
+        bank_amount: u64,
+        reserve_amount: u64,
+        rate_numerator: u64,
+        rate_denominator: u64,
+    ) -> Result<LiquidityAmounts, PoolError> {
+        if self.pool_supply == 0 {
+            // Depositing one side only would leave the price at zero or infinity
+            if bank_amount == 0 || reserve_amount == 0 {
+                return Err(PoolError::ZeroAmountOut);
+            }
+
+            return Ok(LiquidityAmounts {
+                pool_amount: mul_div(
+                    bank_amount,
+                    rate_numerator,
+                    rate_denominator,
+                    Rounding::Down,
+                )?,
+                bank_amount,
+                reserve_amount,
+            });
+        }
+
+        let pool_amount = mul_div(
+            bank_amount,
+            self.pool_supply,
+            self.bank_balance,
+            Rounding::Down,
+        )?
+        .min(mul_div(
+            reserve_amount,
+            self.pool_supply,
+            self.reserve_balance,
+            Rounding::Down,
+        )?);
+
+        Ok(LiquidityAmounts {
+            pool_amount,
+            bank_amount: mul_div(
+                pool_amount,
+                self.bank_balance,
+                self.pool_supply,
+                Rounding::Up,
+            )?,
+            reserve_amount: mul_div(
+                pool_amount,
+                self.reserve_balance,
+                self.pool_supply,
+                Rounding::Up,
+            )?,
+        })
+    }
+
+    /// Calculates the tokens paid out for burning pool tokens, rounded down
+    pub fn calc_withdraw(&self, pool_amount: u64) -> Result<LiquidityAmounts, PoolError> {
+        if pool_amount > self.pool_supply {
+            return Err(PoolError::MathOverflow);
+        }
+
+        Ok(LiquidityAmounts {
+            pool_amount,
+            bank_amount: mul_div(
+                pool_amount,
+                self.bank_balance,
+                self.pool_supply,
+                Rounding::Down,
+            )?,
+            reserve_amount: mul_div(
+                pool_amount,
+                self.reserve_balance,
+                self.pool_supply,
+                Rounding::Down,
+            )?,
+        })
+    }
+
+    /// Calculates the tokens paid for `amount_in` sent to the reserve holding
+    /// `balance_in`, keeping `balance_in * balance_out` from decreasing
+    ///
+    /// The fee is rounded up and the amount out down, both in favour of the pool
+    pub fn calc_trade(
+        balance_in: u64,
+        balance_out: u64,
+        amount_in: u64,
+        fee_bps: u16,
+    ) -> Result<TradeAmounts, PoolError> {
+        let fee = mul_div(
+            amount_in,
+            fee_bps as u64,
+            PoolData::BPS_DENOMINATOR,
+            Rounding::Up,
+        )?;
+        let amount_in = checked_sub(amount_in, fee)?;
+
+        // balance_out - balance_in * balance_out / (balance_in + amount_in)
+        let amount_out = mul_div(
+            balance_out,
+            amount_in,
+            checked_add(balance_in, amount_in)?,
+            Rounding::Down,
+        )?;
+
+        Ok(TradeAmounts { fee, amount_out })
+    }
+}
@@ -0,0 +1,248 @@
+//! Bonding curves pricing pool tokens by the pool mint supply
+
+use crate::{
+    error::PoolError,
+    math::{checked_pow_wad, Rounding, WAD},
+    state::PoolData,
+};
+use borsh::{BorshDeserialize, BorshSchema, BorshSerialize};
+use std::convert::TryFrom;
+
+/// Price curve of pool tokens
+///
+/// Prices are bank tokens per pool token scaled by `PRICE_SCALE`, and supplies are
+/// amounts of pool tokens
+// The schema derive copies the variant fields into structs it never reads
+#[derive(Clone, Copy, Debug, Default, BorshSerialize, BorshDeserialize, BorshSchema, PartialEq)]
+pub enum CurveType {
+    /// Pool tokens are shares of the bank, minted at the exchange rate
+    #[default]
+    Constant,
+
+    /// Price grows by `slope` for every pool token minted
+    Linear {
+        /// Price of the first pool token
+        #[allow(dead_code)]
+        base_price: u64,
+        /// Price increase per pool token
+        #[allow(dead_code)]
+        slope: u64,
+    },
+
+    /// Price grows by `growth_bps` basis points, compounded, every `step_size` pool tokens
+    Exponential {
+        /// Price of the first step
+        #[allow(dead_code)]
+        base_price: u64,
+        /// Price increase per step in basis points
+        #[allow(dead_code)]
+        growth_bps: u64,
+        /// Pool tokens sold at the same price
+        #[allow(dead_code)]
+        step_size: u64,
+    },
+
+    /// Price grows by `increment` every `step_size` pool tokens
+    Step {
+        /// Price of the first step
+        #[allow(dead_code)]
+        base_price: u64,
+        /// Pool tokens sold at the same price
+        #[allow(dead_code)]
+        step_size: u64,
+        /// Price increase per step
+        #[allow(dead_code)]
+        increment: u64,
+    },
+}
+
+impl CurveType {
+    /// Prices are scaled by this, so `PRICE_SCALE` is one bank token per pool token
+    pub const PRICE_SCALE: u64 = 1_000_000_000;
+
+    /// Checks that the curve prices every pool token
+    pub fn is_valid(&self) -> bool {
+        match *self {
+            CurveType::Constant => true,
+            CurveType::Linear { base_price, .. } => base_price > 0,
+            CurveType::Exponential {
+                base_price,
+                step_size,
+                ..
+            }
+            | CurveType::Step {
+                base_price,
+                step_size,
+                ..
+            } => base_price > 0 && step_size > 0,
+        }
+    }
+
+    /// Calculates bank tokens paid along the curve from `from_supply` to `to_supply`
+    ///
+    /// Rounded up it's the cost of minting, rounded down the value of burning
+    pub fn integral(
+        &self,
+        from_supply: u64,
+        to_supply: u64,
+        rounding: Rounding,
+    ) -> Result<u64, PoolError> {
+        if from_supply > to_supply {
+            return Err(PoolError::MathOverflow);
+        }
+
+        let (to_rounding, from_rounding) = match rounding {
+            Rounding::Up => (Rounding::Up, Rounding::Down),
+            Rounding::Down => (Rounding::Down, Rounding::Up),
+        };
+        let to = self.cumulative(to_supply, to_rounding)?;
+        let from = self.cumulative(from_supply, from_rounding)?;
+
+        u64::try_from(to.saturating_sub(from)).map_err(|_| PoolError::MathOverflow)
+    }
+
+    /// Calculates the most pool tokens minted above `supply` for the bank tokens
+    pub fn calc_pool_amount(&self, supply: u64, bank_amount: u64) -> Result<u64, PoolError> {
+        // The cheapest pool token costs at least the base price
+        let base_price = match *self {
+            CurveType::Constant => return Err(PoolError::InvalidCurve),
+            CurveType::Linear { base_price, .. }
+            | CurveType::Exponential { base_price, .. }
+            | CurveType::Step { base_price, .. } => base_price,
+        };
+        let most = (bank_amount as u128 * Self::PRICE_SCALE as u128 / base_price as u128)
+            .min((u64::MAX - supply) as u128) as u64;
+
+        // Costs grow with the amount, so search for the last affordable one
+        let (mut low, mut high) = (0, most);
+        while low < high {
+            let middle = high - (high - low) / 2;
+            let affordable = match self.integral(supply, supply + middle, Rounding::Up) {
+                Ok(cost) => cost <= bank_amount,
+                Err(_) => false,
+            };
+            if affordable {
+                low = middle;
+            } else {
+                high = middle - 1;
+            }
+        }
+
+        Ok(low)
+    }
+
+    /// Calculates bank tokens paid along the curve for the first `supply` pool tokens
+    fn cumulative(&self, supply: u64, rounding: Rounding) -> Result<u128, PoolError> {
+        let supply = supply as u128;
+        let scale = Self::PRICE_SCALE as u128;
+
+        match *self {
+            CurveType::Constant => Err(PoolError::InvalidCurve),
+            CurveType::Linear { base_price, slope } => {
+                // base_price * supply + slope * supply^2 / 2
+                let doubled = (2 * base_price as u128)
+                    .checked_mul(supply)
+                    .and_then(|base| {
+                        (slope as u128)
+                            .checked_mul(supply)?
+                            .checked_mul(supply)?
+                            .checked_add(base)
+                    })
+                    .ok_or(PoolError::MathOverflow)?;
+                Ok(div_rounding(doubled, 2 * scale, rounding))
+            }
+            CurveType::Exponential {
+                base_price,
+                growth_bps,
+                step_size,
+            } => {
+                let steps = supply / step_size as u128;
+                let rest = supply % step_size as u128;
+                if growth_bps == 0 {
+                    return Ok(div_rounding(
+                        (base_price as u128)
+                            .checked_mul(supply)
+                            .ok_or(PoolError::MathOverflow)?,
+                        scale,
+                        rounding,
+                    ));
+                }
+
+                // Each step costs (1 + growth)^step times the first one, and the steps
+                // sum up to step_size * ((1 + growth)^steps - 1) / growth
+                let growth = growth_bps as u128 * WAD / PoolData::BPS_DENOMINATOR as u128;
+                let factor = checked_pow_wad(
+                    WAD + growth,
+                    u64::try_from(steps).map_err(|_| PoolError::MathOverflow)?,
+                    rounding,
+                )?;
+
+                let steps_total = (factor - WAD)
+                    .checked_mul(step_size as u128)
+                    .and_then(|total| total.checked_mul(PoolData::BPS_DENOMINATOR as u128))
+                    .map(|total| total / growth_bps as u128)
+                    .ok_or(PoolError::MathOverflow)?;
+                let rest_total = rest.checked_mul(factor).ok_or(PoolError::MathOverflow)?;
+                let supply_price = div_rounding(
+                    steps_total
+                        .checked_add(rest_total)
+                        .ok_or(PoolError::MathOverflow)?,
+                    WAD,
+                    rounding,
+                );
+
+                Ok(div_rounding(
+                    supply_price
+                        .checked_mul(base_price as u128)
+                        .ok_or(PoolError::MathOverflow)?,
+                    scale,
+                    rounding,
+                ))
+            }
+            CurveType::Step {
+                base_price,
+                step_size,
+                increment,
+            } => {
+                let steps = supply / step_size as u128;
+                let rest = supply % step_size as u128;
+
+                // Full steps cost step_size * (base_price * steps + increment * steps * (steps - 1) / 2),
+                // the rest is sold at the price of the current step
+                let increments = steps
+                    .checked_mul(steps.saturating_sub(1))
+                    .map(|product| product / 2)
+                    .and_then(|product| product.checked_mul(increment as u128))
+                    .ok_or(PoolError::MathOverflow)?;
+                let steps_total = (base_price as u128)
+                    .checked_mul(steps)
+                    .and_then(|total| total.checked_add(increments))
+                    .and_then(|total| total.checked_mul(step_size as u128))
+                    .ok_or(PoolError::MathOverflow)?;
+                let rest_total = (increment as u128)
+                    .checked_mul(steps)
+                    .and_then(|price| price.checked_add(base_price as u128))
+                    .and_then(|price| price.checked_mul(rest))
+                    .ok_or(PoolError::MathOverflow)?;
+
+                Ok(div_rounding(
+                    steps_total
+                        .checked_add(rest_total)
+                        .ok_or(PoolError::MathOverflow)?,
+                    scale,
+                    rounding,
+                ))
+            }
+        }
+    }
+}
+
+fn div_rounding(numerator: u128, denominator: u128, rounding: Rounding) -> u128 {
+    let quotient = numerator / denominator;
+    let remainder = numerator % denominator;
+    if rounding == Rounding::Up && remainder > 0 {
+        quotient + 1
+    } else {
+        quotient
+    }
+}
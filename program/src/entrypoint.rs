@@ -2,8 +2,10 @@
 
 #![cfg(all(target_arch = "bpf", not(feature = "no-entrypoint")))]
 
+use crate::error::PoolError;
 use solana_program::{
-    account_info::AccountInfo, entrypoint, entrypoint::ProgramResult, msg, pubkey::Pubkey,
+    account_info::AccountInfo, entrypoint, entrypoint::ProgramResult,
+    program_error::PrintProgramError, pubkey::Pubkey,
 };
 
 entrypoint!(process_instruction);
@@ -15,7 +17,7 @@ fn process_instruction(
     if let Err(error) =
         crate::processor::process_instruction(program_id, accounts, instruction_data)
     {
-        msg!("Error: {:?}", error);
+        error.print::<PoolError>();
         return Err(error);
     }
     Ok(())
@@ -0,0 +1,193 @@
+//! Error types
+
+use num_derive::FromPrimitive;
+use solana_program::{
+    decode_error::DecodeError, msg, program_error::PrintProgramError, program_error::ProgramError,
+};
+use thiserror::Error;
+
+/// Errors that may be returned by the program
+#[derive(Clone, Debug, Eq, Error, FromPrimitive, PartialEq)]
+pub enum PoolError {
+    /// Manager does not match the pool manager
+    #[error("Invalid manager")]
+    InvalidManager,
+
+    /// Signer does not match the proposed pool manager
+    #[error("Invalid pending manager")]
+    InvalidPendingManager,
+
+    /// Pool is paused by the manager
+    #[error("Pool is paused")]
+    PoolPaused,
+
+    /// Authority does not match the pool authority
+    #[error("Invalid authority")]
+    InvalidAuthority,
+
+    /// Bank does not match the pool bank
+    #[error("Invalid bank")]
+    InvalidBank,
+
+    /// Pool mint does not match the pool mint
+    #[error("Invalid pool mint")]
+    InvalidPoolMint,
+
+    /// Fee receiver does not match the pool fee receiver
+    #[error("Invalid fee receiver")]
+    InvalidFeeReceiver,
+
+    /// Rate numerator or denominator is zero
+    #[error("Invalid rate")]
+    InvalidRate,
+
+    /// Fee takes the whole amount
+    #[error("Invalid fee")]
+    InvalidFee,
+
+    /// Calculation overflowed
+    #[error("Math overflow")]
+    MathOverflow,
+
+    /// Pool account is not derived from the bank mint and index
+    #[error("Invalid pool address")]
+    InvalidPoolAddress,
+
+    /// Pool account has an old version and must be migrated
+    #[error("Stale pool version")]
+    StaleVersion,
+
+    /// Pool tokens are outstanding or the bank holds tokens
+    #[error("Pool is not empty")]
+    PoolNotEmpty,
+
+    /// Swap would mint fewer or take more tokens than the user accepts
+    #[error("Exceeded slippage")]
+    ExceededSlippage,
+
+    /// Swap deposit is too small to mint a pool token
+    #[error("Zero amount out")]
+    ZeroAmountOut,
+
+    /// Pool has no pool tokens
+    #[error("Pool is empty")]
+    PoolEmpty,
+
+    /// Oracle does not match the pool oracle or holds an invalid price
+    #[error("Invalid oracle")]
+    InvalidOracle,
+
+    /// Oracle price is older than the pool accepts
+    #[error("Stale oracle price")]
+    StaleOraclePrice,
+
+    /// Curve parameters leave pool tokens unpriced
+    #[error("Invalid curve")]
+    InvalidCurve,
+
+    /// Reserve does not match the pool reserve
+    #[error("Invalid reserve")]
+    InvalidReserve,
+
+    /// Instruction is not supported by constant product pools, or only by them
+    #[error("Invalid pool mode")]
+    InvalidPoolMode,
+
+    /// Flash loan receiver returned without paying back the amount and fee
+    #[error("Flash loan not repaid")]
+    FlashLoanNotRepaid,
+
+    /// Swap of an allowlisted pool is missing a valid proof for the user
+    #[error("Not allowlisted")]
+    NotAllowlisted,
+
+    /// Swap is missing a permit verified for the pool permit key, or exceeds it
+    #[error("Invalid permit")]
+    InvalidPermit,
+
+    /// Permit expiry slot has passed
+    #[error("Permit expired")]
+    PermitExpired,
+
+    /// Permit nonce was used by an earlier swap
+    #[error("Permit already used")]
+    PermitUsed,
+
+    /// Deposit record account does not match the pool and the user
+    #[error("Invalid deposit record")]
+    InvalidDepositRecord,
+
+    /// Swap takes the user deposits over the pool deposit limit
+    #[error("Deposit limit exceeded")]
+    DepositLimitExceeded,
+
+    /// Deposit record still counts against the deposit limit of the pool
+    #[error("Deposit record in use")]
+    DepositRecordInUse,
+
+    /// Minimum amount in is above the maximum
+    #[error("Invalid swap limits")]
+    InvalidSwapLimits,
+
+    /// Swap takes fewer bank tokens than the pool minimum
+    #[error("Amount in below minimum")]
+    AmountInBelowMinimum,
+
+    /// Swap takes more bank tokens than the pool maximum
+    #[error("Amount in above maximum")]
+    AmountInAboveMaximum,
+
+    /// Swap mints more pool tokens than the supply cap leaves
+    #[error("Supply cap exceeded")]
+    SupplyCapExceeded,
+
+    /// Sale window is over or empty, the soft cap is above the hard cap, or the sale
+    /// already started
+    #[error("Invalid sale")]
+    InvalidSale,
+
+    /// Swap is outside the sale window
+    #[error("Sale not active")]
+    SaleNotActive,
+
+    /// Sale has not ended yet
+    #[error("Sale not ended")]
+    SaleNotEnded,
+
+    /// Sale reached the soft cap, depositors can't get refunds
+    #[error("Soft cap met")]
+    SoftCapMet,
+
+    /// Sale missed the soft cap, the manager can't withdraw the proceeds
+    #[error("Soft cap missed")]
+    SoftCapMissed,
+
+    /// Rewards are still paid out as interest, the manager can't withdraw them
+    #[error("Rewards accruing")]
+    RewardsAccruing,
+}
+
+impl From<PoolError> for ProgramError {
+    fn from(e: PoolError) -> Self {
+        ProgramError::Custom(e as u32)
+    }
+}
+
+impl<T> DecodeError<T> for PoolError {
+    fn type_of() -> &'static str {
+        "PoolError"
+    }
+}
+
+impl PrintProgramError for PoolError {
+    fn print<E>(&self)
+    where
+        E: 'static
+            + std::error::Error
+            + DecodeError<E>
+            + PrintProgramError
+            + num_traits::FromPrimitive,
+    {
+        msg!("Error: {}", self);
+    }
+}
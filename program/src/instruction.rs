@@ -1,52 +1,359 @@
 //! Instruction types
 
+use crate::{
+    curve::CurveType, find_deposit_record_address, find_permit_nonce_address, merkle::MerkleHash,
+    permit::Permit, state::DepositPeriod,
+};
 use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::{
+    clock::UnixTimestamp,
     instruction::{AccountMeta, Instruction},
     pubkey::Pubkey,
-    sysvar,
+    system_program, sysvar,
 };
 use spl_token as token;
 
 /// Instructions supported by the program
 #[derive(Debug, BorshDeserialize, BorshSerialize, PartialEq)]
 pub enum PoolInstruction {
-    /// Initializes a new program
-    Initialize,
+    /// Creates and initializes a new pool at the address derived from the bank mint and index
+    Initialize {
+        /// Index of the pool among the pools for the bank mint
+        index: u64,
+        /// Numerator of the pool tokens minted per bank token by an empty pool
+        rate_numerator: u64,
+        /// Denominator of the pool tokens minted per bank token by an empty pool
+        rate_denominator: u64,
+        /// Swap fee in basis points
+        fee_bps: u16,
+        /// Curve pricing pool tokens by the supply
+        curve: CurveType,
+    },
 
     /// Swap tokens
     Swap {
         /// Amount of token IN
         amount_in: u64,
     },
+
+    /// Redeem pool tokens for bank tokens
+    ///
+    /// Pools with an oracle pay at the oracle price and take the oracle after the token
+    /// program. Pools running a sale only redeem as refunds, once the sale ended short of
+    /// the soft cap
+    Redeem {
+        /// Amount of pool tokens IN
+        amount_in: u64,
+    },
+
+    /// Propose a new manager, signed by the current one
+    ProposeManager,
+
+    /// Accept the manager role, signed by the proposed manager
+    AcceptManager,
+
+    /// Pause swaps and redeems, signed by the manager
+    Pause,
+
+    /// Resume swaps and redeems, signed by the manager
+    Unpause,
+
+    /// Move a pool account of an older version into a new account with the current layout
+    ///
+    /// The new account is allocated by the caller, the pool mint and the bank are handed over to
+    /// the authority of the new pool and the old account is closed into it
+    Migrate,
+
+    /// Close an empty pool and its bank, signed by the manager
+    ///
+    /// The pool mint can't be closed, its mint authority is removed instead
+    ClosePool,
+
+    /// Swap tokens, failing if fewer pool tokens than the minimum would be minted
+    SwapWithMinimum {
+        /// Amount of token IN
+        amount_in: u64,
+        /// Least amount of pool tokens OUT
+        minimum_amount_out: u64,
+    },
+
+    /// Swap tokens for exactly the given amount of pool tokens
+    SwapExactOut {
+        /// Amount of pool tokens OUT
+        amount_out: u64,
+        /// Most amount of token IN
+        maximum_amount_in: u64,
+    },
+
+    /// Add bank tokens without minting pool tokens, signed by the manager
+    ///
+    /// Raises the amount of bank tokens each pool token is redeemed for, at once
+    /// or as interest if the pool has an annual rate
+    DepositRewards {
+        /// Amount of token IN
+        amount: u64,
+    },
+
+    /// Accrue interest up to the current slot, anyone may call it
+    Refresh,
+
+    /// Set the annual interest paid out of the rewards, signed by the manager
+    ///
+    /// Interest is accrued at the old rate first
+    SetApr {
+        /// Annual interest in basis points
+        apr_bps: u16,
+    },
+
+    /// Point swaps and redeems at a price account, signed by the manager
+    ///
+    /// Only empty pools change the price source. The default address goes back to the
+    /// share price
+    SetOracle {
+        /// Most slots since the last oracle update the price is accepted for
+        max_age: u64,
+    },
+
+    /// Turn an empty pool into a constant product pool of the bank mint and a
+    /// reserve mint, signed by the manager
+    ///
+    /// Pool tokens become shares of both reserves, only minted by adding liquidity
+    InitializeReserve,
+
+    /// Deposit bank and reserve tokens in proportion to the reserves for pool tokens
+    ///
+    /// The first deposit sets the price and mints pool tokens at the pool rate
+    AddLiquidity {
+        /// Most amount of bank tokens IN
+        bank_amount: u64,
+        /// Most amount of reserve tokens IN
+        reserve_amount: u64,
+        /// Least amount of pool tokens OUT
+        minimum_pool_amount: u64,
+    },
+
+    /// Burn pool tokens for their share of both reserves
+    RemoveLiquidity {
+        /// Amount of pool tokens IN
+        pool_amount: u64,
+        /// Least amount of bank tokens OUT
+        minimum_bank_amount: u64,
+        /// Least amount of reserve tokens OUT
+        minimum_reserve_amount: u64,
+    },
+
+    /// Swap between the bank and the reserve at the constant product price
+    ///
+    /// The direction follows the order of the pool accounts, and the fee is kept
+    /// in the reserves for the liquidity providers
+    SwapReserves {
+        /// Amount of token IN
+        amount_in: u64,
+        /// Least amount of token OUT
+        minimum_amount_out: u64,
+    },
+
+    /// Lend bank tokens to a receiver program within the instruction
+    ///
+    /// The receiver is invoked with `FlashLoanReceive` after the transfer and must
+    /// pay back the amount and the swap fee to the bank before it returns. Loans
+    /// share `fee_bps` with swaps, so pools without a swap fee lend for free
+    FlashLoan {
+        /// Amount of bank tokens lent
+        amount: u64,
+    },
+
+    /// Swap tokens as a user of the pool allowlist, failing if fewer pool tokens
+    /// than the minimum are minted
+    SwapWithProof {
+        /// Amount of token IN
+        amount_in: u64,
+        /// Least amount of pool tokens OUT
+        minimum_amount_out: u64,
+        /// Sibling hashes from the user transfer authority leaf up to the allowlist root
+        proof: Vec<MerkleHash>,
+    },
+
+    /// Limit swaps to the users of a Merkle tree, signed by the manager
+    ///
+    /// The zero root lets anyone swap again
+    UpdateAllowlistRoot {
+        /// Merkle root of the allowed users
+        root: MerkleHash,
+    },
+
+    /// Swap tokens with a permit of the pool permit key, failing if fewer pool tokens
+    /// than the minimum are minted
+    ///
+    /// An Ed25519 program instruction of the transaction must verify the `Permit` of
+    /// the user transfer authority. The permit stands in for an allowlist proof, and
+    /// its nonce account is created to keep it from being used again
+    SwapWithPermit {
+        /// Amount of token IN
+        amount_in: u64,
+        /// Least amount of pool tokens OUT
+        minimum_amount_out: u64,
+        /// Most bank tokens the permit lets the swap take
+        max_amount: u64,
+        /// Last slot the permit is valid in
+        expiry_slot: u64,
+        /// Permit nonce
+        nonce: u64,
+    },
+
+    /// Require a permit signed by the key for every swap, signed by the manager
+    ///
+    /// The default address lets swaps go without a permit again
+    SetPermitKey,
+
+    /// Limit the bank tokens each user can swap in, signed by the manager
+    ///
+    /// Swaps then count the amount in on the `DepositRecord` of the owner of the swapped
+    /// bank tokens, created by the first swap. Zero lifts the limit
+    SetDepositLimit {
+        /// Most bank tokens a user can swap in, fees included
+        deposit_limit: u64,
+        /// Period the limit applies to
+        period: DepositPeriod,
+    },
+
+    /// Close the deposit record of the user for the rent, signed by the user
+    ///
+    /// Only records that no longer count against a limit can be closed
+    CloseDepositRecord,
+
+    /// Cap the pool token supply minted by swaps and limit the bank tokens a single
+    /// swap takes, signed by the manager
+    ///
+    /// Zero leaves the supply cap or the maximum out
+    SetSwapLimits {
+        /// Most pool tokens in supply after a swap
+        supply_cap: u64,
+        /// Least bank tokens a swap takes, fee included
+        min_amount_in: u64,
+        /// Most bank tokens a swap takes, fee included
+        max_amount_in: u64,
+    },
+
+    /// Run a sale, limiting swaps to the window between the timestamps, signed by the manager
+    ///
+    /// Only empty pools at the constant rate without an oracle or a supply cap start a
+    /// sale, and the hard cap becomes the supply cap. Sale swaps pay no fee. Once the sale
+    /// ends, pool tokens are redeemed for refunds of the whole deposits if the supply is
+    /// short of the soft cap, otherwise the manager withdraws the proceeds less the pool
+    /// fee. The sale can't be changed once it started, a zero end removes it and lifts
+    /// the supply cap again
+    SetSale {
+        /// Unix timestamp swaps open at
+        start: UnixTimestamp,
+        /// Unix timestamp swaps close at
+        end: UnixTimestamp,
+        /// Pool tokens the sale has to sell, in basis points of the hard cap
+        soft_cap_bps: u16,
+        /// Most pool tokens the sale sells
+        hard_cap: u64,
+    },
+
+    /// Withdraw the bank of a sale that reached the soft cap, signed by the manager
+    ///
+    /// The pool fee of the bank goes to the fee receiver
+    WithdrawProceeds,
+
+    /// Withdraw the undistributed rewards of a pool without an annual rate, signed by
+    /// the manager
+    ///
+    /// A pool without pool tokens gives up everything left in the bank
+    WithdrawRewards,
+}
+
+/// Instruction data of the receiver program invoked by `FlashLoan`
+///
+/// The receiver gets the destination, the bank and the token program, followed by
+/// the accounts passed after the receiver program to `FlashLoan`
+#[derive(Debug, BorshDeserialize, BorshSerialize, PartialEq)]
+pub struct FlashLoanReceive {
+    /// Amount of bank tokens lent
+    pub amount: u64,
+    /// Fee to pay back on top of the amount
+    pub fee: u64,
 }
 
 /// Creates 'Initialize' instruction.
+#[allow(clippy::too_many_arguments)]
 pub fn initialize(
     program_id: &Pubkey,
     pool: &Pubkey,
     authority: &Pubkey,
+    manager: &Pubkey,
     bank_mint: &Pubkey,
     pool_mint: &Pubkey,
     bank: &Pubkey,
+    fee_receiver: &Pubkey,
+    payer: &Pubkey,
+    index: u64,
+    rate_numerator: u64,
+    rate_denominator: u64,
+    fee_bps: u16,
+    curve: CurveType,
 ) -> Instruction {
     let accounts = vec![
         // General
         // Sys
         // Programs
-        AccountMeta::new(*pool, true),
+        AccountMeta::new(*pool, false),
         AccountMeta::new_readonly(*authority, false),
+        AccountMeta::new_readonly(*manager, true),
         AccountMeta::new_readonly(*bank_mint, false),
         AccountMeta::new(*pool_mint, false),
         AccountMeta::new(*bank, false),
+        AccountMeta::new_readonly(*fee_receiver, false),
+        AccountMeta::new(*payer, true),
         AccountMeta::new_readonly(sysvar::rent::id(), false),
+        AccountMeta::new_readonly(system_program::id(), false),
         AccountMeta::new_readonly(token::id(), false),
     ];
 
-    Instruction::new_with_borsh(*program_id, &PoolInstruction::Initialize, accounts)
+    Instruction::new_with_borsh(
+        *program_id,
+        &PoolInstruction::Initialize {
+            index,
+            rate_numerator,
+            rate_denominator,
+            fee_bps,
+            curve,
+        },
+        accounts,
+    )
+}
+
+/// Deposit record of swaps of pools with a deposit limit
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DepositRecordKeys {
+    /// Owner of the swapped bank tokens, delegated swaps count against the owner
+    pub user: Pubkey,
+    /// Payer of the record created by the first swap
+    pub payer: Pubkey,
+}
+
+/// Accounts appended to swaps of pools with a deposit limit
+fn deposit_record_accounts(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    deposit_record: &DepositRecordKeys,
+) -> Vec<AccountMeta> {
+    let (deposit_record_address, _) =
+        find_deposit_record_address(program_id, pool, &deposit_record.user);
+
+    vec![
+        AccountMeta::new(deposit_record_address, false),
+        AccountMeta::new(deposit_record.payer, true),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ]
 }
 
 /// Creates 'Swap' instruction.
+#[allow(clippy::too_many_arguments)]
 pub fn swap(
     program_id: &Pubkey,
     pool: &Pubkey,
@@ -54,20 +361,810 @@ pub fn swap(
     user_transfer_authority: &Pubkey,
     pool_mint: &Pubkey,
     bank: &Pubkey,
+    fee_receiver: &Pubkey,
+    sender: &Pubkey,
+    recipient: &Pubkey,
+    oracle: Option<&Pubkey>,
+    deposit_record: Option<&DepositRecordKeys>,
+    amount_in: u64,
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new(*pool, false),
+        AccountMeta::new_readonly(*pool_authority, false),
+        AccountMeta::new_readonly(*user_transfer_authority, true),
+        AccountMeta::new(*pool_mint, false),
+        AccountMeta::new(*bank, false),
+        AccountMeta::new(*fee_receiver, false),
+        AccountMeta::new(*sender, false),
+        AccountMeta::new(*recipient, false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new_readonly(token::id(), false),
+    ];
+    if let Some(oracle) = oracle {
+        accounts.push(AccountMeta::new_readonly(*oracle, false));
+    }
+    if let Some(deposit_record) = deposit_record {
+        accounts.extend(deposit_record_accounts(program_id, pool, deposit_record));
+    }
+
+    Instruction::new_with_borsh(*program_id, &PoolInstruction::Swap { amount_in }, accounts)
+}
+
+/// Creates 'SwapWithMinimum' instruction.
+#[allow(clippy::too_many_arguments)]
+pub fn swap_with_minimum(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    pool_authority: &Pubkey,
+    user_transfer_authority: &Pubkey,
+    pool_mint: &Pubkey,
+    bank: &Pubkey,
+    fee_receiver: &Pubkey,
     sender: &Pubkey,
     recipient: &Pubkey,
+    oracle: Option<&Pubkey>,
+    deposit_record: Option<&DepositRecordKeys>,
     amount_in: u64,
+    minimum_amount_out: u64,
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new(*pool, false),
+        AccountMeta::new_readonly(*pool_authority, false),
+        AccountMeta::new_readonly(*user_transfer_authority, true),
+        AccountMeta::new(*pool_mint, false),
+        AccountMeta::new(*bank, false),
+        AccountMeta::new(*fee_receiver, false),
+        AccountMeta::new(*sender, false),
+        AccountMeta::new(*recipient, false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new_readonly(token::id(), false),
+    ];
+    if let Some(oracle) = oracle {
+        accounts.push(AccountMeta::new_readonly(*oracle, false));
+    }
+    if let Some(deposit_record) = deposit_record {
+        accounts.extend(deposit_record_accounts(program_id, pool, deposit_record));
+    }
+
+    Instruction::new_with_borsh(
+        *program_id,
+        &PoolInstruction::SwapWithMinimum {
+            amount_in,
+            minimum_amount_out,
+        },
+        accounts,
+    )
+}
+
+/// Creates 'SwapExactOut' instruction.
+#[allow(clippy::too_many_arguments)]
+pub fn swap_exact_out(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    pool_authority: &Pubkey,
+    user_transfer_authority: &Pubkey,
+    pool_mint: &Pubkey,
+    bank: &Pubkey,
+    fee_receiver: &Pubkey,
+    sender: &Pubkey,
+    recipient: &Pubkey,
+    oracle: Option<&Pubkey>,
+    deposit_record: Option<&DepositRecordKeys>,
+    amount_out: u64,
+    maximum_amount_in: u64,
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new(*pool, false),
+        AccountMeta::new_readonly(*pool_authority, false),
+        AccountMeta::new_readonly(*user_transfer_authority, true),
+        AccountMeta::new(*pool_mint, false),
+        AccountMeta::new(*bank, false),
+        AccountMeta::new(*fee_receiver, false),
+        AccountMeta::new(*sender, false),
+        AccountMeta::new(*recipient, false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new_readonly(token::id(), false),
+    ];
+    if let Some(oracle) = oracle {
+        accounts.push(AccountMeta::new_readonly(*oracle, false));
+    }
+    if let Some(deposit_record) = deposit_record {
+        accounts.extend(deposit_record_accounts(program_id, pool, deposit_record));
+    }
+
+    Instruction::new_with_borsh(
+        *program_id,
+        &PoolInstruction::SwapExactOut {
+            amount_out,
+            maximum_amount_in,
+        },
+        accounts,
+    )
+}
+
+/// Creates 'Redeem' instruction.
+#[allow(clippy::too_many_arguments)]
+pub fn redeem(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    pool_authority: &Pubkey,
+    user_transfer_authority: &Pubkey,
+    pool_mint: &Pubkey,
+    bank: &Pubkey,
+    sender: &Pubkey,
+    recipient: &Pubkey,
+    oracle: Option<&Pubkey>,
+    amount_in: u64,
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new(*pool, false),
+        AccountMeta::new_readonly(*pool_authority, false),
+        AccountMeta::new_readonly(*user_transfer_authority, true),
+        AccountMeta::new(*pool_mint, false),
+        AccountMeta::new(*bank, false),
+        AccountMeta::new(*sender, false),
+        AccountMeta::new(*recipient, false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new_readonly(token::id(), false),
+    ];
+    if let Some(oracle) = oracle {
+        accounts.push(AccountMeta::new_readonly(*oracle, false));
+    }
+
+    Instruction::new_with_borsh(
+        *program_id,
+        &PoolInstruction::Redeem { amount_in },
+        accounts,
+    )
+}
+
+/// Creates 'ProposeManager' instruction.
+pub fn propose_manager(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    manager: &Pubkey,
+    new_manager: &Pubkey,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(*pool, false),
+        AccountMeta::new_readonly(*manager, true),
+        AccountMeta::new_readonly(*new_manager, false),
+    ];
+
+    Instruction::new_with_borsh(*program_id, &PoolInstruction::ProposeManager, accounts)
+}
+
+/// Creates 'AcceptManager' instruction.
+pub fn accept_manager(program_id: &Pubkey, pool: &Pubkey, new_manager: &Pubkey) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(*pool, false),
+        AccountMeta::new_readonly(*new_manager, true),
+    ];
+
+    Instruction::new_with_borsh(*program_id, &PoolInstruction::AcceptManager, accounts)
+}
+
+/// Creates 'Pause' instruction.
+pub fn pause(program_id: &Pubkey, pool: &Pubkey, manager: &Pubkey) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(*pool, false),
+        AccountMeta::new_readonly(*manager, true),
+    ];
+
+    Instruction::new_with_borsh(*program_id, &PoolInstruction::Pause, accounts)
+}
+
+/// Creates 'Unpause' instruction.
+pub fn unpause(program_id: &Pubkey, pool: &Pubkey, manager: &Pubkey) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(*pool, false),
+        AccountMeta::new_readonly(*manager, true),
+    ];
+
+    Instruction::new_with_borsh(*program_id, &PoolInstruction::Unpause, accounts)
+}
+
+/// Creates 'Migrate' instruction.
+pub fn migrate(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    new_pool: &Pubkey,
+    pool_authority: &Pubkey,
+    new_pool_authority: &Pubkey,
+    pool_mint: &Pubkey,
+    bank: &Pubkey,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(*pool, false),
+        AccountMeta::new(*new_pool, false),
+        AccountMeta::new_readonly(*pool_authority, false),
+        AccountMeta::new_readonly(*new_pool_authority, false),
+        AccountMeta::new(*pool_mint, false),
+        AccountMeta::new(*bank, false),
+        AccountMeta::new_readonly(token::id(), false),
+    ];
+
+    Instruction::new_with_borsh(*program_id, &PoolInstruction::Migrate, accounts)
+}
+
+/// Creates 'ClosePool' instruction.
+#[allow(clippy::too_many_arguments)]
+pub fn close_pool(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    pool_authority: &Pubkey,
+    manager: &Pubkey,
+    pool_mint: &Pubkey,
+    bank: &Pubkey,
+    destination: &Pubkey,
+    reserve: Option<&Pubkey>,
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new(*pool, false),
+        AccountMeta::new_readonly(*pool_authority, false),
+        AccountMeta::new_readonly(*manager, true),
+        AccountMeta::new(*pool_mint, false),
+        AccountMeta::new(*bank, false),
+        AccountMeta::new(*destination, false),
+        AccountMeta::new_readonly(token::id(), false),
+    ];
+    if let Some(reserve) = reserve {
+        accounts.push(AccountMeta::new(*reserve, false));
+    }
+
+    Instruction::new_with_borsh(*program_id, &PoolInstruction::ClosePool, accounts)
+}
+
+/// Creates 'DepositRewards' instruction.
+pub fn deposit_rewards(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    manager: &Pubkey,
+    pool_mint: &Pubkey,
+    bank: &Pubkey,
+    source: &Pubkey,
+    amount: u64,
 ) -> Instruction {
     let accounts = vec![
+        AccountMeta::new(*pool, false),
+        AccountMeta::new_readonly(*manager, true),
+        AccountMeta::new_readonly(*pool_mint, false),
+        AccountMeta::new(*bank, false),
+        AccountMeta::new(*source, false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new_readonly(token::id(), false),
+    ];
+
+    Instruction::new_with_borsh(
+        *program_id,
+        &PoolInstruction::DepositRewards { amount },
+        accounts,
+    )
+}
+
+/// Creates 'Refresh' instruction.
+pub fn refresh(program_id: &Pubkey, pool: &Pubkey, bank: &Pubkey) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(*pool, false),
+        AccountMeta::new_readonly(*bank, false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+    ];
+
+    Instruction::new_with_borsh(*program_id, &PoolInstruction::Refresh, accounts)
+}
+
+/// Creates 'SetApr' instruction.
+pub fn set_apr(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    manager: &Pubkey,
+    bank: &Pubkey,
+    apr_bps: u16,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(*pool, false),
+        AccountMeta::new_readonly(*manager, true),
+        AccountMeta::new_readonly(*bank, false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+    ];
+
+    Instruction::new_with_borsh(*program_id, &PoolInstruction::SetApr { apr_bps }, accounts)
+}
+
+/// Creates 'SetOracle' instruction.
+pub fn set_oracle(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    manager: &Pubkey,
+    pool_mint: &Pubkey,
+    oracle: &Pubkey,
+    max_age: u64,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(*pool, false),
+        AccountMeta::new_readonly(*manager, true),
+        AccountMeta::new_readonly(*pool_mint, false),
+        AccountMeta::new_readonly(*oracle, false),
+    ];
+
+    Instruction::new_with_borsh(
+        *program_id,
+        &PoolInstruction::SetOracle { max_age },
+        accounts,
+    )
+}
+
+/// Creates 'InitializeReserve' instruction.
+#[allow(clippy::too_many_arguments)]
+pub fn initialize_reserve(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    authority: &Pubkey,
+    manager: &Pubkey,
+    reserve_mint: &Pubkey,
+    reserve: &Pubkey,
+    pool_mint: &Pubkey,
+    payer: &Pubkey,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(*pool, false),
+        AccountMeta::new_readonly(*authority, false),
+        AccountMeta::new_readonly(*manager, true),
+        AccountMeta::new_readonly(*reserve_mint, false),
+        AccountMeta::new(*reserve, false),
+        AccountMeta::new_readonly(*pool_mint, false),
+        AccountMeta::new(*payer, true),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(token::id(), false),
+    ];
+
+    Instruction::new_with_borsh(*program_id, &PoolInstruction::InitializeReserve, accounts)
+}
+
+/// Creates 'AddLiquidity' instruction.
+#[allow(clippy::too_many_arguments)]
+pub fn add_liquidity(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    pool_authority: &Pubkey,
+    user_transfer_authority: &Pubkey,
+    pool_mint: &Pubkey,
+    bank: &Pubkey,
+    reserve: &Pubkey,
+    bank_source: &Pubkey,
+    reserve_source: &Pubkey,
+    recipient: &Pubkey,
+    bank_amount: u64,
+    reserve_amount: u64,
+    minimum_pool_amount: u64,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(*pool, false),
+        AccountMeta::new_readonly(*pool_authority, false),
+        AccountMeta::new_readonly(*user_transfer_authority, true),
+        AccountMeta::new(*pool_mint, false),
+        AccountMeta::new(*bank, false),
+        AccountMeta::new(*reserve, false),
+        AccountMeta::new(*bank_source, false),
+        AccountMeta::new(*reserve_source, false),
+        AccountMeta::new(*recipient, false),
+        AccountMeta::new_readonly(token::id(), false),
+    ];
+
+    Instruction::new_with_borsh(
+        *program_id,
+        &PoolInstruction::AddLiquidity {
+            bank_amount,
+            reserve_amount,
+            minimum_pool_amount,
+        },
+        accounts,
+    )
+}
+
+/// Creates 'RemoveLiquidity' instruction.
+#[allow(clippy::too_many_arguments)]
+pub fn remove_liquidity(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    pool_authority: &Pubkey,
+    user_transfer_authority: &Pubkey,
+    pool_mint: &Pubkey,
+    bank: &Pubkey,
+    reserve: &Pubkey,
+    sender: &Pubkey,
+    bank_recipient: &Pubkey,
+    reserve_recipient: &Pubkey,
+    pool_amount: u64,
+    minimum_bank_amount: u64,
+    minimum_reserve_amount: u64,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(*pool, false),
+        AccountMeta::new_readonly(*pool_authority, false),
+        AccountMeta::new_readonly(*user_transfer_authority, true),
+        AccountMeta::new(*pool_mint, false),
+        AccountMeta::new(*bank, false),
+        AccountMeta::new(*reserve, false),
+        AccountMeta::new(*sender, false),
+        AccountMeta::new(*bank_recipient, false),
+        AccountMeta::new(*reserve_recipient, false),
+        AccountMeta::new_readonly(token::id(), false),
+    ];
+
+    Instruction::new_with_borsh(
+        *program_id,
+        &PoolInstruction::RemoveLiquidity {
+            pool_amount,
+            minimum_bank_amount,
+            minimum_reserve_amount,
+        },
+        accounts,
+    )
+}
+
+/// Creates 'SwapReserves' instruction.
+///
+/// `swap_source` and `swap_destination` are the bank and the reserve in the order of
+/// the swap, receiving from `source` and paying to `destination`
+#[allow(clippy::too_many_arguments)]
+pub fn swap_reserves(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    pool_authority: &Pubkey,
+    user_transfer_authority: &Pubkey,
+    swap_source: &Pubkey,
+    swap_destination: &Pubkey,
+    source: &Pubkey,
+    destination: &Pubkey,
+    amount_in: u64,
+    minimum_amount_out: u64,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(*pool, false),
+        AccountMeta::new_readonly(*pool_authority, false),
+        AccountMeta::new_readonly(*user_transfer_authority, true),
+        AccountMeta::new(*swap_source, false),
+        AccountMeta::new(*swap_destination, false),
+        AccountMeta::new(*source, false),
+        AccountMeta::new(*destination, false),
+        AccountMeta::new_readonly(token::id(), false),
+    ];
+
+    Instruction::new_with_borsh(
+        *program_id,
+        &PoolInstruction::SwapReserves {
+            amount_in,
+            minimum_amount_out,
+        },
+        accounts,
+    )
+}
+
+/// Creates 'FlashLoan' instruction.
+///
+/// `receiver_accounts` are passed on to the receiver program with their flags
+#[allow(clippy::too_many_arguments)]
+pub fn flash_loan(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    pool_authority: &Pubkey,
+    bank: &Pubkey,
+    destination: &Pubkey,
+    receiver_program: &Pubkey,
+    receiver_accounts: Vec<AccountMeta>,
+    amount: u64,
+) -> Instruction {
+    let mut accounts = vec![
         AccountMeta::new_readonly(*pool, false),
         AccountMeta::new_readonly(*pool_authority, false),
+        AccountMeta::new(*bank, false),
+        AccountMeta::new(*destination, false),
+        AccountMeta::new_readonly(*receiver_program, false),
+        AccountMeta::new_readonly(token::id(), false),
+    ];
+    accounts.extend(receiver_accounts);
+
+    Instruction::new_with_borsh(
+        *program_id,
+        &PoolInstruction::FlashLoan { amount },
+        accounts,
+    )
+}
+
+/// Creates 'SwapWithProof' instruction.
+#[allow(clippy::too_many_arguments)]
+pub fn swap_with_proof(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    pool_authority: &Pubkey,
+    user_transfer_authority: &Pubkey,
+    pool_mint: &Pubkey,
+    bank: &Pubkey,
+    fee_receiver: &Pubkey,
+    sender: &Pubkey,
+    recipient: &Pubkey,
+    oracle: Option<&Pubkey>,
+    deposit_record: Option<&DepositRecordKeys>,
+    amount_in: u64,
+    minimum_amount_out: u64,
+    proof: Vec<MerkleHash>,
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new(*pool, false),
+        AccountMeta::new_readonly(*pool_authority, false),
         AccountMeta::new_readonly(*user_transfer_authority, true),
         AccountMeta::new(*pool_mint, false),
         AccountMeta::new(*bank, false),
+        AccountMeta::new(*fee_receiver, false),
         AccountMeta::new(*sender, false),
         AccountMeta::new(*recipient, false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
         AccountMeta::new_readonly(token::id(), false),
     ];
+    if let Some(oracle) = oracle {
+        accounts.push(AccountMeta::new_readonly(*oracle, false));
+    }
+    if let Some(deposit_record) = deposit_record {
+        accounts.extend(deposit_record_accounts(program_id, pool, deposit_record));
+    }
 
-    Instruction::new_with_borsh(*program_id, &PoolInstruction::Swap { amount_in }, accounts)
+    Instruction::new_with_borsh(
+        *program_id,
+        &PoolInstruction::SwapWithProof {
+            amount_in,
+            minimum_amount_out,
+            proof,
+        },
+        accounts,
+    )
+}
+
+/// Creates 'UpdateAllowlistRoot' instruction.
+pub fn update_allowlist_root(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    manager: &Pubkey,
+    root: MerkleHash,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(*pool, false),
+        AccountMeta::new_readonly(*manager, true),
+    ];
+
+    Instruction::new_with_borsh(
+        *program_id,
+        &PoolInstruction::UpdateAllowlistRoot { root },
+        accounts,
+    )
+}
+
+/// Creates 'SwapWithPermit' instruction.
+///
+/// The permit pool and user are the pool and the user transfer authority, and the
+/// payer funds the account marking the nonce as used
+#[allow(clippy::too_many_arguments)]
+pub fn swap_with_permit(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    pool_authority: &Pubkey,
+    user_transfer_authority: &Pubkey,
+    pool_mint: &Pubkey,
+    bank: &Pubkey,
+    fee_receiver: &Pubkey,
+    sender: &Pubkey,
+    recipient: &Pubkey,
+    oracle: Option<&Pubkey>,
+    payer: &Pubkey,
+    deposit_record: Option<&DepositRecordKeys>,
+    amount_in: u64,
+    minimum_amount_out: u64,
+    permit: &Permit,
+) -> Instruction {
+    let (nonce_address, _) = find_permit_nonce_address(program_id, pool, permit.nonce);
+
+    let mut accounts = vec![
+        AccountMeta::new(*pool, false),
+        AccountMeta::new_readonly(*pool_authority, false),
+        AccountMeta::new_readonly(*user_transfer_authority, true),
+        AccountMeta::new(*pool_mint, false),
+        AccountMeta::new(*bank, false),
+        AccountMeta::new(*fee_receiver, false),
+        AccountMeta::new(*sender, false),
+        AccountMeta::new(*recipient, false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new_readonly(token::id(), false),
+    ];
+    if let Some(oracle) = oracle {
+        accounts.push(AccountMeta::new_readonly(*oracle, false));
+    }
+    accounts.extend(vec![
+        AccountMeta::new_readonly(sysvar::instructions::id(), false),
+        AccountMeta::new(nonce_address, false),
+        AccountMeta::new(*payer, true),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ]);
+    if let Some(deposit_record) = deposit_record {
+        accounts.extend(deposit_record_accounts(program_id, pool, deposit_record));
+    }
+
+    Instruction::new_with_borsh(
+        *program_id,
+        &PoolInstruction::SwapWithPermit {
+            amount_in,
+            minimum_amount_out,
+            max_amount: permit.max_amount,
+            expiry_slot: permit.expiry_slot,
+            nonce: permit.nonce,
+        },
+        accounts,
+    )
+}
+
+/// Creates 'SetPermitKey' instruction.
+pub fn set_permit_key(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    manager: &Pubkey,
+    permit_key: &Pubkey,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(*pool, false),
+        AccountMeta::new_readonly(*manager, true),
+        AccountMeta::new_readonly(*permit_key, false),
+    ];
+
+    Instruction::new_with_borsh(*program_id, &PoolInstruction::SetPermitKey, accounts)
+}
+
+/// Creates 'SetDepositLimit' instruction.
+pub fn set_deposit_limit(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    manager: &Pubkey,
+    deposit_limit: u64,
+    period: DepositPeriod,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(*pool, false),
+        AccountMeta::new_readonly(*manager, true),
+    ];
+
+    Instruction::new_with_borsh(
+        *program_id,
+        &PoolInstruction::SetDepositLimit {
+            deposit_limit,
+            period,
+        },
+        accounts,
+    )
+}
+
+/// Creates 'CloseDepositRecord' instruction.
+pub fn close_deposit_record(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    user: &Pubkey,
+    destination: &Pubkey,
+) -> Instruction {
+    let (deposit_record, _) = find_deposit_record_address(program_id, pool, user);
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*pool, false),
+        AccountMeta::new(deposit_record, false),
+        AccountMeta::new_readonly(*user, true),
+        AccountMeta::new(*destination, false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+    ];
+
+    Instruction::new_with_borsh(*program_id, &PoolInstruction::CloseDepositRecord, accounts)
+}
+
+/// Creates 'SetSwapLimits' instruction.
+pub fn set_swap_limits(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    manager: &Pubkey,
+    supply_cap: u64,
+    min_amount_in: u64,
+    max_amount_in: u64,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(*pool, false),
+        AccountMeta::new_readonly(*manager, true),
+    ];
+
+    Instruction::new_with_borsh(
+        *program_id,
+        &PoolInstruction::SetSwapLimits {
+            supply_cap,
+            min_amount_in,
+            max_amount_in,
+        },
+        accounts,
+    )
+}
+
+/// Creates 'SetSale' instruction.
+#[allow(clippy::too_many_arguments)]
+pub fn set_sale(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    manager: &Pubkey,
+    pool_mint: &Pubkey,
+    start: UnixTimestamp,
+    end: UnixTimestamp,
+    soft_cap_bps: u16,
+    hard_cap: u64,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(*pool, false),
+        AccountMeta::new_readonly(*manager, true),
+        AccountMeta::new_readonly(*pool_mint, false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+    ];
+
+    Instruction::new_with_borsh(
+        *program_id,
+        &PoolInstruction::SetSale {
+            start,
+            end,
+            soft_cap_bps,
+            hard_cap,
+        },
+        accounts,
+    )
+}
+
+/// Creates 'WithdrawProceeds' instruction.
+#[allow(clippy::too_many_arguments)]
+pub fn withdraw_proceeds(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    pool_authority: &Pubkey,
+    manager: &Pubkey,
+    pool_mint: &Pubkey,
+    bank: &Pubkey,
+    destination: &Pubkey,
+    fee_receiver: &Pubkey,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(*pool, false),
+        AccountMeta::new_readonly(*pool_authority, false),
+        AccountMeta::new_readonly(*manager, true),
+        AccountMeta::new_readonly(*pool_mint, false),
+        AccountMeta::new(*bank, false),
+        AccountMeta::new(*destination, false),
+        AccountMeta::new(*fee_receiver, false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new_readonly(token::id(), false),
+    ];
+
+    Instruction::new_with_borsh(*program_id, &PoolInstruction::WithdrawProceeds, accounts)
+}
+
+/// Creates 'WithdrawRewards' instruction.
+pub fn withdraw_rewards(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    pool_authority: &Pubkey,
+    manager: &Pubkey,
+    pool_mint: &Pubkey,
+    bank: &Pubkey,
+    destination: &Pubkey,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(*pool, false),
+        AccountMeta::new_readonly(*pool_authority, false),
+        AccountMeta::new_readonly(*manager, true),
+        AccountMeta::new_readonly(*pool_mint, false),
+        AccountMeta::new(*bank, false),
+        AccountMeta::new(*destination, false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new_readonly(token::id(), false),
+    ];
+
+    Instruction::new_with_borsh(*program_id, &PoolInstruction::WithdrawRewards, accounts)
 }
@@ -2,7 +2,13 @@
 
 //! A program for simple token pool
 
+pub mod amm;
+pub mod curve;
+pub mod error;
 pub mod instruction;
+pub mod math;
+pub mod merkle;
+pub mod permit;
 pub mod processor;
 pub mod state;
 
@@ -14,7 +20,62 @@ use solana_program::pubkey::Pubkey;
 
 solana_program::declare_id!("4fn9mVmVG4kLDi1iacUyGpbGUH9qrisNHHWd9PueJLiw");
 
+/// Seed for the pool mint address
+pub const POOL_MINT_SEED: &[u8] = b"mint";
+
+/// Seed for the bank address
+pub const BANK_SEED: &[u8] = b"bank";
+
+/// Seed for the reserve address of constant product pools
+pub const RESERVE_SEED: &[u8] = b"reserve";
+
+/// Seed for the used nonce addresses of swap permits
+pub const PERMIT_SEED: &[u8] = b"permit";
+
 /// Generates seed bump for stake pool authorities
 pub fn find_authority_bump_seed(program_id: &Pubkey, pool: &Pubkey) -> (Pubkey, u8) {
     Pubkey::find_program_address(&[&pool.to_bytes()[..32]], program_id)
 }
+
+/// Generates the pool address for the bank mint and pool index
+pub fn find_pool_address(program_id: &Pubkey, bank_mint: &Pubkey, index: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[&bank_mint.to_bytes()[..32], &index.to_le_bytes()],
+        program_id,
+    )
+}
+
+/// Generates the pool mint address for the pool
+pub fn find_pool_mint_address(program_id: &Pubkey, pool: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[&pool.to_bytes()[..32], POOL_MINT_SEED], program_id)
+}
+
+/// Generates the bank address for the pool
+pub fn find_bank_address(program_id: &Pubkey, pool: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[&pool.to_bytes()[..32], BANK_SEED], program_id)
+}
+
+/// Generates the reserve address for the pool
+pub fn find_reserve_address(program_id: &Pubkey, pool: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[&pool.to_bytes()[..32], RESERVE_SEED], program_id)
+}
+
+/// Generates the address marking the permit nonce of the pool as used
+pub fn find_permit_nonce_address(program_id: &Pubkey, pool: &Pubkey, nonce: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[&pool.to_bytes()[..32], PERMIT_SEED, &nonce.to_le_bytes()],
+        program_id,
+    )
+}
+
+/// Generates the deposit record address of the user for the pool
+pub fn find_deposit_record_address(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    user: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[&pool.to_bytes()[..32], &user.to_bytes()[..32]],
+        program_id,
+    )
+}
@@ -0,0 +1,119 @@
+//! Checked arithmetic with explicit rounding
+
+use crate::error::PoolError;
+use std::convert::TryFrom;
+
+/// Direction to round the result of a division
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Rounding {
+    /// Round towards zero
+    Down,
+
+    /// Round away from zero
+    Up,
+}
+
+/// Calculates `value * numerator / denominator` without intermediate overflow
+pub fn mul_div(
+    value: u64,
+    numerator: u64,
+    denominator: u64,
+    rounding: Rounding,
+) -> Result<u64, PoolError> {
+    if denominator == 0 {
+        return Err(PoolError::MathOverflow);
+    }
+
+    // u64 * u64 always fits into u128
+    let product = value as u128 * numerator as u128;
+    let denominator = denominator as u128;
+    let quotient = product / denominator;
+    let remainder = product % denominator;
+    let quotient = match rounding {
+        Rounding::Down => quotient,
+        Rounding::Up if remainder > 0 => quotient + 1,
+        Rounding::Up => quotient,
+    };
+
+    u64::try_from(quotient).map_err(|_| PoolError::MathOverflow)
+}
+
+/// Adds two amounts
+pub fn checked_add(a: u64, b: u64) -> Result<u64, PoolError> {
+    a.checked_add(b).ok_or(PoolError::MathOverflow)
+}
+
+/// Subtracts `b` from `a`
+pub fn checked_sub(a: u64, b: u64) -> Result<u64, PoolError> {
+    a.checked_sub(b).ok_or(PoolError::MathOverflow)
+}
+
+/// Fixed point one used for compounding
+pub(crate) const WAD: u128 = 1_000_000_000_000_000_000;
+
+/// Calculates the interest on `value` compounded over `periods` at
+/// `rate_numerator / rate_denominator` per period, rounded down
+pub fn compound_interest(
+    value: u64,
+    rate_numerator: u64,
+    rate_denominator: u64,
+    periods: u64,
+) -> Result<u64, PoolError> {
+    if rate_denominator == 0 {
+        return Err(PoolError::MathOverflow);
+    }
+    if value == 0 {
+        return Ok(0);
+    }
+
+    let rate = rate_numerator as u128 * WAD / rate_denominator as u128;
+    let growth = checked_pow_wad(WAD + rate, periods, Rounding::Down)?;
+
+    let interest = checked_mul_wad(value as u128 * WAD, growth - WAD, Rounding::Down)? / WAD;
+    u64::try_from(interest).map_err(|_| PoolError::MathOverflow)
+}
+
+/// Raises the fixed point `base` to `exponent` by squaring, each step rounded
+/// the same way
+pub(crate) fn checked_pow_wad(
+    mut base: u128,
+    mut exponent: u64,
+    rounding: Rounding,
+) -> Result<u128, PoolError> {
+    let mut result = WAD;
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = checked_mul_wad(result, base, rounding)?;
+        }
+        exponent >>= 1;
+        if exponent > 0 {
+            base = checked_mul_wad(base, base, rounding)?;
+        }
+    }
+
+    Ok(result)
+}
+
+/// Multiplies fixed point numbers
+///
+/// Both are split into whole and fractional parts, so only a result too large
+/// for `u128` overflows
+fn checked_mul_wad(a: u128, b: u128, rounding: Rounding) -> Result<u128, PoolError> {
+    let (a_whole, a_fraction) = (a / WAD, a % WAD);
+    let (b_whole, b_fraction) = (b / WAD, b % WAD);
+
+    // Only the product of the fractions is truncated
+    let fractions = a_fraction * b_fraction;
+    let fractions = match rounding {
+        Rounding::Up if fractions % WAD != 0 => fractions / WAD + 1,
+        _ => fractions / WAD,
+    };
+
+    a_whole
+        .checked_mul(b_whole)
+        .and_then(|product| product.checked_mul(WAD))
+        .and_then(|product| product.checked_add(a_whole.checked_mul(b_fraction)?))
+        .and_then(|product| product.checked_add(a_fraction.checked_mul(b_whole)?))
+        .and_then(|product| product.checked_add(fractions))
+        .ok_or(PoolError::MathOverflow)
+}
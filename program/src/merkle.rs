@@ -0,0 +1,89 @@
+//! Merkle tree of the users allowed to swap
+
+use solana_program::{hash::hashv, pubkey::Pubkey};
+
+/// Hash of a leaf or an inner node
+pub type MerkleHash = [u8; 32];
+
+/// Prefix of leaf hashes, so inner nodes can't be passed off as users
+const LEAF_PREFIX: &[u8] = &[0];
+
+/// Prefix of inner node hashes
+const NODE_PREFIX: &[u8] = &[1];
+
+/// Hashes the user into a leaf
+pub fn leaf_hash(user: &Pubkey) -> MerkleHash {
+    hashv(&[LEAF_PREFIX, user.as_ref()]).to_bytes()
+}
+
+/// Hashes two children in sorted order, so proofs don't need to tell the sides apart
+pub fn node_hash(a: &MerkleHash, b: &MerkleHash) -> MerkleHash {
+    let (left, right) = if a <= b { (a, b) } else { (b, a) };
+    hashv(&[NODE_PREFIX, left, right]).to_bytes()
+}
+
+/// Checks that the proof hashes the user leaf up to the root
+pub fn verify(root: &MerkleHash, user: &Pubkey, proof: &[MerkleHash]) -> bool {
+    let hash = proof
+        .iter()
+        .fold(leaf_hash(user), |hash, sibling| node_hash(&hash, sibling));
+    hash == *root
+}
+
+/// Tree over a list of users, built off chain for the root and the proofs
+#[derive(Clone, Debug, PartialEq)]
+pub struct MerkleTree {
+    /// Users in leaf order
+    users: Vec<Pubkey>,
+
+    /// Hashes of every level, from the leaves up to the root
+    levels: Vec<Vec<MerkleHash>>,
+}
+
+impl MerkleTree {
+    /// Builds the tree, the last node of an odd level moves up unchanged
+    pub fn new(users: &[Pubkey]) -> Self {
+        let mut levels = vec![users.iter().map(leaf_hash).collect::<Vec<_>>()];
+        while levels.last().unwrap().len() > 1 {
+            let level = levels
+                .last()
+                .unwrap()
+                .chunks(2)
+                .map(|pair| match pair {
+                    [a, b] => node_hash(a, b),
+                    [a] => *a,
+                    _ => unreachable!(),
+                })
+                .collect();
+            levels.push(level);
+        }
+
+        Self {
+            users: users.to_vec(),
+            levels,
+        }
+    }
+
+    /// Root of the tree, zero for an empty tree
+    pub fn root(&self) -> MerkleHash {
+        self.levels
+            .last()
+            .and_then(|level| level.first())
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Sibling hashes from the user leaf up to the root, if the user is in the tree
+    pub fn proof(&self, user: &Pubkey) -> Option<Vec<MerkleHash>> {
+        let mut index = self.users.iter().position(|u| u == user)?;
+
+        let mut proof = vec![];
+        for level in &self.levels[..self.levels.len() - 1] {
+            if let Some(sibling) = level.get(index ^ 1) {
+                proof.push(*sibling);
+            }
+            index /= 2;
+        }
+        Some(proof)
+    }
+}
@@ -0,0 +1,113 @@
+//! Swap permits signed off chain by the pool permit key
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{instruction::Instruction, pubkey::Pubkey};
+
+/// Program verifying Ed25519 signatures before the transaction runs
+pub mod ed25519_program {
+    solana_program::declare_id!("Ed25519SigVerify111111111111111111111111111");
+}
+
+/// Approval of a single swap by the pool permit key
+///
+/// The Borsh serialization of 88 bytes is the signed message
+#[derive(Clone, Copy, Debug, Default, BorshSerialize, BorshDeserialize, PartialEq)]
+pub struct Permit {
+    /// Pool the permit is valid for
+    pub pool: Pubkey,
+
+    /// User transfer authority allowed to swap
+    pub user: Pubkey,
+
+    /// Most bank tokens the swap takes, fee included
+    pub max_amount: u64,
+
+    /// Last slot the permit is valid in
+    pub expiry_slot: u64,
+
+    /// Number used once per pool, so the permit can't be replayed
+    pub nonce: u64,
+}
+
+impl Permit {
+    /// Message signed by the permit key
+    pub fn message(&self) -> Result<Vec<u8>, std::io::Error> {
+        self.try_to_vec()
+    }
+}
+
+/// Length of an Ed25519 public key
+const PUBKEY_LEN: usize = 32;
+
+/// Length of an Ed25519 signature
+const SIGNATURE_LEN: usize = 64;
+
+/// Start of the offsets after the signature count and a padding byte
+const OFFSETS_START: usize = 2;
+
+/// Length of the offsets of one signature, seven u16
+const OFFSETS_LEN: usize = 14;
+
+/// Instruction index of data held by the Ed25519 instruction itself
+const CURRENT_INSTRUCTION: u16 = u16::MAX;
+
+/// Creates an Ed25519 program instruction verifying one signature of the message
+pub fn ed25519_instruction(pubkey: &Pubkey, signature: &[u8], message: &[u8]) -> Instruction {
+    let public_key_offset = OFFSETS_START + OFFSETS_LEN;
+    let signature_offset = public_key_offset + PUBKEY_LEN;
+    let message_data_offset = signature_offset + SIGNATURE_LEN;
+
+    let mut data = vec![1, 0];
+    for offset in &[
+        signature_offset as u16,
+        CURRENT_INSTRUCTION,
+        public_key_offset as u16,
+        CURRENT_INSTRUCTION,
+        message_data_offset as u16,
+        message.len() as u16,
+        CURRENT_INSTRUCTION,
+    ] {
+        data.extend_from_slice(&offset.to_le_bytes());
+    }
+    data.extend_from_slice(pubkey.as_ref());
+    data.extend_from_slice(signature);
+    data.extend_from_slice(message);
+
+    Instruction {
+        program_id: ed25519_program::id(),
+        accounts: vec![],
+        data,
+    }
+}
+
+/// Checks whether the Ed25519 instruction data verifies a signature of the message by
+/// the key, with both held by the instruction itself
+pub fn is_verified_by(data: &[u8], pubkey: &Pubkey, message: &[u8]) -> bool {
+    let count = match data.first() {
+        Some(count) => *count as usize,
+        None => return false,
+    };
+
+    (0..count).any(|index| {
+        let start = OFFSETS_START + index * OFFSETS_LEN;
+        let offsets = match data.get(start..start + OFFSETS_LEN) {
+            Some(offsets) => offsets,
+            None => return false,
+        };
+        let offset = |i: usize| u16::from_le_bytes([offsets[2 * i], offsets[2 * i + 1]]);
+
+        // Data of other instructions could be changed without changing this one
+        if offset(1) != CURRENT_INSTRUCTION
+            || offset(3) != CURRENT_INSTRUCTION
+            || offset(6) != CURRENT_INSTRUCTION
+        {
+            return false;
+        }
+
+        let public_key_offset = offset(2) as usize;
+        let message_offset = offset(4) as usize;
+        let message_len = offset(5) as usize;
+        data.get(public_key_offset..public_key_offset + PUBKEY_LEN) == Some(pubkey.as_ref())
+            && data.get(message_offset..message_offset + message_len) == Some(message)
+    })
+}
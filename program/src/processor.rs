@@ -1,17 +1,33 @@
 //! Program state processor
 
-use crate::{find_authority_bump_seed, instruction::PoolInstruction, state::PoolData};
+use crate::{
+    amm::{ConstantProduct, TradeAmounts},
+    curve::CurveType,
+    error::PoolError,
+    find_authority_bump_seed, find_bank_address, find_deposit_record_address,
+    find_permit_nonce_address, find_pool_address, find_pool_mint_address, find_reserve_address,
+    instruction::{FlashLoanReceive, PoolInstruction},
+    math,
+    merkle::MerkleHash,
+    permit::{self, ed25519_program, Permit},
+    state::{
+        DepositRecord, ExchangeRate, PoolData, PoolDataV1, PoolStatus, PriceData, SwapAmounts,
+    },
+    BANK_SEED, PERMIT_SEED, POOL_MINT_SEED, RESERVE_SEED,
+};
 use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
     entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction},
     msg,
     program::{invoke, invoke_signed},
     program_error::ProgramError,
     program_pack::{IsInitialized, Pack},
     pubkey::Pubkey,
     rent::Rent,
-    sysvar::Sysvar,
+    system_instruction,
+    sysvar::{self, clock::Clock, instructions::load_instruction_at, Sysvar},
 };
 use spl_token as token;
 
@@ -25,25 +41,70 @@ pub fn process_instruction(
     let account_info_iter = &mut accounts.iter();
 
     match instruction {
-        PoolInstruction::Initialize => {
+        PoolInstruction::Initialize {
+            index,
+            rate_numerator,
+            rate_denominator,
+            fee_bps,
+            curve,
+        } => {
             msg!("PoolInstruction::Initialize");
 
             let pool_info = next_account_info(account_info_iter)?;
             let authority_info = next_account_info(account_info_iter)?;
+            let manager_info = next_account_info(account_info_iter)?;
             let bank_mint_info = next_account_info(account_info_iter)?;
             let pool_mint_info = next_account_info(account_info_iter)?;
             let bank_info = next_account_info(account_info_iter)?;
+            let fee_receiver_info = next_account_info(account_info_iter)?;
+            let payer_info = next_account_info(account_info_iter)?;
             let rent_info = next_account_info(account_info_iter)?;
+            let system_program_info = next_account_info(account_info_iter)?;
+            let token_program_info = next_account_info(account_info_iter)?;
 
             let rent = &Rent::from_account_info(rent_info)?;
 
-            if pool_info.owner != program_id {
-                return Err(ProgramError::IncorrectProgramId);
+            check_token_program(token_program_info)?;
+
+            // Pool address is derived from the bank mint, so anyone can find it
+            let (pool_address, pool_bump_seed) =
+                find_pool_address(program_id, bank_mint_info.key, index);
+            if pool_address != *pool_info.key {
+                msg!(
+                    "Incorrect pool address provided, expected {}, received {}",
+                    pool_address,
+                    pool_info.key
+                );
+                return Err(PoolError::InvalidPoolAddress.into());
             }
 
-            let mut pool_data = PoolData::try_from_slice(&pool_info.data.borrow())?;
-            if pool_data.is_initialized() {
-                return Err(ProgramError::AccountAlreadyInitialized);
+            // Pool mint and bank are created by the program as well
+            let (pool_mint_address, pool_mint_bump_seed) =
+                find_pool_mint_address(program_id, pool_info.key);
+            if pool_mint_address != *pool_mint_info.key {
+                return Err(PoolError::InvalidPoolMint.into());
+            }
+
+            let (bank_address, bank_bump_seed) = find_bank_address(program_id, pool_info.key);
+            if bank_address != *bank_info.key {
+                return Err(PoolError::InvalidBank.into());
+            }
+
+            for account in &[pool_info, pool_mint_info, bank_info] {
+                if !account.data_is_empty() {
+                    return Err(ProgramError::AccountAlreadyInitialized);
+                }
+            }
+
+            check_account_owner(bank_mint_info, &token::id())?;
+
+            if !manager_info.is_signer {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+
+            if rate_numerator == 0 || rate_denominator == 0 {
+                msg!("Rate numerator and denominator must be non-zero");
+                return Err(PoolError::InvalidRate.into());
             }
 
             let bank_mint = token::state::Mint::unpack_from_slice(&bank_mint_info.data.borrow())?;
@@ -51,19 +112,72 @@ pub fn process_instruction(
                 return Err(ProgramError::UninitializedAccount);
             }
 
-            // Check rent
-            for account in &[pool_info, bank_info, pool_mint_info] {
-                if !rent.is_exempt(account.lamports(), account.data_len()) {
-                    return Err(ProgramError::AccountNotRentExempt);
-                }
+            // A fee of the whole amount leaves nothing to mint
+            if fee_bps as u64 >= PoolData::BPS_DENOMINATOR {
+                msg!("Fee must be below {} bps", PoolData::BPS_DENOMINATOR);
+                return Err(PoolError::InvalidFee.into());
+            }
+
+            if !curve.is_valid() {
+                msg!("Curve must have a base price and a step size");
+                return Err(PoolError::InvalidCurve.into());
+            }
+
+            // Fees are taken in bank tokens
+            check_account_owner(fee_receiver_info, &token::id())?;
+            let fee_receiver = token::state::Account::unpack(&fee_receiver_info.data.borrow())?;
+            if fee_receiver.mint != *bank_mint_info.key {
+                return Err(PoolError::InvalidFeeReceiver.into());
             }
 
             // Calculate authority address
-            let (authority, bump_seed) = find_authority_bump_seed(program_id, &pool_info.key);
+            let (authority, bump_seed) = find_authority_bump_seed(program_id, pool_info.key);
             if authority != *authority_info.key {
-                return Err(ProgramError::InvalidArgument);
+                return Err(PoolError::InvalidAuthority.into());
             }
 
+            create_pda_account(
+                payer_info.clone(),
+                pool_info.clone(),
+                system_program_info.clone(),
+                rent,
+                PoolData::LEN,
+                program_id,
+                &[
+                    &bank_mint_info.key.to_bytes()[..32],
+                    &index.to_le_bytes(),
+                    &[pool_bump_seed],
+                ],
+            )?;
+
+            create_pda_account(
+                payer_info.clone(),
+                pool_mint_info.clone(),
+                system_program_info.clone(),
+                rent,
+                token::state::Mint::LEN,
+                &token::id(),
+                &[
+                    &pool_info.key.to_bytes()[..32],
+                    POOL_MINT_SEED,
+                    &[pool_mint_bump_seed],
+                ],
+            )?;
+
+            create_pda_account(
+                payer_info.clone(),
+                bank_info.clone(),
+                system_program_info.clone(),
+                rent,
+                token::state::Account::LEN,
+                &token::id(),
+                &[
+                    &pool_info.key.to_bytes()[..32],
+                    BANK_SEED,
+                    &[bank_bump_seed],
+                ],
+            )?;
+
             // Initialize account for spl token
             spl_initialize_account(
                 bank_info.clone(),
@@ -80,17 +194,66 @@ pub fn process_instruction(
                 bank_mint.decimals,
             )?;
 
+            let mut pool_data = PoolData::try_from_slice_unchecked(&pool_info.data.borrow())?;
             pool_data.version = PoolData::CURRENT_VERSION;
             pool_data.authority = *authority_info.key;
             pool_data.bank_mint = *bank_mint_info.key;
             pool_data.pool_mint = *pool_mint_info.key;
             pool_data.bank = *bank_info.key;
             pool_data.bump_seed = bump_seed;
+            pool_data.rate_numerator = rate_numerator;
+            pool_data.rate_denominator = rate_denominator;
+            pool_data.fee_bps = fee_bps;
+            pool_data.curve = curve;
+            pool_data.fee_receiver = *fee_receiver_info.key;
+            pool_data.manager = *manager_info.key;
 
             pool_data.serialize(&mut *pool_info.data.borrow_mut())?;
         }
         PoolInstruction::Swap { amount_in } => {
             msg!("PoolInstruction::Swap");
+            process_swap(
+                program_id,
+                accounts,
+                SwapLimit::ExactIn {
+                    amount_in,
+                    minimum_amount_out: 0,
+                },
+                SwapApproval::None,
+            )?;
+        }
+        PoolInstruction::SwapWithMinimum {
+            amount_in,
+            minimum_amount_out,
+        } => {
+            msg!("PoolInstruction::SwapWithMinimum");
+            process_swap(
+                program_id,
+                accounts,
+                SwapLimit::ExactIn {
+                    amount_in,
+                    minimum_amount_out,
+                },
+                SwapApproval::None,
+            )?;
+        }
+        PoolInstruction::SwapExactOut {
+            amount_out,
+            maximum_amount_in,
+        } => {
+            msg!("PoolInstruction::SwapExactOut");
+            process_swap(
+                program_id,
+                accounts,
+                SwapLimit::ExactOut {
+                    amount_out,
+                    maximum_amount_in,
+                },
+                SwapApproval::None,
+            )?;
+        }
+        PoolInstruction::Redeem { amount_in } => {
+            msg!("PoolInstruction::Redeem");
 
             let pool_info = next_account_info(account_info_iter)?;
             let pool_authority_info = next_account_info(account_info_iter)?;
@@ -99,120 +262,2215 @@ pub fn process_instruction(
             let bank_info = next_account_info(account_info_iter)?;
             let sender_info = next_account_info(account_info_iter)?;
             let recipient_info = next_account_info(account_info_iter)?;
+            let clock_info = next_account_info(account_info_iter)?;
+            let token_program_info = next_account_info(account_info_iter)?;
 
-            let pool_data = PoolData::try_from_slice(&pool_info.data.borrow())?;
+            check_account_owner(pool_info, program_id)?;
+            check_token_program(token_program_info)?;
+
+            let mut pool_data = PoolData::try_from_slice_unchecked(&pool_info.data.borrow())?;
 
             if !pool_data.is_initialized() {
                 return Err(ProgramError::UninitializedAccount);
             }
+            pool_data.check_version()?;
+
+            pool_data.check_constant_product(false)?;
+
+            pool_data.check_not_paused()?;
 
             // Check autority
             pool_data.check_authority(pool_authority_info.key, program_id, pool_info.key)?;
+            check_user_transfer_authority(user_transfer_authority_info, pool_authority_info)?;
 
             if pool_data.bank != *bank_info.key {
-                return Err(ProgramError::InvalidArgument);
+                return Err(PoolError::InvalidBank.into());
+            }
+
+            if pool_data.pool_mint != *pool_mint_info.key {
+                return Err(PoolError::InvalidPoolMint.into());
             }
 
-            // Transfer savings tokens from user
+            accrue_interest(&mut pool_data, bank_info, clock_info)?;
+            let rate = if pool_data.oracle == Pubkey::default() {
+                exchange_rate(&pool_data, pool_mint_info, bank_info)?
+            } else {
+                let oracle_info = next_account_info(account_info_iter)?;
+                oracle_exchange_rate(
+                    &pool_data,
+                    oracle_info,
+                    pool_mint_info,
+                    bank_info,
+                    clock_info,
+                )?
+            };
+
+            // Sale pools only refund a sale that missed the soft cap
+            if pool_data.has_sale() {
+                let clock = Clock::from_account_info(clock_info)?;
+                pool_data.check_sale_ended(clock.unix_timestamp)?;
+                if pool_data.is_soft_cap_met(rate.pool_supply)? {
+                    msg!("Sale reached the soft cap, there are no refunds");
+                    return Err(PoolError::SoftCapMet.into());
+                }
+            }
+
+            let amount_out = rate.calc_bank_amount(amount_in)?;
+
+            pool_data.serialize(&mut *pool_info.data.borrow_mut())?;
+
+            // Burn pool tokens from user
+            spl_token_user_burn(
+                sender_info.clone(),
+                pool_mint_info.clone(),
+                user_transfer_authority_info.clone(),
+                amount_in,
+            )?;
+
+            // Transfer bank tokens to user
             spl_token_transfer(
                 pool_info.key,
-                sender_info.clone(),
                 bank_info.clone(),
-                user_transfer_authority_info.clone(),
+                recipient_info.clone(),
+                pool_authority_info.clone(),
                 pool_data.bump_seed,
-                amount_in,
+                amount_out,
             )?;
+        }
+        PoolInstruction::ProposeManager => {
+            msg!("PoolInstruction::ProposeManager");
 
-            // Mint pool tokens to user
-            spl_token_mint_to(
+            let pool_info = next_account_info(account_info_iter)?;
+            let manager_info = next_account_info(account_info_iter)?;
+            let new_manager_info = next_account_info(account_info_iter)?;
+
+            check_account_owner(pool_info, program_id)?;
+
+            let mut pool_data = PoolData::try_from_slice_unchecked(&pool_info.data.borrow())?;
+
+            if !pool_data.is_initialized() {
+                return Err(ProgramError::UninitializedAccount);
+            }
+            pool_data.check_version()?;
+
+            pool_data.check_manager(manager_info)?;
+
+            pool_data.pending_manager = *new_manager_info.key;
+
+            pool_data.serialize(&mut *pool_info.data.borrow_mut())?;
+        }
+        PoolInstruction::AcceptManager => {
+            msg!("PoolInstruction::AcceptManager");
+
+            let pool_info = next_account_info(account_info_iter)?;
+            let new_manager_info = next_account_info(account_info_iter)?;
+
+            check_account_owner(pool_info, program_id)?;
+
+            let mut pool_data = PoolData::try_from_slice_unchecked(&pool_info.data.borrow())?;
+
+            if !pool_data.is_initialized() {
+                return Err(ProgramError::UninitializedAccount);
+            }
+            pool_data.check_version()?;
+
+            if !new_manager_info.is_signer {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+
+            if pool_data.pending_manager == Pubkey::default()
+                || pool_data.pending_manager != *new_manager_info.key
+            {
+                return Err(PoolError::InvalidPendingManager.into());
+            }
+
+            pool_data.manager = pool_data.pending_manager;
+            pool_data.pending_manager = Pubkey::default();
+
+            pool_data.serialize(&mut *pool_info.data.borrow_mut())?;
+        }
+        PoolInstruction::Pause => {
+            msg!("PoolInstruction::Pause");
+
+            let pool_info = next_account_info(account_info_iter)?;
+            let manager_info = next_account_info(account_info_iter)?;
+
+            check_account_owner(pool_info, program_id)?;
+
+            let mut pool_data = PoolData::try_from_slice_unchecked(&pool_info.data.borrow())?;
+
+            if !pool_data.is_initialized() {
+                return Err(ProgramError::UninitializedAccount);
+            }
+            pool_data.check_version()?;
+
+            pool_data.check_manager(manager_info)?;
+
+            pool_data.status = PoolStatus::Paused;
+
+            pool_data.serialize(&mut *pool_info.data.borrow_mut())?;
+        }
+        PoolInstruction::Unpause => {
+            msg!("PoolInstruction::Unpause");
+
+            let pool_info = next_account_info(account_info_iter)?;
+            let manager_info = next_account_info(account_info_iter)?;
+
+            check_account_owner(pool_info, program_id)?;
+
+            let mut pool_data = PoolData::try_from_slice_unchecked(&pool_info.data.borrow())?;
+
+            if !pool_data.is_initialized() {
+                return Err(ProgramError::UninitializedAccount);
+            }
+            pool_data.check_version()?;
+
+            pool_data.check_manager(manager_info)?;
+
+            pool_data.status = PoolStatus::Active;
+
+            pool_data.serialize(&mut *pool_info.data.borrow_mut())?;
+        }
+        PoolInstruction::Migrate => {
+            msg!("PoolInstruction::Migrate");
+
+            let pool_info = next_account_info(account_info_iter)?;
+            let new_pool_info = next_account_info(account_info_iter)?;
+            let pool_authority_info = next_account_info(account_info_iter)?;
+            let new_pool_authority_info = next_account_info(account_info_iter)?;
+            let pool_mint_info = next_account_info(account_info_iter)?;
+            let bank_info = next_account_info(account_info_iter)?;
+            let token_program_info = next_account_info(account_info_iter)?;
+
+            check_account_owner(pool_info, program_id)?;
+            check_token_program(token_program_info)?;
+
+            let version = *pool_info
+                .data
+                .borrow()
+                .first()
+                .ok_or(ProgramError::UninitializedAccount)?;
+            let mut pool_data: PoolData = match version {
+                0 => return Err(ProgramError::UninitializedAccount),
+                PoolDataV1::VERSION => {
+                    // Accounts of the same version with other sizes were never released
+                    if pool_info.data_len() != PoolDataV1::LEN {
+                        msg!(
+                            "Pool account of version {} takes {} bytes, received {}",
+                            version,
+                            PoolDataV1::LEN,
+                            pool_info.data_len()
+                        );
+                        return Err(ProgramError::InvalidAccountData);
+                    }
+                    PoolDataV1::try_from_slice_unchecked(&pool_info.data.borrow())?.into()
+                }
+                PoolData::CURRENT_VERSION => {
+                    msg!("Pool is already at version {}", version);
+                    return Ok(());
+                }
+                _ => {
+                    msg!("Pool version {} is not supported", version);
+                    return Err(ProgramError::InvalidAccountData);
+                }
+            };
+
+            pool_data.check_authority(pool_authority_info.key, program_id, pool_info.key)?;
+
+            if pool_data.bank != *bank_info.key {
+                return Err(PoolError::InvalidBank.into());
+            }
+
+            if pool_data.pool_mint != *pool_mint_info.key {
+                return Err(PoolError::InvalidPoolMint.into());
+            }
+
+            // The old account can't grow, so the pool moves to a new account
+            check_account_owner(new_pool_info, program_id)?;
+            if new_pool_info.key == pool_info.key {
+                msg!("Pool can't be migrated into itself");
+                return Err(ProgramError::InvalidArgument);
+            }
+            if new_pool_info.data_len() != PoolData::LEN {
+                msg!(
+                    "New pool account takes {} bytes, received {}",
+                    PoolData::LEN,
+                    new_pool_info.data_len()
+                );
+                return Err(ProgramError::InvalidAccountData);
+            }
+            let new_pool_data = PoolData::try_from_slice_unchecked(&new_pool_info.data.borrow())?;
+            if new_pool_data.is_initialized() {
+                return Err(ProgramError::AccountAlreadyInitialized);
+            }
+
+            let (new_pool_authority, new_bump_seed) =
+                find_authority_bump_seed(program_id, new_pool_info.key);
+            if new_pool_authority != *new_pool_authority_info.key {
+                return Err(PoolError::InvalidAuthority.into());
+            }
+
+            msg!("Migrating pool from version {}", version);
+
+            // Hand the pool mint and the bank over to the new pool
+            spl_token_set_authority(
                 pool_info.key,
                 pool_mint_info.clone(),
-                recipient_info.clone(),
+                &new_pool_authority,
+                token::instruction::AuthorityType::MintTokens,
+                pool_authority_info.clone(),
+                pool_data.bump_seed,
+            )?;
+            spl_token_set_authority(
+                pool_info.key,
+                bank_info.clone(),
+                &new_pool_authority,
+                token::instruction::AuthorityType::AccountOwner,
                 pool_authority_info.clone(),
                 pool_data.bump_seed,
-                amount_in * (PoolData::MINT_MULTIPLIER as u64),
             )?;
+
+            pool_data.authority = new_pool_authority;
+            pool_data.bump_seed = new_bump_seed;
+            pool_data.serialize(&mut *new_pool_info.data.borrow_mut())?;
+
+            // The runtime purges the old pool account once it has no lamports
+            let pool_lamports = pool_info.lamports();
+            **new_pool_info.lamports.borrow_mut() =
+                math::checked_add(new_pool_info.lamports(), pool_lamports)?;
+            **pool_info.lamports.borrow_mut() = 0;
+
+            for byte in pool_info.data.borrow_mut().iter_mut() {
+                *byte = 0;
+            }
         }
-    }
+        PoolInstruction::ClosePool => {
+            msg!("PoolInstruction::ClosePool");
 
-    Ok(())
-}
+            let pool_info = next_account_info(account_info_iter)?;
+            let pool_authority_info = next_account_info(account_info_iter)?;
+            let manager_info = next_account_info(account_info_iter)?;
+            let pool_mint_info = next_account_info(account_info_iter)?;
+            let bank_info = next_account_info(account_info_iter)?;
+            let destination_info = next_account_info(account_info_iter)?;
+            let token_program_info = next_account_info(account_info_iter)?;
 
-/// Create a mint instruction.
-pub fn spl_initialize_mint<'a>(
-    mint: AccountInfo<'a>,
-    mint_authority: AccountInfo<'a>,
-    rent: AccountInfo<'a>,
-    decimals: u8,
-) -> Result<(), ProgramError> {
-    let ix = token::instruction::initialize_mint(
-        &token::id(),
-        mint.key,
-        mint_authority.key,
-        None,
-        decimals,
-    )?;
+            check_account_owner(pool_info, program_id)?;
+            check_token_program(token_program_info)?;
 
-    invoke(&ix, &[mint, rent])
-}
+            let pool_data = PoolData::try_from_slice_unchecked(&pool_info.data.borrow())?;
 
-/// Create an accont instruction.
-pub fn spl_initialize_account<'a>(
-    account: AccountInfo<'a>,
-    mint: AccountInfo<'a>,
-    authority: AccountInfo<'a>,
-    rent: AccountInfo<'a>,
-) -> Result<(), ProgramError> {
-    let ix =
-        token::instruction::initialize_account(&token::id(), account.key, mint.key, authority.key)?;
+            if !pool_data.is_initialized() {
+                return Err(ProgramError::UninitializedAccount);
+            }
+            pool_data.check_version()?;
 
-    invoke(&ix, &[account, mint, authority, rent])
-}
+            pool_data.check_manager(manager_info)?;
 
-/// Issue a transfer instruction.
-pub fn spl_token_transfer<'a>(
-    pool: &Pubkey,
-    source: AccountInfo<'a>,
-    destination: AccountInfo<'a>,
-    authority: AccountInfo<'a>,
-    bump_seed: u8,
-    amount: u64,
-) -> Result<(), ProgramError> {
-    let authority_signature_seeds = [&pool.to_bytes()[..32], &[bump_seed]];
-    let signers = &[&authority_signature_seeds[..]];
+            // Check autority
+            pool_data.check_authority(pool_authority_info.key, program_id, pool_info.key)?;
 
-    let ix = token::instruction::transfer(
-        &token::id(),
-        source.key,
-        destination.key,
-        authority.key,
-        &[],
-        amount,
-    )?;
+            if pool_data.bank != *bank_info.key {
+                return Err(PoolError::InvalidBank.into());
+            }
 
-    invoke_signed(&ix, &[source, destination, authority], signers)
-}
+            if pool_data.pool_mint != *pool_mint_info.key {
+                return Err(PoolError::InvalidPoolMint.into());
+            }
 
-/// Issue a mint instruction.
-pub fn spl_token_mint_to<'a>(
-    pool: &Pubkey,
-    mint: AccountInfo<'a>,
-    destination: AccountInfo<'a>,
-    authority: AccountInfo<'a>,
-    bump_seed: u8,
-    amount: u64,
-) -> Result<(), ProgramError> {
-    let authority_signature_seeds = [&pool.to_bytes()[..32], &[bump_seed]];
-    let signers = &[&authority_signature_seeds[..]];
+            if destination_info.key == pool_info.key {
+                msg!("Pool lamports can't be sent to the pool itself");
+                return Err(ProgramError::InvalidArgument);
+            }
 
-    let ix = token::instruction::mint_to(
-        &token::id(),
-        mint.key,
-        destination.key,
-        authority.key,
-        &[],
-        amount,
-    )?;
+            // Closing must not strand anyone's tokens
+            let pool_mint = token::state::Mint::unpack(&pool_mint_info.data.borrow())?;
+            if pool_mint.supply != 0 {
+                msg!("Pool mint supply is {}", pool_mint.supply);
+                return Err(PoolError::PoolNotEmpty.into());
+            }
 
-    invoke_signed(&ix, &[mint, destination, authority], signers)
+            let bank = token::state::Account::unpack(&bank_info.data.borrow())?;
+            if bank.amount != 0 {
+                msg!("Bank balance is {}", bank.amount);
+                return Err(PoolError::PoolNotEmpty.into());
+            }
+
+            spl_token_close_account(
+                pool_info.key,
+                bank_info.clone(),
+                destination_info.clone(),
+                pool_authority_info.clone(),
+                pool_data.bump_seed,
+            )?;
+
+            // Constant product pools close their reserve as well
+            if pool_data.is_constant_product() {
+                let reserve_info = next_account_info(account_info_iter)?;
+                if pool_data.reserve != *reserve_info.key {
+                    return Err(PoolError::InvalidReserve.into());
+                }
+
+                let reserve = token::state::Account::unpack(&reserve_info.data.borrow())?;
+                if reserve.amount != 0 {
+                    msg!("Reserve balance is {}", reserve.amount);
+                    return Err(PoolError::PoolNotEmpty.into());
+                }
+
+                spl_token_close_account(
+                    pool_info.key,
+                    reserve_info.clone(),
+                    destination_info.clone(),
+                    pool_authority_info.clone(),
+                    pool_data.bump_seed,
+                )?;
+            }
+
+            // No more pool tokens can be minted once the pool is gone
+            spl_token_remove_mint_authority(
+                pool_info.key,
+                pool_mint_info.clone(),
+                pool_authority_info.clone(),
+                pool_data.bump_seed,
+            )?;
+
+            // The runtime purges the pool account once it has no lamports
+            let pool_lamports = pool_info.lamports();
+            **destination_info.lamports.borrow_mut() =
+                math::checked_add(destination_info.lamports(), pool_lamports)?;
+            **pool_info.lamports.borrow_mut() = 0;
+
+            for byte in pool_info.data.borrow_mut().iter_mut() {
+                *byte = 0;
+            }
+        }
+        PoolInstruction::DepositRewards { amount } => {
+            msg!("PoolInstruction::DepositRewards");
+
+            let pool_info = next_account_info(account_info_iter)?;
+            let manager_info = next_account_info(account_info_iter)?;
+            let pool_mint_info = next_account_info(account_info_iter)?;
+            let bank_info = next_account_info(account_info_iter)?;
+            let source_info = next_account_info(account_info_iter)?;
+            let clock_info = next_account_info(account_info_iter)?;
+            let token_program_info = next_account_info(account_info_iter)?;
+
+            check_account_owner(pool_info, program_id)?;
+            check_token_program(token_program_info)?;
+
+            let mut pool_data = PoolData::try_from_slice_unchecked(&pool_info.data.borrow())?;
+
+            if !pool_data.is_initialized() {
+                return Err(ProgramError::UninitializedAccount);
+            }
+            pool_data.check_version()?;
+
+            pool_data.check_constant_product(false)?;
+
+            pool_data.check_manager(manager_info)?;
+
+            if pool_data.bank != *bank_info.key {
+                return Err(PoolError::InvalidBank.into());
+            }
+
+            if pool_data.pool_mint != *pool_mint_info.key {
+                return Err(PoolError::InvalidPoolMint.into());
+            }
+
+            // Rewards for an empty pool would go to the first swap
+            let pool_mint = token::state::Mint::unpack(&pool_mint_info.data.borrow())?;
+            if pool_mint.supply == 0 {
+                msg!("Pool has no pool tokens to reward");
+                return Err(PoolError::PoolEmpty.into());
+            }
+
+            // With an annual rate the rewards are paid out as interest over time
+            accrue_interest(&mut pool_data, bank_info, clock_info)?;
+            if pool_data.apr_bps > 0 {
+                pool_data.undistributed_rewards =
+                    math::checked_add(pool_data.undistributed_rewards, amount)?;
+            }
+
+            pool_data.serialize(&mut *pool_info.data.borrow_mut())?;
+
+            // Transfer reward tokens from manager, no pool tokens are minted
+            spl_token_transfer(
+                pool_info.key,
+                source_info.clone(),
+                bank_info.clone(),
+                manager_info.clone(),
+                pool_data.bump_seed,
+                amount,
+            )?;
+        }
+        PoolInstruction::Refresh => {
+            msg!("PoolInstruction::Refresh");
+
+            let pool_info = next_account_info(account_info_iter)?;
+            let bank_info = next_account_info(account_info_iter)?;
+            let clock_info = next_account_info(account_info_iter)?;
+
+            check_account_owner(pool_info, program_id)?;
+
+            let mut pool_data = PoolData::try_from_slice_unchecked(&pool_info.data.borrow())?;
+
+            if !pool_data.is_initialized() {
+                return Err(ProgramError::UninitializedAccount);
+            }
+            pool_data.check_version()?;
+
+            if pool_data.bank != *bank_info.key {
+                return Err(PoolError::InvalidBank.into());
+            }
+
+            accrue_interest(&mut pool_data, bank_info, clock_info)?;
+
+            pool_data.serialize(&mut *pool_info.data.borrow_mut())?;
+        }
+        PoolInstruction::SetApr { apr_bps } => {
+            msg!("PoolInstruction::SetApr");
+
+            let pool_info = next_account_info(account_info_iter)?;
+            let manager_info = next_account_info(account_info_iter)?;
+            let bank_info = next_account_info(account_info_iter)?;
+            let clock_info = next_account_info(account_info_iter)?;
+
+            check_account_owner(pool_info, program_id)?;
+
+            let mut pool_data = PoolData::try_from_slice_unchecked(&pool_info.data.borrow())?;
+
+            if !pool_data.is_initialized() {
+                return Err(ProgramError::UninitializedAccount);
+            }
+            pool_data.check_version()?;
+
+            pool_data.check_manager(manager_info)?;
+
+            if pool_data.bank != *bank_info.key {
+                return Err(PoolError::InvalidBank.into());
+            }
+
+            // Interest up to now is owed at the old rate
+            accrue_interest(&mut pool_data, bank_info, clock_info)?;
+            pool_data.apr_bps = apr_bps;
+
+            pool_data.serialize(&mut *pool_info.data.borrow_mut())?;
+        }
+        PoolInstruction::SetOracle { max_age } => {
+            msg!("PoolInstruction::SetOracle");
+
+            let pool_info = next_account_info(account_info_iter)?;
+            let manager_info = next_account_info(account_info_iter)?;
+            let pool_mint_info = next_account_info(account_info_iter)?;
+            let oracle_info = next_account_info(account_info_iter)?;
+
+            check_account_owner(pool_info, program_id)?;
+
+            let mut pool_data = PoolData::try_from_slice_unchecked(&pool_info.data.borrow())?;
+
+            if !pool_data.is_initialized() {
+                return Err(ProgramError::UninitializedAccount);
+            }
+            pool_data.check_version()?;
+
+            pool_data.check_manager(manager_info)?;
+
+            pool_data.check_constant_product(false)?;
+
+            if pool_data.curve != CurveType::Constant {
+                msg!("Pools priced by a curve can't have an oracle");
+                return Err(PoolError::InvalidPoolMode.into());
+            }
+
+            // Sale refunds pay back the deposits at the share price
+            if pool_data.has_sale() {
+                msg!("Pools running a sale can't have an oracle");
+                return Err(PoolError::InvalidPoolMode.into());
+            }
+
+            if pool_data.pool_mint != *pool_mint_info.key {
+                return Err(PoolError::InvalidPoolMint.into());
+            }
+
+            // Pool tokens minted at one price would redeem at another
+            let pool_mint = token::state::Mint::unpack(&pool_mint_info.data.borrow())?;
+            if pool_mint.supply != 0 {
+                msg!("Pool mint supply is {}", pool_mint.supply);
+                return Err(PoolError::PoolNotEmpty.into());
+            }
+
+            // Swaps would fail on a price that can't be read
+            if *oracle_info.key != Pubkey::default() {
+                let price = PriceData::try_from_slice_unchecked(&oracle_info.data.borrow())
+                    .map_err(|_| PoolError::InvalidOracle)?;
+                price.exchange_rate(0, 0)?;
+            }
+
+            pool_data.oracle = *oracle_info.key;
+            pool_data.oracle_max_age = max_age;
+
+            pool_data.serialize(&mut *pool_info.data.borrow_mut())?;
+        }
+        PoolInstruction::InitializeReserve => {
+            msg!("PoolInstruction::InitializeReserve");
+
+            let pool_info = next_account_info(account_info_iter)?;
+            let authority_info = next_account_info(account_info_iter)?;
+            let manager_info = next_account_info(account_info_iter)?;
+            let reserve_mint_info = next_account_info(account_info_iter)?;
+            let reserve_info = next_account_info(account_info_iter)?;
+            let pool_mint_info = next_account_info(account_info_iter)?;
+            let payer_info = next_account_info(account_info_iter)?;
+            let rent_info = next_account_info(account_info_iter)?;
+            let system_program_info = next_account_info(account_info_iter)?;
+            let token_program_info = next_account_info(account_info_iter)?;
+
+            let rent = &Rent::from_account_info(rent_info)?;
+
+            check_account_owner(pool_info, program_id)?;
+            check_token_program(token_program_info)?;
+
+            let mut pool_data = PoolData::try_from_slice_unchecked(&pool_info.data.borrow())?;
+
+            if !pool_data.is_initialized() {
+                return Err(ProgramError::UninitializedAccount);
+            }
+            pool_data.check_version()?;
+
+            pool_data.check_manager(manager_info)?;
+
+            // Check autority
+            pool_data.check_authority(authority_info.key, program_id, pool_info.key)?;
+
+            if pool_data.pool_mint != *pool_mint_info.key {
+                return Err(PoolError::InvalidPoolMint.into());
+            }
+
+            // Reserve prices replace the bank share, the curve and the oracle
+            pool_data.check_constant_product(false)?;
+            if pool_data.curve != CurveType::Constant || pool_data.oracle != Pubkey::default() {
+                msg!("Pools priced by a curve or an oracle can't hold a reserve");
+                return Err(PoolError::InvalidPoolMode.into());
+            }
+
+            // Constant product swaps don't check the swap restrictions
+            if pool_data.has_allowlist()
+                || pool_data.has_permit_key()
+                || pool_data.has_deposit_limit()
+                || pool_data.has_swap_limits()
+                || pool_data.has_sale()
+            {
+                msg!("Pools restricting swaps can't hold a reserve");
+                return Err(PoolError::InvalidPoolMode.into());
+            }
+
+            let pool_mint = token::state::Mint::unpack(&pool_mint_info.data.borrow())?;
+            if pool_mint.supply != 0 {
+                msg!("Pool mint supply is {}", pool_mint.supply);
+                return Err(PoolError::PoolNotEmpty.into());
+            }
+
+            let (reserve_address, reserve_bump_seed) =
+                find_reserve_address(program_id, pool_info.key);
+            if reserve_address != *reserve_info.key {
+                return Err(PoolError::InvalidReserve.into());
+            }
+
+            if !reserve_info.data_is_empty() {
+                return Err(ProgramError::AccountAlreadyInitialized);
+            }
+
+            check_account_owner(reserve_mint_info, &token::id())?;
+            if *reserve_mint_info.key == pool_data.bank_mint {
+                msg!("Reserve mint must differ from the bank mint");
+                return Err(PoolError::InvalidReserve.into());
+            }
+
+            create_pda_account(
+                payer_info.clone(),
+                reserve_info.clone(),
+                system_program_info.clone(),
+                rent,
+                token::state::Account::LEN,
+                &token::id(),
+                &[
+                    &pool_info.key.to_bytes()[..32],
+                    RESERVE_SEED,
+                    &[reserve_bump_seed],
+                ],
+            )?;
+
+            spl_initialize_account(
+                reserve_info.clone(),
+                reserve_mint_info.clone(),
+                authority_info.clone(),
+                rent_info.clone(),
+            )?;
+
+            pool_data.reserve_mint = *reserve_mint_info.key;
+            pool_data.reserve = *reserve_info.key;
+
+            pool_data.serialize(&mut *pool_info.data.borrow_mut())?;
+        }
+        PoolInstruction::AddLiquidity {
+            bank_amount,
+            reserve_amount,
+            minimum_pool_amount,
+        } => {
+            msg!("PoolInstruction::AddLiquidity");
+
+            let pool_info = next_account_info(account_info_iter)?;
+            let pool_authority_info = next_account_info(account_info_iter)?;
+            let user_transfer_authority_info = next_account_info(account_info_iter)?;
+            let pool_mint_info = next_account_info(account_info_iter)?;
+            let bank_info = next_account_info(account_info_iter)?;
+            let reserve_info = next_account_info(account_info_iter)?;
+            let bank_source_info = next_account_info(account_info_iter)?;
+            let reserve_source_info = next_account_info(account_info_iter)?;
+            let recipient_info = next_account_info(account_info_iter)?;
+            let token_program_info = next_account_info(account_info_iter)?;
+
+            let pool_data = check_constant_product_accounts(
+                program_id,
+                pool_info,
+                pool_authority_info,
+                pool_mint_info,
+                bank_info,
+                reserve_info,
+                token_program_info,
+            )?;
+
+            check_user_transfer_authority(user_transfer_authority_info, pool_authority_info)?;
+            check_user_source(bank_source_info, &pool_data)?;
+            check_user_source(reserve_source_info, &pool_data)?;
+
+            let amounts = constant_product(pool_mint_info, bank_info, reserve_info)?.calc_deposit(
+                bank_amount,
+                reserve_amount,
+                pool_data.rate_numerator,
+                pool_data.rate_denominator,
+            )?;
+
+            if amounts.pool_amount == 0 {
+                msg!("Deposit mints no pool tokens");
+                return Err(PoolError::ZeroAmountOut.into());
+            }
+
+            if amounts.pool_amount < minimum_pool_amount {
+                msg!(
+                    "Pool amount {} is below the minimum {}",
+                    amounts.pool_amount,
+                    minimum_pool_amount
+                );
+                return Err(PoolError::ExceededSlippage.into());
+            }
+
+            spl_token_user_transfer(
+                bank_source_info.clone(),
+                bank_info.clone(),
+                user_transfer_authority_info.clone(),
+                amounts.bank_amount,
+            )?;
+
+            spl_token_user_transfer(
+                reserve_source_info.clone(),
+                reserve_info.clone(),
+                user_transfer_authority_info.clone(),
+                amounts.reserve_amount,
+            )?;
+
+            spl_token_mint_to(
+                pool_info.key,
+                pool_mint_info.clone(),
+                recipient_info.clone(),
+                pool_authority_info.clone(),
+                pool_data.bump_seed,
+                amounts.pool_amount,
+            )?;
+        }
+        PoolInstruction::RemoveLiquidity {
+            pool_amount,
+            minimum_bank_amount,
+            minimum_reserve_amount,
+        } => {
+            msg!("PoolInstruction::RemoveLiquidity");
+
+            let pool_info = next_account_info(account_info_iter)?;
+            let pool_authority_info = next_account_info(account_info_iter)?;
+            let user_transfer_authority_info = next_account_info(account_info_iter)?;
+            let pool_mint_info = next_account_info(account_info_iter)?;
+            let bank_info = next_account_info(account_info_iter)?;
+            let reserve_info = next_account_info(account_info_iter)?;
+            let sender_info = next_account_info(account_info_iter)?;
+            let bank_recipient_info = next_account_info(account_info_iter)?;
+            let reserve_recipient_info = next_account_info(account_info_iter)?;
+            let token_program_info = next_account_info(account_info_iter)?;
+
+            let pool_data = check_constant_product_accounts(
+                program_id,
+                pool_info,
+                pool_authority_info,
+                pool_mint_info,
+                bank_info,
+                reserve_info,
+                token_program_info,
+            )?;
+
+            check_user_transfer_authority(user_transfer_authority_info, pool_authority_info)?;
+
+            let amounts = constant_product(pool_mint_info, bank_info, reserve_info)?
+                .calc_withdraw(pool_amount)?;
+
+            if amounts.bank_amount < minimum_bank_amount
+                || amounts.reserve_amount < minimum_reserve_amount
+            {
+                msg!(
+                    "Bank amount {} and reserve amount {} are below the minimums",
+                    amounts.bank_amount,
+                    amounts.reserve_amount
+                );
+                return Err(PoolError::ExceededSlippage.into());
+            }
+
+            spl_token_user_burn(
+                sender_info.clone(),
+                pool_mint_info.clone(),
+                user_transfer_authority_info.clone(),
+                pool_amount,
+            )?;
+
+            spl_token_transfer(
+                pool_info.key,
+                bank_info.clone(),
+                bank_recipient_info.clone(),
+                pool_authority_info.clone(),
+                pool_data.bump_seed,
+                amounts.bank_amount,
+            )?;
+
+            spl_token_transfer(
+                pool_info.key,
+                reserve_info.clone(),
+                reserve_recipient_info.clone(),
+                pool_authority_info.clone(),
+                pool_data.bump_seed,
+                amounts.reserve_amount,
+            )?;
+        }
+        PoolInstruction::SwapReserves {
+            amount_in,
+            minimum_amount_out,
+        } => {
+            msg!("PoolInstruction::SwapReserves");
+
+            let pool_info = next_account_info(account_info_iter)?;
+            let pool_authority_info = next_account_info(account_info_iter)?;
+            let user_transfer_authority_info = next_account_info(account_info_iter)?;
+            let swap_source_info = next_account_info(account_info_iter)?;
+            let swap_destination_info = next_account_info(account_info_iter)?;
+            let source_info = next_account_info(account_info_iter)?;
+            let destination_info = next_account_info(account_info_iter)?;
+            let token_program_info = next_account_info(account_info_iter)?;
+
+            check_account_owner(pool_info, program_id)?;
+            check_token_program(token_program_info)?;
+
+            let pool_data = PoolData::try_from_slice_unchecked(&pool_info.data.borrow())?;
+
+            if !pool_data.is_initialized() {
+                return Err(ProgramError::UninitializedAccount);
+            }
+            pool_data.check_version()?;
+
+            pool_data.check_constant_product(true)?;
+
+            pool_data.check_not_paused()?;
+
+            // Check autority
+            pool_data.check_authority(pool_authority_info.key, program_id, pool_info.key)?;
+
+            // Either way between the bank and the reserve
+            let pool_accounts = (*swap_source_info.key, *swap_destination_info.key);
+            if pool_accounts != (pool_data.bank, pool_data.reserve)
+                && pool_accounts != (pool_data.reserve, pool_data.bank)
+            {
+                msg!("Swap accounts must be the bank and the reserve of the pool");
+                return Err(PoolError::InvalidReserve.into());
+            }
+
+            check_user_transfer_authority(user_transfer_authority_info, pool_authority_info)?;
+            check_user_source(source_info, &pool_data)?;
+
+            let balance_in = token::state::Account::unpack(&swap_source_info.data.borrow())?.amount;
+            let balance_out =
+                token::state::Account::unpack(&swap_destination_info.data.borrow())?.amount;
+            let TradeAmounts { amount_out, .. } =
+                ConstantProduct::calc_trade(balance_in, balance_out, amount_in, pool_data.fee_bps)?;
+
+            if amount_out < minimum_amount_out {
+                msg!(
+                    "Amount out {} is below the minimum {}",
+                    amount_out,
+                    minimum_amount_out
+                );
+                return Err(PoolError::ExceededSlippage.into());
+            }
+
+            // Users lose the whole amount if the price rounds it down to nothing
+            if amount_in > 0 && amount_out == 0 {
+                msg!("Amount in {} pays out nothing", amount_in);
+                return Err(PoolError::ZeroAmountOut.into());
+            }
+
+            // The fee stays in the pool with the rest of the amount in
+            spl_token_user_transfer(
+                source_info.clone(),
+                swap_source_info.clone(),
+                user_transfer_authority_info.clone(),
+                amount_in,
+            )?;
+
+            spl_token_transfer(
+                pool_info.key,
+                swap_destination_info.clone(),
+                destination_info.clone(),
+                pool_authority_info.clone(),
+                pool_data.bump_seed,
+                amount_out,
+            )?;
+        }
+        PoolInstruction::FlashLoan { amount } => {
+            msg!("PoolInstruction::FlashLoan");
+
+            let pool_info = next_account_info(account_info_iter)?;
+            let pool_authority_info = next_account_info(account_info_iter)?;
+            let bank_info = next_account_info(account_info_iter)?;
+            let destination_info = next_account_info(account_info_iter)?;
+            let receiver_program_info = next_account_info(account_info_iter)?;
+            let token_program_info = next_account_info(account_info_iter)?;
+            let receiver_account_infos = account_info_iter.as_slice();
+
+            check_account_owner(pool_info, program_id)?;
+            check_token_program(token_program_info)?;
+
+            let pool_data = PoolData::try_from_slice_unchecked(&pool_info.data.borrow())?;
+
+            if !pool_data.is_initialized() {
+                return Err(ProgramError::UninitializedAccount);
+            }
+            pool_data.check_version()?;
+
+            pool_data.check_not_paused()?;
+
+            // Check autority
+            pool_data.check_authority(pool_authority_info.key, program_id, pool_info.key)?;
+
+            if pool_data.bank != *bank_info.key {
+                return Err(PoolError::InvalidBank.into());
+            }
+
+            // The receiver data would be read as a pool instruction
+            if receiver_program_info.key == program_id {
+                msg!("Flash loan receiver can't be the pool program");
+                return Err(ProgramError::IncorrectProgramId);
+            }
+
+            let bank_balance = token::state::Account::unpack(&bank_info.data.borrow())?.amount;
+            let fee = pool_data.calc_fee(amount)?;
+            let repay_balance = math::checked_add(bank_balance, fee)?;
+
+            spl_token_transfer(
+                pool_info.key,
+                bank_info.clone(),
+                destination_info.clone(),
+                pool_authority_info.clone(),
+                pool_data.bump_seed,
+                amount,
+            )?;
+
+            let mut receiver_accounts = vec![
+                AccountMeta::new(*destination_info.key, false),
+                AccountMeta::new(*bank_info.key, false),
+                AccountMeta::new_readonly(*token_program_info.key, false),
+            ];
+            receiver_accounts.extend(receiver_account_infos.iter().map(|account_info| {
+                AccountMeta {
+                    pubkey: *account_info.key,
+                    is_signer: account_info.is_signer,
+                    is_writable: account_info.is_writable,
+                }
+            }));
+            let ix = Instruction {
+                program_id: *receiver_program_info.key,
+                accounts: receiver_accounts,
+                data: FlashLoanReceive { amount, fee }.try_to_vec()?,
+            };
+
+            let mut account_infos = vec![
+                destination_info.clone(),
+                bank_info.clone(),
+                token_program_info.clone(),
+                receiver_program_info.clone(),
+            ];
+            account_infos.extend_from_slice(receiver_account_infos);
+            invoke(&ix, &account_infos)?;
+
+            // The fee stays in the bank for the pool token holders
+            let bank_balance = token::state::Account::unpack(&bank_info.data.borrow())?.amount;
+            if bank_balance < repay_balance {
+                msg!(
+                    "Bank holds {} after the loan, expected at least {}",
+                    bank_balance,
+                    repay_balance
+                );
+                return Err(PoolError::FlashLoanNotRepaid.into());
+            }
+        }
+        PoolInstruction::SwapWithProof {
+            amount_in,
+            minimum_amount_out,
+            proof,
+        } => {
+            msg!("PoolInstruction::SwapWithProof");
+            process_swap(
+                program_id,
+                accounts,
+                SwapLimit::ExactIn {
+                    amount_in,
+                    minimum_amount_out,
+                },
+                SwapApproval::Allowlist(&proof),
+            )?;
+        }
+        PoolInstruction::UpdateAllowlistRoot { root } => {
+            msg!("PoolInstruction::UpdateAllowlistRoot");
+
+            let pool_info = next_account_info(account_info_iter)?;
+            let manager_info = next_account_info(account_info_iter)?;
+
+            check_account_owner(pool_info, program_id)?;
+
+            let mut pool_data = PoolData::try_from_slice_unchecked(&pool_info.data.borrow())?;
+
+            if !pool_data.is_initialized() {
+                return Err(ProgramError::UninitializedAccount);
+            }
+            pool_data.check_version()?;
+
+            pool_data.check_manager(manager_info)?;
+
+            pool_data.check_constant_product(false)?;
+
+            pool_data.allowlist_root = root;
+
+            pool_data.serialize(&mut *pool_info.data.borrow_mut())?;
+        }
+        PoolInstruction::SwapWithPermit {
+            amount_in,
+            minimum_amount_out,
+            max_amount,
+            expiry_slot,
+            nonce,
+        } => {
+            msg!("PoolInstruction::SwapWithPermit");
+            process_swap(
+                program_id,
+                accounts,
+                SwapLimit::ExactIn {
+                    amount_in,
+                    minimum_amount_out,
+                },
+                SwapApproval::Permit {
+                    max_amount,
+                    expiry_slot,
+                    nonce,
+                },
+            )?;
+        }
+        PoolInstruction::SetPermitKey => {
+            msg!("PoolInstruction::SetPermitKey");
+
+            let pool_info = next_account_info(account_info_iter)?;
+            let manager_info = next_account_info(account_info_iter)?;
+            let permit_key_info = next_account_info(account_info_iter)?;
+
+            check_account_owner(pool_info, program_id)?;
+
+            let mut pool_data = PoolData::try_from_slice_unchecked(&pool_info.data.borrow())?;
+
+            if !pool_data.is_initialized() {
+                return Err(ProgramError::UninitializedAccount);
+            }
+            pool_data.check_version()?;
+
+            pool_data.check_manager(manager_info)?;
+
+            pool_data.check_constant_product(false)?;
+
+            pool_data.permit_key = *permit_key_info.key;
+
+            pool_data.serialize(&mut *pool_info.data.borrow_mut())?;
+        }
+        PoolInstruction::SetDepositLimit {
+            deposit_limit,
+            period,
+        } => {
+            msg!("PoolInstruction::SetDepositLimit");
+
+            let pool_info = next_account_info(account_info_iter)?;
+            let manager_info = next_account_info(account_info_iter)?;
+
+            check_account_owner(pool_info, program_id)?;
+
+            let mut pool_data = PoolData::try_from_slice_unchecked(&pool_info.data.borrow())?;
+
+            if !pool_data.is_initialized() {
+                return Err(ProgramError::UninitializedAccount);
+            }
+            pool_data.check_version()?;
+
+            pool_data.check_manager(manager_info)?;
+
+            pool_data.check_constant_product(false)?;
+
+            pool_data.deposit_limit = deposit_limit;
+            pool_data.deposit_period = period;
+
+            pool_data.serialize(&mut *pool_info.data.borrow_mut())?;
+        }
+        PoolInstruction::CloseDepositRecord => {
+            msg!("PoolInstruction::CloseDepositRecord");
+
+            let pool_info = next_account_info(account_info_iter)?;
+            let deposit_record_info = next_account_info(account_info_iter)?;
+            let user_info = next_account_info(account_info_iter)?;
+            let destination_info = next_account_info(account_info_iter)?;
+            let clock_info = next_account_info(account_info_iter)?;
+
+            check_account_owner(deposit_record_info, program_id)?;
+
+            if !user_info.is_signer {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+
+            let deposit_record =
+                DepositRecord::try_from_slice_unchecked(&deposit_record_info.data.borrow())?;
+            if !deposit_record.is_initialized() {
+                return Err(ProgramError::UninitializedAccount);
+            }
+            if deposit_record.pool != *pool_info.key || deposit_record.user != *user_info.key {
+                return Err(PoolError::InvalidDepositRecord.into());
+            }
+
+            if destination_info.key == deposit_record_info.key {
+                msg!("Record lamports can't be sent to the record itself");
+                return Err(ProgramError::InvalidArgument);
+            }
+
+            // Records of a closed pool are always unused
+            if pool_info.owner == program_id {
+                let pool_data = PoolData::try_from_slice_unchecked(&pool_info.data.borrow())?;
+                let epoch = Clock::from_account_info(clock_info)?.epoch;
+                if pool_data.is_initialized() && !deposit_record.is_unused(&pool_data, epoch) {
+                    msg!("Deposit record counts against the pool deposit limit");
+                    return Err(PoolError::DepositRecordInUse.into());
+                }
+            }
+
+            // Zeroed so the record can't be read again before the runtime purges it
+            for byte in deposit_record_info.data.borrow_mut().iter_mut() {
+                *byte = 0;
+            }
+            let record_lamports = deposit_record_info.lamports();
+            **destination_info.lamports.borrow_mut() =
+                math::checked_add(destination_info.lamports(), record_lamports)?;
+            **deposit_record_info.lamports.borrow_mut() = 0;
+        }
+        PoolInstruction::SetSwapLimits {
+            supply_cap,
+            min_amount_in,
+            max_amount_in,
+        } => {
+            msg!("PoolInstruction::SetSwapLimits");
+
+            let pool_info = next_account_info(account_info_iter)?;
+            let manager_info = next_account_info(account_info_iter)?;
+
+            check_account_owner(pool_info, program_id)?;
+
+            let mut pool_data = PoolData::try_from_slice_unchecked(&pool_info.data.borrow())?;
+
+            if !pool_data.is_initialized() {
+                return Err(ProgramError::UninitializedAccount);
+            }
+            pool_data.check_version()?;
+
+            pool_data.check_manager(manager_info)?;
+
+            pool_data.check_constant_product(false)?;
+
+            if max_amount_in != 0 && min_amount_in > max_amount_in {
+                msg!(
+                    "Minimum amount in {} is above the maximum {}",
+                    min_amount_in,
+                    max_amount_in
+                );
+                return Err(PoolError::InvalidSwapLimits.into());
+            }
+
+            // The supply cap of a sale is its hard cap
+            if pool_data.has_sale() && supply_cap != pool_data.supply_cap {
+                msg!("Supply cap is the hard cap of the sale, set it with the sale");
+                return Err(PoolError::InvalidSale.into());
+            }
+
+            pool_data.supply_cap = supply_cap;
+            pool_data.min_amount_in = min_amount_in;
+            pool_data.max_amount_in = max_amount_in;
+
+            pool_data.serialize(&mut *pool_info.data.borrow_mut())?;
+        }
+        PoolInstruction::SetSale {
+            start,
+            end,
+            soft_cap_bps,
+            hard_cap,
+        } => {
+            msg!("PoolInstruction::SetSale");
+
+            let pool_info = next_account_info(account_info_iter)?;
+            let manager_info = next_account_info(account_info_iter)?;
+            let pool_mint_info = next_account_info(account_info_iter)?;
+            let clock_info = next_account_info(account_info_iter)?;
+
+            check_account_owner(pool_info, program_id)?;
+
+            let mut pool_data = PoolData::try_from_slice_unchecked(&pool_info.data.borrow())?;
+
+            if !pool_data.is_initialized() {
+                return Err(ProgramError::UninitializedAccount);
+            }
+            pool_data.check_version()?;
+
+            pool_data.check_constant_product(false)?;
+
+            pool_data.check_manager(manager_info)?;
+
+            if pool_data.pool_mint != *pool_mint_info.key {
+                return Err(PoolError::InvalidPoolMint.into());
+            }
+
+            // Refunds only pay back the deposits at a rate that doesn't move
+            if pool_data.curve != CurveType::Constant || pool_data.oracle != Pubkey::default() {
+                msg!("Pools priced by a curve or an oracle can't run a sale");
+                return Err(PoolError::InvalidPoolMode.into());
+            }
+
+            // Depositors of a running sale count on its caps and refunds
+            let clock = Clock::from_account_info(clock_info)?;
+            if pool_data.has_sale() && clock.unix_timestamp >= pool_data.sale_start {
+                msg!("Sale started at {}", pool_data.sale_start);
+                return Err(PoolError::InvalidSale.into());
+            }
+
+            if end == 0 {
+                // Sales only start without a supply cap, so the hard cap is lifted with them
+                if pool_data.has_sale() {
+                    pool_data.supply_cap = 0;
+                }
+                pool_data.sale_start = 0;
+                pool_data.sale_end = 0;
+                pool_data.soft_cap_bps = 0;
+            } else {
+                if start >= end || end <= clock.unix_timestamp {
+                    msg!(
+                        "Sale from {} to {} has no time left, now is {}",
+                        start,
+                        end,
+                        clock.unix_timestamp
+                    );
+                    return Err(PoolError::InvalidSale.into());
+                }
+
+                // The proceeds of a sale are the whole bank
+                let pool_mint = token::state::Mint::unpack(&pool_mint_info.data.borrow())?;
+                if pool_mint.supply != 0 {
+                    msg!("Pool mint supply is {}", pool_mint.supply);
+                    return Err(PoolError::PoolNotEmpty.into());
+                }
+
+                // The hard cap would replace the supply cap, which removing the sale can't restore
+                if !pool_data.has_sale() && pool_data.supply_cap != 0 {
+                    msg!(
+                        "Supply cap is {}, lift it before the sale",
+                        pool_data.supply_cap
+                    );
+                    return Err(PoolError::InvalidSale.into());
+                }
+
+                if soft_cap_bps as u64 > PoolData::BPS_DENOMINATOR
+                    || (soft_cap_bps != 0 && hard_cap == 0)
+                {
+                    msg!("Soft cap of {} bps needs a hard cap", soft_cap_bps);
+                    return Err(PoolError::InvalidSale.into());
+                }
+
+                pool_data.sale_start = start;
+                pool_data.sale_end = end;
+                pool_data.soft_cap_bps = soft_cap_bps;
+                pool_data.supply_cap = hard_cap;
+            }
+
+            pool_data.serialize(&mut *pool_info.data.borrow_mut())?;
+        }
+        PoolInstruction::WithdrawProceeds => {
+            msg!("PoolInstruction::WithdrawProceeds");
+
+            let pool_info = next_account_info(account_info_iter)?;
+            let pool_authority_info = next_account_info(account_info_iter)?;
+            let manager_info = next_account_info(account_info_iter)?;
+            let pool_mint_info = next_account_info(account_info_iter)?;
+            let bank_info = next_account_info(account_info_iter)?;
+            let destination_info = next_account_info(account_info_iter)?;
+            let fee_receiver_info = next_account_info(account_info_iter)?;
+            let clock_info = next_account_info(account_info_iter)?;
+            let token_program_info = next_account_info(account_info_iter)?;
+
+            check_account_owner(pool_info, program_id)?;
+            check_token_program(token_program_info)?;
+
+            let mut pool_data = PoolData::try_from_slice_unchecked(&pool_info.data.borrow())?;
+
+            if !pool_data.is_initialized() {
+                return Err(ProgramError::UninitializedAccount);
+            }
+            pool_data.check_version()?;
+
+            pool_data.check_manager(manager_info)?;
+
+            // Check autority
+            pool_data.check_authority(pool_authority_info.key, program_id, pool_info.key)?;
+
+            if pool_data.bank != *bank_info.key {
+                return Err(PoolError::InvalidBank.into());
+            }
+
+            if pool_data.pool_mint != *pool_mint_info.key {
+                return Err(PoolError::InvalidPoolMint.into());
+            }
+
+            if pool_data.fee_receiver != *fee_receiver_info.key {
+                return Err(PoolError::InvalidFeeReceiver.into());
+            }
+
+            let clock = Clock::from_account_info(clock_info)?;
+            pool_data.check_sale_ended(clock.unix_timestamp)?;
+
+            // Refunds would be owed out of the bank instead
+            let pool_mint = token::state::Mint::unpack(&pool_mint_info.data.borrow())?;
+            if !pool_data.is_soft_cap_met(pool_mint.supply)? {
+                msg!(
+                    "Sale sold {} of the {} soft cap",
+                    pool_mint.supply,
+                    pool_data.soft_cap()?
+                );
+                return Err(PoolError::SoftCapMissed.into());
+            }
+
+            // The rewards leave with the rest of the bank
+            let bank = token::state::Account::unpack(&bank_info.data.borrow())?;
+            pool_data.undistributed_rewards = 0;
+
+            pool_data.serialize(&mut *pool_info.data.borrow_mut())?;
+
+            // Sale swaps paid no fee, so it's taken out of the proceeds
+            let fee = pool_data.calc_fee(bank.amount)?;
+            if fee > 0 {
+                spl_token_transfer(
+                    pool_info.key,
+                    bank_info.clone(),
+                    fee_receiver_info.clone(),
+                    pool_authority_info.clone(),
+                    pool_data.bump_seed,
+                    fee,
+                )?;
+            }
+
+            spl_token_transfer(
+                pool_info.key,
+                bank_info.clone(),
+                destination_info.clone(),
+                pool_authority_info.clone(),
+                pool_data.bump_seed,
+                math::checked_sub(bank.amount, fee)?,
+            )?;
+        }
+        PoolInstruction::WithdrawRewards => {
+            msg!("PoolInstruction::WithdrawRewards");
+
+            let pool_info = next_account_info(account_info_iter)?;
+            let pool_authority_info = next_account_info(account_info_iter)?;
+            let manager_info = next_account_info(account_info_iter)?;
+            let pool_mint_info = next_account_info(account_info_iter)?;
+            let bank_info = next_account_info(account_info_iter)?;
+            let destination_info = next_account_info(account_info_iter)?;
+            let clock_info = next_account_info(account_info_iter)?;
+            let token_program_info = next_account_info(account_info_iter)?;
+
+            check_account_owner(pool_info, program_id)?;
+            check_token_program(token_program_info)?;
+
+            let mut pool_data = PoolData::try_from_slice_unchecked(&pool_info.data.borrow())?;
+
+            if !pool_data.is_initialized() {
+                return Err(ProgramError::UninitializedAccount);
+            }
+            pool_data.check_version()?;
+
+            pool_data.check_manager(manager_info)?;
+
+            // Check autority
+            pool_data.check_authority(pool_authority_info.key, program_id, pool_info.key)?;
+
+            if pool_data.bank != *bank_info.key {
+                return Err(PoolError::InvalidBank.into());
+            }
+
+            if pool_data.pool_mint != *pool_mint_info.key {
+                return Err(PoolError::InvalidPoolMint.into());
+            }
+
+            accrue_interest(&mut pool_data, bank_info, clock_info)?;
+
+            // Nothing in the bank of a pool without pool tokens is owned by anyone, the
+            // virtual tokens may have kept some of it from the last redeem
+            let pool_mint = token::state::Mint::unpack(&pool_mint_info.data.borrow())?;
+            let amount = if pool_mint.supply == 0 {
+                token::state::Account::unpack(&bank_info.data.borrow())?.amount
+            } else {
+                // Rewards still owed as interest belong to the pool token holders
+                if pool_data.apr_bps > 0 {
+                    msg!("Rewards are paid out at {} bps a year", pool_data.apr_bps);
+                    return Err(PoolError::RewardsAccruing.into());
+                }
+                pool_data.undistributed_rewards
+            };
+            pool_data.undistributed_rewards = 0;
+
+            pool_data.serialize(&mut *pool_info.data.borrow_mut())?;
+
+            spl_token_transfer(
+                pool_info.key,
+                bank_info.clone(),
+                destination_info.clone(),
+                pool_authority_info.clone(),
+                pool_data.bump_seed,
+                amount,
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Approval of the user to swap with a restricted pool
+enum SwapApproval<'a> {
+    /// Plain swap
+    None,
+    /// Merkle proof of the user on the pool allowlist
+    Allowlist(&'a [MerkleHash]),
+    /// Permit to verify with an Ed25519 instruction of the transaction
+    Permit {
+        max_amount: u64,
+        expiry_slot: u64,
+        nonce: u64,
+    },
+}
+
+/// Side of a swap fixed by the user, with the limit for the other side
+enum SwapLimit {
+    ExactIn {
+        amount_in: u64,
+        minimum_amount_out: u64,
+    },
+    ExactOut {
+        amount_out: u64,
+        maximum_amount_in: u64,
+    },
+}
+
+/// Swaps bank tokens to pool tokens, failing if the amounts are outside the limit
+fn process_swap(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    limit: SwapLimit,
+    approval: SwapApproval,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let pool_info = next_account_info(account_info_iter)?;
+    let pool_authority_info = next_account_info(account_info_iter)?;
+    let user_transfer_authority_info = next_account_info(account_info_iter)?;
+    let pool_mint_info = next_account_info(account_info_iter)?;
+    let bank_info = next_account_info(account_info_iter)?;
+    let fee_receiver_info = next_account_info(account_info_iter)?;
+    let sender_info = next_account_info(account_info_iter)?;
+    let recipient_info = next_account_info(account_info_iter)?;
+    let clock_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+
+    check_account_owner(pool_info, program_id)?;
+    check_token_program(token_program_info)?;
+
+    let mut pool_data = PoolData::try_from_slice_unchecked(&pool_info.data.borrow())?;
+
+    if !pool_data.is_initialized() {
+        return Err(ProgramError::UninitializedAccount);
+    }
+    pool_data.check_version()?;
+
+    pool_data.check_constant_product(false)?;
+
+    pool_data.check_not_paused()?;
+
+    // Check autority
+    pool_data.check_authority(pool_authority_info.key, program_id, pool_info.key)?;
+    check_user_transfer_authority(user_transfer_authority_info, pool_authority_info)?;
+
+    if pool_data.bank != *bank_info.key {
+        return Err(PoolError::InvalidBank.into());
+    }
+
+    if pool_data.pool_mint != *pool_mint_info.key {
+        return Err(PoolError::InvalidPoolMint.into());
+    }
+
+    if pool_data.fee_receiver != *fee_receiver_info.key {
+        return Err(PoolError::InvalidFeeReceiver.into());
+    }
+
+    // A permit of the permit key stands in for the allowlist proof
+    match approval {
+        SwapApproval::None => pool_data.check_allowlist(user_transfer_authority_info, None)?,
+        SwapApproval::Allowlist(proof) => {
+            pool_data.check_allowlist(user_transfer_authority_info, Some(proof))?
+        }
+        SwapApproval::Permit { .. } => {}
+    }
+    if pool_data.has_permit_key() && !matches!(approval, SwapApproval::Permit { .. }) {
+        msg!("Pool only swaps with a permit");
+        return Err(PoolError::InvalidPermit.into());
+    }
+
+    let clock = Clock::from_account_info(clock_info)?;
+    pool_data.check_sale_open(clock.unix_timestamp)?;
+
+    accrue_interest(&mut pool_data, bank_info, clock_info)?;
+    let rate = if pool_data.oracle == Pubkey::default() {
+        exchange_rate(&pool_data, pool_mint_info, bank_info)?
+    } else {
+        let oracle_info = next_account_info(account_info_iter)?;
+        oracle_exchange_rate(
+            &pool_data,
+            oracle_info,
+            pool_mint_info,
+            bank_info,
+            clock_info,
+        )?
+    };
+    let SwapAmounts {
+        amount_in,
+        fee,
+        deposit,
+        amount_out,
+    } = match limit {
+        SwapLimit::ExactIn {
+            amount_in,
+            minimum_amount_out,
+        } => {
+            let amounts = pool_data.calc_swap_exact_in(&rate, amount_in)?;
+            if amounts.amount_out < minimum_amount_out {
+                msg!(
+                    "Amount out {} is below the minimum {}",
+                    amounts.amount_out,
+                    minimum_amount_out
+                );
+                return Err(PoolError::ExceededSlippage.into());
+            }
+            amounts
+        }
+        SwapLimit::ExactOut {
+            amount_out,
+            maximum_amount_in,
+        } => {
+            let amounts = pool_data.calc_swap_exact_out(&rate, amount_out)?;
+            if amounts.amount_in > maximum_amount_in {
+                msg!(
+                    "Amount in {} is above the maximum {}",
+                    amounts.amount_in,
+                    maximum_amount_in
+                );
+                return Err(PoolError::ExceededSlippage.into());
+            }
+            amounts
+        }
+    };
+
+    pool_data.check_amount_in(amount_in)?;
+    pool_data.check_supply_cap(rate.pool_supply, amount_out)?;
+
+    // Users lose the whole amount if the fee and the price round it down to nothing
+    if amount_in > 0 && amount_out == 0 {
+        msg!("Amount in {} mints no pool tokens", amount_in);
+        return Err(PoolError::ZeroAmountOut.into());
+    }
+
+    if let SwapApproval::Permit {
+        max_amount,
+        expiry_slot,
+        nonce,
+    } = approval
+    {
+        let permit = Permit {
+            pool: *pool_info.key,
+            user: *user_transfer_authority_info.key,
+            max_amount,
+            expiry_slot,
+            nonce,
+        };
+        if !user_transfer_authority_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        use_permit(
+            program_id,
+            &pool_data,
+            &permit,
+            amount_in,
+            pool_info,
+            clock_info,
+            account_info_iter,
+        )?;
+    }
+
+    if pool_data.has_deposit_limit() {
+        // Delegates swap against the limit of the owner of the bank tokens
+        let user = token::state::Account::unpack(&sender_info.data.borrow())?.owner;
+        record_deposit(
+            program_id,
+            &pool_data,
+            amount_in,
+            pool_info,
+            &user,
+            clock_info,
+            account_info_iter,
+        )?;
+    }
+
+    pool_data.serialize(&mut *pool_info.data.borrow_mut())?;
+
+    // Transfer fee tokens from user
+    if fee > 0 {
+        spl_token_user_transfer(
+            sender_info.clone(),
+            fee_receiver_info.clone(),
+            user_transfer_authority_info.clone(),
+            fee,
+        )?;
+    }
+
+    // Transfer savings tokens from user
+    spl_token_user_transfer(
+        sender_info.clone(),
+        bank_info.clone(),
+        user_transfer_authority_info.clone(),
+        deposit,
+    )?;
+
+    // Mint pool tokens to user
+    spl_token_mint_to(
+        pool_info.key,
+        pool_mint_info.clone(),
+        recipient_info.clone(),
+        pool_authority_info.clone(),
+        pool_data.bump_seed,
+        amount_out,
+    )?;
+
+    Ok(())
+}
+
+/// Checks the permit is verified for the permit key and marks its nonce as used
+fn use_permit<'a, 'b>(
+    program_id: &Pubkey,
+    pool_data: &PoolData,
+    permit: &Permit,
+    amount_in: u64,
+    pool_info: &AccountInfo<'a>,
+    clock_info: &AccountInfo<'a>,
+    account_info_iter: &mut std::slice::Iter<'b, AccountInfo<'a>>,
+) -> ProgramResult {
+    let instructions_info = next_account_info(account_info_iter)?;
+    let nonce_info = next_account_info(account_info_iter)?;
+    let payer_info = next_account_info(account_info_iter)?;
+    let rent_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+
+    if !pool_data.has_permit_key() {
+        msg!("Pool has no permit key");
+        return Err(PoolError::InvalidPermit.into());
+    }
+
+    let clock = Clock::from_account_info(clock_info)?;
+    if clock.slot > permit.expiry_slot {
+        msg!(
+            "Permit expired at slot {}, current slot {}",
+            permit.expiry_slot,
+            clock.slot
+        );
+        return Err(PoolError::PermitExpired.into());
+    }
+
+    if amount_in > permit.max_amount {
+        msg!(
+            "Amount in {} is above the permit maximum {}",
+            amount_in,
+            permit.max_amount
+        );
+        return Err(PoolError::InvalidPermit.into());
+    }
+
+    if *instructions_info.key != sysvar::instructions::id() {
+        msg!(
+            "Incorrect instructions sysvar provided, received {}",
+            instructions_info.key
+        );
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    // The Ed25519 program checks the signatures before the transaction runs
+    let message = permit.message()?;
+    let instructions = instructions_info.data.borrow();
+    let mut index = 0;
+    let mut verified = false;
+    while let Ok(instruction) = load_instruction_at(index, &instructions) {
+        if instruction.program_id == ed25519_program::id()
+            && permit::is_verified_by(&instruction.data, &pool_data.permit_key, &message)
+        {
+            verified = true;
+            break;
+        }
+        index += 1;
+    }
+    if !verified {
+        msg!("No Ed25519 instruction verifies the permit");
+        return Err(PoolError::InvalidPermit.into());
+    }
+
+    let (nonce_address, nonce_bump_seed) =
+        find_permit_nonce_address(program_id, pool_info.key, permit.nonce);
+    if nonce_address != *nonce_info.key {
+        msg!(
+            "Incorrect nonce account provided, expected {}, received {}",
+            nonce_address,
+            nonce_info.key
+        );
+        return Err(PoolError::InvalidPermit.into());
+    }
+
+    if nonce_info.owner == program_id {
+        msg!("Permit nonce {} was used", permit.nonce);
+        return Err(PoolError::PermitUsed.into());
+    }
+
+    // The empty account at the nonce address marks it as used
+    create_pda_account(
+        payer_info.clone(),
+        nonce_info.clone(),
+        system_program_info.clone(),
+        &Rent::from_account_info(rent_info)?,
+        0,
+        program_id,
+        &[
+            &pool_info.key.to_bytes()[..32],
+            PERMIT_SEED,
+            &permit.nonce.to_le_bytes(),
+            &[nonce_bump_seed],
+        ],
+    )
+}
+
+/// Counts the swap on the deposit record of the user, created by the first swap
+fn record_deposit<'a, 'b>(
+    program_id: &Pubkey,
+    pool_data: &PoolData,
+    amount_in: u64,
+    pool_info: &AccountInfo<'a>,
+    user: &Pubkey,
+    clock_info: &AccountInfo<'a>,
+    account_info_iter: &mut std::slice::Iter<'b, AccountInfo<'a>>,
+) -> ProgramResult {
+    let deposit_record_info = next_account_info(account_info_iter)?;
+    let payer_info = next_account_info(account_info_iter)?;
+    let rent_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+
+    let (deposit_record_address, deposit_record_bump_seed) =
+        find_deposit_record_address(program_id, pool_info.key, user);
+    if deposit_record_address != *deposit_record_info.key {
+        msg!(
+            "Incorrect deposit record provided, expected {}, received {}",
+            deposit_record_address,
+            deposit_record_info.key
+        );
+        return Err(PoolError::InvalidDepositRecord.into());
+    }
+
+    let epoch = Clock::from_account_info(clock_info)?.epoch;
+    let mut deposit_record = if deposit_record_info.owner == program_id {
+        let deposit_record =
+            DepositRecord::try_from_slice_unchecked(&deposit_record_info.data.borrow())?;
+        if !deposit_record.is_initialized() {
+            return Err(PoolError::InvalidDepositRecord.into());
+        }
+        deposit_record
+    } else {
+        create_pda_account(
+            payer_info.clone(),
+            deposit_record_info.clone(),
+            system_program_info.clone(),
+            &Rent::from_account_info(rent_info)?,
+            DepositRecord::LEN,
+            program_id,
+            &[
+                &pool_info.key.to_bytes()[..32],
+                &user.to_bytes()[..32],
+                &[deposit_record_bump_seed],
+            ],
+        )?;
+        DepositRecord {
+            pool: *pool_info.key,
+            user: *user,
+            amount_in: 0,
+            epoch,
+        }
+    };
+
+    deposit_record.add_deposit(pool_data, amount_in, epoch)?;
+    deposit_record.serialize(&mut *deposit_record_info.data.borrow_mut())?;
+
+    Ok(())
+}
+
+/// Reads the exchange rate from the pool mint supply and the bank balance
+fn exchange_rate(
+    pool_data: &PoolData,
+    pool_mint_info: &AccountInfo,
+    bank_info: &AccountInfo,
+) -> Result<ExchangeRate, ProgramError> {
+    let pool_mint = token::state::Mint::unpack(&pool_mint_info.data.borrow())?;
+    let bank = token::state::Account::unpack(&bank_info.data.borrow())?;
+
+    Ok(pool_data.exchange_rate(pool_mint.supply, bank.amount)?)
+}
+
+/// Reads the exchange rate swapping and redeeming pool tokens at the oracle price
+fn oracle_exchange_rate(
+    pool_data: &PoolData,
+    oracle_info: &AccountInfo,
+    pool_mint_info: &AccountInfo,
+    bank_info: &AccountInfo,
+    clock_info: &AccountInfo,
+) -> Result<ExchangeRate, ProgramError> {
+    if pool_data.oracle != *oracle_info.key {
+        msg!(
+            "Incorrect oracle provided, expected {}, received {}",
+            pool_data.oracle,
+            oracle_info.key
+        );
+        return Err(PoolError::InvalidOracle.into());
+    }
+
+    let clock = Clock::from_account_info(clock_info)?;
+    let price = PriceData::try_from_slice_unchecked(&oracle_info.data.borrow())?;
+    price.check_age(clock.slot, pool_data.oracle_max_age)?;
+
+    let pool_mint = token::state::Mint::unpack(&pool_mint_info.data.borrow())?;
+    let bank = token::state::Account::unpack(&bank_info.data.borrow())?;
+    let distributed = math::checked_sub(bank.amount, pool_data.undistributed_rewards)?;
+
+    Ok(price.exchange_rate(pool_mint.supply, distributed)?)
+}
+
+/// Checks the accounts moving liquidity of a constant product pool
+fn check_constant_product_accounts(
+    program_id: &Pubkey,
+    pool_info: &AccountInfo,
+    pool_authority_info: &AccountInfo,
+    pool_mint_info: &AccountInfo,
+    bank_info: &AccountInfo,
+    reserve_info: &AccountInfo,
+    token_program_info: &AccountInfo,
+) -> Result<PoolData, ProgramError> {
+    check_account_owner(pool_info, program_id)?;
+    check_token_program(token_program_info)?;
+
+    let pool_data = PoolData::try_from_slice_unchecked(&pool_info.data.borrow())?;
+
+    if !pool_data.is_initialized() {
+        return Err(ProgramError::UninitializedAccount);
+    }
+    pool_data.check_version()?;
+
+    pool_data.check_constant_product(true)?;
+
+    pool_data.check_not_paused()?;
+
+    // Check autority
+    pool_data.check_authority(pool_authority_info.key, program_id, pool_info.key)?;
+
+    if pool_data.bank != *bank_info.key {
+        return Err(PoolError::InvalidBank.into());
+    }
+
+    if pool_data.reserve != *reserve_info.key {
+        return Err(PoolError::InvalidReserve.into());
+    }
+
+    if pool_data.pool_mint != *pool_mint_info.key {
+        return Err(PoolError::InvalidPoolMint.into());
+    }
+
+    Ok(pool_data)
+}
+
+/// Reads the pool mint supply and the balances of both reserves
+fn constant_product(
+    pool_mint_info: &AccountInfo,
+    bank_info: &AccountInfo,
+    reserve_info: &AccountInfo,
+) -> Result<ConstantProduct, ProgramError> {
+    let pool_mint = token::state::Mint::unpack(&pool_mint_info.data.borrow())?;
+    let bank = token::state::Account::unpack(&bank_info.data.borrow())?;
+    let reserve = token::state::Account::unpack(&reserve_info.data.borrow())?;
+
+    Ok(ConstantProduct {
+        pool_supply: pool_mint.supply,
+        bank_balance: bank.amount,
+        reserve_balance: reserve.amount,
+    })
+}
+
+/// Accrues the pool interest up to the slot of the clock sysvar
+fn accrue_interest(
+    pool_data: &mut PoolData,
+    bank_info: &AccountInfo,
+    clock_info: &AccountInfo,
+) -> ProgramResult {
+    let clock = Clock::from_account_info(clock_info)?;
+    let bank = token::state::Account::unpack(&bank_info.data.borrow())?;
+
+    Ok(pool_data.accrue_interest(bank.amount, clock.slot)?)
+}
+
+/// Checks that the account is owned by the expected program
+fn check_account_owner(account_info: &AccountInfo, owner: &Pubkey) -> ProgramResult {
+    if account_info.owner != owner {
+        msg!(
+            "Incorrect owner of {}, expected {}, received {}",
+            account_info.key,
+            owner,
+            account_info.owner
+        );
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    Ok(())
+}
+
+/// Checks that user tokens are never moved with the pool authority
+fn check_user_transfer_authority(
+    user_transfer_authority_info: &AccountInfo,
+    pool_authority_info: &AccountInfo,
+) -> ProgramResult {
+    if user_transfer_authority_info.key == pool_authority_info.key {
+        msg!("Pool authority can't authorize user transfers");
+        return Err(PoolError::InvalidAuthority.into());
+    }
+
+    Ok(())
+}
+
+/// Checks that user tokens don't come from the bank or the reserve
+fn check_user_source(source_info: &AccountInfo, pool_data: &PoolData) -> ProgramResult {
+    if *source_info.key == pool_data.bank || *source_info.key == pool_data.reserve {
+        msg!("User tokens can't come from the bank or the reserve");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    Ok(())
+}
+
+/// Checks that the token program is valid
+fn check_token_program(token_program_info: &AccountInfo) -> ProgramResult {
+    if *token_program_info.key != token::id() {
+        msg!(
+            "Incorrect token program provided, expected {}, received {}",
+            token::id(),
+            token_program_info.key
+        );
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    Ok(())
+}
+
+/// Creates an account at the program address, funded by the payer
+pub fn create_pda_account<'a>(
+    payer: AccountInfo<'a>,
+    new_account: AccountInfo<'a>,
+    system_program: AccountInfo<'a>,
+    rent: &Rent,
+    space: usize,
+    owner: &Pubkey,
+    signer_seeds: &[&[u8]],
+) -> Result<(), ProgramError> {
+    let required_lamports = rent.minimum_balance(space);
+
+    if new_account.lamports() == 0 {
+        let ix = system_instruction::create_account(
+            payer.key,
+            new_account.key,
+            required_lamports,
+            space as u64,
+            owner,
+        );
+
+        return invoke_signed(&ix, &[payer, new_account, system_program], &[signer_seeds]);
+    }
+
+    // Someone may have sent lamports to the address, so create_account would fail
+    let top_up = required_lamports.saturating_sub(new_account.lamports());
+    if top_up > 0 {
+        let ix = system_instruction::transfer(payer.key, new_account.key, top_up);
+        invoke(&ix, &[payer, new_account.clone(), system_program.clone()])?;
+    }
+
+    let ix = system_instruction::allocate(new_account.key, space as u64);
+    invoke_signed(
+        &ix,
+        &[new_account.clone(), system_program.clone()],
+        &[signer_seeds],
+    )?;
+
+    let ix = system_instruction::assign(new_account.key, owner);
+    invoke_signed(&ix, &[new_account, system_program], &[signer_seeds])
+}
+
+/// Create a mint instruction.
+pub fn spl_initialize_mint<'a>(
+    mint: AccountInfo<'a>,
+    mint_authority: AccountInfo<'a>,
+    rent: AccountInfo<'a>,
+    decimals: u8,
+) -> Result<(), ProgramError> {
+    let ix = token::instruction::initialize_mint(
+        &token::id(),
+        mint.key,
+        mint_authority.key,
+        None,
+        decimals,
+    )?;
+
+    invoke(&ix, &[mint, rent])
+}
+
+/// Create an accont instruction.
+pub fn spl_initialize_account<'a>(
+    account: AccountInfo<'a>,
+    mint: AccountInfo<'a>,
+    authority: AccountInfo<'a>,
+    rent: AccountInfo<'a>,
+) -> Result<(), ProgramError> {
+    let ix =
+        token::instruction::initialize_account(&token::id(), account.key, mint.key, authority.key)?;
+
+    invoke(&ix, &[account, mint, authority, rent])
+}
+
+/// Issue a transfer instruction.
+pub fn spl_token_transfer<'a>(
+    pool: &Pubkey,
+    source: AccountInfo<'a>,
+    destination: AccountInfo<'a>,
+    authority: AccountInfo<'a>,
+    bump_seed: u8,
+    amount: u64,
+) -> Result<(), ProgramError> {
+    let authority_signature_seeds = [&pool.to_bytes()[..32], &[bump_seed]];
+    let signers = &[&authority_signature_seeds[..]];
+
+    let ix = token::instruction::transfer(
+        &token::id(),
+        source.key,
+        destination.key,
+        authority.key,
+        &[],
+        amount,
+    )?;
+
+    invoke_signed(&ix, &[source, destination, authority], signers)
+}
+
+/// Issue a transfer instruction signed by the user, never by the pool authority.
+pub fn spl_token_user_transfer<'a>(
+    source: AccountInfo<'a>,
+    destination: AccountInfo<'a>,
+    authority: AccountInfo<'a>,
+    amount: u64,
+) -> Result<(), ProgramError> {
+    let ix = token::instruction::transfer(
+        &token::id(),
+        source.key,
+        destination.key,
+        authority.key,
+        &[],
+        amount,
+    )?;
+
+    invoke(&ix, &[source, destination, authority])
+}
+
+/// Issue a mint instruction.
+pub fn spl_token_mint_to<'a>(
+    pool: &Pubkey,
+    mint: AccountInfo<'a>,
+    destination: AccountInfo<'a>,
+    authority: AccountInfo<'a>,
+    bump_seed: u8,
+    amount: u64,
+) -> Result<(), ProgramError> {
+    let authority_signature_seeds = [&pool.to_bytes()[..32], &[bump_seed]];
+    let signers = &[&authority_signature_seeds[..]];
+
+    let ix = token::instruction::mint_to(
+        &token::id(),
+        mint.key,
+        destination.key,
+        authority.key,
+        &[],
+        amount,
+    )?;
+
+    invoke_signed(&ix, &[mint, destination, authority], signers)
+}
+
+/// Issue a burn instruction.
+pub fn spl_token_burn<'a>(
+    pool: &Pubkey,
+    account: AccountInfo<'a>,
+    mint: AccountInfo<'a>,
+    authority: AccountInfo<'a>,
+    bump_seed: u8,
+    amount: u64,
+) -> Result<(), ProgramError> {
+    let authority_signature_seeds = [&pool.to_bytes()[..32], &[bump_seed]];
+    let signers = &[&authority_signature_seeds[..]];
+
+    let ix = token::instruction::burn(
+        &token::id(),
+        account.key,
+        mint.key,
+        authority.key,
+        &[],
+        amount,
+    )?;
+
+    invoke_signed(&ix, &[account, mint, authority], signers)
+}
+
+/// Issue a burn instruction signed by the user, never by the pool authority.
+pub fn spl_token_user_burn<'a>(
+    account: AccountInfo<'a>,
+    mint: AccountInfo<'a>,
+    authority: AccountInfo<'a>,
+    amount: u64,
+) -> Result<(), ProgramError> {
+    let ix = token::instruction::burn(
+        &token::id(),
+        account.key,
+        mint.key,
+        authority.key,
+        &[],
+        amount,
+    )?;
+
+    invoke(&ix, &[account, mint, authority])
+}
+
+/// Issue a close account instruction.
+pub fn spl_token_close_account<'a>(
+    pool: &Pubkey,
+    account: AccountInfo<'a>,
+    destination: AccountInfo<'a>,
+    authority: AccountInfo<'a>,
+    bump_seed: u8,
+) -> Result<(), ProgramError> {
+    let authority_signature_seeds = [&pool.to_bytes()[..32], &[bump_seed]];
+    let signers = &[&authority_signature_seeds[..]];
+
+    let ix = token::instruction::close_account(
+        &token::id(),
+        account.key,
+        destination.key,
+        authority.key,
+        &[],
+    )?;
+
+    invoke_signed(&ix, &[account, destination, authority], signers)
+}
+
+/// Issue a set authority instruction handing the account over to a new authority.
+pub fn spl_token_set_authority<'a>(
+    pool: &Pubkey,
+    owned: AccountInfo<'a>,
+    new_authority: &Pubkey,
+    authority_type: token::instruction::AuthorityType,
+    authority: AccountInfo<'a>,
+    bump_seed: u8,
+) -> Result<(), ProgramError> {
+    let authority_signature_seeds = [&pool.to_bytes()[..32], &[bump_seed]];
+    let signers = &[&authority_signature_seeds[..]];
+
+    let ix = token::instruction::set_authority(
+        &token::id(),
+        owned.key,
+        Some(new_authority),
+        authority_type,
+        authority.key,
+        &[],
+    )?;
+
+    invoke_signed(&ix, &[owned, authority], signers)
+}
+
+/// Issue a set authority instruction removing the mint authority.
+pub fn spl_token_remove_mint_authority<'a>(
+    pool: &Pubkey,
+    mint: AccountInfo<'a>,
+    authority: AccountInfo<'a>,
+    bump_seed: u8,
+) -> Result<(), ProgramError> {
+    let authority_signature_seeds = [&pool.to_bytes()[..32], &[bump_seed]];
+    let signers = &[&authority_signature_seeds[..]];
+
+    let ix = token::instruction::set_authority(
+        &token::id(),
+        mint.key,
+        None,
+        token::instruction::AuthorityType::MintTokens,
+        authority.key,
+        &[],
+    )?;
+
+    invoke_signed(&ix, &[mint, authority], signers)
 }
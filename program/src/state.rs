@@ -1,9 +1,22 @@
 //! State transition types
 
-use solana_program::{msg, program_error::ProgramError};
+use crate::{
+    curve::CurveType,
+    error::PoolError,
+    math::{checked_add, checked_sub, compound_interest, mul_div, Rounding},
+    merkle::{self, MerkleHash},
+};
+use solana_program::{
+    account_info::AccountInfo, borsh::try_from_slice_unchecked, clock::UnixTimestamp, msg,
+    program_error::ProgramError,
+};
+use std::{collections::HashMap, fmt, io};
 
 use {
-    borsh::{BorshDeserialize, BorshSchema, BorshSerialize},
+    borsh::{
+        schema::{Declaration, Definition},
+        BorshDeserialize, BorshSchema, BorshSerialize,
+    },
     solana_program::{program_pack::IsInitialized, pubkey::Pubkey},
 };
 
@@ -27,14 +40,432 @@ pub struct PoolData {
 
     /// Account for tokens from user
     pub bank: Pubkey,
+
+    /// Numerator of the pool tokens minted per bank token by an empty pool
+    pub rate_numerator: u64,
+
+    /// Denominator of the pool tokens minted per bank token by an empty pool
+    pub rate_denominator: u64,
+
+    /// Swap fee in basis points, also charged on flash loans and sale proceeds
+    pub fee_bps: u16,
+
+    /// Bank mint token account receiving swap fees
+    pub fee_receiver: Pubkey,
+
+    /// The account allowed to administer the pool
+    pub manager: Pubkey,
+
+    /// Manager proposed by the current one, default if there is no proposal
+    pub pending_manager: Pubkey,
+
+    /// Swaps and redeems are rejected while the pool is paused
+    pub status: PoolStatus,
+
+    /// Annual interest paid to pool tokens out of the undistributed rewards, in basis points
+    pub apr_bps: u16,
+
+    /// Rewards held in the bank that are not yet paid out as interest
+    pub undistributed_rewards: u64,
+
+    /// Slot up to which interest is accrued
+    pub last_update_slot: u64,
+
+    /// Price account swaps mint at, default to mint at the share price
+    pub oracle: Pubkey,
+
+    /// Most slots since the last oracle update the price is accepted for
+    pub oracle_max_age: u64,
+
+    /// Curve pricing pool tokens by the supply, takes up to 25 bytes
+    pub curve: CurveType,
+
+    /// Second mint of a constant product pool, default if the pool only mints pool tokens
+    pub reserve_mint: Pubkey,
+
+    /// Account for the reserve mint tokens of a constant product pool
+    pub reserve: Pubkey,
+
+    /// Merkle root of the users allowed to swap, zero if anyone can swap
+    pub allowlist_root: MerkleHash,
+
+    /// Key signing swap permits, default if swaps need no permit
+    pub permit_key: Pubkey,
+
+    /// Most bank tokens a user can swap in, zero if users have no limit
+    pub deposit_limit: u64,
+
+    /// Period the deposit limit applies to
+    pub deposit_period: DepositPeriod,
+
+    /// Most pool tokens in supply after a swap, zero if the supply has no cap
+    pub supply_cap: u64,
+
+    /// Least bank tokens a swap takes, fee included
+    pub min_amount_in: u64,
+
+    /// Most bank tokens a swap takes, fee included, zero if swaps have no maximum
+    pub max_amount_in: u64,
+
+    /// Unix timestamp swaps open at when the pool runs a sale
+    pub sale_start: UnixTimestamp,
+
+    /// Unix timestamp swaps close at, zero if the pool runs no sale
+    pub sale_end: UnixTimestamp,
+
+    /// Pool tokens the sale has to sell to succeed, in basis points of the supply cap
+    pub soft_cap_bps: u16,
+
+    /// Zeroed space for fields added by later versions
+    pub reserved: Reserved,
+}
+
+/// Pool data layout of version 1, written by the first release of the program
+#[derive(Clone, Debug, BorshSerialize, BorshDeserialize, BorshSchema, PartialEq)]
+pub struct PoolDataV1 {
+    /// Struct version, always 1
+    pub version: u8,
+
+    /// The account allowed to update the data
+    pub authority: Pubkey,
+
+    /// Bump seed
+    pub bump_seed: u8,
+
+    /// Mint for the tokens sent to the pool
+    pub bank_mint: Pubkey,
+
+    /// Mint for sending tokens to user
+    pub pool_mint: Pubkey,
+
+    /// Account for tokens from user
+    pub bank: Pubkey,
+}
+
+impl PoolDataV1 {
+    /// Version of the layout
+    pub const VERSION: u8 = 1;
+
+    /// Size of the version 1 pool account
+    pub const LEN: usize = 130;
+
+    /// Deserializes the version 1 data
+    pub fn try_from_slice_unchecked(data: &[u8]) -> Result<Self, ProgramError> {
+        try_from_slice_unchecked(data).map_err(|_| ProgramError::InvalidAccountData)
+    }
+}
+
+/// Version 1 pools minted one pool token per bank token and had no manager
+impl From<PoolDataV1> for PoolData {
+    fn from(v1: PoolDataV1) -> Self {
+        Self {
+            version: PoolData::CURRENT_VERSION,
+            authority: v1.authority,
+            bump_seed: v1.bump_seed,
+            bank_mint: v1.bank_mint,
+            pool_mint: v1.pool_mint,
+            bank: v1.bank,
+            rate_numerator: 1,
+            rate_denominator: 1,
+            fee_bps: 0,
+            fee_receiver: Pubkey::default(),
+            manager: Pubkey::default(),
+            pending_manager: Pubkey::default(),
+            status: PoolStatus::Active,
+            apr_bps: 0,
+            undistributed_rewards: 0,
+            last_update_slot: 0,
+            oracle: Pubkey::default(),
+            oracle_max_age: 0,
+            curve: CurveType::Constant,
+            reserve_mint: Pubkey::default(),
+            reserve: Pubkey::default(),
+            allowlist_root: MerkleHash::default(),
+            permit_key: Pubkey::default(),
+            deposit_limit: 0,
+            deposit_period: DepositPeriod::Lifetime,
+            supply_cap: 0,
+            min_amount_in: 0,
+            max_amount_in: 0,
+            sale_start: 0,
+            sale_end: 0,
+            soft_cap_bps: 0,
+            reserved: Reserved::default(),
+        }
+    }
+}
+
+/// Size of the reserved space, shrinks as fields are added to `PoolData`
+const RESERVED_LEN: usize = 5;
+
+/// Reserved bytes of the pool account
+#[derive(Clone, PartialEq)]
+pub struct Reserved(pub [u8; RESERVED_LEN]);
+
+impl Default for Reserved {
+    fn default() -> Self {
+        Self([0; RESERVED_LEN])
+    }
+}
+
+impl fmt::Debug for Reserved {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Reserved({} bytes)", RESERVED_LEN)
+    }
+}
+
+// Borsh only implements its traits for a few array sizes
+impl BorshSerialize for Reserved {
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(&self.0)
+    }
+}
+
+impl BorshDeserialize for Reserved {
+    fn deserialize(buf: &mut &[u8]) -> io::Result<Self> {
+        if buf.len() < RESERVED_LEN {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Unexpected length of input",
+            ));
+        }
+
+        let mut reserved = [0; RESERVED_LEN];
+        reserved.copy_from_slice(&buf[..RESERVED_LEN]);
+        *buf = &buf[RESERVED_LEN..];
+        Ok(Self(reserved))
+    }
+}
+
+impl BorshSchema for Reserved {
+    fn add_definitions_recursively(definitions: &mut HashMap<Declaration, Definition>) {
+        let definition = Definition::Array {
+            length: RESERVED_LEN as u32,
+            elements: u8::declaration(),
+        };
+        Self::add_definition(Self::declaration(), definition, definitions);
+    }
+
+    fn declaration() -> Declaration {
+        "Reserved".to_string()
+    }
+}
+
+/// Price account read by oracle priced pools
+///
+/// Borsh layout of 20 bytes: `price` as u64, `exponent` as i32 and `last_update_slot`
+/// as u64, all little endian. One bank token is worth `price * 10^exponent` pool tokens
+#[derive(Clone, Copy, Debug, Default, BorshSerialize, BorshDeserialize, BorshSchema, PartialEq)]
+pub struct PriceData {
+    /// Price without the exponent
+    pub price: u64,
+
+    /// Power of ten the price is multiplied by
+    pub exponent: i32,
+
+    /// Slot the price was last updated at
+    pub last_update_slot: u64,
+}
+
+impl PriceData {
+    /// Size of the price data
+    pub const LEN: usize = 20;
+
+    /// Deserializes the price data, ignoring the unused tail of the account
+    pub fn try_from_slice_unchecked(data: &[u8]) -> Result<Self, ProgramError> {
+        try_from_slice_unchecked(data).map_err(|_| ProgramError::InvalidAccountData)
+    }
+
+    /// Checks that the price was updated at most `max_age` slots before `slot`
+    pub(crate) fn check_age(&self, slot: u64, max_age: u64) -> Result<(), ProgramError> {
+        let age = slot.saturating_sub(self.last_update_slot);
+        if age > max_age {
+            msg!(
+                "Oracle price is {} slots old, at most {} are accepted",
+                age,
+                max_age
+            );
+            return Err(PoolError::StaleOraclePrice.into());
+        }
+
+        Ok(())
+    }
+
+    /// Calculates the exchange rate swapping and redeeming pool tokens at the price
+    pub fn exchange_rate(
+        &self,
+        pool_supply: u64,
+        bank_balance: u64,
+    ) -> Result<ExchangeRate, PoolError> {
+        if self.price == 0 {
+            return Err(PoolError::InvalidOracle);
+        }
+
+        let scale = self
+            .exponent
+            .checked_abs()
+            .and_then(|exponent| 10u64.checked_pow(exponent as u32))
+            .ok_or(PoolError::InvalidOracle)?;
+        let (pool_amount, bank_amount) = if self.exponent >= 0 {
+            (
+                self.price
+                    .checked_mul(scale)
+                    .ok_or(PoolError::InvalidOracle)?,
+                1,
+            )
+        } else {
+            (self.price, scale)
+        };
+
+        Ok(ExchangeRate {
+            pool_supply,
+            bank_balance,
+            pool_amount,
+            bank_amount,
+            curve: CurveType::Constant,
+        })
+    }
+}
+
+/// Bank tokens swapped in by a user, at the `[pool, user]` address
+#[derive(Clone, Copy, Debug, Default, BorshSerialize, BorshDeserialize, BorshSchema, PartialEq)]
+pub struct DepositRecord {
+    /// Pool the deposits went to
+    pub pool: Pubkey,
+
+    /// Owner of the bank tokens swapped in
+    pub user: Pubkey,
+
+    /// Bank tokens swapped in, fees included, since the record epoch for per epoch limits
+    pub amount_in: u64,
+
+    /// Epoch of the last swap
+    pub epoch: u64,
+}
+
+impl DepositRecord {
+    /// Size of the deposit record
+    pub const LEN: usize = 80;
+
+    /// Deserializes the deposit record
+    pub fn try_from_slice_unchecked(data: &[u8]) -> Result<Self, ProgramError> {
+        try_from_slice_unchecked(data).map_err(|_| ProgramError::InvalidAccountData)
+    }
+
+    /// Adds the swap to the record, failing if the user goes over the pool deposit limit
+    pub fn add_deposit(
+        &mut self,
+        pool_data: &PoolData,
+        amount_in: u64,
+        epoch: u64,
+    ) -> Result<(), ProgramError> {
+        if pool_data.deposit_period == DepositPeriod::Epoch && self.epoch != epoch {
+            self.amount_in = 0;
+        }
+        self.epoch = epoch;
+
+        let total = checked_add(self.amount_in, amount_in)?;
+        if total > pool_data.deposit_limit {
+            msg!(
+                "User deposits {} would exceed the limit {}",
+                total,
+                pool_data.deposit_limit
+            );
+            return Err(PoolError::DepositLimitExceeded.into());
+        }
+        self.amount_in = total;
+
+        Ok(())
+    }
+
+    /// Checks whether the record no longer counts against a limit of the pool
+    pub fn is_unused(&self, pool_data: &PoolData, epoch: u64) -> bool {
+        !pool_data.has_deposit_limit()
+            || (pool_data.deposit_period == DepositPeriod::Epoch && self.epoch < epoch)
+    }
+}
+
+impl IsInitialized for DepositRecord {
+    /// Is initialized
+    fn is_initialized(&self) -> bool {
+        self.pool != Pubkey::default()
+    }
+}
+
+/// Token amounts moved by a swap
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SwapAmounts {
+    /// Bank tokens taken from the user
+    pub amount_in: u64,
+
+    /// Part of `amount_in` sent to the fee receiver
+    pub fee: u64,
+
+    /// Part of `amount_in` sent to the bank
+    pub deposit: u64,
+
+    /// Pool tokens minted to the user
+    pub amount_out: u64,
+}
+
+/// Pool status
+#[derive(Clone, Copy, Debug, Default, BorshSerialize, BorshDeserialize, BorshSchema, PartialEq)]
+pub enum PoolStatus {
+    /// Pool accepts swaps and redeems
+    #[default]
+    Active,
+
+    /// Pool is paused by the manager
+    Paused,
+}
+
+/// Period a deposit limit applies to
+#[derive(Clone, Copy, Debug, Default, BorshSerialize, BorshDeserialize, BorshSchema, PartialEq)]
+pub enum DepositPeriod {
+    /// Limit on all deposits of the user
+    #[default]
+    Lifetime,
+
+    /// Limit starting over every epoch
+    Epoch,
 }
 
 impl PoolData {
     /// Version to fill in on new created accounts
-    pub const CURRENT_VERSION: u8 = 1;
+    pub const CURRENT_VERSION: u8 = 2;
 
-    /// Mint multiplier
-    pub const MINT_MULTIPLIER: u8 = 1;
+    /// Size of pool accounts, accounts can't be resized so new fields take the reserved space
+    pub const LEN: usize = 512;
+
+    /// Basis points in one whole
+    pub const BPS_DENOMINATOR: u64 = 10_000;
+
+    /// Slots in a year of 400ms slots, interest is compounded every slot
+    pub const SLOTS_PER_YEAR: u64 = 78_840_000;
+
+    /// Deserializes the pool data, ignoring the unused tail of the account
+    pub fn try_from_slice_unchecked(data: &[u8]) -> Result<Self, ProgramError> {
+        try_from_slice_unchecked(data).map_err(|_| ProgramError::InvalidAccountData)
+    }
+
+    /// Checks that the account was written by the current version of the program
+    pub(crate) fn check_version(&self) -> Result<(), ProgramError> {
+        if self.version < Self::CURRENT_VERSION {
+            msg!(
+                "Pool version {} is outdated, migrate it to version {}",
+                self.version,
+                Self::CURRENT_VERSION
+            );
+            return Err(PoolError::StaleVersion.into());
+        }
+
+        if self.version > Self::CURRENT_VERSION {
+            msg!("Pool version {} is not supported", self.version);
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        Ok(())
+    }
 
     /// Checks that the withdraw or deposit authority is valid
     pub(crate) fn check_authority(
@@ -56,14 +487,412 @@ impl PoolData {
                 expected_address,
                 authority_address
             );
-            Err(ProgramError::InvalidArgument)
+            Err(PoolError::InvalidAuthority.into())
+        }
+    }
+
+    /// Checks that the manager is valid and signed the transaction
+    pub(crate) fn check_manager(&self, manager_info: &AccountInfo) -> Result<(), ProgramError> {
+        if !manager_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        if *manager_info.key == self.manager {
+            Ok(())
+        } else {
+            msg!(
+                "Incorrect manager provided, expected {}, received {}",
+                self.manager,
+                manager_info.key
+            );
+            Err(PoolError::InvalidManager.into())
+        }
+    }
+
+    /// Checks whether the pool is a constant product pool
+    pub fn is_constant_product(&self) -> bool {
+        self.reserve_mint != Pubkey::default()
+    }
+
+    /// Checks that the pool is a constant product pool, or that it is not
+    pub(crate) fn check_constant_product(&self, expected: bool) -> Result<(), ProgramError> {
+        if self.is_constant_product() != expected {
+            if expected {
+                msg!("Pool is not a constant product pool");
+            } else {
+                msg!("Pool is a constant product pool");
+            }
+            return Err(PoolError::InvalidPoolMode.into());
+        }
+
+        Ok(())
+    }
+
+    /// Checks whether swaps are limited to the users of the allowlist
+    pub fn has_allowlist(&self) -> bool {
+        self.allowlist_root != MerkleHash::default()
+    }
+
+    /// Checks that the user signed and is on the allowlist, if the pool has one
+    pub(crate) fn check_allowlist(
+        &self,
+        user_info: &AccountInfo,
+        proof: Option<&[MerkleHash]>,
+    ) -> Result<(), ProgramError> {
+        if !self.has_allowlist() {
+            return Ok(());
+        }
+
+        if !user_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        match proof {
+            Some(proof) if merkle::verify(&self.allowlist_root, user_info.key, proof) => Ok(()),
+            Some(_) => {
+                msg!("Proof does not match {} to the allowlist", user_info.key);
+                Err(PoolError::NotAllowlisted.into())
+            }
+            None => {
+                msg!("Pool only swaps with an allowlist proof");
+                Err(PoolError::NotAllowlisted.into())
+            }
+        }
+    }
+
+    /// Checks whether swaps need a permit signed by the permit key
+    pub fn has_permit_key(&self) -> bool {
+        self.permit_key != Pubkey::default()
+    }
+
+    /// Checks whether swaps are counted against a deposit limit of the user
+    pub fn has_deposit_limit(&self) -> bool {
+        self.deposit_limit != 0
+    }
+
+    /// Checks whether swaps are limited by a supply cap or by the amount in
+    pub fn has_swap_limits(&self) -> bool {
+        self.supply_cap != 0 || self.min_amount_in != 0 || self.max_amount_in != 0
+    }
+
+    /// Pool tokens swaps can still mint under the supply cap, if the pool has one
+    pub fn remaining_supply(&self, pool_supply: u64) -> Option<u64> {
+        if self.supply_cap == 0 {
+            return None;
+        }
+        Some(self.supply_cap.saturating_sub(pool_supply))
+    }
+
+    /// Checks that the amount in is within the limits of a single swap
+    pub(crate) fn check_amount_in(&self, amount_in: u64) -> Result<(), ProgramError> {
+        if amount_in < self.min_amount_in {
+            msg!(
+                "Amount in {} is below the pool minimum {}",
+                amount_in,
+                self.min_amount_in
+            );
+            return Err(PoolError::AmountInBelowMinimum.into());
+        }
+
+        if self.max_amount_in != 0 && amount_in > self.max_amount_in {
+            msg!(
+                "Amount in {} is above the pool maximum {}",
+                amount_in,
+                self.max_amount_in
+            );
+            return Err(PoolError::AmountInAboveMaximum.into());
+        }
+
+        Ok(())
+    }
+
+    /// Checks that minting the amount keeps the pool mint supply within the cap
+    pub(crate) fn check_supply_cap(
+        &self,
+        pool_supply: u64,
+        amount_out: u64,
+    ) -> Result<(), ProgramError> {
+        if let Some(remaining_supply) = self.remaining_supply(pool_supply) {
+            if amount_out > remaining_supply {
+                msg!(
+                    "Amount out {} is above the {} left under the supply cap",
+                    amount_out,
+                    remaining_supply
+                );
+                return Err(PoolError::SupplyCapExceeded.into());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks whether swaps are limited to the sale window
+    pub fn has_sale(&self) -> bool {
+        self.sale_end != 0
+    }
+
+    /// Pool tokens in supply at the end of the sale for it to succeed
+    pub fn soft_cap(&self) -> Result<u64, PoolError> {
+        mul_div(
+            self.supply_cap,
+            self.soft_cap_bps as u64,
+            Self::BPS_DENOMINATOR,
+            Rounding::Up,
+        )
+    }
+
+    /// Checks that swaps are open at the timestamp
+    pub(crate) fn check_sale_open(&self, now: UnixTimestamp) -> Result<(), ProgramError> {
+        if self.has_sale() && (now < self.sale_start || now >= self.sale_end) {
+            msg!(
+                "Sale runs from {} to {}, now is {}",
+                self.sale_start,
+                self.sale_end,
+                now
+            );
+            return Err(PoolError::SaleNotActive.into());
+        }
+
+        Ok(())
+    }
+
+    /// Checks whether the pool mint supply reached the soft cap of the sale
+    ///
+    /// Swaps are closed after the end, so the supply only goes down by refunds and a
+    /// missed soft cap stays missed.
+    pub fn is_soft_cap_met(&self, pool_supply: u64) -> Result<bool, PoolError> {
+        Ok(pool_supply >= self.soft_cap()?)
+    }
+
+    /// Checks that the pool runs a sale that has ended
+    pub(crate) fn check_sale_ended(&self, now: UnixTimestamp) -> Result<(), ProgramError> {
+        if !self.has_sale() {
+            msg!("Pool runs no sale");
+            return Err(PoolError::InvalidPoolMode.into());
+        }
+
+        if now < self.sale_end {
+            msg!("Sale ends at {}, now is {}", self.sale_end, now);
+            return Err(PoolError::SaleNotEnded.into());
+        }
+
+        Ok(())
+    }
+
+    /// Checks that the pool is not paused
+    pub(crate) fn check_not_paused(&self) -> Result<(), ProgramError> {
+        if self.status == PoolStatus::Paused {
+            msg!("Pool is paused");
+            return Err(PoolError::PoolPaused.into());
         }
+
+        Ok(())
+    }
+
+    /// Pays interest out of the undistributed rewards for the slots since the last update
+    ///
+    /// Interest compounds every slot on the bank tokens owned by the pool tokens, and stops
+    /// once the undistributed rewards run out
+    pub fn accrue_interest(&mut self, bank_balance: u64, slot: u64) -> Result<(), PoolError> {
+        if slot <= self.last_update_slot {
+            return Ok(());
+        }
+        let slots = slot - self.last_update_slot;
+        self.last_update_slot = slot;
+
+        if self.apr_bps == 0 || self.undistributed_rewards == 0 {
+            return Ok(());
+        }
+
+        let distributed = checked_sub(bank_balance, self.undistributed_rewards)?;
+        // Interest too large to calculate is more than the rewards anyway
+        let interest = compound_interest(
+            distributed,
+            self.apr_bps as u64,
+            Self::BPS_DENOMINATOR * Self::SLOTS_PER_YEAR,
+            slots,
+        )
+        .unwrap_or(u64::MAX);
+        self.undistributed_rewards -= interest.min(self.undistributed_rewards);
+
+        Ok(())
+    }
+
+    /// Calculates the exchange rate for the pool mint supply and the bank balance
+    ///
+    /// The configured rate is added to both sides as virtual tokens, so it sets the price
+    /// of an empty pool, and tokens sent straight to the bank can't inflate the price of
+    /// a nearly empty pool enough to round the next swap down to nothing.
+    /// Undistributed rewards don't count towards the price until they are accrued
+    pub fn exchange_rate(
+        &self,
+        pool_supply: u64,
+        bank_balance: u64,
+    ) -> Result<ExchangeRate, PoolError> {
+        let distributed = checked_sub(bank_balance, self.undistributed_rewards)?;
+
+        Ok(ExchangeRate {
+            pool_supply,
+            bank_balance: distributed,
+            pool_amount: checked_add(pool_supply, self.rate_numerator)?,
+            bank_amount: checked_add(distributed, self.rate_denominator)?,
+            curve: self.curve,
+        })
+    }
+
+    /// Calculates pool fee for the bank tokens amount, rounded up
+    pub fn calc_fee(&self, bank_amount: u64) -> Result<u64, PoolError> {
+        mul_div(
+            bank_amount,
+            self.fee_bps as u64,
+            Self::BPS_DENOMINATOR,
+            Rounding::Up,
+        )
+    }
+
+    /// Fee of swaps in basis points
+    ///
+    /// Sales take the fee out of the proceeds instead, so refunds pay back whole deposits
+    pub fn swap_fee_bps(&self) -> u16 {
+        if self.has_sale() {
+            0
+        } else {
+            self.fee_bps
+        }
+    }
+
+    /// Calculates swap fee for the bank tokens amount, rounded up
+    fn calc_swap_fee(&self, bank_amount: u64) -> Result<u64, PoolError> {
+        mul_div(
+            bank_amount,
+            self.swap_fee_bps() as u64,
+            Self::BPS_DENOMINATOR,
+            Rounding::Up,
+        )
+    }
+
+    /// Calculates the amounts of a swap of exactly `amount_in` bank tokens
+    pub fn calc_swap_exact_in(
+        &self,
+        rate: &ExchangeRate,
+        amount_in: u64,
+    ) -> Result<SwapAmounts, PoolError> {
+        let fee = self.calc_swap_fee(amount_in)?;
+        let deposit = checked_sub(amount_in, fee)?;
+        let amount_out = rate.calc_pool_amount(deposit)?;
+
+        Ok(SwapAmounts {
+            amount_in,
+            fee,
+            deposit,
+            amount_out,
+        })
+    }
+
+    /// Calculates the least bank tokens to swap for exactly `amount_out` pool tokens
+    pub fn calc_swap_exact_out(
+        &self,
+        rate: &ExchangeRate,
+        amount_out: u64,
+    ) -> Result<SwapAmounts, PoolError> {
+        let min_deposit = rate.calc_deposit(amount_out)?;
+
+        // amount_in - fee is floor(amount_in * (1 - fee_bps / BPS_DENOMINATOR)),
+        // the least amount_in keeping it above min_deposit is rounded up
+        let amount_in = mul_div(
+            min_deposit,
+            Self::BPS_DENOMINATOR,
+            Self::BPS_DENOMINATOR - self.swap_fee_bps() as u64,
+            Rounding::Up,
+        )?;
+        let fee = self.calc_swap_fee(amount_in)?;
+        let deposit = checked_sub(amount_in, fee)?;
+
+        Ok(SwapAmounts {
+            amount_in,
+            fee,
+            deposit,
+            amount_out,
+        })
+    }
+}
+
+/// Price of pool tokens in bank tokens
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ExchangeRate {
+    /// Pool mint supply
+    pub pool_supply: u64,
+
+    /// Bank tokens held by the pool, less the undistributed rewards
+    pub bank_balance: u64,
+
+    /// Pool tokens side of the rate, including the virtual tokens
+    pub pool_amount: u64,
+
+    /// Bank tokens side of the rate, including the virtual tokens
+    pub bank_amount: u64,
+
+    /// Curve pricing the pool tokens instead of the rate, unless constant
+    pub curve: CurveType,
+}
+
+impl ExchangeRate {
+    /// Calculates pool tokens for the bank tokens amount, rounded down
+    pub fn calc_pool_amount(&self, bank_amount: u64) -> Result<u64, PoolError> {
+        if self.curve != CurveType::Constant {
+            return self.curve.calc_pool_amount(self.pool_supply, bank_amount);
+        }
+
+        mul_div(
+            bank_amount,
+            self.pool_amount,
+            self.bank_amount,
+            Rounding::Down,
+        )
+    }
+
+    /// Calculates bank tokens worth at least the pool tokens amount, rounded up
+    pub fn calc_deposit(&self, pool_amount: u64) -> Result<u64, PoolError> {
+        if self.curve != CurveType::Constant {
+            let to_supply = checked_add(self.pool_supply, pool_amount)?;
+            return self
+                .curve
+                .integral(self.pool_supply, to_supply, Rounding::Up);
+        }
+
+        mul_div(
+            pool_amount,
+            self.bank_amount,
+            self.pool_amount,
+            Rounding::Up,
+        )
+    }
+
+    /// Calculates bank tokens for the pool tokens amount, rounded down
+    pub fn calc_bank_amount(&self, pool_amount: u64) -> Result<u64, PoolError> {
+        // Burning goes back down the curve
+        if self.curve != CurveType::Constant {
+            let from_supply = checked_sub(self.pool_supply, pool_amount)?;
+            let bank_amount = self
+                .curve
+                .integral(from_supply, self.pool_supply, Rounding::Down)?;
+            return Ok(bank_amount.min(self.bank_balance));
+        }
+
+        // An oracle price can value the pool tokens above the bank
+        let bank_amount = mul_div(
+            pool_amount,
+            self.bank_amount,
+            self.pool_amount,
+            Rounding::Down,
+        )?;
+        Ok(bank_amount.min(self.bank_balance))
     }
 }
 
 impl IsInitialized for PoolData {
     /// Is initialized
     fn is_initialized(&self) -> bool {
-        self.version == Self::CURRENT_VERSION
+        self.version != 0
     }
 }
@@ -0,0 +1,249 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::*;
+
+use simple_token_pool::{error::PoolError, merkle::MerkleTree, state::PoolData};
+use solana_program::pubkey::Pubkey;
+use solana_program_test::*;
+use solana_sdk::signer::Signer;
+
+const SENDER_MINT_AMOUNT: u64 = 10000;
+
+/// Allowlist of the user among other users
+fn allowlist_with(user: &Pubkey) -> MerkleTree {
+    let mut users = (0..6).map(|_| Pubkey::new_unique()).collect::<Vec<_>>();
+    users.insert(3, *user);
+    MerkleTree::new(&users)
+}
+
+#[tokio::test]
+async fn success_with_valid_proof() {
+    let (mut banks_client, payer, recent_blockhash, pool_accounts, bank_token, pool_token) =
+        setup_pool_with_sender(SENDER_MINT_AMOUNT).await;
+    let tree = allowlist_with(&pool_accounts.sender.pubkey());
+
+    update_allowlist_root(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.owner,
+        tree.root(),
+    )
+    .await
+    .unwrap();
+
+    let pool_data = PoolData::try_from_slice_unchecked(
+        &get_account(&mut banks_client, &pool_accounts.pool)
+            .await
+            .data,
+    )
+    .unwrap();
+    assert_eq!(pool_data.allowlist_root, tree.root());
+
+    swap_with_proof(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &bank_token,
+        &pool_token,
+        &pool_accounts,
+        1000,
+        1000,
+        tree.proof(&pool_accounts.sender.pubkey()).unwrap(),
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(
+        get_token_balance(&mut banks_client, &pool_token).await,
+        1000
+    );
+}
+
+#[tokio::test]
+async fn success_without_allowlist() {
+    let (mut banks_client, payer, recent_blockhash, pool_accounts, bank_token, pool_token) =
+        setup_pool_with_sender(SENDER_MINT_AMOUNT).await;
+
+    // Proofs are ignored while anyone can swap
+    swap_with_proof(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &bank_token,
+        &pool_token,
+        &pool_accounts,
+        1000,
+        0,
+        vec![[7; 32]],
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(
+        get_token_balance(&mut banks_client, &pool_token).await,
+        1000
+    );
+}
+
+#[tokio::test]
+async fn success_cleared_allowlist() {
+    let (mut banks_client, payer, recent_blockhash, pool_accounts, bank_token, pool_token) =
+        setup_pool_with_sender(SENDER_MINT_AMOUNT).await;
+    let tree = allowlist_with(&Pubkey::new_unique());
+
+    update_allowlist_root(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.owner,
+        tree.root(),
+    )
+    .await
+    .unwrap();
+    update_allowlist_root(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.owner,
+        [0; 32],
+    )
+    .await
+    .unwrap();
+
+    swap(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &bank_token,
+        &pool_token,
+        &pool_accounts,
+        1000,
+    )
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn fail_with_forged_proof() {
+    let (mut banks_client, payer, recent_blockhash, pool_accounts, bank_token, pool_token) =
+        setup_pool_with_sender(SENDER_MINT_AMOUNT).await;
+    let member = Pubkey::new_unique();
+    let tree = allowlist_with(&member);
+
+    update_allowlist_root(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.owner,
+        tree.root(),
+    )
+    .await
+    .unwrap();
+
+    // The proof of a member doesn't let the sender in
+    let error = swap_with_proof(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &bank_token,
+        &pool_token,
+        &pool_accounts,
+        1000,
+        0,
+        tree.proof(&member).unwrap(),
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_pool_error(error, PoolError::NotAllowlisted);
+
+    // Neither does a proof ending at the root
+    let error = swap_with_proof(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &bank_token,
+        &pool_token,
+        &pool_accounts,
+        1001,
+        0,
+        vec![tree.root()],
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_pool_error(error, PoolError::NotAllowlisted);
+}
+
+#[tokio::test]
+async fn fail_swap_without_proof() {
+    let (mut banks_client, payer, recent_blockhash, pool_accounts, bank_token, pool_token) =
+        setup_pool_with_sender(SENDER_MINT_AMOUNT).await;
+    let tree = allowlist_with(&pool_accounts.sender.pubkey());
+
+    update_allowlist_root(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.owner,
+        tree.root(),
+    )
+    .await
+    .unwrap();
+
+    let error = swap(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &bank_token,
+        &pool_token,
+        &pool_accounts,
+        1000,
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_pool_error(error, PoolError::NotAllowlisted);
+
+    let error = swap_exact_out(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &bank_token,
+        &pool_token,
+        &pool_accounts,
+        1000,
+        1000,
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_pool_error(error, PoolError::NotAllowlisted);
+}
+
+#[tokio::test]
+async fn fail_update_with_wrong_manager() {
+    let (mut banks_client, payer, recent_blockhash, pool_accounts, _, _) =
+        setup_pool_with_sender(SENDER_MINT_AMOUNT).await;
+
+    let error = update_allowlist_root(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.sender,
+        [1; 32],
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_pool_error(error, PoolError::InvalidManager);
+}
@@ -0,0 +1,339 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::*;
+
+use simple_token_pool::error::PoolError;
+use solana_program::{
+    hash::Hash, instruction::InstructionError, program_option::COption, program_pack::Pack,
+    pubkey::Pubkey,
+};
+use solana_program_test::*;
+use solana_sdk::{
+    signature::Keypair, signer::Signer, transaction::TransactionError, transport::TransportError,
+};
+
+const SENDER_MINT_AMOUNT: u64 = 10000;
+
+async fn setup() -> (BanksClient, Keypair, Hash, PoolAccounts) {
+    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+    let pool_accounts = PoolAccounts::new();
+    create_accounts(&mut banks_client, &payer, &recent_blockhash, &pool_accounts).await;
+
+    pool_accounts
+        .initialize(&mut banks_client, &payer, &recent_blockhash)
+        .await
+        .unwrap();
+
+    (banks_client, payer, recent_blockhash, pool_accounts)
+}
+
+async fn prepare_sender(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: &Hash,
+    pool_accounts: &PoolAccounts,
+) -> (Pubkey, Pubkey) {
+    let (bank_token, pool_token) = create_sender_accounts(
+        banks_client,
+        payer,
+        recent_blockhash,
+        &pool_accounts,
+        SENDER_MINT_AMOUNT,
+    )
+    .await;
+
+    (bank_token, pool_token)
+}
+
+#[tokio::test]
+async fn success() {
+    let (mut banks_client, payer, recent_blockhash, pool_accounts) = setup().await;
+    let destination = Pubkey::new_unique();
+
+    let pool_lamports = get_account(&mut banks_client, &pool_accounts.pool)
+        .await
+        .lamports;
+    let bank_lamports = get_account(&mut banks_client, &pool_accounts.bank)
+        .await
+        .lamports;
+
+    close_pool(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.owner,
+        &destination,
+    )
+    .await
+    .unwrap();
+
+    assert!(banks_client
+        .get_account(pool_accounts.pool)
+        .await
+        .unwrap()
+        .is_none());
+    assert!(banks_client
+        .get_account(pool_accounts.bank)
+        .await
+        .unwrap()
+        .is_none());
+
+    let destination = get_account(&mut banks_client, &destination).await;
+    assert_eq!(destination.lamports, pool_lamports + bank_lamports);
+
+    let pool_mint = get_account(&mut banks_client, &pool_accounts.pool_mint).await;
+    let pool_mint = spl_token::state::Mint::unpack(&pool_mint.data).unwrap();
+    assert_eq!(pool_mint.mint_authority, COption::None);
+    assert_eq!(pool_mint.supply, 0);
+}
+
+#[tokio::test]
+async fn success_after_redeem() {
+    let (mut banks_client, payer, recent_blockhash, pool_accounts) = setup().await;
+    let (bank_token, pool_token) =
+        prepare_sender(&mut banks_client, &payer, &recent_blockhash, &pool_accounts).await;
+
+    swap(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &bank_token,
+        &pool_token,
+        &pool_accounts,
+        SENDER_MINT_AMOUNT,
+    )
+    .await
+    .unwrap();
+
+    redeem(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_token,
+        &bank_token,
+        &pool_accounts,
+        SENDER_MINT_AMOUNT,
+    )
+    .await
+    .unwrap();
+
+    close_pool(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.owner,
+        &Pubkey::new_unique(),
+    )
+    .await
+    .unwrap();
+
+    assert!(banks_client
+        .get_account(pool_accounts.pool)
+        .await
+        .unwrap()
+        .is_none());
+}
+
+#[tokio::test]
+async fn success_after_withdrawing_bank_dust() {
+    let (mut banks_client, payer, recent_blockhash, pool_accounts) = setup().await;
+    let (bank_token, pool_token) =
+        prepare_sender(&mut banks_client, &payer, &recent_blockhash, &pool_accounts).await;
+
+    swap(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &bank_token,
+        &pool_token,
+        &pool_accounts,
+        1000,
+    )
+    .await
+    .unwrap();
+
+    // Tokens sent straight to the bank, the virtual tokens keep a share of them
+    mint_tokens(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts.bank_mint.pubkey(),
+        &pool_accounts.bank,
+        &pool_accounts.owner,
+        1000,
+    )
+    .await
+    .unwrap();
+
+    redeem(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_token,
+        &bank_token,
+        &pool_accounts,
+        1000,
+    )
+    .await
+    .unwrap();
+
+    let bank_balance = get_token_balance(&mut banks_client, &pool_accounts.bank).await;
+    assert!(bank_balance > 0);
+
+    let tx_error = close_pool(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.owner,
+        &Pubkey::new_unique(),
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_pool_error(tx_error, PoolError::PoolNotEmpty);
+
+    // The bank of a pool without pool tokens goes to the manager
+    withdraw_rewards(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.owner,
+        &bank_token,
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        get_token_balance(&mut banks_client, &pool_accounts.bank).await,
+        0
+    );
+    assert_eq!(
+        get_token_balance(&mut banks_client, &bank_token).await,
+        SENDER_MINT_AMOUNT + 1000
+    );
+
+    close_pool(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.owner,
+        &Pubkey::new_unique(),
+    )
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn fail_with_pool_tokens() {
+    let (mut banks_client, payer, recent_blockhash, pool_accounts) = setup().await;
+    let (bank_token, pool_token) =
+        prepare_sender(&mut banks_client, &payer, &recent_blockhash, &pool_accounts).await;
+
+    swap(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &bank_token,
+        &pool_token,
+        &pool_accounts,
+        5000,
+    )
+    .await
+    .unwrap();
+
+    let tx_error = close_pool(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.owner,
+        &Pubkey::new_unique(),
+    )
+    .await
+    .err()
+    .unwrap();
+
+    assert_pool_error(tx_error, PoolError::PoolNotEmpty);
+}
+
+#[tokio::test]
+async fn fail_with_bank_balance() {
+    let (mut banks_client, payer, recent_blockhash, pool_accounts) = setup().await;
+
+    // Tokens sent straight to the bank, without minting pool tokens
+    mint_tokens(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts.bank_mint.pubkey(),
+        &pool_accounts.bank,
+        &pool_accounts.owner,
+        1,
+    )
+    .await
+    .unwrap();
+
+    let tx_error = close_pool(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.owner,
+        &Pubkey::new_unique(),
+    )
+    .await
+    .err()
+    .unwrap();
+
+    assert_pool_error(tx_error, PoolError::PoolNotEmpty);
+}
+
+#[tokio::test]
+async fn fail_with_wrong_manager() {
+    let (mut banks_client, payer, recent_blockhash, pool_accounts) = setup().await;
+
+    let tx_error = close_pool(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &Keypair::new(),
+        &Pubkey::new_unique(),
+    )
+    .await
+    .err()
+    .unwrap();
+
+    assert_pool_error(tx_error, PoolError::InvalidManager);
+}
+
+#[tokio::test]
+async fn fail_with_pool_as_destination() {
+    let (mut banks_client, payer, recent_blockhash, pool_accounts) = setup().await;
+
+    let tx_error = close_pool(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.owner,
+        &pool_accounts.pool,
+    )
+    .await
+    .err()
+    .unwrap();
+
+    match tx_error {
+        TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::InvalidArgument,
+        )) => {}
+        _ => panic!("Wrong error"),
+    }
+}
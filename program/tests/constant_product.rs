@@ -0,0 +1,931 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::*;
+
+use simple_token_pool::{
+    error::PoolError, find_authority_bump_seed, find_reserve_address, id, state::DepositPeriod,
+};
+use solana_program::{hash::Hash, instruction::InstructionError, pubkey::Pubkey};
+use solana_program_test::*;
+use solana_sdk::{
+    signature::Keypair, signer::Signer, transaction::TransactionError, transport::TransportError,
+};
+
+const SENDER_MINT_AMOUNT: u64 = 10000;
+
+/// Constant product pool with a user holding bank and reserve tokens
+struct Setup {
+    banks_client: BanksClient,
+    payer: Keypair,
+    recent_blockhash: Hash,
+    pool_accounts: PoolAccounts,
+    bank_token: Pubkey,
+    reserve_token: Pubkey,
+    pool_token: Pubkey,
+}
+
+async fn setup(fee_bps: u16) -> Setup {
+    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+
+    let mut pool_accounts = PoolAccounts::new();
+    pool_accounts.fee_bps = fee_bps;
+    create_accounts(&mut banks_client, &payer, &recent_blockhash, &pool_accounts).await;
+    pool_accounts
+        .initialize(&mut banks_client, &payer, &recent_blockhash)
+        .await
+        .unwrap();
+
+    let reserve_mint = Keypair::new();
+    create_mint(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &reserve_mint,
+        &pool_accounts.owner.pubkey(),
+    )
+    .await
+    .unwrap();
+
+    initialize_reserve(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.owner,
+        &reserve_mint.pubkey(),
+    )
+    .await
+    .unwrap();
+    pool_accounts.reserve = Some(find_reserve_address(&id(), &pool_accounts.pool).0);
+
+    let bank_token = Keypair::new();
+    let reserve_token = Keypair::new();
+    let pool_token = Keypair::new();
+    for (account, mint) in &[
+        (&bank_token, pool_accounts.bank_mint.pubkey()),
+        (&reserve_token, reserve_mint.pubkey()),
+        (&pool_token, pool_accounts.pool_mint),
+    ] {
+        create_token_account(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            account,
+            mint,
+            &pool_accounts.sender.pubkey(),
+        )
+        .await
+        .unwrap();
+    }
+
+    for (account, mint) in &[
+        (&bank_token, pool_accounts.bank_mint.pubkey()),
+        (&reserve_token, reserve_mint.pubkey()),
+    ] {
+        mint_tokens(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            mint,
+            &account.pubkey(),
+            &pool_accounts.owner,
+            SENDER_MINT_AMOUNT,
+        )
+        .await
+        .unwrap();
+    }
+
+    Setup {
+        banks_client,
+        payer,
+        recent_blockhash,
+        pool_accounts,
+        bank_token: bank_token.pubkey(),
+        reserve_token: reserve_token.pubkey(),
+        pool_token: pool_token.pubkey(),
+    }
+}
+
+/// Sets up a pool holding 1000 bank tokens and 4000 reserve tokens
+async fn setup_with_liquidity(fee_bps: u16) -> Setup {
+    let mut setup = setup(fee_bps).await;
+    add_liquidity(
+        &mut setup.banks_client,
+        &setup.payer,
+        &setup.recent_blockhash,
+        &setup.pool_accounts,
+        &setup.bank_token,
+        &setup.reserve_token,
+        &setup.pool_token,
+        1000,
+        4000,
+        1000,
+    )
+    .await
+    .unwrap();
+    setup
+}
+
+/// Sets up a pool without a reserve
+async fn setup_without_reserve() -> (BanksClient, Keypair, Hash, PoolAccounts) {
+    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+
+    let pool_accounts = PoolAccounts::new();
+    create_accounts(&mut banks_client, &payer, &recent_blockhash, &pool_accounts).await;
+    pool_accounts
+        .initialize(&mut banks_client, &payer, &recent_blockhash)
+        .await
+        .unwrap();
+
+    (banks_client, payer, recent_blockhash, pool_accounts)
+}
+
+/// Creates a reserve mint and gives the pool a reserve of it
+async fn initialize_new_reserve(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: &Hash,
+    pool_accounts: &PoolAccounts,
+    manager: &Keypair,
+) -> Result<(), TransportError> {
+    let reserve_mint = Keypair::new();
+    create_mint(
+        banks_client,
+        payer,
+        recent_blockhash,
+        &reserve_mint,
+        &pool_accounts.owner.pubkey(),
+    )
+    .await
+    .unwrap();
+
+    initialize_reserve(
+        banks_client,
+        payer,
+        recent_blockhash,
+        pool_accounts,
+        manager,
+        &reserve_mint.pubkey(),
+    )
+    .await
+}
+
+#[tokio::test]
+async fn success_add_and_remove_liquidity() {
+    let Setup {
+        mut banks_client,
+        payer,
+        recent_blockhash,
+        pool_accounts,
+        bank_token,
+        reserve_token,
+        pool_token,
+    } = setup_with_liquidity(0).await;
+    let reserve = pool_accounts.reserve.unwrap();
+
+    // The first deposit mints at the pool rate of 1
+    assert_eq!(
+        get_token_balance(&mut banks_client, &pool_token).await,
+        1000
+    );
+
+    // Half the pool tokens need half the bank and the reserve, the extra reserve stays
+    add_liquidity(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &bank_token,
+        &reserve_token,
+        &pool_token,
+        500,
+        3000,
+        500,
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        get_token_balance(&mut banks_client, &pool_token).await,
+        1500
+    );
+    assert_eq!(
+        get_token_balance(&mut banks_client, &pool_accounts.bank).await,
+        1500
+    );
+    assert_eq!(get_token_balance(&mut banks_client, &reserve).await, 6000);
+
+    remove_liquidity(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_token,
+        &bank_token,
+        &reserve_token,
+        300,
+        300,
+        1200,
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        get_token_balance(&mut banks_client, &pool_token).await,
+        1200
+    );
+    assert_eq!(
+        get_token_balance(&mut banks_client, &bank_token).await,
+        SENDER_MINT_AMOUNT - 1200
+    );
+    assert_eq!(
+        get_token_balance(&mut banks_client, &reserve_token).await,
+        SENDER_MINT_AMOUNT - 4800
+    );
+}
+
+#[tokio::test]
+async fn success_swap_both_ways() {
+    let Setup {
+        mut banks_client,
+        payer,
+        recent_blockhash,
+        pool_accounts,
+        bank_token,
+        reserve_token,
+        ..
+    } = setup_with_liquidity(30).await;
+    let reserve = pool_accounts.reserve.unwrap();
+
+    // Fee of 1 stays in the bank, 4000 * 99 / 1099 = 360.33 is paid out
+    swap_reserves(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.bank,
+        &reserve,
+        &bank_token,
+        &reserve_token,
+        100,
+        360,
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        get_token_balance(&mut banks_client, &pool_accounts.bank).await,
+        1100
+    );
+    assert_eq!(get_token_balance(&mut banks_client, &reserve).await, 3640);
+
+    // Fee of 2 stays in the reserve, 1100 * 358 / 3998 = 98.5 is paid out
+    swap_reserves(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &reserve,
+        &pool_accounts.bank,
+        &reserve_token,
+        &bank_token,
+        360,
+        98,
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        get_token_balance(&mut banks_client, &pool_accounts.bank).await,
+        1002
+    );
+    assert_eq!(get_token_balance(&mut banks_client, &reserve).await, 4000);
+
+    // Fees went to the liquidity providers, the user lost them
+    assert_eq!(
+        get_token_balance(&mut banks_client, &bank_token).await,
+        SENDER_MINT_AMOUNT - 1002
+    );
+    assert_eq!(
+        get_token_balance(&mut banks_client, &reserve_token).await,
+        SENDER_MINT_AMOUNT - 4000
+    );
+}
+
+#[tokio::test]
+async fn success_close_pool() {
+    let Setup {
+        mut banks_client,
+        payer,
+        recent_blockhash,
+        pool_accounts,
+        bank_token,
+        reserve_token,
+        pool_token,
+    } = setup_with_liquidity(0).await;
+
+    // The last pool tokens take both reserves
+    remove_liquidity(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_token,
+        &bank_token,
+        &reserve_token,
+        1000,
+        1000,
+        4000,
+    )
+    .await
+    .unwrap();
+
+    close_pool(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.owner,
+        &pool_accounts.owner.pubkey(),
+    )
+    .await
+    .unwrap();
+
+    let reserve = banks_client
+        .get_account(pool_accounts.reserve.unwrap())
+        .await
+        .unwrap();
+    assert!(reserve.is_none());
+}
+
+#[tokio::test]
+async fn fail_swap_with_exceeded_slippage() {
+    let Setup {
+        mut banks_client,
+        payer,
+        recent_blockhash,
+        pool_accounts,
+        bank_token,
+        reserve_token,
+        ..
+    } = setup_with_liquidity(30).await;
+
+    let error = swap_reserves(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.bank,
+        &pool_accounts.reserve.unwrap(),
+        &bank_token,
+        &reserve_token,
+        100,
+        361,
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_pool_error(error, PoolError::ExceededSlippage);
+}
+
+#[tokio::test]
+async fn fail_swap_with_wrong_reserve() {
+    let Setup {
+        mut banks_client,
+        payer,
+        recent_blockhash,
+        pool_accounts,
+        bank_token,
+        reserve_token,
+        ..
+    } = setup_with_liquidity(0).await;
+
+    // Paying out of the account receiving the tokens would drain it
+    let error = swap_reserves(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.bank,
+        &pool_accounts.bank,
+        &bank_token,
+        &reserve_token,
+        100,
+        0,
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_pool_error(error, PoolError::InvalidReserve);
+}
+
+#[tokio::test]
+async fn fail_remove_liquidity_with_exceeded_slippage() {
+    let Setup {
+        mut banks_client,
+        payer,
+        recent_blockhash,
+        pool_accounts,
+        bank_token,
+        reserve_token,
+        pool_token,
+    } = setup_with_liquidity(0).await;
+
+    let error = remove_liquidity(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_token,
+        &bank_token,
+        &reserve_token,
+        100,
+        100,
+        401,
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_pool_error(error, PoolError::ExceededSlippage);
+}
+
+#[tokio::test]
+async fn fail_one_way_swap() {
+    let Setup {
+        mut banks_client,
+        payer,
+        recent_blockhash,
+        pool_accounts,
+        bank_token,
+        pool_token,
+        ..
+    } = setup_with_liquidity(0).await;
+
+    // Minting pool tokens for bank tokens alone would dilute the reserve
+    let error = swap(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &bank_token,
+        &pool_token,
+        &pool_accounts,
+        100,
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_pool_error(error, PoolError::InvalidPoolMode);
+}
+
+#[tokio::test]
+async fn fail_initialize_reserve_of_pool_with_supply() {
+    let (mut banks_client, payer, recent_blockhash, pool_accounts) = setup_without_reserve().await;
+
+    let (bank_token, pool_token) = create_sender_accounts(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        SENDER_MINT_AMOUNT,
+    )
+    .await;
+    swap(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &bank_token,
+        &pool_token,
+        &pool_accounts,
+        100,
+    )
+    .await
+    .unwrap();
+
+    let error = initialize_new_reserve(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.owner,
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_pool_error(error, PoolError::PoolNotEmpty);
+}
+
+#[tokio::test]
+async fn fail_initialize_reserve_with_wrong_manager() {
+    let (mut banks_client, payer, recent_blockhash, pool_accounts) = setup_without_reserve().await;
+
+    let error = initialize_new_reserve(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &Keypair::new(),
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_pool_error(error, PoolError::InvalidManager);
+}
+
+#[tokio::test]
+async fn fail_with_allowlist() {
+    let Setup {
+        mut banks_client,
+        payer,
+        recent_blockhash,
+        pool_accounts,
+        ..
+    } = setup(0).await;
+
+    // Constant product swaps don't check the allowlist
+    let error = update_allowlist_root(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.owner,
+        [1; 32],
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_pool_error(error, PoolError::InvalidPoolMode);
+
+    let (mut banks_client, payer, recent_blockhash, pool_accounts) = setup_without_reserve().await;
+    update_allowlist_root(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.owner,
+        [1; 32],
+    )
+    .await
+    .unwrap();
+
+    let error = initialize_new_reserve(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.owner,
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_pool_error(error, PoolError::InvalidPoolMode);
+}
+
+#[tokio::test]
+async fn fail_with_permit_key() {
+    let Setup {
+        mut banks_client,
+        payer,
+        recent_blockhash,
+        pool_accounts,
+        ..
+    } = setup(0).await;
+
+    // Constant product swaps don't take permits
+    let error = set_permit_key(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.owner,
+        &Pubkey::new_unique(),
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_pool_error(error, PoolError::InvalidPoolMode);
+
+    let (mut banks_client, payer, recent_blockhash, pool_accounts) = setup_without_reserve().await;
+    set_permit_key(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.owner,
+        &Pubkey::new_unique(),
+    )
+    .await
+    .unwrap();
+
+    let error = initialize_new_reserve(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.owner,
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_pool_error(error, PoolError::InvalidPoolMode);
+}
+
+#[tokio::test]
+async fn fail_with_deposit_limit() {
+    let Setup {
+        mut banks_client,
+        payer,
+        recent_blockhash,
+        pool_accounts,
+        ..
+    } = setup(0).await;
+
+    // Constant product swaps don't count deposits
+    let error = set_deposit_limit(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.owner,
+        1000,
+        DepositPeriod::Lifetime,
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_pool_error(error, PoolError::InvalidPoolMode);
+
+    let (mut banks_client, payer, recent_blockhash, pool_accounts) = setup_without_reserve().await;
+    set_deposit_limit(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.owner,
+        1000,
+        DepositPeriod::Lifetime,
+    )
+    .await
+    .unwrap();
+
+    let error = initialize_new_reserve(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.owner,
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_pool_error(error, PoolError::InvalidPoolMode);
+}
+
+#[tokio::test]
+async fn fail_with_swap_limits() {
+    let Setup {
+        mut banks_client,
+        payer,
+        recent_blockhash,
+        pool_accounts,
+        ..
+    } = setup(0).await;
+
+    // Constant product swaps don't check the swap limits
+    let error = set_swap_limits(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.owner,
+        1000,
+        0,
+        0,
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_pool_error(error, PoolError::InvalidPoolMode);
+
+    for (supply_cap, min_amount_in, max_amount_in) in &[(1000, 0, 0), (0, 10, 0), (0, 0, 100)] {
+        let (mut banks_client, payer, recent_blockhash, pool_accounts) =
+            setup_without_reserve().await;
+        set_swap_limits(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &pool_accounts,
+            &pool_accounts.owner,
+            *supply_cap,
+            *min_amount_in,
+            *max_amount_in,
+        )
+        .await
+        .unwrap();
+
+        let error = initialize_new_reserve(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &pool_accounts,
+            &pool_accounts.owner,
+        )
+        .await
+        .err()
+        .unwrap();
+        assert_pool_error(error, PoolError::InvalidPoolMode);
+    }
+}
+
+#[tokio::test]
+async fn fail_initialize_reserve_with_sale() {
+    let (mut banks_client, payer, recent_blockhash, pool_accounts) = setup_without_reserve().await;
+    let now = get_clock(&mut banks_client).await.unix_timestamp;
+
+    // Liquidity providers would get no refunds
+    set_sale(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.owner,
+        now,
+        now + 600,
+        0,
+        0,
+    )
+    .await
+    .unwrap();
+
+    let error = initialize_new_reserve(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.owner,
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_pool_error(error, PoolError::InvalidPoolMode);
+}
+
+#[tokio::test]
+async fn fail_with_pool_authority_as_transfer_authority() {
+    let Setup {
+        mut banks_client,
+        payer,
+        recent_blockhash,
+        pool_accounts,
+        reserve_token,
+        pool_token,
+        ..
+    } = setup_with_liquidity(0).await;
+    let (pool_authority, _) = find_authority_bump_seed(&id(), &pool_accounts.pool);
+
+    // Bank tokens held by the pool authority can only move with its signature
+    let authority_bank_token = Keypair::new();
+    create_token_account(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &authority_bank_token,
+        &pool_accounts.bank_mint.pubkey(),
+        &pool_authority,
+    )
+    .await
+    .unwrap();
+    mint_tokens(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts.bank_mint.pubkey(),
+        &authority_bank_token.pubkey(),
+        &pool_accounts.owner,
+        1000,
+    )
+    .await
+    .unwrap();
+
+    let mut instructions = vec![
+        simple_token_pool::instruction::swap_reserves(
+            &id(),
+            &pool_accounts.pool,
+            &pool_authority,
+            &pool_accounts.sender.pubkey(),
+            &pool_accounts.bank,
+            &pool_accounts.reserve.unwrap(),
+            &authority_bank_token.pubkey(),
+            &reserve_token,
+            1000,
+            0,
+        ),
+        simple_token_pool::instruction::add_liquidity(
+            &id(),
+            &pool_accounts.pool,
+            &pool_authority,
+            &pool_accounts.sender.pubkey(),
+            &pool_accounts.pool_mint,
+            &pool_accounts.bank,
+            &pool_accounts.reserve.unwrap(),
+            &authority_bank_token.pubkey(),
+            &reserve_token,
+            &pool_token,
+            1000,
+            4000,
+            0,
+        ),
+    ];
+    for instruction in instructions.iter_mut() {
+        substitute_pool_authority(instruction, &pool_accounts);
+        let error = process_instruction(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            instruction.clone(),
+            &[],
+        )
+        .await
+        .err()
+        .unwrap();
+        assert_pool_error(error, PoolError::InvalidAuthority);
+    }
+
+    let authority_bank_token_balance =
+        get_token_balance(&mut banks_client, &authority_bank_token.pubkey()).await;
+    assert_eq!(authority_bank_token_balance, 1000);
+}
+
+#[tokio::test]
+async fn fail_with_pool_account_as_source() {
+    let Setup {
+        mut banks_client,
+        payer,
+        recent_blockhash,
+        pool_accounts,
+        bank_token,
+        reserve_token,
+        pool_token,
+    } = setup_with_liquidity(0).await;
+    let reserve = pool_accounts.reserve.unwrap();
+
+    let error = swap_reserves(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &reserve,
+        &pool_accounts.bank,
+        &reserve,
+        &bank_token,
+        1000,
+        0,
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_invalid_argument(error);
+
+    let error = add_liquidity(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.bank,
+        &reserve_token,
+        &pool_token,
+        1000,
+        4000,
+        0,
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_invalid_argument(error);
+
+    let error = add_liquidity(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &bank_token,
+        &reserve,
+        &pool_token,
+        1000,
+        4000,
+        0,
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_invalid_argument(error);
+
+    let reserve_balance = get_token_balance(&mut banks_client, &reserve).await;
+    assert_eq!(reserve_balance, 4000);
+}
+
+fn assert_invalid_argument(tx_error: TransportError) {
+    match tx_error {
+        TransportError::TransactionError(TransactionError::InstructionError(_, error)) => {
+            assert_eq!(error, InstructionError::InvalidArgument);
+        }
+        _ => panic!("Wrong error"),
+    }
+}
@@ -0,0 +1,196 @@
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use simple_token_pool::{curve::CurveType, math::Rounding};
+
+const ITERATIONS: usize = 10_000;
+
+/// One bank token per pool token
+const PRICE: u64 = CurveType::PRICE_SCALE;
+
+fn random_curve(rng: &mut StdRng) -> CurveType {
+    let base_price = rng.gen_range(1, 100 * PRICE);
+    match rng.gen_range(0, 3) {
+        0 => CurveType::Linear {
+            base_price,
+            slope: rng.gen_range(0, PRICE),
+        },
+        // At most 200 steps, so the price stays below 10^9 times the base
+        1 => CurveType::Exponential {
+            base_price,
+            growth_bps: rng.gen_range(0, 1_000),
+            step_size: rng.gen_range(10_000, 100_000),
+        },
+        _ => CurveType::Step {
+            base_price,
+            step_size: rng.gen_range(1, 10_000),
+            increment: rng.gen_range(0, PRICE),
+        },
+    }
+}
+
+#[test]
+fn linear_closed_form() {
+    let curve = CurveType::Linear {
+        base_price: PRICE,
+        slope: PRICE / 100,
+    };
+
+    // 100 + 0.01 * 100^2 / 2
+    assert_eq!(curve.integral(0, 100, Rounding::Up), Ok(150));
+    assert_eq!(curve.integral(0, 100, Rounding::Down), Ok(150));
+    // 100 + 0.01 * (200^2 - 100^2) / 2
+    assert_eq!(curve.integral(100, 200, Rounding::Down), Ok(250));
+    // 1 + 0.01 * (100^2 - 99^2) / 2 = 1.995
+    assert_eq!(curve.integral(99, 100, Rounding::Up), Ok(2));
+    assert_eq!(curve.integral(99, 100, Rounding::Down), Ok(1));
+
+    assert_eq!(curve.calc_pool_amount(0, 150), Ok(100));
+    assert_eq!(curve.calc_pool_amount(0, 149), Ok(99));
+    assert_eq!(curve.calc_pool_amount(100, 250), Ok(100));
+
+    // Flat curve is a fixed price
+    let curve = CurveType::Linear {
+        base_price: PRICE / 2,
+        slope: 0,
+    };
+    assert_eq!(curve.integral(1000, 1100, Rounding::Up), Ok(50));
+    assert_eq!(curve.calc_pool_amount(1000, 50), Ok(100));
+}
+
+#[test]
+fn step_closed_form() {
+    let curve = CurveType::Step {
+        base_price: PRICE,
+        step_size: 10,
+        increment: PRICE,
+    };
+
+    // 10 tokens at 1, 10 at 2 and 5 at 3
+    assert_eq!(curve.integral(0, 25, Rounding::Up), Ok(45));
+    assert_eq!(curve.integral(5, 15, Rounding::Up), Ok(15));
+    // 10 * (1 + 2 + ... + 100)
+    assert_eq!(curve.integral(0, 1000, Rounding::Down), Ok(50_500));
+
+    assert_eq!(curve.calc_pool_amount(0, 45), Ok(25));
+    assert_eq!(curve.calc_pool_amount(0, 47), Ok(25));
+    assert_eq!(curve.calc_pool_amount(0, 48), Ok(26));
+}
+
+#[test]
+fn exponential_closed_form() {
+    // Price doubles with every pool token: 1 + 2 + ... + 2^9
+    let curve = CurveType::Exponential {
+        base_price: PRICE,
+        growth_bps: 10_000,
+        step_size: 1,
+    };
+    assert_eq!(curve.integral(0, 10, Rounding::Down), Ok(1023));
+    assert!(curve.integral(0, 10, Rounding::Up).unwrap() <= 1024);
+    assert_eq!(curve.integral(3, 5, Rounding::Down), Ok(24));
+    assert_eq!(curve.calc_pool_amount(0, 1024), Ok(10));
+
+    // No growth is a fixed price
+    let curve = CurveType::Exponential {
+        base_price: 2 * PRICE,
+        growth_bps: 0,
+        step_size: 7,
+    };
+    assert_eq!(curve.integral(10, 20, Rounding::Up), Ok(20));
+
+    // 10% per 100 pool tokens: 100 * (1.1^k - 1) / 0.1 + rest * 1.1^k
+    let curve = CurveType::Exponential {
+        base_price: PRICE,
+        growth_bps: 1_000,
+        step_size: 100,
+    };
+    let mut rng = StdRng::seed_from_u64(0);
+    for _ in 0..ITERATIONS {
+        let supply = rng.gen_range(0, 20_000);
+        let steps = (supply / 100) as i32;
+        let rest = (supply % 100) as f64;
+        let expected = 100.0 * (1.1f64.powi(steps) - 1.0) / 0.1 + rest * 1.1f64.powi(steps);
+
+        let down = curve.integral(0, supply, Rounding::Down).unwrap() as f64;
+        let up = curve.integral(0, supply, Rounding::Up).unwrap() as f64;
+        let tolerance = expected * 1e-9 + 1.0;
+        assert!(down <= expected + tolerance && down >= expected - tolerance);
+        assert!(up >= expected - tolerance && up <= expected + tolerance + 1.0);
+    }
+}
+
+#[test]
+fn integral_rounds_in_favour_of_the_pool() {
+    let mut rng = StdRng::seed_from_u64(1);
+    for _ in 0..ITERATIONS {
+        let curve = random_curve(&mut rng);
+        let from = rng.gen_range(0, 1_000_000);
+        let to = from + rng.gen_range(0, 1_000_000);
+
+        let up = curve.integral(from, to, Rounding::Up).unwrap();
+        let down = curve.integral(from, to, Rounding::Down).unwrap();
+        assert!(down <= up, "{:?} {} {}", curve, from, to);
+
+        // Buying in two parts costs at least as much as at once
+        let middle = rng.gen_range(from, to + 1);
+        let parts = curve.integral(from, middle, Rounding::Up).unwrap()
+            + curve.integral(middle, to, Rounding::Up).unwrap();
+        assert!(parts >= down, "{:?} {} {} {}", curve, from, middle, to);
+    }
+}
+
+#[test]
+fn calc_pool_amount_is_the_most_affordable() {
+    let mut rng = StdRng::seed_from_u64(2);
+    for _ in 0..ITERATIONS {
+        let curve = random_curve(&mut rng);
+        let supply = rng.gen_range(0, 1_000_000);
+        let bank_amount = rng.gen_range(0, 1_000_000_000);
+
+        let amount = curve.calc_pool_amount(supply, bank_amount).unwrap();
+        let cost = curve
+            .integral(supply, supply + amount, Rounding::Up)
+            .unwrap();
+        assert!(
+            cost <= bank_amount,
+            "{:?} {} {}",
+            curve,
+            supply,
+            bank_amount
+        );
+
+        let more = curve
+            .integral(supply, supply + amount + 1, Rounding::Up)
+            .unwrap();
+        assert!(more > bank_amount, "{:?} {} {}", curve, supply, bank_amount);
+    }
+}
+
+#[test]
+fn invalid_curves() {
+    assert!(CurveType::Constant.is_valid());
+    assert!(!CurveType::Linear {
+        base_price: 0,
+        slope: 1
+    }
+    .is_valid());
+    assert!(!CurveType::Exponential {
+        base_price: 1,
+        growth_bps: 1,
+        step_size: 0
+    }
+    .is_valid());
+    assert!(!CurveType::Step {
+        base_price: 1,
+        step_size: 0,
+        increment: 1
+    }
+    .is_valid());
+
+    // Constant curve prices by the bank, not by the supply
+    assert!(CurveType::Constant.integral(0, 1, Rounding::Up).is_err());
+    assert!(CurveType::Linear {
+        base_price: 1,
+        slope: 0
+    }
+    .integral(1, 0, Rounding::Up)
+    .is_err());
+}
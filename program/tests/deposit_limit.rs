@@ -0,0 +1,482 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::*;
+
+use simple_token_pool::{
+    error::PoolError,
+    find_authority_bump_seed, find_deposit_record_address, id,
+    instruction::{self, DepositRecordKeys},
+    state::{DepositPeriod, DepositRecord},
+};
+use solana_program::{hash::Hash, program_pack::IsInitialized, pubkey::Pubkey};
+use solana_program_test::*;
+use solana_sdk::{signature::Keypair, signer::Signer, transport::TransportError};
+
+const SENDER_MINT_AMOUNT: u64 = 10000;
+
+/// Most bank tokens the sender can swap in
+const DEPOSIT_LIMIT: u64 = 2000;
+
+struct Setup {
+    context: ProgramTestContext,
+    pool_accounts: PoolAccounts,
+    bank_token: Pubkey,
+    pool_token: Pubkey,
+}
+
+/// Pool with a deposit limit and a sender holding bank tokens
+async fn setup(period: DepositPeriod) -> Setup {
+    let mut context = program_test().start_with_context().await;
+    let payer = &context.payer;
+    let banks_client = &mut context.banks_client;
+    let recent_blockhash = context.last_blockhash;
+
+    let mut pool_accounts = PoolAccounts::new();
+    create_accounts(banks_client, payer, &recent_blockhash, &pool_accounts).await;
+    pool_accounts
+        .initialize(banks_client, payer, &recent_blockhash)
+        .await
+        .unwrap();
+
+    set_deposit_limit(
+        banks_client,
+        payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.owner,
+        DEPOSIT_LIMIT,
+        period,
+    )
+    .await
+    .unwrap();
+    pool_accounts.deposit_payer = Some(payer.pubkey());
+
+    let (bank_token, pool_token) = create_sender_accounts(
+        banks_client,
+        payer,
+        &recent_blockhash,
+        &pool_accounts,
+        SENDER_MINT_AMOUNT,
+    )
+    .await;
+
+    Setup {
+        context,
+        pool_accounts,
+        bank_token,
+        pool_token,
+    }
+}
+
+async fn get_deposit_record(
+    banks_client: &mut BanksClient,
+    pool_accounts: &PoolAccounts,
+) -> Option<DepositRecord> {
+    let (address, _) =
+        find_deposit_record_address(&id(), &pool_accounts.pool, &pool_accounts.sender.pubkey());
+    banks_client
+        .get_account(address)
+        .await
+        .unwrap()
+        .map(|account| DepositRecord::try_from_slice_unchecked(&account.data).unwrap())
+}
+
+async fn swap_sender(
+    context: &mut ProgramTestContext,
+    recent_blockhash: &Hash,
+    pool_accounts: &PoolAccounts,
+    bank_token: &Pubkey,
+    pool_token: &Pubkey,
+    amount: u64,
+) -> Result<(), TransportError> {
+    swap(
+        &mut context.banks_client,
+        &context.payer,
+        recent_blockhash,
+        bank_token,
+        pool_token,
+        pool_accounts,
+        amount,
+    )
+    .await
+}
+
+#[tokio::test]
+async fn success_first_deposit_creates_record() {
+    let Setup {
+        mut context,
+        pool_accounts,
+        bank_token,
+        pool_token,
+    } = setup(DepositPeriod::Lifetime).await;
+    let recent_blockhash = context.last_blockhash;
+
+    assert!(
+        get_deposit_record(&mut context.banks_client, &pool_accounts)
+            .await
+            .is_none()
+    );
+
+    swap_sender(
+        &mut context,
+        &recent_blockhash,
+        &pool_accounts,
+        &bank_token,
+        &pool_token,
+        600,
+    )
+    .await
+    .unwrap();
+
+    let deposit_record = get_deposit_record(&mut context.banks_client, &pool_accounts)
+        .await
+        .unwrap();
+    assert!(deposit_record.is_initialized());
+    assert_eq!(deposit_record.pool, pool_accounts.pool);
+    assert_eq!(deposit_record.user, pool_accounts.sender.pubkey());
+    assert_eq!(deposit_record.amount_in, 600);
+
+    // Later swaps add to the record up to the limit
+    swap_sender(
+        &mut context,
+        &recent_blockhash,
+        &pool_accounts,
+        &bank_token,
+        &pool_token,
+        DEPOSIT_LIMIT - 600,
+    )
+    .await
+    .unwrap();
+
+    let deposit_record = get_deposit_record(&mut context.banks_client, &pool_accounts)
+        .await
+        .unwrap();
+    assert_eq!(deposit_record.amount_in, DEPOSIT_LIMIT);
+    assert_eq!(
+        get_token_balance(&mut context.banks_client, &pool_token).await,
+        DEPOSIT_LIMIT
+    );
+}
+
+#[tokio::test]
+async fn success_per_epoch_limit_starts_over() {
+    let Setup {
+        mut context,
+        pool_accounts,
+        bank_token,
+        pool_token,
+    } = setup(DepositPeriod::Epoch).await;
+    let recent_blockhash = context.last_blockhash;
+
+    swap_sender(
+        &mut context,
+        &recent_blockhash,
+        &pool_accounts,
+        &bank_token,
+        &pool_token,
+        DEPOSIT_LIMIT,
+    )
+    .await
+    .unwrap();
+    let deposit_record = get_deposit_record(&mut context.banks_client, &pool_accounts)
+        .await
+        .unwrap();
+
+    let next_epoch_slot = context
+        .genesis_config()
+        .epoch_schedule
+        .get_first_slot_in_epoch(deposit_record.epoch + 1);
+    context.warp_to_slot(next_epoch_slot).unwrap();
+    let recent_blockhash = context.banks_client.get_recent_blockhash().await.unwrap();
+
+    swap_sender(
+        &mut context,
+        &recent_blockhash,
+        &pool_accounts,
+        &bank_token,
+        &pool_token,
+        1000,
+    )
+    .await
+    .unwrap();
+
+    let new_record = get_deposit_record(&mut context.banks_client, &pool_accounts)
+        .await
+        .unwrap();
+    assert_eq!(new_record.amount_in, 1000);
+    assert_eq!(new_record.epoch, deposit_record.epoch + 1);
+}
+
+#[tokio::test]
+async fn fail_over_limit() {
+    let Setup {
+        mut context,
+        pool_accounts,
+        bank_token,
+        pool_token,
+    } = setup(DepositPeriod::Lifetime).await;
+    let recent_blockhash = context.last_blockhash;
+
+    // The first swap alone can't go over the limit, and leaves no record behind
+    let error = swap_sender(
+        &mut context,
+        &recent_blockhash,
+        &pool_accounts,
+        &bank_token,
+        &pool_token,
+        DEPOSIT_LIMIT + 1,
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_pool_error(error, PoolError::DepositLimitExceeded);
+    assert!(
+        get_deposit_record(&mut context.banks_client, &pool_accounts)
+            .await
+            .is_none()
+    );
+
+    swap_sender(
+        &mut context,
+        &recent_blockhash,
+        &pool_accounts,
+        &bank_token,
+        &pool_token,
+        1500,
+    )
+    .await
+    .unwrap();
+
+    let error = swap_sender(
+        &mut context,
+        &recent_blockhash,
+        &pool_accounts,
+        &bank_token,
+        &pool_token,
+        DEPOSIT_LIMIT - 1499,
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_pool_error(error, PoolError::DepositLimitExceeded);
+}
+
+#[tokio::test]
+async fn fail_over_limit_with_delegate() {
+    let Setup {
+        mut context,
+        pool_accounts,
+        bank_token,
+        pool_token,
+    } = setup(DepositPeriod::Lifetime).await;
+    let recent_blockhash = context.last_blockhash;
+    let (pool_authority, _) = find_authority_bump_seed(&id(), &pool_accounts.pool);
+
+    let delegate = Keypair::new();
+    process_instruction(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        spl_token::instruction::approve(
+            &spl_token::id(),
+            &bank_token,
+            &delegate.pubkey(),
+            &pool_accounts.sender.pubkey(),
+            &[],
+            SENDER_MINT_AMOUNT,
+        )
+        .unwrap(),
+        &[&pool_accounts.sender],
+    )
+    .await
+    .unwrap();
+
+    swap_sender(
+        &mut context,
+        &recent_blockhash,
+        &pool_accounts,
+        &bank_token,
+        &pool_token,
+        1500,
+    )
+    .await
+    .unwrap();
+
+    let delegate_swap = |user: &Pubkey, amount: u64| {
+        instruction::swap(
+            &id(),
+            &pool_accounts.pool,
+            &pool_authority,
+            &delegate.pubkey(),
+            &pool_accounts.pool_mint,
+            &pool_accounts.bank,
+            &pool_accounts.fee_receiver.pubkey(),
+            &bank_token,
+            &pool_token,
+            None,
+            Some(&DepositRecordKeys {
+                user: *user,
+                payer: context.payer.pubkey(),
+            }),
+            amount,
+        )
+    };
+
+    // Swaps of a delegate count against the owner of the bank tokens
+    let owner_swap = delegate_swap(&pool_accounts.sender.pubkey(), DEPOSIT_LIMIT - 1499);
+    let delegate_record_swap = delegate_swap(&delegate.pubkey(), DEPOSIT_LIMIT - 1499);
+    let error = process_instruction(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        owner_swap,
+        &[&delegate],
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_pool_error(error, PoolError::DepositLimitExceeded);
+
+    let error = process_instruction(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        delegate_record_swap,
+        &[&delegate],
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_pool_error(error, PoolError::InvalidDepositRecord);
+
+    let deposit_record = get_deposit_record(&mut context.banks_client, &pool_accounts)
+        .await
+        .unwrap();
+    assert_eq!(deposit_record.amount_in, 1500);
+}
+
+#[tokio::test]
+async fn fail_with_wrong_record() {
+    let Setup {
+        mut context,
+        pool_accounts,
+        bank_token,
+        pool_token,
+    } = setup(DepositPeriod::Lifetime).await;
+    let (record, _) =
+        find_deposit_record_address(&id(), &pool_accounts.pool, &pool_accounts.sender.pubkey());
+    let (other_record, _) =
+        find_deposit_record_address(&id(), &pool_accounts.pool, &Pubkey::new_unique());
+
+    // Deposits can't be counted on the record of another user
+    let mut instruction = swap_instruction(&bank_token, &pool_token, &pool_accounts, 1000);
+    substitute_account(&mut instruction, &record, &other_record);
+    let error = process_instruction(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        instruction,
+        &[&pool_accounts.sender],
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_pool_error(error, PoolError::InvalidDepositRecord);
+}
+
+#[tokio::test]
+async fn success_close_unused_record() {
+    let Setup {
+        mut context,
+        pool_accounts,
+        bank_token,
+        pool_token,
+    } = setup(DepositPeriod::Lifetime).await;
+    let recent_blockhash = context.last_blockhash;
+    let destination = Pubkey::new_unique();
+
+    swap_sender(
+        &mut context,
+        &recent_blockhash,
+        &pool_accounts,
+        &bank_token,
+        &pool_token,
+        1000,
+    )
+    .await
+    .unwrap();
+
+    // The lifetime limit would start over without the record
+    let error = close_deposit_record(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.sender,
+        &Pubkey::new_unique(),
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_pool_error(error, PoolError::DepositRecordInUse);
+
+    set_deposit_limit(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.owner,
+        0,
+        DepositPeriod::Lifetime,
+    )
+    .await
+    .unwrap();
+
+    close_deposit_record(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.sender,
+        &destination,
+    )
+    .await
+    .unwrap();
+
+    assert!(
+        get_deposit_record(&mut context.banks_client, &pool_accounts)
+            .await
+            .is_none()
+    );
+    assert!(
+        get_account(&mut context.banks_client, &destination)
+            .await
+            .lamports
+            > 0
+    );
+}
+
+#[tokio::test]
+async fn fail_set_with_wrong_manager() {
+    let Setup {
+        mut context,
+        pool_accounts,
+        ..
+    } = setup(DepositPeriod::Lifetime).await;
+
+    let error = set_deposit_limit(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &pool_accounts,
+        &pool_accounts.sender,
+        0,
+        DepositPeriod::Lifetime,
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_pool_error(error, PoolError::InvalidManager);
+}
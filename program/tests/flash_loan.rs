@@ -0,0 +1,280 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::*;
+
+use simple_token_pool::{error::PoolError, find_authority_bump_seed, id, instruction};
+use solana_program::{hash::Hash, pubkey::Pubkey};
+use solana_program_test::*;
+use solana_sdk::{signature::Keypair, signer::Signer};
+
+const BANK_AMOUNT: u64 = 5000;
+
+/// Borrower funds to pay the fee with
+const BORROWER_MINT_AMOUNT: u64 = 100;
+
+/// Pool with 5000 bank tokens and a borrower bank token account
+///
+/// The bank tokens are minted straight to the bank, so pools with any fee can lend
+async fn setup(fee_bps: u16) -> (BanksClient, Keypair, Hash, PoolAccounts, Keypair, Pubkey) {
+    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+
+    let mut pool_accounts = PoolAccounts::new();
+    pool_accounts.fee_bps = fee_bps;
+    create_accounts(&mut banks_client, &payer, &recent_blockhash, &pool_accounts).await;
+    pool_accounts
+        .initialize(&mut banks_client, &payer, &recent_blockhash)
+        .await
+        .unwrap();
+
+    mint_tokens(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts.bank_mint.pubkey(),
+        &pool_accounts.bank,
+        &pool_accounts.owner,
+        BANK_AMOUNT,
+    )
+    .await
+    .unwrap();
+
+    let borrower = Keypair::new();
+    let borrower_token = Keypair::new();
+    create_token_account(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &borrower_token,
+        &pool_accounts.bank_mint.pubkey(),
+        &borrower.pubkey(),
+    )
+    .await
+    .unwrap();
+    mint_tokens(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts.bank_mint.pubkey(),
+        &borrower_token.pubkey(),
+        &pool_accounts.owner,
+        BORROWER_MINT_AMOUNT,
+    )
+    .await
+    .unwrap();
+
+    (
+        banks_client,
+        payer,
+        recent_blockhash,
+        pool_accounts,
+        borrower,
+        borrower_token.pubkey(),
+    )
+}
+
+#[tokio::test]
+async fn success() {
+    let (mut banks_client, payer, recent_blockhash, pool_accounts, borrower, borrower_token) =
+        setup(100).await;
+    let bank_balance = get_token_balance(&mut banks_client, &pool_accounts.bank).await;
+
+    flash_loan(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &borrower_token,
+        Some(&borrower),
+        1000,
+    )
+    .await
+    .unwrap();
+
+    // 1% fee on 1000 stays in the bank
+    assert_eq!(
+        get_token_balance(&mut banks_client, &pool_accounts.bank).await,
+        bank_balance + 10
+    );
+    assert_eq!(
+        get_token_balance(&mut banks_client, &borrower_token).await,
+        BORROWER_MINT_AMOUNT - 10
+    );
+}
+
+#[tokio::test]
+async fn success_whole_bank() {
+    let (mut banks_client, payer, recent_blockhash, pool_accounts, borrower, borrower_token) =
+        setup(0).await;
+    let bank_balance = get_token_balance(&mut banks_client, &pool_accounts.bank).await;
+
+    flash_loan(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &borrower_token,
+        Some(&borrower),
+        bank_balance,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(
+        get_token_balance(&mut banks_client, &pool_accounts.bank).await,
+        bank_balance
+    );
+    assert_eq!(
+        get_token_balance(&mut banks_client, &borrower_token).await,
+        BORROWER_MINT_AMOUNT
+    );
+}
+
+#[tokio::test]
+async fn success_without_fee() {
+    let (mut banks_client, payer, recent_blockhash, pool_accounts, borrower, borrower_token) =
+        setup(0).await;
+
+    flash_loan(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &borrower_token,
+        Some(&borrower),
+        1000,
+    )
+    .await
+    .unwrap();
+
+    // Pools without a swap fee lend for free
+    assert_eq!(
+        get_token_balance(&mut banks_client, &pool_accounts.bank).await,
+        BANK_AMOUNT
+    );
+    assert_eq!(
+        get_token_balance(&mut banks_client, &borrower_token).await,
+        BORROWER_MINT_AMOUNT
+    );
+}
+
+#[tokio::test]
+async fn success_with_max_fee() {
+    let (mut banks_client, payer, recent_blockhash, pool_accounts, borrower, borrower_token) =
+        setup(9999).await;
+
+    flash_loan(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &borrower_token,
+        Some(&borrower),
+        BORROWER_MINT_AMOUNT,
+    )
+    .await
+    .unwrap();
+
+    // 99.99% fee on 100 rounds up to the whole amount
+    assert_eq!(
+        get_token_balance(&mut banks_client, &pool_accounts.bank).await,
+        BANK_AMOUNT + BORROWER_MINT_AMOUNT
+    );
+    assert_eq!(
+        get_token_balance(&mut banks_client, &borrower_token).await,
+        0
+    );
+}
+
+#[tokio::test]
+async fn fail_not_repaid() {
+    let (mut banks_client, payer, recent_blockhash, pool_accounts, _, borrower_token) =
+        setup(100).await;
+    let bank_balance = get_token_balance(&mut banks_client, &pool_accounts.bank).await;
+
+    // Without the borrower the receiver keeps the loan
+    let error = flash_loan(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &borrower_token,
+        None,
+        1000,
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_pool_error(error, PoolError::FlashLoanNotRepaid);
+
+    // The transfer out is rolled back with the transaction
+    assert_eq!(
+        get_token_balance(&mut banks_client, &pool_accounts.bank).await,
+        bank_balance
+    );
+    assert_eq!(
+        get_token_balance(&mut banks_client, &borrower_token).await,
+        BORROWER_MINT_AMOUNT
+    );
+}
+
+#[tokio::test]
+async fn fail_paused() {
+    let (mut banks_client, payer, recent_blockhash, pool_accounts, borrower, borrower_token) =
+        setup(100).await;
+
+    set_paused(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.owner,
+        true,
+    )
+    .await
+    .unwrap();
+
+    let error = flash_loan(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &borrower_token,
+        Some(&borrower),
+        1000,
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_pool_error(error, PoolError::PoolPaused);
+}
+
+#[tokio::test]
+async fn fail_with_wrong_bank() {
+    let (mut banks_client, payer, recent_blockhash, pool_accounts, _, borrower_token) =
+        setup(100).await;
+    let (pool_authority, _) = find_authority_bump_seed(&id(), &pool_accounts.pool);
+
+    // Only the bank can be lent
+    let error = process_instruction(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        instruction::flash_loan(
+            &id(),
+            &pool_accounts.pool,
+            &pool_authority,
+            &borrower_token,
+            &borrower_token,
+            &flash_loan_receiver::id(),
+            vec![],
+            1000,
+        ),
+        &[],
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_pool_error(error, PoolError::InvalidBank);
+}
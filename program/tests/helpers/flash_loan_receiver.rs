@@ -0,0 +1,49 @@
+//! Flash loan receiver program paying back the loan and fee when given the borrower
+
+use borsh::BorshDeserialize;
+use simple_token_pool::instruction::FlashLoanReceive;
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    program::invoke,
+    pubkey::Pubkey,
+};
+
+solana_program::declare_id!("7Sb5mFmrWhRqLX8Btb5r5BDZtRVWsYvuqpgSUC9jdTWK");
+
+/// Pays back the amount and fee from the destination if the destination owner
+/// signed, keeps the loan otherwise
+pub fn process_instruction(
+    _program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    input: &[u8],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let destination_info = next_account_info(account_info_iter)?;
+    let bank_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+
+    let FlashLoanReceive { amount, fee } = FlashLoanReceive::try_from_slice(input)?;
+
+    if let Ok(borrower_info) = next_account_info(account_info_iter) {
+        let ix = spl_token::instruction::transfer(
+            token_program_info.key,
+            destination_info.key,
+            bank_info.key,
+            borrower_info.key,
+            &[],
+            amount + fee,
+        )?;
+        invoke(
+            &ix,
+            &[
+                destination_info.clone(),
+                bank_info.clone(),
+                borrower_info.clone(),
+                token_program_info.clone(),
+            ],
+        )?;
+    }
+
+    Ok(())
+}
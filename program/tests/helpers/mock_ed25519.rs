@@ -0,0 +1,42 @@
+//! Mock of the Ed25519 program, which the test validator does not have yet
+//!
+//! The real program verifies the signatures before the transaction runs, the mock
+//! fails the transaction when it gets to the instruction
+
+use solana_program::{
+    account_info::AccountInfo, entrypoint::ProgramResult, program_error::ProgramError,
+    pubkey::Pubkey,
+};
+use solana_sdk::signature::Signature;
+
+pub use simple_token_pool::permit::ed25519_program::id;
+
+/// Verifies every signature of the instruction data
+pub fn process_instruction(
+    _program_id: &Pubkey,
+    _accounts: &[AccountInfo],
+    input: &[u8],
+) -> ProgramResult {
+    let count = *input.first().ok_or(ProgramError::InvalidInstructionData)? as usize;
+
+    for index in 0..count {
+        let offsets = input
+            .get(2 + index * 14..2 + (index + 1) * 14)
+            .ok_or(ProgramError::InvalidInstructionData)?;
+        let offset = |i: usize| u16::from_le_bytes([offsets[2 * i], offsets[2 * i + 1]]) as usize;
+        let slice = |start: usize, len: usize| {
+            input
+                .get(start..start + len)
+                .ok_or(ProgramError::InvalidInstructionData)
+        };
+
+        let signature = Signature::new(slice(offset(0), 64)?);
+        let pubkey = slice(offset(2), 32)?;
+        let message = slice(offset(4), offset(5))?;
+        if !signature.verify(pubkey, message) {
+            return Err(ProgramError::InvalidArgument);
+        }
+    }
+
+    Ok(())
+}
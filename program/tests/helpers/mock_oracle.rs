@@ -0,0 +1,37 @@
+//! Mock oracle program writing whatever price it is given into a price account
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use simple_token_pool::state::PriceData;
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+
+solana_program::declare_id!("Cze5Py1LUfWuT4pcL537SrGgmsPsNuVzhgBYjg3CCXd5");
+
+/// Writes the price data of the instruction into the price account
+pub fn process_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    input: &[u8],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let price_info = next_account_info(account_info_iter)?;
+
+    if price_info.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    let price = PriceData::try_from_slice(input)?;
+    price.serialize(&mut &mut price_info.data.borrow_mut()[..])?;
+
+    Ok(())
+}
+
+/// Creates an instruction setting the price
+pub fn set_price(price_account: &Pubkey, price: PriceData) -> Instruction {
+    Instruction::new_with_borsh(id(), &price, vec![AccountMeta::new(*price_account, false)])
+}
@@ -1,12 +1,34 @@
 #![allow(dead_code)]
 
-use simple_token_pool::{find_authority_bump_seed, id, instruction, processor, state::PoolData};
+pub mod flash_loan_receiver;
+pub mod mock_ed25519;
+pub mod mock_oracle;
+
+use simple_token_pool::{
+    curve::CurveType,
+    error::PoolError,
+    find_authority_bump_seed, find_bank_address, find_pool_address, find_pool_mint_address,
+    find_reserve_address, id,
+    instruction::{self, DepositRecordKeys},
+    merkle::MerkleHash,
+    permit::{self, Permit},
+    processor,
+    state::{DepositPeriod, PoolData, PriceData},
+};
 use solana_program::{
-    borsh::get_packed_len, hash::Hash, program_pack::Pack, pubkey::Pubkey, system_instruction,
+    clock::{Clock, UnixTimestamp},
+    hash::Hash,
+    instruction::{AccountMeta, Instruction, InstructionError},
+    program_pack::Pack,
+    pubkey::Pubkey,
+    system_instruction, sysvar,
 };
 use solana_program_test::*;
 use solana_sdk::{
-    account::Account, signature::Keypair, signer::Signer, transaction::Transaction,
+    account::Account,
+    signature::Keypair,
+    signer::Signer,
+    transaction::{Transaction, TransactionError},
     transport::TransportError,
 };
 use spl_token as token;
@@ -14,69 +36,162 @@ use spl_token as token;
 #[derive(Debug)]
 pub struct PoolAccounts {
     pub owner: Keypair,
-    pub pool: Keypair,
+    pub pool: Pubkey,
+    pub index: u64,
     pub bank_mint: Keypair,
-    pub pool_mint: Keypair,
-    pub bank: Keypair,
+    pub pool_mint: Pubkey,
+    pub bank: Pubkey,
+    pub fee_receiver: Keypair,
     pub sender: Keypair,
     pub recipient: Keypair,
+    pub rate_numerator: u64,
+    pub rate_denominator: u64,
+    pub fee_bps: u16,
+    pub curve: CurveType,
+    pub oracle: Option<Pubkey>,
+    pub reserve: Option<Pubkey>,
+    pub deposit_payer: Option<Pubkey>,
 }
 
 impl PoolAccounts {
     pub fn new() -> Self {
         let owner = Keypair::new();
-        let pool = Keypair::new();
         let bank_mint = Keypair::new();
-        let pool_mint = Keypair::new();
-        let bank = Keypair::new();
+        let index = 0;
+        let (pool, _) = find_pool_address(&id(), &bank_mint.pubkey(), index);
+        let (pool_mint, _) = find_pool_mint_address(&id(), &pool);
+        let (bank, _) = find_bank_address(&id(), &pool);
+        let fee_receiver = Keypair::new();
         let sender = Keypair::new();
         let recipient = Keypair::new();
 
         Self {
             owner,
             pool,
+            index,
             bank_mint,
             pool_mint,
             bank,
+            fee_receiver,
             sender,
             recipient,
+            rate_numerator: 1,
+            rate_denominator: 1,
+            fee_bps: 0,
+            curve: CurveType::Constant,
+            oracle: None,
+            reserve: None,
+            deposit_payer: None,
         }
     }
 
+    /// Deposit record of the sender, for pools with a deposit limit
+    pub fn deposit_record(&self) -> Option<DepositRecordKeys> {
+        self.deposit_payer.map(|payer| DepositRecordKeys {
+            user: self.sender.pubkey(),
+            payer,
+        })
+    }
+
+    pub fn initialize_instruction(&self, payer: &Pubkey) -> Instruction {
+        let (authority, _) = find_authority_bump_seed(&simple_token_pool::id(), &self.pool);
+
+        instruction::initialize(
+            &simple_token_pool::id(),
+            &self.pool,
+            &authority,
+            &self.owner.pubkey(),
+            &self.bank_mint.pubkey(),
+            &self.pool_mint,
+            &self.bank,
+            &self.fee_receiver.pubkey(),
+            payer,
+            self.index,
+            self.rate_numerator,
+            self.rate_denominator,
+            self.fee_bps,
+            self.curve,
+        )
+    }
+
     pub async fn initialize(
         &self,
         banks_client: &mut BanksClient,
         payer: &Keypair,
         recent_blockhash: &Hash,
     ) -> Result<(), TransportError> {
-        let (authority, _) =
-            find_authority_bump_seed(&simple_token_pool::id(), &self.pool.pubkey());
+        process_instruction(
+            banks_client,
+            payer,
+            recent_blockhash,
+            self.initialize_instruction(&payer.pubkey()),
+            &[&self.owner],
+        )
+        .await
+    }
+}
 
-        let mut tx = Transaction::new_with_payer(
-            &[instruction::initialize(
-                &simple_token_pool::id(),
-                &self.pool.pubkey(),
-                &authority,
-                &self.bank_mint.pubkey(),
-                &self.pool_mint.pubkey(),
-                &self.bank.pubkey(),
-            )],
-            Some(&payer.pubkey()),
-        );
-
-        tx.sign(&[payer, &self.pool], *recent_blockhash);
-        banks_client.process_transaction(tx).await?;
-
-        Ok(())
+pub async fn process_instruction(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: &Hash,
+    instruction: Instruction,
+    signers: &[&Keypair],
+) -> Result<(), TransportError> {
+    let mut tx = Transaction::new_with_payer(&[instruction], Some(&payer.pubkey()));
+
+    let mut all_signers = vec![payer];
+    all_signers.extend_from_slice(signers);
+
+    tx.sign(&all_signers, *recent_blockhash);
+    banks_client.process_transaction(tx).await?;
+
+    Ok(())
+}
+
+/// Replaces the instruction account, keeping its flags
+pub fn substitute_account(instruction: &mut Instruction, from: &Pubkey, to: &Pubkey) {
+    for account in instruction.accounts.iter_mut() {
+        if account.pubkey == *from {
+            account.pubkey = *to;
+        }
+    }
+}
+
+/// Passes the pool authority, which can't sign, as the user transfer authority
+pub fn substitute_pool_authority(instruction: &mut Instruction, pool_accounts: &PoolAccounts) {
+    let (pool_authority, _) =
+        find_authority_bump_seed(&simple_token_pool::id(), &pool_accounts.pool);
+    for account in instruction.accounts.iter_mut() {
+        if account.pubkey == pool_accounts.sender.pubkey() {
+            account.pubkey = pool_authority;
+            account.is_signer = false;
+        }
     }
 }
 
 pub fn program_test() -> ProgramTest {
-    ProgramTest::new(
+    let mut program_test = ProgramTest::new(
         "simple_token_pool",
         id(),
         processor!(processor::process_instruction),
-    )
+    );
+    program_test.add_program(
+        "mock_oracle",
+        mock_oracle::id(),
+        processor!(mock_oracle::process_instruction),
+    );
+    program_test.add_program(
+        "flash_loan_receiver",
+        flash_loan_receiver::id(),
+        processor!(flash_loan_receiver::process_instruction),
+    );
+    program_test.add_program(
+        "mock_ed25519",
+        mock_ed25519::id(),
+        processor!(mock_ed25519::process_instruction),
+    );
+    program_test
 }
 
 pub async fn get_account(banks_client: &mut BanksClient, pubkey: &Pubkey) -> Account {
@@ -95,6 +210,26 @@ pub async fn get_token_balance(banks_client: &mut BanksClient, token: &Pubkey) -
     account_info.amount
 }
 
+pub async fn get_pool_data(
+    banks_client: &mut BanksClient,
+    pool_accounts: &PoolAccounts,
+) -> PoolData {
+    let pool = get_account(banks_client, &pool_accounts.pool).await;
+    PoolData::try_from_slice_unchecked(&pool.data).unwrap()
+}
+
+pub fn assert_pool_error(tx_error: TransportError, error: PoolError) {
+    match tx_error {
+        TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(code),
+        )) => {
+            assert_eq!(code, error as u32);
+        }
+        _ => panic!("Wrong error"),
+    }
+}
+
 pub async fn create_mint(
     banks_client: &mut BanksClient,
     payer: &Keypair,
@@ -219,87 +354,348 @@ pub async fn create_token_account(
     Ok(())
 }
 
-/// Create pool accounts
-pub async fn create_pool_accounts(
+pub async fn create_accounts(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: &Hash,
+    pool_accounts: &PoolAccounts,
+) {
+    // Create token (incoming)
+    create_mint(
+        banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts.bank_mint,
+        &pool_accounts.owner.pubkey(),
+    )
+    .await
+    .unwrap();
+
+    // Fee receiver for the bank tokens
+    create_token_account(
+        banks_client,
+        payer,
+        recent_blockhash,
+        &pool_accounts.fee_receiver,
+        &pool_accounts.bank_mint.pubkey(),
+        &pool_accounts.owner.pubkey(),
+    )
+    .await
+    .unwrap();
+}
+
+/// Creates bank and pool token accounts of the sender, holding the bank tokens amount
+pub async fn create_sender_accounts(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: &Hash,
+    pool_accounts: &PoolAccounts,
+    amount: u64,
+) -> (Pubkey, Pubkey) {
+    let bank_token = Keypair::new();
+    let pool_token = Keypair::new();
+    create_token_account(
+        banks_client,
+        payer,
+        recent_blockhash,
+        &bank_token,
+        &pool_accounts.bank_mint.pubkey(),
+        &pool_accounts.sender.pubkey(),
+    )
+    .await
+    .unwrap();
+    create_token_account(
+        banks_client,
+        payer,
+        recent_blockhash,
+        &pool_token,
+        &pool_accounts.pool_mint,
+        &pool_accounts.sender.pubkey(),
+    )
+    .await
+    .unwrap();
+    mint_tokens(
+        banks_client,
+        payer,
+        recent_blockhash,
+        &pool_accounts.bank_mint.pubkey(),
+        &bank_token.pubkey(),
+        &pool_accounts.owner,
+        amount,
+    )
+    .await
+    .unwrap();
+
+    (bank_token.pubkey(), pool_token.pubkey())
+}
+
+/// Starts a test with an initialized pool and a sender holding the bank tokens amount
+pub async fn setup_pool_with_sender(
+    amount: u64,
+) -> (BanksClient, Keypair, Hash, PoolAccounts, Pubkey, Pubkey) {
+    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+
+    let pool_accounts = PoolAccounts::new();
+    create_accounts(&mut banks_client, &payer, &recent_blockhash, &pool_accounts).await;
+    pool_accounts
+        .initialize(&mut banks_client, &payer, &recent_blockhash)
+        .await
+        .unwrap();
+
+    let (bank_token, pool_token) = create_sender_accounts(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        amount,
+    )
+    .await;
+
+    (
+        banks_client,
+        payer,
+        recent_blockhash,
+        pool_accounts,
+        bank_token,
+        pool_token,
+    )
+}
+
+pub fn swap_instruction(
+    sender: &Pubkey,
+    recipient: &Pubkey,
+    pool_accounts: &PoolAccounts,
+    amount: u64,
+) -> Instruction {
+    let (pool_authority, _) =
+        find_authority_bump_seed(&simple_token_pool::id(), &pool_accounts.pool);
+
+    instruction::swap(
+        &simple_token_pool::id(),
+        &pool_accounts.pool,
+        &pool_authority,
+        &pool_accounts.sender.pubkey(),
+        &pool_accounts.pool_mint,
+        &pool_accounts.bank,
+        &pool_accounts.fee_receiver.pubkey(),
+        &sender,
+        &recipient,
+        pool_accounts.oracle.as_ref(),
+        pool_accounts.deposit_record().as_ref(),
+        amount,
+    )
+}
+
+pub async fn swap(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: &Hash,
+    sender: &Pubkey,
+    recipient: &Pubkey,
+    pool_accounts: &PoolAccounts,
+    amount: u64,
+) -> Result<(), TransportError> {
+    process_instruction(
+        banks_client,
+        payer,
+        recent_blockhash,
+        swap_instruction(sender, recipient, pool_accounts, amount),
+        &[&pool_accounts.sender],
+    )
+    .await
+}
+
+pub fn redeem_instruction(
+    sender: &Pubkey,
+    recipient: &Pubkey,
+    pool_accounts: &PoolAccounts,
+    amount: u64,
+) -> Instruction {
+    let (pool_authority, _) =
+        find_authority_bump_seed(&simple_token_pool::id(), &pool_accounts.pool);
+
+    instruction::redeem(
+        &simple_token_pool::id(),
+        &pool_accounts.pool,
+        &pool_authority,
+        &pool_accounts.sender.pubkey(),
+        &pool_accounts.pool_mint,
+        &pool_accounts.bank,
+        &sender,
+        &recipient,
+        pool_accounts.oracle.as_ref(),
+        amount,
+    )
+}
+
+pub async fn redeem(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: &Hash,
+    sender: &Pubkey,
+    recipient: &Pubkey,
+    pool_accounts: &PoolAccounts,
+    amount: u64,
+) -> Result<(), TransportError> {
+    process_instruction(
+        banks_client,
+        payer,
+        recent_blockhash,
+        redeem_instruction(sender, recipient, pool_accounts, amount),
+        &[&pool_accounts.sender],
+    )
+    .await
+}
+
+pub async fn propose_manager(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: &Hash,
+    pool_accounts: &PoolAccounts,
+    manager: &Keypair,
+    new_manager: &Pubkey,
+) -> Result<(), TransportError> {
+    let mut tx = Transaction::new_with_payer(
+        &[instruction::propose_manager(
+            &simple_token_pool::id(),
+            &pool_accounts.pool,
+            &manager.pubkey(),
+            new_manager,
+        )],
+        Some(&payer.pubkey()),
+    );
+
+    tx.sign(&[payer, manager], *recent_blockhash);
+    banks_client.process_transaction(tx).await?;
+
+    Ok(())
+}
+
+pub async fn accept_manager(
     banks_client: &mut BanksClient,
     payer: &Keypair,
     recent_blockhash: &Hash,
-    pool: &Keypair,
-    pool_mint: &Keypair,
-    bank: &Keypair,
+    pool_accounts: &PoolAccounts,
+    new_manager: &Keypair,
 ) -> Result<(), TransportError> {
-    let rent = banks_client.get_rent().await.unwrap();
+    let mut tx = Transaction::new_with_payer(
+        &[instruction::accept_manager(
+            &simple_token_pool::id(),
+            &pool_accounts.pool,
+            &new_manager.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+
+    tx.sign(&[payer, new_manager], *recent_blockhash);
+    banks_client.process_transaction(tx).await?;
+
+    Ok(())
+}
+
+pub async fn set_paused(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: &Hash,
+    pool_accounts: &PoolAccounts,
+    manager: &Keypair,
+    paused: bool,
+) -> Result<(), TransportError> {
+    let ix = if paused {
+        instruction::pause(
+            &simple_token_pool::id(),
+            &pool_accounts.pool,
+            &manager.pubkey(),
+        )
+    } else {
+        instruction::unpause(
+            &simple_token_pool::id(),
+            &pool_accounts.pool,
+            &manager.pubkey(),
+        )
+    };
+
+    let mut tx = Transaction::new_with_payer(&[ix], Some(&payer.pubkey()));
+
+    tx.sign(&[payer, manager], *recent_blockhash);
+    banks_client.process_transaction(tx).await?;
+
+    Ok(())
+}
 
-    let pool_rent = rent.minimum_balance(get_packed_len::<PoolData>());
-    let pool_mint_rent = rent.minimum_balance(token::state::Mint::LEN);
-    let bank_rent = rent.minimum_balance(token::state::Account::LEN);
+/// Migrates the pool into a new account created in the same transaction
+pub async fn migrate(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: &Hash,
+    pool_accounts: &PoolAccounts,
+    new_pool: &Keypair,
+) -> Result<(), TransportError> {
+    let rent = banks_client.get_rent().await.unwrap();
+    let (pool_authority, _) =
+        find_authority_bump_seed(&simple_token_pool::id(), &pool_accounts.pool);
+    let (new_pool_authority, _) =
+        find_authority_bump_seed(&simple_token_pool::id(), &new_pool.pubkey());
 
     let mut tx = Transaction::new_with_payer(
         &[
             system_instruction::create_account(
                 &payer.pubkey(),
-                &pool.pubkey(),
-                pool_rent,
-                get_packed_len::<PoolData>() as u64,
+                &new_pool.pubkey(),
+                rent.minimum_balance(PoolData::LEN),
+                PoolData::LEN as u64,
                 &simple_token_pool::id(),
             ),
-            // Pool mint account
-            system_instruction::create_account(
-                &payer.pubkey(),
-                &pool_mint.pubkey(),
-                pool_mint_rent,
-                token::state::Mint::LEN as u64,
-                &token::id(),
-            ),
-            // Account for the bank
-            system_instruction::create_account(
-                &payer.pubkey(),
-                &bank.pubkey(),
-                bank_rent,
-                token::state::Account::LEN as u64,
-                &token::id(),
+            instruction::migrate(
+                &simple_token_pool::id(),
+                &pool_accounts.pool,
+                &new_pool.pubkey(),
+                &pool_authority,
+                &new_pool_authority,
+                &pool_accounts.pool_mint,
+                &pool_accounts.bank,
             ),
         ],
         Some(&payer.pubkey()),
     );
 
-    tx.sign(&[payer, pool, pool_mint, bank], *recent_blockhash);
+    tx.sign(&[payer, new_pool], *recent_blockhash);
     banks_client.process_transaction(tx).await?;
 
     Ok(())
 }
 
-pub async fn create_accounts(
+pub async fn close_pool(
     banks_client: &mut BanksClient,
     payer: &Keypair,
     recent_blockhash: &Hash,
     pool_accounts: &PoolAccounts,
-) {
-    // Create token (incoming)
-    create_mint(
-        banks_client,
-        &payer,
-        &recent_blockhash,
-        &pool_accounts.bank_mint,
-        &pool_accounts.owner.pubkey(),
-    )
-    .await
-    .unwrap();
+    manager: &Keypair,
+    destination: &Pubkey,
+) -> Result<(), TransportError> {
+    let (pool_authority, _) =
+        find_authority_bump_seed(&simple_token_pool::id(), &pool_accounts.pool);
 
-    // Pool accounts
-    create_pool_accounts(
+    process_instruction(
         banks_client,
         payer,
         recent_blockhash,
-        &pool_accounts.pool,
-        &pool_accounts.pool_mint,
-        &pool_accounts.bank,
+        instruction::close_pool(
+            &simple_token_pool::id(),
+            &pool_accounts.pool,
+            &pool_authority,
+            &manager.pubkey(),
+            &pool_accounts.pool_mint,
+            &pool_accounts.bank,
+            destination,
+            pool_accounts.reserve.as_ref(),
+        ),
+        &[manager],
     )
     .await
-    .unwrap();
 }
 
-pub async fn swap(
+pub async fn swap_with_minimum(
     banks_client: &mut BanksClient,
     payer: &Keypair,
     recent_blockhash: &Hash,
@@ -307,27 +703,700 @@ pub async fn swap(
     recipient: &Pubkey,
     pool_accounts: &PoolAccounts,
     amount: u64,
+    minimum_amount_out: u64,
 ) -> Result<(), TransportError> {
     let (pool_authority, _) =
-        find_authority_bump_seed(&simple_token_pool::id(), &pool_accounts.pool.pubkey());
+        find_authority_bump_seed(&simple_token_pool::id(), &pool_accounts.pool);
 
-    let mut tx = Transaction::new_with_payer(
-        &[instruction::swap(
+    process_instruction(
+        banks_client,
+        payer,
+        recent_blockhash,
+        instruction::swap_with_minimum(
             &simple_token_pool::id(),
-            &pool_accounts.pool.pubkey(),
+            &pool_accounts.pool,
             &pool_authority,
             &pool_accounts.sender.pubkey(),
-            &pool_accounts.pool_mint.pubkey(),
-            &pool_accounts.bank.pubkey(),
-            &sender,
-            &recipient,
+            &pool_accounts.pool_mint,
+            &pool_accounts.bank,
+            &pool_accounts.fee_receiver.pubkey(),
+            sender,
+            recipient,
+            pool_accounts.oracle.as_ref(),
+            pool_accounts.deposit_record().as_ref(),
             amount,
-        )],
-        Some(&payer.pubkey()),
-    );
+            minimum_amount_out,
+        ),
+        &[&pool_accounts.sender],
+    )
+    .await
+}
+
+pub async fn swap_with_proof(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: &Hash,
+    sender: &Pubkey,
+    recipient: &Pubkey,
+    pool_accounts: &PoolAccounts,
+    amount: u64,
+    minimum_amount_out: u64,
+    proof: Vec<MerkleHash>,
+) -> Result<(), TransportError> {
+    let (pool_authority, _) =
+        find_authority_bump_seed(&simple_token_pool::id(), &pool_accounts.pool);
+
+    process_instruction(
+        banks_client,
+        payer,
+        recent_blockhash,
+        instruction::swap_with_proof(
+            &simple_token_pool::id(),
+            &pool_accounts.pool,
+            &pool_authority,
+            &pool_accounts.sender.pubkey(),
+            &pool_accounts.pool_mint,
+            &pool_accounts.bank,
+            &pool_accounts.fee_receiver.pubkey(),
+            sender,
+            recipient,
+            pool_accounts.oracle.as_ref(),
+            pool_accounts.deposit_record().as_ref(),
+            amount,
+            minimum_amount_out,
+            proof,
+        ),
+        &[&pool_accounts.sender],
+    )
+    .await
+}
+
+pub async fn swap_exact_out(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: &Hash,
+    sender: &Pubkey,
+    recipient: &Pubkey,
+    pool_accounts: &PoolAccounts,
+    amount_out: u64,
+    maximum_amount_in: u64,
+) -> Result<(), TransportError> {
+    let (pool_authority, _) =
+        find_authority_bump_seed(&simple_token_pool::id(), &pool_accounts.pool);
+
+    process_instruction(
+        banks_client,
+        payer,
+        recent_blockhash,
+        instruction::swap_exact_out(
+            &simple_token_pool::id(),
+            &pool_accounts.pool,
+            &pool_authority,
+            &pool_accounts.sender.pubkey(),
+            &pool_accounts.pool_mint,
+            &pool_accounts.bank,
+            &pool_accounts.fee_receiver.pubkey(),
+            sender,
+            recipient,
+            pool_accounts.oracle.as_ref(),
+            pool_accounts.deposit_record().as_ref(),
+            amount_out,
+            maximum_amount_in,
+        ),
+        &[&pool_accounts.sender],
+    )
+    .await
+}
+
+pub async fn deposit_rewards(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: &Hash,
+    pool_accounts: &PoolAccounts,
+    manager: &Keypair,
+    source: &Pubkey,
+    amount: u64,
+) -> Result<(), TransportError> {
+    process_instruction(
+        banks_client,
+        payer,
+        recent_blockhash,
+        instruction::deposit_rewards(
+            &simple_token_pool::id(),
+            &pool_accounts.pool,
+            &manager.pubkey(),
+            &pool_accounts.pool_mint,
+            &pool_accounts.bank,
+            source,
+            amount,
+        ),
+        &[manager],
+    )
+    .await
+}
+
+pub async fn refresh(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: &Hash,
+    pool_accounts: &PoolAccounts,
+) -> Result<(), TransportError> {
+    process_instruction(
+        banks_client,
+        payer,
+        recent_blockhash,
+        instruction::refresh(
+            &simple_token_pool::id(),
+            &pool_accounts.pool,
+            &pool_accounts.bank,
+        ),
+        &[],
+    )
+    .await
+}
+
+pub async fn set_apr(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: &Hash,
+    pool_accounts: &PoolAccounts,
+    manager: &Keypair,
+    apr_bps: u16,
+) -> Result<(), TransportError> {
+    process_instruction(
+        banks_client,
+        payer,
+        recent_blockhash,
+        instruction::set_apr(
+            &simple_token_pool::id(),
+            &pool_accounts.pool,
+            &manager.pubkey(),
+            &pool_accounts.bank,
+            apr_bps,
+        ),
+        &[manager],
+    )
+    .await
+}
+
+/// Creates a mock oracle price account holding the price
+pub async fn create_price_account(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: &Hash,
+    account: &Keypair,
+    price: PriceData,
+) -> Result<(), TransportError> {
+    let rent = banks_client.get_rent().await.unwrap();
+    let account_rent = rent.minimum_balance(PriceData::LEN);
+
+    let mut tx = Transaction::new_with_payer(
+        &[
+            system_instruction::create_account(
+                &payer.pubkey(),
+                &account.pubkey(),
+                account_rent,
+                PriceData::LEN as u64,
+                &mock_oracle::id(),
+            ),
+            mock_oracle::set_price(&account.pubkey(), price),
+        ],
+        Some(&payer.pubkey()),
+    );
+
+    tx.sign(&[payer, account], *recent_blockhash);
+    banks_client.process_transaction(tx).await?;
+
+    Ok(())
+}
+
+pub async fn set_price(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: &Hash,
+    account: &Pubkey,
+    price: PriceData,
+) -> Result<(), TransportError> {
+    process_instruction(
+        banks_client,
+        payer,
+        recent_blockhash,
+        mock_oracle::set_price(account, price),
+        &[],
+    )
+    .await
+}
+
+pub async fn set_oracle(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: &Hash,
+    pool_accounts: &PoolAccounts,
+    manager: &Keypair,
+    oracle: &Pubkey,
+    max_age: u64,
+) -> Result<(), TransportError> {
+    process_instruction(
+        banks_client,
+        payer,
+        recent_blockhash,
+        instruction::set_oracle(
+            &simple_token_pool::id(),
+            &pool_accounts.pool,
+            &manager.pubkey(),
+            &pool_accounts.pool_mint,
+            oracle,
+            max_age,
+        ),
+        &[manager],
+    )
+    .await
+}
+
+pub async fn initialize_reserve(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: &Hash,
+    pool_accounts: &PoolAccounts,
+    manager: &Keypair,
+    reserve_mint: &Pubkey,
+) -> Result<(), TransportError> {
+    let (authority, _) = find_authority_bump_seed(&simple_token_pool::id(), &pool_accounts.pool);
+    let (reserve, _) = find_reserve_address(&simple_token_pool::id(), &pool_accounts.pool);
+
+    process_instruction(
+        banks_client,
+        payer,
+        recent_blockhash,
+        instruction::initialize_reserve(
+            &simple_token_pool::id(),
+            &pool_accounts.pool,
+            &authority,
+            &manager.pubkey(),
+            reserve_mint,
+            &reserve,
+            &pool_accounts.pool_mint,
+            &payer.pubkey(),
+        ),
+        &[manager],
+    )
+    .await
+}
+
+pub async fn add_liquidity(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: &Hash,
+    pool_accounts: &PoolAccounts,
+    bank_source: &Pubkey,
+    reserve_source: &Pubkey,
+    recipient: &Pubkey,
+    bank_amount: u64,
+    reserve_amount: u64,
+    minimum_pool_amount: u64,
+) -> Result<(), TransportError> {
+    let (pool_authority, _) =
+        find_authority_bump_seed(&simple_token_pool::id(), &pool_accounts.pool);
+
+    process_instruction(
+        banks_client,
+        payer,
+        recent_blockhash,
+        instruction::add_liquidity(
+            &simple_token_pool::id(),
+            &pool_accounts.pool,
+            &pool_authority,
+            &pool_accounts.sender.pubkey(),
+            &pool_accounts.pool_mint,
+            &pool_accounts.bank,
+            &pool_accounts.reserve.unwrap(),
+            bank_source,
+            reserve_source,
+            recipient,
+            bank_amount,
+            reserve_amount,
+            minimum_pool_amount,
+        ),
+        &[&pool_accounts.sender],
+    )
+    .await
+}
+
+pub async fn remove_liquidity(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: &Hash,
+    pool_accounts: &PoolAccounts,
+    sender: &Pubkey,
+    bank_recipient: &Pubkey,
+    reserve_recipient: &Pubkey,
+    pool_amount: u64,
+    minimum_bank_amount: u64,
+    minimum_reserve_amount: u64,
+) -> Result<(), TransportError> {
+    let (pool_authority, _) =
+        find_authority_bump_seed(&simple_token_pool::id(), &pool_accounts.pool);
+
+    process_instruction(
+        banks_client,
+        payer,
+        recent_blockhash,
+        instruction::remove_liquidity(
+            &simple_token_pool::id(),
+            &pool_accounts.pool,
+            &pool_authority,
+            &pool_accounts.sender.pubkey(),
+            &pool_accounts.pool_mint,
+            &pool_accounts.bank,
+            &pool_accounts.reserve.unwrap(),
+            sender,
+            bank_recipient,
+            reserve_recipient,
+            pool_amount,
+            minimum_bank_amount,
+            minimum_reserve_amount,
+        ),
+        &[&pool_accounts.sender],
+    )
+    .await
+}
+
+pub async fn swap_reserves(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: &Hash,
+    pool_accounts: &PoolAccounts,
+    swap_source: &Pubkey,
+    swap_destination: &Pubkey,
+    source: &Pubkey,
+    destination: &Pubkey,
+    amount_in: u64,
+    minimum_amount_out: u64,
+) -> Result<(), TransportError> {
+    let (pool_authority, _) =
+        find_authority_bump_seed(&simple_token_pool::id(), &pool_accounts.pool);
+
+    process_instruction(
+        banks_client,
+        payer,
+        recent_blockhash,
+        instruction::swap_reserves(
+            &simple_token_pool::id(),
+            &pool_accounts.pool,
+            &pool_authority,
+            &pool_accounts.sender.pubkey(),
+            swap_source,
+            swap_destination,
+            source,
+            destination,
+            amount_in,
+            minimum_amount_out,
+        ),
+        &[&pool_accounts.sender],
+    )
+    .await
+}
+
+/// Borrows from the bank with the sample receiver, which pays back only if the
+/// borrower is given
+pub async fn flash_loan(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: &Hash,
+    pool_accounts: &PoolAccounts,
+    destination: &Pubkey,
+    borrower: Option<&Keypair>,
+    amount: u64,
+) -> Result<(), TransportError> {
+    let (pool_authority, _) =
+        find_authority_bump_seed(&simple_token_pool::id(), &pool_accounts.pool);
+    let receiver_accounts = borrower
+        .iter()
+        .map(|borrower| AccountMeta::new_readonly(borrower.pubkey(), true))
+        .collect();
+
+    process_instruction(
+        banks_client,
+        payer,
+        recent_blockhash,
+        instruction::flash_loan(
+            &simple_token_pool::id(),
+            &pool_accounts.pool,
+            &pool_authority,
+            &pool_accounts.bank,
+            destination,
+            &flash_loan_receiver::id(),
+            receiver_accounts,
+            amount,
+        ),
+        &borrower.into_iter().collect::<Vec<_>>(),
+    )
+    .await
+}
+
+pub async fn update_allowlist_root(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: &Hash,
+    pool_accounts: &PoolAccounts,
+    manager: &Keypair,
+    root: MerkleHash,
+) -> Result<(), TransportError> {
+    process_instruction(
+        banks_client,
+        payer,
+        recent_blockhash,
+        instruction::update_allowlist_root(
+            &simple_token_pool::id(),
+            &pool_accounts.pool,
+            &manager.pubkey(),
+            root,
+        ),
+        &[manager],
+    )
+    .await
+}
+
+pub async fn set_permit_key(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: &Hash,
+    pool_accounts: &PoolAccounts,
+    manager: &Keypair,
+    permit_key: &Pubkey,
+) -> Result<(), TransportError> {
+    process_instruction(
+        banks_client,
+        payer,
+        recent_blockhash,
+        instruction::set_permit_key(
+            &simple_token_pool::id(),
+            &pool_accounts.pool,
+            &manager.pubkey(),
+            permit_key,
+        ),
+        &[manager],
+    )
+    .await
+}
+
+/// Ed25519 instruction verifying the permit signed by the key
+pub fn permit_instruction(permit_key: &Keypair, permit: &Permit) -> Instruction {
+    let message = permit.message().unwrap();
+    let signature = permit_key.sign_message(&message);
+    permit::ed25519_instruction(&permit_key.pubkey(), signature.as_ref(), &message)
+}
+
+/// Swaps with the permit, verified by the Ed25519 instruction before the swap
+pub async fn swap_with_permit(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: &Hash,
+    sender: &Pubkey,
+    recipient: &Pubkey,
+    pool_accounts: &PoolAccounts,
+    amount: u64,
+    ed25519_instruction: Instruction,
+    permit: &Permit,
+) -> Result<(), TransportError> {
+    let (pool_authority, _) =
+        find_authority_bump_seed(&simple_token_pool::id(), &pool_accounts.pool);
+
+    let mut tx = Transaction::new_with_payer(
+        &[
+            ed25519_instruction,
+            instruction::swap_with_permit(
+                &simple_token_pool::id(),
+                &pool_accounts.pool,
+                &pool_authority,
+                &pool_accounts.sender.pubkey(),
+                &pool_accounts.pool_mint,
+                &pool_accounts.bank,
+                &pool_accounts.fee_receiver.pubkey(),
+                sender,
+                recipient,
+                pool_accounts.oracle.as_ref(),
+                &payer.pubkey(),
+                pool_accounts.deposit_record().as_ref(),
+                amount,
+                0,
+                permit,
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
 
     tx.sign(&[payer, &pool_accounts.sender], *recent_blockhash);
     banks_client.process_transaction(tx).await?;
 
     Ok(())
 }
+
+pub async fn set_deposit_limit(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: &Hash,
+    pool_accounts: &PoolAccounts,
+    manager: &Keypair,
+    deposit_limit: u64,
+    period: DepositPeriod,
+) -> Result<(), TransportError> {
+    process_instruction(
+        banks_client,
+        payer,
+        recent_blockhash,
+        instruction::set_deposit_limit(
+            &simple_token_pool::id(),
+            &pool_accounts.pool,
+            &manager.pubkey(),
+            deposit_limit,
+            period,
+        ),
+        &[manager],
+    )
+    .await
+}
+
+pub async fn close_deposit_record(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: &Hash,
+    pool_accounts: &PoolAccounts,
+    user: &Keypair,
+    destination: &Pubkey,
+) -> Result<(), TransportError> {
+    process_instruction(
+        banks_client,
+        payer,
+        recent_blockhash,
+        instruction::close_deposit_record(
+            &simple_token_pool::id(),
+            &pool_accounts.pool,
+            &user.pubkey(),
+            destination,
+        ),
+        &[user],
+    )
+    .await
+}
+
+pub async fn set_swap_limits(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: &Hash,
+    pool_accounts: &PoolAccounts,
+    manager: &Keypair,
+    supply_cap: u64,
+    min_amount_in: u64,
+    max_amount_in: u64,
+) -> Result<(), TransportError> {
+    process_instruction(
+        banks_client,
+        payer,
+        recent_blockhash,
+        instruction::set_swap_limits(
+            &simple_token_pool::id(),
+            &pool_accounts.pool,
+            &manager.pubkey(),
+            supply_cap,
+            min_amount_in,
+            max_amount_in,
+        ),
+        &[manager],
+    )
+    .await
+}
+
+pub async fn get_clock(banks_client: &mut BanksClient) -> Clock {
+    let clock_account = get_account(banks_client, &sysvar::clock::id()).await;
+    solana_sdk::account::from_account(&clock_account).unwrap()
+}
+
+pub async fn set_sale(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: &Hash,
+    pool_accounts: &PoolAccounts,
+    manager: &Keypair,
+    start: UnixTimestamp,
+    end: UnixTimestamp,
+    soft_cap_bps: u16,
+    hard_cap: u64,
+) -> Result<(), TransportError> {
+    process_instruction(
+        banks_client,
+        payer,
+        recent_blockhash,
+        instruction::set_sale(
+            &simple_token_pool::id(),
+            &pool_accounts.pool,
+            &manager.pubkey(),
+            &pool_accounts.pool_mint,
+            start,
+            end,
+            soft_cap_bps,
+            hard_cap,
+        ),
+        &[manager],
+    )
+    .await
+}
+
+pub async fn withdraw_proceeds(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: &Hash,
+    pool_accounts: &PoolAccounts,
+    manager: &Keypair,
+    destination: &Pubkey,
+) -> Result<(), TransportError> {
+    let (pool_authority, _) =
+        find_authority_bump_seed(&simple_token_pool::id(), &pool_accounts.pool);
+
+    process_instruction(
+        banks_client,
+        payer,
+        recent_blockhash,
+        instruction::withdraw_proceeds(
+            &simple_token_pool::id(),
+            &pool_accounts.pool,
+            &pool_authority,
+            &manager.pubkey(),
+            &pool_accounts.pool_mint,
+            &pool_accounts.bank,
+            destination,
+            &pool_accounts.fee_receiver.pubkey(),
+        ),
+        &[manager],
+    )
+    .await
+}
+
+pub async fn withdraw_rewards(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: &Hash,
+    pool_accounts: &PoolAccounts,
+    manager: &Keypair,
+    destination: &Pubkey,
+) -> Result<(), TransportError> {
+    let (pool_authority, _) =
+        find_authority_bump_seed(&simple_token_pool::id(), &pool_accounts.pool);
+
+    process_instruction(
+        banks_client,
+        payer,
+        recent_blockhash,
+        instruction::withdraw_rewards(
+            &simple_token_pool::id(),
+            &pool_accounts.pool,
+            &pool_authority,
+            &manager.pubkey(),
+            &pool_accounts.pool_mint,
+            &pool_accounts.bank,
+            destination,
+        ),
+        &[manager],
+    )
+    .await
+}
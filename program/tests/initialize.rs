@@ -2,12 +2,18 @@
 
 mod helpers;
 
-use borsh::BorshDeserialize;
 use helpers::*;
-use simple_token_pool::{id, state::PoolData};
-use solana_program::instruction::InstructionError;
+use simple_token_pool::{
+    curve::CurveType, error::PoolError, find_authority_bump_seed, find_pool_address, id,
+    state::PoolData,
+};
+use solana_program::{
+    hash::Hash, instruction::InstructionError, program_pack::Pack, pubkey::Pubkey, system_program,
+};
 use solana_program_test::*;
-use solana_sdk::{signer::Signer, transaction::TransactionError, transport::TransportError};
+use solana_sdk::{
+    signature::Keypair, signer::Signer, transaction::TransactionError, transport::TransportError,
+};
 
 #[tokio::test]
 async fn success() {
@@ -21,18 +27,32 @@ async fn success() {
         .await
         .unwrap();
 
-    let pool = get_account(&mut banks_client, &pool_accounts.pool.pubkey()).await;
-    let pool_data = PoolData::try_from_slice(&pool.data).unwrap();
+    let pool = get_account(&mut banks_client, &pool_accounts.pool).await;
+    let pool_data = PoolData::try_from_slice_unchecked(&pool.data).unwrap();
 
     assert_eq!(pool.owner, id());
-    assert_eq!(pool_data.bank, pool_accounts.bank.pubkey())
+    assert_eq!(pool.data.len(), PoolData::LEN);
+    assert_eq!(pool_data.bank, pool_accounts.bank);
+    assert_eq!(pool_data.pool_mint, pool_accounts.pool_mint);
+
+    // Pool mint and bank are created by the program and controlled by the authority
+    let (authority, _) = find_authority_bump_seed(&id(), &pool_accounts.pool);
+
+    let pool_mint = get_account(&mut banks_client, &pool_accounts.pool_mint).await;
+    let pool_mint = spl_token::state::Mint::unpack(&pool_mint.data).unwrap();
+    assert_eq!(pool_mint.mint_authority, Some(authority).into());
+
+    let bank = get_account(&mut banks_client, &pool_accounts.bank).await;
+    let bank = spl_token::state::Account::unpack(&bank.data).unwrap();
+    assert_eq!(bank.owner, authority);
+    assert_eq!(bank.mint, pool_accounts.bank_mint.pubkey());
 }
 
 #[tokio::test]
 async fn fail_double_initialize() {
     let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
 
-    let pool_accounts = PoolAccounts::new();
+    let mut pool_accounts = PoolAccounts::new();
     create_accounts(&mut banks_client, &payer, &recent_blockhash, &pool_accounts).await;
 
     pool_accounts
@@ -42,6 +62,9 @@ async fn fail_double_initialize() {
 
     let latest_blockhash = banks_client.get_recent_blockhash().await.unwrap();
 
+    // Pool accounts are derived, so change the fee to send a different transaction
+    pool_accounts.fee_bps += 1;
+
     let tx_error = pool_accounts
         .initialize(&mut banks_client, &payer, &latest_blockhash)
         .await
@@ -72,18 +95,6 @@ async fn fail_with_wrong_mint() {
     .await
     .unwrap();
 
-    // Pool accounts
-    create_pool_accounts(
-        &mut banks_client,
-        &payer,
-        &recent_blockhash,
-        &pool_accounts.pool,
-        &pool_accounts.pool_mint,
-        &pool_accounts.bank,
-    )
-    .await
-    .unwrap();
-
     let tx_error = pool_accounts
         .initialize(&mut banks_client, &payer, &recent_blockhash)
         .await
@@ -97,3 +108,246 @@ async fn fail_with_wrong_mint() {
         _ => panic!("Wrong error"),
     }
 }
+
+#[tokio::test]
+async fn fail_with_zero_rate() {
+    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+
+    let mut pool_accounts = PoolAccounts::new();
+    pool_accounts.rate_denominator = 0;
+    create_accounts(&mut banks_client, &payer, &recent_blockhash, &pool_accounts).await;
+
+    let tx_error = pool_accounts
+        .initialize(&mut banks_client, &payer, &recent_blockhash)
+        .await
+        .err()
+        .unwrap();
+
+    match tx_error {
+        TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(code),
+        )) => {
+            assert_eq!(code, PoolError::InvalidRate as u32);
+        }
+        _ => panic!("Wrong error"),
+    }
+}
+
+#[tokio::test]
+async fn fail_with_excessive_fee() {
+    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+
+    let mut pool_accounts = PoolAccounts::new();
+    pool_accounts.fee_bps = 10_000;
+    create_accounts(&mut banks_client, &payer, &recent_blockhash, &pool_accounts).await;
+
+    let tx_error = pool_accounts
+        .initialize(&mut banks_client, &payer, &recent_blockhash)
+        .await
+        .err()
+        .unwrap();
+
+    match tx_error {
+        TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(code),
+        )) => {
+            assert_eq!(code, PoolError::InvalidFee as u32);
+        }
+        _ => panic!("Wrong error"),
+    }
+}
+
+#[tokio::test]
+async fn fail_with_invalid_curve() {
+    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+
+    let mut pool_accounts = PoolAccounts::new();
+    pool_accounts.curve = CurveType::Step {
+        base_price: CurveType::PRICE_SCALE,
+        step_size: 0,
+        increment: 1,
+    };
+    create_accounts(&mut banks_client, &payer, &recent_blockhash, &pool_accounts).await;
+
+    let tx_error = pool_accounts
+        .initialize(&mut banks_client, &payer, &recent_blockhash)
+        .await
+        .err()
+        .unwrap();
+
+    match tx_error {
+        TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(code),
+        )) => {
+            assert_eq!(code, PoolError::InvalidCurve as u32);
+        }
+        _ => panic!("Wrong error"),
+    }
+}
+
+async fn initialize_with_substitution(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: &Hash,
+    pool_accounts: &PoolAccounts,
+    from: &Pubkey,
+    to: &Pubkey,
+) -> TransportError {
+    let mut instruction = pool_accounts.initialize_instruction(&payer.pubkey());
+    substitute_account(&mut instruction, from, to);
+
+    process_instruction(
+        banks_client,
+        payer,
+        recent_blockhash,
+        instruction,
+        &[&pool_accounts.owner],
+    )
+    .await
+    .err()
+    .unwrap()
+}
+
+#[tokio::test]
+async fn fail_with_wrong_authority() {
+    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+
+    let pool_accounts = PoolAccounts::new();
+    create_accounts(&mut banks_client, &payer, &recent_blockhash, &pool_accounts).await;
+    let (authority, _) = find_authority_bump_seed(&id(), &pool_accounts.pool);
+
+    let tx_error = initialize_with_substitution(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &authority,
+        &payer.pubkey(),
+    )
+    .await;
+
+    match tx_error {
+        TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(code),
+        )) => {
+            assert_eq!(code, PoolError::InvalidAuthority as u32);
+        }
+        _ => panic!("Wrong error"),
+    }
+}
+
+#[tokio::test]
+async fn fail_with_wrong_token_program() {
+    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+
+    let pool_accounts = PoolAccounts::new();
+    create_accounts(&mut banks_client, &payer, &recent_blockhash, &pool_accounts).await;
+
+    let tx_error = initialize_with_substitution(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &spl_token::id(),
+        &system_program::id(),
+    )
+    .await;
+
+    match tx_error {
+        TransportError::TransactionError(TransactionError::InstructionError(_, error)) => {
+            assert_eq!(error, InstructionError::IncorrectProgramId);
+        }
+        _ => panic!("Wrong error"),
+    }
+}
+
+#[tokio::test]
+async fn fail_with_wrong_pool_mint() {
+    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+
+    let pool_accounts = PoolAccounts::new();
+    create_accounts(&mut banks_client, &payer, &recent_blockhash, &pool_accounts).await;
+
+    let tx_error = initialize_with_substitution(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.pool_mint,
+        &Keypair::new().pubkey(),
+    )
+    .await;
+
+    match tx_error {
+        TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(code),
+        )) => {
+            assert_eq!(code, PoolError::InvalidPoolMint as u32);
+        }
+        _ => panic!("Wrong error"),
+    }
+}
+
+#[tokio::test]
+async fn fail_with_wrong_bank() {
+    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+
+    let pool_accounts = PoolAccounts::new();
+    create_accounts(&mut banks_client, &payer, &recent_blockhash, &pool_accounts).await;
+
+    let tx_error = initialize_with_substitution(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.bank,
+        &Keypair::new().pubkey(),
+    )
+    .await;
+
+    match tx_error {
+        TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(code),
+        )) => {
+            assert_eq!(code, PoolError::InvalidBank as u32);
+        }
+        _ => panic!("Wrong error"),
+    }
+}
+
+#[tokio::test]
+async fn fail_with_wrong_pool_address() {
+    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+
+    let pool_accounts = PoolAccounts::new();
+    create_accounts(&mut banks_client, &payer, &recent_blockhash, &pool_accounts).await;
+
+    // Address of the pool with another index
+    let (pool, _) = find_pool_address(&id(), &pool_accounts.bank_mint.pubkey(), 1);
+
+    let tx_error = initialize_with_substitution(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.pool,
+        &pool,
+    )
+    .await;
+
+    match tx_error {
+        TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(code),
+        )) => {
+            assert_eq!(code, PoolError::InvalidPoolAddress as u32);
+        }
+        _ => panic!("Wrong error"),
+    }
+}
@@ -0,0 +1,506 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::*;
+
+use simple_token_pool::{
+    error::PoolError, id, instruction, math::compound_interest, state::PoolData,
+};
+use solana_program::{hash::Hash, instruction::InstructionError, pubkey::Pubkey, sysvar};
+use solana_program_test::*;
+use solana_sdk::{
+    signature::Keypair, signer::Signer, transaction::TransactionError, transport::TransportError,
+};
+
+const SENDER_MINT_AMOUNT: u64 = 2_000_000;
+const DEPOSIT_AMOUNT: u64 = 1_000_000;
+const REWARDS_AMOUNT: u64 = 200_000;
+
+/// Pool with the manager rewards account and a user holding bank tokens
+struct Setup {
+    context: ProgramTestContext,
+    pool_accounts: PoolAccounts,
+    rewards: Pubkey,
+    bank_token: Pubkey,
+    pool_token: Pubkey,
+}
+
+async fn setup() -> Setup {
+    let mut context = program_test().start_with_context().await;
+    let payer = &context.payer;
+    let banks_client = &mut context.banks_client;
+    let recent_blockhash = context.last_blockhash;
+
+    let pool_accounts = PoolAccounts::new();
+    create_accounts(banks_client, payer, &recent_blockhash, &pool_accounts).await;
+    pool_accounts
+        .initialize(banks_client, payer, &recent_blockhash)
+        .await
+        .unwrap();
+
+    let rewards = Keypair::new();
+    let bank_token = Keypair::new();
+    let pool_token = Keypair::new();
+    for (account, mint, owner) in &[
+        (
+            &rewards,
+            pool_accounts.bank_mint.pubkey(),
+            pool_accounts.owner.pubkey(),
+        ),
+        (
+            &bank_token,
+            pool_accounts.bank_mint.pubkey(),
+            pool_accounts.sender.pubkey(),
+        ),
+        (
+            &pool_token,
+            pool_accounts.pool_mint,
+            pool_accounts.sender.pubkey(),
+        ),
+    ] {
+        create_token_account(banks_client, payer, &recent_blockhash, account, mint, owner)
+            .await
+            .unwrap();
+    }
+
+    for account in &[&rewards, &bank_token] {
+        mint_tokens(
+            banks_client,
+            payer,
+            &recent_blockhash,
+            &pool_accounts.bank_mint.pubkey(),
+            &account.pubkey(),
+            &pool_accounts.owner,
+            SENDER_MINT_AMOUNT,
+        )
+        .await
+        .unwrap();
+    }
+
+    Setup {
+        context,
+        pool_accounts,
+        rewards: rewards.pubkey(),
+        bank_token: bank_token.pubkey(),
+        pool_token: pool_token.pubkey(),
+    }
+}
+
+/// Swaps half of the user tokens and funds `REWARDS_AMOUNT` of interest at the annual rate
+async fn fund_interest(setup: &mut Setup, apr_bps: u16) {
+    let context = &mut setup.context;
+    let pool_accounts = &setup.pool_accounts;
+
+    swap(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &setup.bank_token,
+        &setup.pool_token,
+        pool_accounts,
+        DEPOSIT_AMOUNT,
+    )
+    .await
+    .unwrap();
+
+    set_apr(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        pool_accounts,
+        &pool_accounts.owner,
+        apr_bps,
+    )
+    .await
+    .unwrap();
+
+    deposit_rewards(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        pool_accounts,
+        &pool_accounts.owner,
+        &setup.rewards,
+        REWARDS_AMOUNT,
+    )
+    .await
+    .unwrap();
+}
+
+/// Warps forward and returns a blockhash of the new slot
+async fn warp_slots(context: &mut ProgramTestContext, pool_data: &PoolData, slots: u64) -> Hash {
+    context
+        .warp_to_slot(pool_data.last_update_slot + slots)
+        .unwrap();
+    context.banks_client.get_recent_blockhash().await.unwrap()
+}
+
+/// Interest expected on the distributed bank tokens between the two updates
+fn expected_interest(before: &PoolData, after: &PoolData, distributed: u64) -> u64 {
+    compound_interest(
+        distributed,
+        before.apr_bps as u64,
+        PoolData::BPS_DENOMINATOR * PoolData::SLOTS_PER_YEAR,
+        after.last_update_slot - before.last_update_slot,
+    )
+    .unwrap()
+}
+
+#[tokio::test]
+async fn success() {
+    let mut setup = setup().await;
+    fund_interest(&mut setup, 1000).await;
+    let Setup {
+        mut context,
+        pool_accounts,
+        bank_token,
+        pool_token,
+        ..
+    } = setup;
+
+    // Rewards wait in the bank until they are accrued
+    let before = get_pool_data(&mut context.banks_client, &pool_accounts).await;
+    assert_eq!(before.apr_bps, 1000);
+    assert_eq!(before.undistributed_rewards, REWARDS_AMOUNT);
+
+    let recent_blockhash = warp_slots(&mut context, &before, PoolData::SLOTS_PER_YEAR).await;
+
+    // Anyone can bring the rate up to date
+    refresh(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        &pool_accounts,
+    )
+    .await
+    .unwrap();
+
+    let after = get_pool_data(&mut context.banks_client, &pool_accounts).await;
+    assert_eq!(
+        after.last_update_slot,
+        before.last_update_slot + PoolData::SLOTS_PER_YEAR
+    );
+    let interest = expected_interest(&before, &after, DEPOSIT_AMOUNT);
+    assert_eq!(after.undistributed_rewards, REWARDS_AMOUNT - interest);
+
+    // 10% compounded every slot for a year is e^0.1 - 1
+    let closed_form = DEPOSIT_AMOUNT as f64 * (0.1f64.exp() - 1.0);
+    assert!((interest as f64 - closed_form).abs() < 2.0);
+
+    // A quarter of the pool tokens take a quarter of the interest: 250000 * 1105171 / 1000001
+    let distributed = DEPOSIT_AMOUNT + interest;
+    redeem(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        &pool_token,
+        &bank_token,
+        &pool_accounts,
+        DEPOSIT_AMOUNT / 4,
+    )
+    .await
+    .unwrap();
+    let first_redeemed = DEPOSIT_AMOUNT / 4 * (distributed + 1) / (DEPOSIT_AMOUNT + 1);
+    assert_eq!(
+        get_token_balance(&mut context.banks_client, &bank_token).await,
+        SENDER_MINT_AMOUNT - DEPOSIT_AMOUNT + first_redeemed
+    );
+
+    // The last pool tokens leave the undistributed rewards and the share of the virtual tokens
+    let remaining = distributed - first_redeemed;
+    let last_amount = DEPOSIT_AMOUNT - DEPOSIT_AMOUNT / 4;
+    let last_redeemed = last_amount * (remaining + 1) / (last_amount + 1);
+    assert!(remaining - last_redeemed <= 1);
+    redeem(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        &pool_token,
+        &bank_token,
+        &pool_accounts,
+        DEPOSIT_AMOUNT - DEPOSIT_AMOUNT / 4,
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        get_token_balance(&mut context.banks_client, &bank_token).await,
+        SENDER_MINT_AMOUNT - DEPOSIT_AMOUNT + first_redeemed + last_redeemed
+    );
+    assert_eq!(
+        get_token_balance(&mut context.banks_client, &pool_accounts.bank).await,
+        REWARDS_AMOUNT - interest + remaining - last_redeemed
+    );
+    let pool_data = get_pool_data(&mut context.banks_client, &pool_accounts).await;
+    assert_eq!(pool_data.undistributed_rewards, REWARDS_AMOUNT - interest);
+}
+
+#[tokio::test]
+async fn swap_accrues_interest() {
+    let mut setup = setup().await;
+    fund_interest(&mut setup, 500).await;
+    let Setup {
+        mut context,
+        pool_accounts,
+        bank_token,
+        pool_token,
+        ..
+    } = setup;
+
+    let before = get_pool_data(&mut context.banks_client, &pool_accounts).await;
+    let recent_blockhash = warp_slots(&mut context, &before, 1_000_000).await;
+
+    // Pool tokens are worth more, so the second swap mints fewer of them
+    swap(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        &bank_token,
+        &pool_token,
+        &pool_accounts,
+        100_000,
+    )
+    .await
+    .unwrap();
+
+    let after = get_pool_data(&mut context.banks_client, &pool_accounts).await;
+    assert_eq!(after.last_update_slot, before.last_update_slot + 1_000_000);
+    let interest = expected_interest(&before, &after, DEPOSIT_AMOUNT);
+    assert!(interest > 0);
+    assert_eq!(after.undistributed_rewards, REWARDS_AMOUNT - interest);
+
+    // 100000 * 1000001 / (1000001 + interest)
+    assert_eq!(
+        get_token_balance(&mut context.banks_client, &pool_token).await,
+        DEPOSIT_AMOUNT + 100_000 * (DEPOSIT_AMOUNT + 1) / (DEPOSIT_AMOUNT + interest + 1)
+    );
+}
+
+#[tokio::test]
+async fn interest_stops_when_rewards_run_out() {
+    let mut setup = setup().await;
+    fund_interest(&mut setup, 10_000).await;
+    let Setup {
+        mut context,
+        pool_accounts,
+        ..
+    } = setup;
+
+    // A year at 100% is far more than the rewards
+    let before = get_pool_data(&mut context.banks_client, &pool_accounts).await;
+    let recent_blockhash = warp_slots(&mut context, &before, PoolData::SLOTS_PER_YEAR).await;
+    refresh(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        &pool_accounts,
+    )
+    .await
+    .unwrap();
+
+    let after = get_pool_data(&mut context.banks_client, &pool_accounts).await;
+    assert_eq!(after.undistributed_rewards, 0);
+    assert_eq!(
+        get_token_balance(&mut context.banks_client, &pool_accounts.bank).await,
+        DEPOSIT_AMOUNT + REWARDS_AMOUNT
+    );
+}
+
+#[tokio::test]
+async fn set_apr_accrues_at_old_rate() {
+    let mut setup = setup().await;
+    fund_interest(&mut setup, 1000).await;
+    let Setup {
+        mut context,
+        pool_accounts,
+        ..
+    } = setup;
+
+    let before = get_pool_data(&mut context.banks_client, &pool_accounts).await;
+    let recent_blockhash = warp_slots(&mut context, &before, 1_000_000).await;
+
+    // Zero rate stops the interest, the rewards stay undistributed
+    set_apr(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.owner,
+        0,
+    )
+    .await
+    .unwrap();
+
+    let after = get_pool_data(&mut context.banks_client, &pool_accounts).await;
+    let interest = expected_interest(&before, &after, DEPOSIT_AMOUNT);
+    assert!(interest > 0);
+    assert_eq!(after.apr_bps, 0);
+    assert_eq!(after.undistributed_rewards, REWARDS_AMOUNT - interest);
+
+    let recent_blockhash = warp_slots(&mut context, &after, 1_000_000).await;
+    refresh(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        &pool_accounts,
+    )
+    .await
+    .unwrap();
+
+    let pool_data = get_pool_data(&mut context.banks_client, &pool_accounts).await;
+    assert_eq!(pool_data.undistributed_rewards, after.undistributed_rewards);
+}
+
+#[tokio::test]
+async fn withdraw_rewards_once_apr_stopped() {
+    let mut setup = setup().await;
+    fund_interest(&mut setup, 1000).await;
+    let Setup {
+        mut context,
+        pool_accounts,
+        rewards,
+        bank_token,
+        pool_token,
+    } = setup;
+
+    let before = get_pool_data(&mut context.banks_client, &pool_accounts).await;
+    let recent_blockhash = warp_slots(&mut context, &before, 1_000_000).await;
+
+    // Rewards still owed to the pool token holders stay in the bank
+    let error = withdraw_rewards(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.owner,
+        &rewards,
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_pool_error(error, PoolError::RewardsAccruing);
+
+    set_apr(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.owner,
+        0,
+    )
+    .await
+    .unwrap();
+
+    // Interest stopped, so the slots after don't change the rewards
+    let stopped = get_pool_data(&mut context.banks_client, &pool_accounts).await;
+    let recent_blockhash = warp_slots(&mut context, &stopped, 1_000).await;
+
+    let error = withdraw_rewards(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.sender,
+        &bank_token,
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_pool_error(error, PoolError::InvalidManager);
+
+    withdraw_rewards(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.owner,
+        &rewards,
+    )
+    .await
+    .unwrap();
+
+    let after = get_pool_data(&mut context.banks_client, &pool_accounts).await;
+    let interest = expected_interest(&before, &stopped, DEPOSIT_AMOUNT);
+    assert!(interest > 0);
+    assert_eq!(after.undistributed_rewards, 0);
+    assert_eq!(
+        get_token_balance(&mut context.banks_client, &rewards).await,
+        SENDER_MINT_AMOUNT - interest
+    );
+
+    // The accrued interest stays with the pool tokens, less the share of the virtual tokens
+    redeem(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        &pool_token,
+        &bank_token,
+        &pool_accounts,
+        DEPOSIT_AMOUNT,
+    )
+    .await
+    .unwrap();
+    let redeemed = DEPOSIT_AMOUNT * (DEPOSIT_AMOUNT + interest + 1) / (DEPOSIT_AMOUNT + 1);
+    assert_eq!(
+        get_token_balance(&mut context.banks_client, &bank_token).await,
+        SENDER_MINT_AMOUNT - DEPOSIT_AMOUNT + redeemed
+    );
+    assert_eq!(
+        get_token_balance(&mut context.banks_client, &pool_accounts.bank).await,
+        DEPOSIT_AMOUNT + interest - redeemed
+    );
+}
+
+#[tokio::test]
+async fn fail_set_apr_with_wrong_manager() {
+    let Setup {
+        mut context,
+        pool_accounts,
+        ..
+    } = setup().await;
+
+    let error = set_apr(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &pool_accounts,
+        &Keypair::new(),
+        1000,
+    )
+    .await
+    .err()
+    .unwrap();
+
+    assert_pool_error(error, PoolError::InvalidManager);
+}
+
+#[tokio::test]
+async fn fail_refresh_with_wrong_clock() {
+    let Setup {
+        mut context,
+        pool_accounts,
+        ..
+    } = setup().await;
+
+    let mut instruction = instruction::refresh(&id(), &pool_accounts.pool, &pool_accounts.bank);
+    substitute_account(&mut instruction, &sysvar::clock::id(), &sysvar::rent::id());
+
+    let error = process_instruction(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        instruction,
+        &[],
+    )
+    .await
+    .err()
+    .unwrap();
+
+    match error {
+        TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::InvalidArgument,
+        )) => {}
+        _ => panic!("Wrong error"),
+    }
+}
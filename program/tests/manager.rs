@@ -0,0 +1,161 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::*;
+use simple_token_pool::{error::PoolError, state::PoolData};
+use solana_program::{hash::Hash, instruction::InstructionError, pubkey::Pubkey};
+use solana_program_test::*;
+use solana_sdk::{
+    signature::Keypair, signer::Signer, transaction::TransactionError, transport::TransportError,
+};
+
+async fn setup() -> (BanksClient, Keypair, Hash, PoolAccounts) {
+    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+    let pool_accounts = PoolAccounts::new();
+    create_accounts(&mut banks_client, &payer, &recent_blockhash, &pool_accounts).await;
+
+    pool_accounts
+        .initialize(&mut banks_client, &payer, &recent_blockhash)
+        .await
+        .unwrap();
+
+    (banks_client, payer, recent_blockhash, pool_accounts)
+}
+
+async fn get_pool_data(banks_client: &mut BanksClient, pool_accounts: &PoolAccounts) -> PoolData {
+    let pool = get_account(banks_client, &pool_accounts.pool).await;
+    PoolData::try_from_slice_unchecked(&pool.data).unwrap()
+}
+
+#[tokio::test]
+async fn success() {
+    let (mut banks_client, payer, recent_blockhash, pool_accounts) = setup().await;
+    let new_manager = Keypair::new();
+
+    let pool_data = get_pool_data(&mut banks_client, &pool_accounts).await;
+    assert_eq!(pool_data.manager, pool_accounts.owner.pubkey());
+
+    propose_manager(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.owner,
+        &new_manager.pubkey(),
+    )
+    .await
+    .unwrap();
+
+    // Current manager keeps the role until the proposal is accepted
+    let pool_data = get_pool_data(&mut banks_client, &pool_accounts).await;
+    assert_eq!(pool_data.manager, pool_accounts.owner.pubkey());
+    assert_eq!(pool_data.pending_manager, new_manager.pubkey());
+
+    accept_manager(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &new_manager,
+    )
+    .await
+    .unwrap();
+
+    let pool_data = get_pool_data(&mut banks_client, &pool_accounts).await;
+    assert_eq!(pool_data.manager, new_manager.pubkey());
+    assert_eq!(pool_data.pending_manager, Pubkey::default());
+}
+
+#[tokio::test]
+async fn fail_propose_with_wrong_manager() {
+    let (mut banks_client, payer, recent_blockhash, pool_accounts) = setup().await;
+    let fake_manager = Keypair::new();
+
+    let tx_error = propose_manager(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &fake_manager,
+        &fake_manager.pubkey(),
+    )
+    .await
+    .err()
+    .unwrap();
+
+    match tx_error {
+        TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(code),
+        )) => {
+            assert_eq!(code, PoolError::InvalidManager as u32);
+        }
+        _ => panic!("Wrong error"),
+    }
+}
+
+#[tokio::test]
+async fn fail_accept_with_wrong_pending_manager() {
+    let (mut banks_client, payer, recent_blockhash, pool_accounts) = setup().await;
+    let new_manager = Keypair::new();
+    let fake_manager = Keypair::new();
+
+    propose_manager(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.owner,
+        &new_manager.pubkey(),
+    )
+    .await
+    .unwrap();
+
+    let tx_error = accept_manager(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &fake_manager,
+    )
+    .await
+    .err()
+    .unwrap();
+
+    match tx_error {
+        TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(code),
+        )) => {
+            assert_eq!(code, PoolError::InvalidPendingManager as u32);
+        }
+        _ => panic!("Wrong error"),
+    }
+}
+
+#[tokio::test]
+async fn fail_accept_without_proposal() {
+    let (mut banks_client, payer, recent_blockhash, pool_accounts) = setup().await;
+
+    let tx_error = accept_manager(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.owner,
+    )
+    .await
+    .err()
+    .unwrap();
+
+    match tx_error {
+        TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(code),
+        )) => {
+            assert_eq!(code, PoolError::InvalidPendingManager as u32);
+        }
+        _ => panic!("Wrong error"),
+    }
+}
@@ -0,0 +1,208 @@
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use simple_token_pool::{
+    error::PoolError,
+    math::{checked_add, checked_sub, compound_interest, mul_div, Rounding},
+};
+
+const ITERATIONS: usize = 100_000;
+
+/// Values at the edges of the u64 range, mixed into the random samples
+const EDGES: [u64; 8] = [
+    0,
+    1,
+    2,
+    u32::MAX as u64,
+    u32::MAX as u64 + 1,
+    u64::MAX / 2,
+    u64::MAX - 1,
+    u64::MAX,
+];
+
+fn sample(rng: &mut StdRng) -> u64 {
+    match rng.gen_range(0, 4) {
+        0 => EDGES[rng.gen_range(0, EDGES.len())],
+        // Small values give non-trivial remainders more often
+        1 => rng.gen_range(0, 10_000),
+        2 => rng.gen::<u32>() as u64,
+        _ => rng.gen(),
+    }
+}
+
+fn check_mul_div(value: u64, numerator: u64, denominator: u64) {
+    let down = mul_div(value, numerator, denominator, Rounding::Down);
+    let up = mul_div(value, numerator, denominator, Rounding::Up);
+
+    if denominator == 0 {
+        assert_eq!(down, Err(PoolError::MathOverflow));
+        assert_eq!(up, Err(PoolError::MathOverflow));
+        return;
+    }
+
+    let product = value as u128 * numerator as u128;
+    let denominator = denominator as u128;
+    let exact = product % denominator == 0;
+
+    match &down {
+        Ok(down) => {
+            // down * denominator <= product < (down + 1) * denominator
+            let down = *down as u128;
+            assert!(down * denominator <= product);
+            assert!(product < (down + 1) * denominator);
+        }
+        Err(error) => {
+            assert_eq!(*error, PoolError::MathOverflow);
+            assert!(product / denominator > u64::MAX as u128);
+        }
+    }
+
+    match up {
+        Ok(up) => {
+            // (up - 1) * denominator < product <= up * denominator
+            let up = up as u128;
+            assert!(product <= up * denominator);
+            if up > 0 {
+                assert!((up - 1) * denominator < product);
+            }
+            if let Ok(down) = down {
+                let expected = if exact {
+                    down as u128
+                } else {
+                    down as u128 + 1
+                };
+                assert_eq!(up, expected);
+            }
+        }
+        Err(error) => {
+            assert_eq!(error, PoolError::MathOverflow);
+            let quotient = product / denominator + if exact { 0 } else { 1 };
+            assert!(quotient > u64::MAX as u128);
+        }
+    }
+}
+
+#[test]
+fn mul_div_rounding_properties() {
+    let mut rng = StdRng::seed_from_u64(0);
+    for _ in 0..ITERATIONS {
+        let value = sample(&mut rng);
+        let numerator = sample(&mut rng);
+        let denominator = sample(&mut rng);
+        check_mul_div(value, numerator, denominator);
+    }
+}
+
+#[test]
+fn mul_div_edges() {
+    for &value in EDGES.iter() {
+        for &numerator in EDGES.iter() {
+            for &denominator in EDGES.iter() {
+                check_mul_div(value, numerator, denominator);
+            }
+        }
+    }
+}
+
+#[test]
+fn mul_div_identity() {
+    let mut rng = StdRng::seed_from_u64(1);
+    for _ in 0..ITERATIONS {
+        let value = sample(&mut rng);
+        let rate = sample(&mut rng).max(1);
+        assert_eq!(mul_div(value, rate, rate, Rounding::Down), Ok(value));
+        assert_eq!(mul_div(value, rate, rate, Rounding::Up), Ok(value));
+    }
+}
+
+#[test]
+fn mul_div_examples() {
+    assert_eq!(mul_div(3, 3, 2, Rounding::Down), Ok(4));
+    assert_eq!(mul_div(3, 3, 2, Rounding::Up), Ok(5));
+    assert_eq!(mul_div(150, 100, 10_000, Rounding::Up), Ok(2));
+    assert_eq!(mul_div(1, 100, 10_000, Rounding::Down), Ok(0));
+    assert_eq!(
+        mul_div(u64::MAX, u64::MAX, u64::MAX, Rounding::Up),
+        Ok(u64::MAX)
+    );
+    assert_eq!(
+        mul_div(u64::MAX, 2, 1, Rounding::Down),
+        Err(PoolError::MathOverflow)
+    );
+}
+
+#[test]
+fn add_sub_properties() {
+    let mut rng = StdRng::seed_from_u64(2);
+    for _ in 0..ITERATIONS {
+        let a = sample(&mut rng);
+        let b = sample(&mut rng);
+
+        match checked_add(a, b) {
+            Ok(sum) => assert_eq!(sum as u128, a as u128 + b as u128),
+            Err(error) => {
+                assert_eq!(error, PoolError::MathOverflow);
+                assert!(a as u128 + b as u128 > u64::MAX as u128);
+            }
+        }
+
+        match checked_sub(a, b) {
+            Ok(difference) => assert_eq!(difference + b, a),
+            Err(error) => {
+                assert_eq!(error, PoolError::MathOverflow);
+                assert!(a < b);
+            }
+        }
+    }
+}
+
+#[test]
+fn compound_interest_examples() {
+    assert_eq!(compound_interest(1000, 1, 10, 0), Ok(0));
+    assert_eq!(compound_interest(1000, 0, 10, 100), Ok(0));
+    assert_eq!(compound_interest(1000, 1, 10, 1), Ok(100));
+    // 1000 * (1.1^2 - 1)
+    assert_eq!(compound_interest(1000, 1, 10, 2), Ok(210));
+    // 1000 * (1.1^3 - 1) = 331
+    assert_eq!(compound_interest(1000, 1, 10, 3), Ok(331));
+    assert_eq!(
+        compound_interest(1000, 1, 0, 1),
+        Err(PoolError::MathOverflow)
+    );
+
+    // Doubling every period for long enough can't be represented
+    assert_eq!(
+        compound_interest(1, 1, 1, 200),
+        Err(PoolError::MathOverflow)
+    );
+}
+
+#[test]
+fn compound_interest_matches_closed_form() {
+    let mut rng = StdRng::seed_from_u64(4);
+    for _ in 0..ITERATIONS {
+        let value = rng.gen_range(0, 1_000_000_000_000);
+        let rate_numerator = rng.gen_range(0, 50_000);
+        let rate_denominator = rng.gen_range(1, 1_000_000_000_000);
+        let periods = rng.gen_range(0, 100_000_000);
+
+        let rate = rate_numerator as f64 / rate_denominator as f64;
+        let expected = value as f64 * ((periods as f64 * rate.ln_1p()).exp() - 1.0);
+        let interest = match compound_interest(value, rate_numerator, rate_denominator, periods) {
+            Ok(interest) => interest,
+            Err(_) => {
+                // Only interest too large for u64 fails
+                let growth_ln = periods as f64 * rate.ln_1p();
+                assert!(growth_ln + (value as f64).ln() > 44.0);
+                continue;
+            }
+        };
+
+        // The rate is truncated to 18 decimals and every step is rounded down
+        let tolerance = expected * 1e-5 + 1.0;
+        assert!(
+            (interest as f64) <= expected + tolerance && (interest as f64) >= expected - tolerance,
+            "interest {}, expected {}",
+            interest,
+            expected
+        );
+    }
+}
@@ -0,0 +1,100 @@
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use simple_token_pool::merkle::{self, MerkleTree};
+use solana_program::pubkey::Pubkey;
+
+const ITERATIONS: usize = 200;
+
+fn random_users(rng: &mut StdRng, count: usize) -> Vec<Pubkey> {
+    (0..count)
+        .map(|_| Pubkey::new(&rng.gen::<[u8; 32]>()))
+        .collect()
+}
+
+#[test]
+fn every_user_has_a_valid_proof() {
+    let mut rng = StdRng::seed_from_u64(0);
+    for _ in 0..ITERATIONS {
+        let count = rng.gen_range(1, 100);
+        let users = random_users(&mut rng, count);
+        let tree = MerkleTree::new(&users);
+        let root = tree.root();
+
+        for user in &users {
+            let proof = tree.proof(user).unwrap();
+            // Odd levels move nodes up without a sibling
+            assert!(proof.len() <= 7);
+            assert!(merkle::verify(&root, user, &proof), "{} of {}", user, count);
+        }
+    }
+}
+
+#[test]
+fn single_user_is_the_root() {
+    let user = Pubkey::new_unique();
+    let tree = MerkleTree::new(&[user]);
+
+    assert_eq!(tree.root(), merkle::leaf_hash(&user));
+    assert_eq!(tree.proof(&user), Some(vec![]));
+    assert!(merkle::verify(&tree.root(), &user, &[]));
+}
+
+#[test]
+fn empty_tree_has_the_zero_root() {
+    let tree = MerkleTree::new(&[]);
+
+    assert_eq!(tree.root(), [0; 32]);
+    assert_eq!(tree.proof(&Pubkey::new_unique()), None);
+}
+
+#[test]
+fn forged_proofs_fail() {
+    let mut rng = StdRng::seed_from_u64(1);
+    for _ in 0..ITERATIONS {
+        let count = rng.gen_range(2, 100);
+        let users = random_users(&mut rng, count);
+        let tree = MerkleTree::new(&users);
+        let root = tree.root();
+        let outsider = Pubkey::new(&rng.gen::<[u8; 32]>());
+        let member = users[rng.gen_range(0, count)];
+        let proof = tree.proof(&member).unwrap();
+
+        assert_eq!(tree.proof(&outsider), None);
+
+        // Someone else's proof
+        assert!(!merkle::verify(&root, &outsider, &proof));
+
+        // Any changed byte of the proof
+        let mut forged = proof.clone();
+        let index = rng.gen_range(0, forged.len());
+        forged[index][rng.gen_range(0, 32)] ^= 1 << rng.gen_range(0, 8);
+        assert!(!merkle::verify(&root, &member, &forged));
+
+        // A prefix of the proof, ending at an inner node
+        assert!(!merkle::verify(&root, &member, &proof[..proof.len() - 1]));
+    }
+}
+
+#[test]
+fn inner_nodes_are_not_users() {
+    let users = [
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+    ];
+    let tree = MerkleTree::new(&users);
+    let root = tree.root();
+
+    // Claiming the hash of the first pair as a user with the proof of the second pair
+    let first_pair = Pubkey::new(&merkle::node_hash(
+        &merkle::leaf_hash(&users[0]),
+        &merkle::leaf_hash(&users[1]),
+    ));
+    let second_pair =
+        merkle::node_hash(&merkle::leaf_hash(&users[2]), &merkle::leaf_hash(&users[3]));
+    assert_eq!(
+        root,
+        merkle::node_hash(&first_pair.to_bytes(), &second_pair)
+    );
+    assert!(!merkle::verify(&root, &first_pair, &[second_pair]));
+}
@@ -0,0 +1,312 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use borsh::BorshSerialize;
+use helpers::*;
+use simple_token_pool::{
+    find_authority_bump_seed, id,
+    state::{PoolData, PoolDataV1},
+};
+use solana_program::{
+    borsh::get_packed_len, instruction::InstructionError, program_option::COption,
+    program_pack::Pack, pubkey::Pubkey, rent::Rent,
+};
+use solana_program_test::*;
+use solana_sdk::{
+    account::Account, signature::Keypair, signer::Signer, transaction::TransactionError,
+    transport::TransportError,
+};
+use spl_token::state::{Account as TokenAccount, AccountState, Mint};
+
+const POOL_TOKEN_AMOUNT: u64 = 5000;
+
+/// Pool data as written by the version 1 program
+fn v1_pool_data(pool_accounts: &PoolAccounts) -> PoolDataV1 {
+    let (authority, bump_seed) = find_authority_bump_seed(&id(), &pool_accounts.pool);
+
+    PoolDataV1 {
+        version: PoolDataV1::VERSION,
+        authority,
+        bump_seed,
+        bank_mint: pool_accounts.bank_mint.pubkey(),
+        pool_mint: pool_accounts.pool_mint,
+        bank: pool_accounts.bank,
+    }
+}
+
+fn program_account(data: Vec<u8>, owner: &Pubkey) -> Account {
+    Account {
+        lamports: Rent::default().minimum_balance(data.len()),
+        data,
+        owner: *owner,
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+fn mint_account(authority: &Pubkey, supply: u64) -> Account {
+    let mint = Mint {
+        mint_authority: COption::Some(*authority),
+        supply,
+        decimals: 0,
+        is_initialized: true,
+        freeze_authority: COption::None,
+    };
+    let mut data = vec![0; Mint::LEN];
+    mint.pack_into_slice(&mut data);
+
+    program_account(data, &spl_token::id())
+}
+
+fn token_account(mint: &Pubkey, owner: &Pubkey, amount: u64) -> Account {
+    let token = TokenAccount {
+        mint: *mint,
+        owner: *owner,
+        amount,
+        delegate: COption::None,
+        state: AccountState::Initialized,
+        is_native: COption::None,
+        delegated_amount: 0,
+        close_authority: COption::None,
+    };
+    let mut data = vec![0; TokenAccount::LEN];
+    token.pack_into_slice(&mut data);
+
+    program_account(data, &spl_token::id())
+}
+
+/// Adds a version 1 pool whose sender swapped in and holds the pool tokens
+fn add_v1_pool(
+    program_test: &mut ProgramTest,
+    pool_accounts: &PoolAccounts,
+    pool_data: Vec<u8>,
+) -> (Pubkey, Pubkey) {
+    let (authority, _) = find_authority_bump_seed(&id(), &pool_accounts.pool);
+    let bank_mint = pool_accounts.bank_mint.pubkey();
+    let bank_token = Pubkey::new_unique();
+    let pool_token = Pubkey::new_unique();
+    let sender = pool_accounts.sender.pubkey();
+
+    program_test.add_account(pool_accounts.pool, program_account(pool_data, &id()));
+    program_test.add_account(
+        bank_mint,
+        mint_account(&pool_accounts.owner.pubkey(), POOL_TOKEN_AMOUNT),
+    );
+    program_test.add_account(
+        pool_accounts.pool_mint,
+        mint_account(&authority, POOL_TOKEN_AMOUNT),
+    );
+    program_test.add_account(
+        pool_accounts.bank,
+        token_account(&bank_mint, &authority, POOL_TOKEN_AMOUNT),
+    );
+    program_test.add_account(bank_token, token_account(&bank_mint, &sender, 0));
+    program_test.add_account(
+        pool_token,
+        token_account(&pool_accounts.pool_mint, &sender, POOL_TOKEN_AMOUNT),
+    );
+
+    (bank_token, pool_token)
+}
+
+#[test]
+fn packed_len() {
+    // New fields must take their space from the reserved bytes
+    assert_eq!(get_packed_len::<PoolData>(), PoolData::LEN);
+    assert_eq!(get_packed_len::<PoolDataV1>(), PoolDataV1::LEN);
+}
+
+#[tokio::test]
+async fn success() {
+    let mut pool_accounts = PoolAccounts::new();
+    let v1_pool_data = v1_pool_data(&pool_accounts);
+
+    let mut program_test = program_test();
+    let (bank_token, pool_token) = add_v1_pool(
+        &mut program_test,
+        &pool_accounts,
+        v1_pool_data.try_to_vec().unwrap(),
+    );
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    // Stale pool is rejected until it's migrated
+    redeem(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_token,
+        &bank_token,
+        &pool_accounts,
+        1000,
+    )
+    .await
+    .err()
+    .unwrap();
+
+    let new_pool = Keypair::new();
+    migrate(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &new_pool,
+    )
+    .await
+    .unwrap();
+
+    let old_pool = banks_client.get_account(pool_accounts.pool).await.unwrap();
+    assert!(old_pool.is_none());
+
+    let pool = get_account(&mut banks_client, &new_pool.pubkey()).await;
+    let pool_data = PoolData::try_from_slice_unchecked(&pool.data).unwrap();
+    let (authority, bump_seed) = find_authority_bump_seed(&id(), &new_pool.pubkey());
+
+    let mut expected_pool_data = PoolData::from(v1_pool_data);
+    expected_pool_data.authority = authority;
+    expected_pool_data.bump_seed = bump_seed;
+    assert_eq!(pool.data.len(), PoolData::LEN);
+    assert_eq!(pool_data.version, PoolData::CURRENT_VERSION);
+    assert_eq!(pool_data, expected_pool_data);
+
+    let pool_mint = get_account(&mut banks_client, &pool_accounts.pool_mint).await;
+    let pool_mint = Mint::unpack(&pool_mint.data).unwrap();
+    assert_eq!(pool_mint.mint_authority, COption::Some(authority));
+
+    let bank = get_account(&mut banks_client, &pool_accounts.bank).await;
+    let bank = TokenAccount::unpack(&bank.data).unwrap();
+    assert_eq!(bank.owner, authority);
+
+    // Pool tokens minted by the version 1 pool redeem from the new one
+    pool_accounts.pool = new_pool.pubkey();
+    redeem(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_token,
+        &bank_token,
+        &pool_accounts,
+        1000,
+    )
+    .await
+    .unwrap();
+
+    let bank_token_balance = get_token_balance(&mut banks_client, &bank_token).await;
+    assert_eq!(bank_token_balance, 1000);
+
+    let pool_token_balance = get_token_balance(&mut banks_client, &pool_token).await;
+    assert_eq!(pool_token_balance, POOL_TOKEN_AMOUNT - 1000);
+}
+
+#[tokio::test]
+async fn success_with_current_version() {
+    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+
+    let pool_accounts = PoolAccounts::new();
+    create_accounts(&mut banks_client, &payer, &recent_blockhash, &pool_accounts).await;
+
+    pool_accounts
+        .initialize(&mut banks_client, &payer, &recent_blockhash)
+        .await
+        .unwrap();
+
+    let pool = get_account(&mut banks_client, &pool_accounts.pool).await;
+
+    let new_pool = Keypair::new();
+    migrate(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &new_pool,
+    )
+    .await
+    .unwrap();
+
+    let migrated_pool = get_account(&mut banks_client, &pool_accounts.pool).await;
+    assert_eq!(migrated_pool.data, pool.data);
+
+    let new_pool = get_account(&mut banks_client, &new_pool.pubkey()).await;
+    assert_eq!(new_pool.data, vec![0; PoolData::LEN]);
+}
+
+#[tokio::test]
+async fn fail_with_unreleased_layout() {
+    let pool_accounts = PoolAccounts::new();
+
+    // Version 1 accounts of any other size are not the released layout
+    let mut pool_data = v1_pool_data(&pool_accounts).try_to_vec().unwrap();
+    pool_data.resize(PoolData::LEN, 0);
+
+    let mut program_test = program_test();
+    add_v1_pool(&mut program_test, &pool_accounts, pool_data);
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    let tx_error = migrate(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &Keypair::new(),
+    )
+    .await
+    .err()
+    .unwrap();
+
+    match tx_error {
+        TransportError::TransactionError(TransactionError::InstructionError(_, error)) => {
+            assert_eq!(error, InstructionError::InvalidAccountData);
+        }
+        _ => panic!("Wrong error"),
+    }
+}
+
+#[tokio::test]
+async fn fail_into_initialized_pool() {
+    let v1_pool_accounts = PoolAccounts::new();
+    let v1_pool_data = v1_pool_data(&v1_pool_accounts);
+
+    let mut program_test = program_test();
+    add_v1_pool(
+        &mut program_test,
+        &v1_pool_accounts,
+        v1_pool_data.try_to_vec().unwrap(),
+    );
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    let pool_accounts = PoolAccounts::new();
+    create_accounts(&mut banks_client, &payer, &recent_blockhash, &pool_accounts).await;
+    pool_accounts
+        .initialize(&mut banks_client, &payer, &recent_blockhash)
+        .await
+        .unwrap();
+
+    let (pool_authority, _) = find_authority_bump_seed(&id(), &pool_accounts.pool);
+    let instruction = simple_token_pool::instruction::migrate(
+        &id(),
+        &v1_pool_accounts.pool,
+        &pool_accounts.pool,
+        &v1_pool_data.authority,
+        &pool_authority,
+        &v1_pool_accounts.pool_mint,
+        &v1_pool_accounts.bank,
+    );
+
+    let tx_error = process_instruction(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        instruction,
+        &[],
+    )
+    .await
+    .err()
+    .unwrap();
+
+    match tx_error {
+        TransportError::TransactionError(TransactionError::InstructionError(_, error)) => {
+            assert_eq!(error, InstructionError::AccountAlreadyInitialized);
+        }
+        _ => panic!("Wrong error"),
+    }
+}
@@ -0,0 +1,617 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::*;
+
+use simple_token_pool::{curve::CurveType, error::PoolError, state::PriceData};
+use solana_program::{instruction::InstructionError, pubkey::Pubkey};
+use solana_program_test::*;
+use solana_sdk::{
+    signature::Keypair, signer::Signer, transaction::TransactionError, transport::TransportError,
+};
+
+const SENDER_MINT_AMOUNT: u64 = 10000;
+const MAX_AGE: u64 = 100;
+
+/// Pool priced by a mock oracle, with a user holding bank tokens
+struct Setup {
+    context: ProgramTestContext,
+    pool_accounts: PoolAccounts,
+    bank_token: Pubkey,
+    pool_token: Pubkey,
+}
+
+async fn setup(price: u64, exponent: i32) -> Setup {
+    let mut context = program_test().start_with_context().await;
+    let payer = &context.payer;
+    let banks_client = &mut context.banks_client;
+    let recent_blockhash = context.last_blockhash;
+
+    let mut pool_accounts = PoolAccounts::new();
+    create_accounts(banks_client, payer, &recent_blockhash, &pool_accounts).await;
+    pool_accounts
+        .initialize(banks_client, payer, &recent_blockhash)
+        .await
+        .unwrap();
+
+    let oracle = Keypair::new();
+    create_price_account(
+        banks_client,
+        payer,
+        &recent_blockhash,
+        &oracle,
+        PriceData {
+            price,
+            exponent,
+            last_update_slot: 0,
+        },
+    )
+    .await
+    .unwrap();
+
+    set_oracle(
+        banks_client,
+        payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.owner,
+        &oracle.pubkey(),
+        MAX_AGE,
+    )
+    .await
+    .unwrap();
+    pool_accounts.oracle = Some(oracle.pubkey());
+
+    let (bank_token, pool_token) = create_sender_accounts(
+        banks_client,
+        payer,
+        &recent_blockhash,
+        &pool_accounts,
+        SENDER_MINT_AMOUNT,
+    )
+    .await;
+
+    Setup {
+        context,
+        pool_accounts,
+        bank_token,
+        pool_token,
+    }
+}
+
+#[tokio::test]
+async fn success() {
+    let Setup {
+        mut context,
+        pool_accounts,
+        bank_token,
+        pool_token,
+    } = setup(15, -1).await;
+
+    // 1.5 pool tokens for each bank token
+    swap(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &bank_token,
+        &pool_token,
+        &pool_accounts,
+        1000,
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        get_token_balance(&mut context.banks_client, &pool_token).await,
+        1500
+    );
+
+    // The next swap mints at the new price, whatever the bank holds
+    set_price(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &pool_accounts.oracle.unwrap(),
+        PriceData {
+            price: 2,
+            exponent: 0,
+            last_update_slot: 0,
+        },
+    )
+    .await
+    .unwrap();
+
+    swap(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &bank_token,
+        &pool_token,
+        &pool_accounts,
+        2000,
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        get_token_balance(&mut context.banks_client, &pool_token).await,
+        1500 + 4000
+    );
+}
+
+#[tokio::test]
+async fn success_redeem_at_oracle_price() {
+    let Setup {
+        mut context,
+        pool_accounts,
+        bank_token,
+        pool_token,
+    } = setup(15, -1).await;
+
+    swap(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &bank_token,
+        &pool_token,
+        &pool_accounts,
+        1000,
+    )
+    .await
+    .unwrap();
+
+    // 333 bank tokens mint 499.5 pool tokens, rounded down
+    swap(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &bank_token,
+        &pool_token,
+        &pool_accounts,
+        333,
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        get_token_balance(&mut context.banks_client, &pool_token).await,
+        1500 + 499
+    );
+
+    // Redeeming them back at the same price pays 332.67, rounded down
+    redeem(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &pool_token,
+        &bank_token,
+        &pool_accounts,
+        499,
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        get_token_balance(&mut context.banks_client, &bank_token).await,
+        SENDER_MINT_AMOUNT - 1000 - 333 + 332
+    );
+
+    // Redeems follow the price, not the share of the bank
+    set_price(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &pool_accounts.oracle.unwrap(),
+        PriceData {
+            price: 2,
+            exponent: 0,
+            last_update_slot: 0,
+        },
+    )
+    .await
+    .unwrap();
+
+    redeem(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &pool_token,
+        &bank_token,
+        &pool_accounts,
+        1000,
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        get_token_balance(&mut context.banks_client, &bank_token).await,
+        SENDER_MINT_AMOUNT - 1000 - 333 + 332 + 500
+    );
+    assert_eq!(
+        get_token_balance(&mut context.banks_client, &pool_accounts.bank).await,
+        1000 + 333 - 332 - 500
+    );
+}
+
+#[tokio::test]
+async fn success_exact_out() {
+    let Setup {
+        mut context,
+        pool_accounts,
+        bank_token,
+        pool_token,
+    } = setup(3, -1).await;
+
+    // 100 pool tokens at 0.3 need 333.33 bank tokens, rounded up
+    swap_exact_out(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &bank_token,
+        &pool_token,
+        &pool_accounts,
+        100,
+        334,
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        get_token_balance(&mut context.banks_client, &pool_token).await,
+        100
+    );
+    assert_eq!(
+        get_token_balance(&mut context.banks_client, &bank_token).await,
+        SENDER_MINT_AMOUNT - 334
+    );
+}
+
+#[tokio::test]
+async fn success_clear_oracle() {
+    let Setup {
+        mut context,
+        mut pool_accounts,
+        bank_token,
+        pool_token,
+    } = setup(2, 0).await;
+
+    set_oracle(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &pool_accounts,
+        &pool_accounts.owner,
+        &Pubkey::default(),
+        0,
+    )
+    .await
+    .unwrap();
+    pool_accounts.oracle = None;
+
+    // Back to the configured rate of an empty pool
+    swap(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &bank_token,
+        &pool_token,
+        &pool_accounts,
+        1000,
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        get_token_balance(&mut context.banks_client, &pool_token).await,
+        1000
+    );
+}
+
+#[tokio::test]
+async fn fail_with_stale_price() {
+    let Setup {
+        mut context,
+        pool_accounts,
+        bank_token,
+        pool_token,
+    } = setup(2, 0).await;
+
+    context.warp_to_slot(MAX_AGE + 10).unwrap();
+    let recent_blockhash = context.banks_client.get_recent_blockhash().await.unwrap();
+
+    let error = swap(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        &bank_token,
+        &pool_token,
+        &pool_accounts,
+        1000,
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_pool_error(error, PoolError::StaleOraclePrice);
+
+    // A fresh price is accepted again
+    set_price(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        &pool_accounts.oracle.unwrap(),
+        PriceData {
+            price: 2,
+            exponent: 0,
+            last_update_slot: MAX_AGE,
+        },
+    )
+    .await
+    .unwrap();
+
+    swap(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        &bank_token,
+        &pool_token,
+        &pool_accounts,
+        999,
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        get_token_balance(&mut context.banks_client, &pool_token).await,
+        1998
+    );
+}
+
+#[tokio::test]
+async fn fail_redeem_with_stale_price() {
+    let Setup {
+        mut context,
+        pool_accounts,
+        bank_token,
+        pool_token,
+    } = setup(2, 0).await;
+
+    swap(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &bank_token,
+        &pool_token,
+        &pool_accounts,
+        1000,
+    )
+    .await
+    .unwrap();
+
+    context.warp_to_slot(MAX_AGE + 10).unwrap();
+    let recent_blockhash = context.banks_client.get_recent_blockhash().await.unwrap();
+
+    let error = redeem(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        &pool_token,
+        &bank_token,
+        &pool_accounts,
+        1000,
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_pool_error(error, PoolError::StaleOraclePrice);
+}
+
+#[tokio::test]
+async fn fail_with_wrong_oracle() {
+    let Setup {
+        mut context,
+        mut pool_accounts,
+        bank_token,
+        pool_token,
+    } = setup(2, 0).await;
+
+    let other_oracle = Keypair::new();
+    create_price_account(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &other_oracle,
+        PriceData {
+            price: 1000,
+            exponent: 0,
+            last_update_slot: 0,
+        },
+    )
+    .await
+    .unwrap();
+    pool_accounts.oracle = Some(other_oracle.pubkey());
+
+    let error = swap(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &bank_token,
+        &pool_token,
+        &pool_accounts,
+        1000,
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_pool_error(error, PoolError::InvalidOracle);
+}
+
+#[tokio::test]
+async fn fail_without_oracle() {
+    let Setup {
+        mut context,
+        mut pool_accounts,
+        bank_token,
+        pool_token,
+    } = setup(2, 0).await;
+    pool_accounts.oracle = None;
+
+    let error = swap(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &bank_token,
+        &pool_token,
+        &pool_accounts,
+        1000,
+    )
+    .await
+    .err()
+    .unwrap();
+
+    match error {
+        TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::NotEnoughAccountKeys,
+        )) => {}
+        _ => panic!("Wrong error"),
+    }
+}
+
+#[tokio::test]
+async fn fail_set_oracle_with_wrong_manager() {
+    let Setup {
+        mut context,
+        pool_accounts,
+        ..
+    } = setup(2, 0).await;
+
+    let error = set_oracle(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &pool_accounts,
+        &Keypair::new(),
+        &Pubkey::default(),
+        0,
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_pool_error(error, PoolError::InvalidManager);
+}
+
+#[tokio::test]
+async fn fail_set_oracle_with_invalid_price() {
+    let Setup {
+        mut context,
+        pool_accounts,
+        ..
+    } = setup(2, 0).await;
+
+    for (price, exponent) in &[(0, 0), (1, 20), (u64::MAX, 1)] {
+        let oracle = Keypair::new();
+        create_price_account(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &oracle,
+            PriceData {
+                price: *price,
+                exponent: *exponent,
+                last_update_slot: 0,
+            },
+        )
+        .await
+        .unwrap();
+
+        let error = set_oracle(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &pool_accounts,
+            &pool_accounts.owner,
+            &oracle.pubkey(),
+            MAX_AGE,
+        )
+        .await
+        .err()
+        .unwrap();
+        assert_pool_error(error, PoolError::InvalidOracle);
+    }
+}
+
+#[tokio::test]
+async fn fail_set_oracle_with_pool_tokens() {
+    let Setup {
+        mut context,
+        pool_accounts,
+        bank_token,
+        pool_token,
+    } = setup(2, 0).await;
+
+    swap(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &bank_token,
+        &pool_token,
+        &pool_accounts,
+        1000,
+    )
+    .await
+    .unwrap();
+
+    // Pool tokens minted at the oracle price would redeem at the share price
+    let error = set_oracle(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &pool_accounts,
+        &pool_accounts.owner,
+        &Pubkey::default(),
+        0,
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_pool_error(error, PoolError::PoolNotEmpty);
+}
+
+#[tokio::test]
+async fn fail_set_oracle_on_curve_pool() {
+    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+
+    let mut pool_accounts = PoolAccounts::new();
+    pool_accounts.curve = CurveType::Linear {
+        base_price: CurveType::PRICE_SCALE,
+        slope: CurveType::PRICE_SCALE / 100,
+    };
+    create_accounts(&mut banks_client, &payer, &recent_blockhash, &pool_accounts).await;
+    pool_accounts
+        .initialize(&mut banks_client, &payer, &recent_blockhash)
+        .await
+        .unwrap();
+
+    let oracle = Keypair::new();
+    create_price_account(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &oracle,
+        PriceData {
+            price: 2,
+            exponent: 0,
+            last_update_slot: 0,
+        },
+    )
+    .await
+    .unwrap();
+
+    // The curve sets the price of a curve pool
+    let error = set_oracle(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.owner,
+        &oracle.pubkey(),
+        MAX_AGE,
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_pool_error(error, PoolError::InvalidPoolMode);
+}
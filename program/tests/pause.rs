@@ -0,0 +1,211 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::*;
+
+use simple_token_pool::error::PoolError;
+use solana_program::{hash::Hash, instruction::InstructionError, pubkey::Pubkey};
+use solana_program_test::*;
+use solana_sdk::{
+    signature::Keypair, signer::Signer, transaction::TransactionError, transport::TransportError,
+};
+
+const SENDER_MINT_AMOUNT: u64 = 10000;
+
+async fn setup() -> (BanksClient, Keypair, Hash, PoolAccounts, Pubkey, Pubkey) {
+    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+    let pool_accounts = PoolAccounts::new();
+    create_accounts(&mut banks_client, &payer, &recent_blockhash, &pool_accounts).await;
+
+    pool_accounts
+        .initialize(&mut banks_client, &payer, &recent_blockhash)
+        .await
+        .unwrap();
+
+    let bank_token = Keypair::new();
+    let pool_token = Keypair::new();
+
+    create_token_account(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &bank_token,
+        &pool_accounts.bank_mint.pubkey(),
+        &pool_accounts.sender.pubkey(),
+    )
+    .await
+    .unwrap();
+
+    create_token_account(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_token,
+        &pool_accounts.pool_mint,
+        &pool_accounts.sender.pubkey(),
+    )
+    .await
+    .unwrap();
+
+    mint_tokens(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts.bank_mint.pubkey(),
+        &bank_token.pubkey(),
+        &pool_accounts.owner,
+        SENDER_MINT_AMOUNT,
+    )
+    .await
+    .unwrap();
+
+    (
+        banks_client,
+        payer,
+        recent_blockhash,
+        pool_accounts,
+        bank_token.pubkey(),
+        pool_token.pubkey(),
+    )
+}
+
+fn assert_paused_error(tx_error: TransportError) {
+    match tx_error {
+        TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(code),
+        )) => {
+            assert_eq!(code, PoolError::PoolPaused as u32);
+        }
+        _ => panic!("Wrong error"),
+    }
+}
+
+#[tokio::test]
+async fn success() {
+    let (mut banks_client, payer, recent_blockhash, pool_accounts, bank_token, pool_token) =
+        setup().await;
+
+    set_paused(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.owner,
+        true,
+    )
+    .await
+    .unwrap();
+
+    let tx_error = swap(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &bank_token,
+        &pool_token,
+        &pool_accounts,
+        5000,
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_paused_error(tx_error);
+
+    set_paused(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.owner,
+        false,
+    )
+    .await
+    .unwrap();
+
+    swap(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &bank_token,
+        &pool_token,
+        &pool_accounts,
+        4000,
+    )
+    .await
+    .unwrap();
+
+    let pool_token_balance = get_token_balance(&mut banks_client, &pool_token).await;
+    assert_eq!(pool_token_balance, 4000);
+}
+
+#[tokio::test]
+async fn fail_redeem_while_paused() {
+    let (mut banks_client, payer, recent_blockhash, pool_accounts, bank_token, pool_token) =
+        setup().await;
+
+    swap(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &bank_token,
+        &pool_token,
+        &pool_accounts,
+        5000,
+    )
+    .await
+    .unwrap();
+
+    set_paused(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.owner,
+        true,
+    )
+    .await
+    .unwrap();
+
+    let tx_error = redeem(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_token,
+        &bank_token,
+        &pool_accounts,
+        5000,
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_paused_error(tx_error);
+}
+
+#[tokio::test]
+async fn fail_with_wrong_manager() {
+    let (mut banks_client, payer, recent_blockhash, pool_accounts, _, _) = setup().await;
+    let fake_manager = Keypair::new();
+
+    let tx_error = set_paused(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &fake_manager,
+        true,
+    )
+    .await
+    .err()
+    .unwrap();
+
+    match tx_error {
+        TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(code),
+        )) => {
+            assert_eq!(code, PoolError::InvalidManager as u32);
+        }
+        _ => panic!("Wrong error"),
+    }
+}
@@ -0,0 +1,342 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::*;
+
+use simple_token_pool::{error::PoolError, permit::Permit};
+use solana_program::pubkey::Pubkey;
+use solana_program_test::*;
+use solana_sdk::{signature::Keypair, signer::Signer};
+
+const SENDER_MINT_AMOUNT: u64 = 10000;
+
+/// Slot the permits of the tests expire after
+const EXPIRY_SLOT: u64 = 1000;
+
+struct Setup {
+    context: ProgramTestContext,
+    pool_accounts: PoolAccounts,
+    permit_key: Keypair,
+    bank_token: Pubkey,
+    pool_token: Pubkey,
+}
+
+/// Pool with a permit key and a sender holding bank tokens
+async fn setup() -> Setup {
+    let mut context = program_test().start_with_context().await;
+    let payer = &context.payer;
+    let banks_client = &mut context.banks_client;
+    let recent_blockhash = context.last_blockhash;
+
+    let pool_accounts = PoolAccounts::new();
+    create_accounts(banks_client, payer, &recent_blockhash, &pool_accounts).await;
+    pool_accounts
+        .initialize(banks_client, payer, &recent_blockhash)
+        .await
+        .unwrap();
+
+    let permit_key = Keypair::new();
+    set_permit_key(
+        banks_client,
+        payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.owner,
+        &permit_key.pubkey(),
+    )
+    .await
+    .unwrap();
+
+    let (bank_token, pool_token) = create_sender_accounts(
+        banks_client,
+        payer,
+        &recent_blockhash,
+        &pool_accounts,
+        SENDER_MINT_AMOUNT,
+    )
+    .await;
+
+    Setup {
+        context,
+        pool_accounts,
+        permit_key,
+        bank_token,
+        pool_token,
+    }
+}
+
+/// Permit of the sender for the pool
+fn permit(pool_accounts: &PoolAccounts, max_amount: u64, nonce: u64) -> Permit {
+    Permit {
+        pool: pool_accounts.pool,
+        user: pool_accounts.sender.pubkey(),
+        max_amount,
+        expiry_slot: EXPIRY_SLOT,
+        nonce,
+    }
+}
+
+#[tokio::test]
+async fn success() {
+    let Setup {
+        mut context,
+        pool_accounts,
+        permit_key,
+        bank_token,
+        pool_token,
+    } = setup().await;
+    let permit = permit(&pool_accounts, 1000, 1);
+
+    swap_with_permit(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &bank_token,
+        &pool_token,
+        &pool_accounts,
+        1000,
+        permit_instruction(&permit_key, &permit),
+        &permit,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(
+        get_token_balance(&mut context.banks_client, &pool_token).await,
+        1000
+    );
+}
+
+#[tokio::test]
+async fn success_with_new_nonce() {
+    let Setup {
+        mut context,
+        pool_accounts,
+        permit_key,
+        bank_token,
+        pool_token,
+    } = setup().await;
+
+    for nonce in 1..=2 {
+        let permit = permit(&pool_accounts, 1000, nonce);
+        swap_with_permit(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &bank_token,
+            &pool_token,
+            &pool_accounts,
+            500,
+            permit_instruction(&permit_key, &permit),
+            &permit,
+        )
+        .await
+        .unwrap();
+    }
+
+    assert_eq!(
+        get_token_balance(&mut context.banks_client, &pool_token).await,
+        1000
+    );
+}
+
+#[tokio::test]
+async fn fail_replayed() {
+    let Setup {
+        mut context,
+        pool_accounts,
+        permit_key,
+        bank_token,
+        pool_token,
+    } = setup().await;
+    let permit = permit(&pool_accounts, 1000, 1);
+
+    swap_with_permit(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &bank_token,
+        &pool_token,
+        &pool_accounts,
+        500,
+        permit_instruction(&permit_key, &permit),
+        &permit,
+    )
+    .await
+    .unwrap();
+
+    let error = swap_with_permit(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &bank_token,
+        &pool_token,
+        &pool_accounts,
+        400,
+        permit_instruction(&permit_key, &permit),
+        &permit,
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_pool_error(error, PoolError::PermitUsed);
+}
+
+#[tokio::test]
+async fn fail_expired() {
+    let Setup {
+        mut context,
+        pool_accounts,
+        permit_key,
+        bank_token,
+        pool_token,
+    } = setup().await;
+    let permit = permit(&pool_accounts, 1000, 1);
+
+    context.warp_to_slot(EXPIRY_SLOT + 1).unwrap();
+    let recent_blockhash = context.banks_client.get_recent_blockhash().await.unwrap();
+
+    let error = swap_with_permit(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        &bank_token,
+        &pool_token,
+        &pool_accounts,
+        1000,
+        permit_instruction(&permit_key, &permit),
+        &permit,
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_pool_error(error, PoolError::PermitExpired);
+}
+
+#[tokio::test]
+async fn fail_above_max_amount() {
+    let Setup {
+        mut context,
+        pool_accounts,
+        permit_key,
+        bank_token,
+        pool_token,
+    } = setup().await;
+    let permit = permit(&pool_accounts, 1000, 1);
+
+    let error = swap_with_permit(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &bank_token,
+        &pool_token,
+        &pool_accounts,
+        1001,
+        permit_instruction(&permit_key, &permit),
+        &permit,
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_pool_error(error, PoolError::InvalidPermit);
+}
+
+#[tokio::test]
+async fn fail_with_wrong_signer() {
+    let Setup {
+        mut context,
+        pool_accounts,
+        permit_key,
+        bank_token,
+        pool_token,
+    } = setup().await;
+    let permit = permit(&pool_accounts, 1000, 1);
+
+    // Signed by another key
+    let error = swap_with_permit(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &bank_token,
+        &pool_token,
+        &pool_accounts,
+        1000,
+        permit_instruction(&Keypair::new(), &permit),
+        &permit,
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_pool_error(error, PoolError::InvalidPermit);
+
+    // Signed for another amount
+    let error = swap_with_permit(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &bank_token,
+        &pool_token,
+        &pool_accounts,
+        1000,
+        permit_instruction(
+            &permit_key,
+            &Permit {
+                max_amount: 900,
+                ..permit
+            },
+        ),
+        &permit,
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_pool_error(error, PoolError::InvalidPermit);
+}
+
+#[tokio::test]
+async fn fail_swap_without_permit() {
+    let Setup {
+        mut context,
+        pool_accounts,
+        bank_token,
+        pool_token,
+        ..
+    } = setup().await;
+
+    let error = swap(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &bank_token,
+        &pool_token,
+        &pool_accounts,
+        1000,
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_pool_error(error, PoolError::InvalidPermit);
+}
+
+#[tokio::test]
+async fn fail_set_with_wrong_manager() {
+    let Setup {
+        mut context,
+        pool_accounts,
+        ..
+    } = setup().await;
+
+    let error = set_permit_key(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &pool_accounts,
+        &pool_accounts.sender,
+        &Pubkey::new_unique(),
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_pool_error(error, PoolError::InvalidManager);
+}
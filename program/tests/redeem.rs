@@ -0,0 +1,303 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::*;
+
+use simple_token_pool::{error::PoolError, find_authority_bump_seed, id};
+use solana_program::{hash::Hash, instruction::InstructionError, pubkey::Pubkey, system_program};
+use solana_program_test::*;
+use solana_sdk::{
+    signature::Keypair, signer::Signer, transaction::TransactionError, transport::TransportError,
+};
+
+const SENDER_MINT_AMOUNT: u64 = 10000;
+const SWAP_AMOUNT: u64 = 5000;
+
+async fn setup() -> (BanksClient, Keypair, Hash, PoolAccounts, Pubkey, Pubkey) {
+    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+    let pool_accounts = PoolAccounts::new();
+    create_accounts(&mut banks_client, &payer, &recent_blockhash, &pool_accounts).await;
+
+    pool_accounts
+        .initialize(&mut banks_client, &payer, &recent_blockhash)
+        .await
+        .unwrap();
+
+    let bank_token = Keypair::new();
+    let pool_token = Keypair::new();
+
+    create_token_account(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &bank_token,
+        &pool_accounts.bank_mint.pubkey(),
+        &pool_accounts.sender.pubkey(),
+    )
+    .await
+    .unwrap();
+
+    create_token_account(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_token,
+        &pool_accounts.pool_mint,
+        &pool_accounts.sender.pubkey(),
+    )
+    .await
+    .unwrap();
+
+    mint_tokens(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts.bank_mint.pubkey(),
+        &bank_token.pubkey(),
+        &pool_accounts.owner,
+        SENDER_MINT_AMOUNT,
+    )
+    .await
+    .unwrap();
+
+    // Put bank tokens into the pool
+    swap(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &bank_token.pubkey(),
+        &pool_token.pubkey(),
+        &pool_accounts,
+        SWAP_AMOUNT,
+    )
+    .await
+    .unwrap();
+
+    (
+        banks_client,
+        payer,
+        recent_blockhash,
+        pool_accounts,
+        bank_token.pubkey(),
+        pool_token.pubkey(),
+    )
+}
+
+#[tokio::test]
+async fn success() {
+    let (mut banks_client, payer, recent_blockhash, pool_accounts, bank_token, pool_token) =
+        setup().await;
+
+    redeem(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_token,
+        &bank_token,
+        &pool_accounts,
+        2000,
+    )
+    .await
+    .unwrap();
+
+    let pool_token_balance = get_token_balance(&mut banks_client, &pool_token).await;
+    assert_eq!(pool_token_balance, SWAP_AMOUNT - 2000);
+
+    let bank_token_balance = get_token_balance(&mut banks_client, &bank_token).await;
+    assert_eq!(bank_token_balance, SENDER_MINT_AMOUNT - SWAP_AMOUNT + 2000);
+
+    let bank_balance = get_token_balance(&mut banks_client, &pool_accounts.bank).await;
+    assert_eq!(bank_balance, SWAP_AMOUNT - 2000);
+}
+
+#[tokio::test]
+async fn fail_with_insufficient_tokens() {
+    let (mut banks_client, payer, recent_blockhash, pool_accounts, bank_token, pool_token) =
+        setup().await;
+
+    let tx_error = redeem(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_token,
+        &bank_token,
+        &pool_accounts,
+        SWAP_AMOUNT + 1,
+    )
+    .await
+    .err()
+    .unwrap();
+
+    match tx_error {
+        TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(code),
+        )) => {
+            assert_eq!(code, spl_token::error::TokenError::InsufficientFunds as u32);
+        }
+        _ => panic!("Wrong error"),
+    }
+}
+
+#[tokio::test]
+async fn fail_with_wrong_pool_mint() {
+    let (mut banks_client, payer, recent_blockhash, pool_accounts, bank_token, _) = setup().await;
+
+    // Burning tokens of any other mint must not release bank tokens
+    let fake_pool_mint = Keypair::new();
+    let fake_pool_token = Keypair::new();
+    create_mint(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &fake_pool_mint,
+        &pool_accounts.owner.pubkey(),
+    )
+    .await
+    .unwrap();
+    create_token_account(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &fake_pool_token,
+        &fake_pool_mint.pubkey(),
+        &pool_accounts.sender.pubkey(),
+    )
+    .await
+    .unwrap();
+    mint_tokens(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &fake_pool_mint.pubkey(),
+        &fake_pool_token.pubkey(),
+        &pool_accounts.owner,
+        SWAP_AMOUNT,
+    )
+    .await
+    .unwrap();
+
+    let mut instruction = redeem_instruction(
+        &fake_pool_token.pubkey(),
+        &bank_token,
+        &pool_accounts,
+        SWAP_AMOUNT,
+    );
+    substitute_account(
+        &mut instruction,
+        &pool_accounts.pool_mint,
+        &fake_pool_mint.pubkey(),
+    );
+
+    let tx_error = process_instruction(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        instruction,
+        &[&pool_accounts.sender],
+    )
+    .await
+    .err()
+    .unwrap();
+
+    match tx_error {
+        TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(code),
+        )) => {
+            assert_eq!(code, PoolError::InvalidPoolMint as u32);
+        }
+        _ => panic!("Wrong error"),
+    }
+}
+
+#[tokio::test]
+async fn fail_with_pool_authority_as_transfer_authority() {
+    let (mut banks_client, payer, recent_blockhash, pool_accounts, bank_token, _) = setup().await;
+    let (pool_authority, _) = find_authority_bump_seed(&id(), &pool_accounts.pool);
+
+    // Pool tokens held by the pool authority can only move with its signature
+    let authority_pool_token = Keypair::new();
+    create_token_account(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &authority_pool_token,
+        &pool_accounts.pool_mint,
+        &pool_authority,
+    )
+    .await
+    .unwrap();
+    swap(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &bank_token,
+        &authority_pool_token.pubkey(),
+        &pool_accounts,
+        1000,
+    )
+    .await
+    .unwrap();
+
+    let mut instruction = redeem_instruction(
+        &authority_pool_token.pubkey(),
+        &bank_token,
+        &pool_accounts,
+        1000,
+    );
+    substitute_pool_authority(&mut instruction, &pool_accounts);
+
+    let tx_error = process_instruction(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        instruction,
+        &[],
+    )
+    .await
+    .err()
+    .unwrap();
+
+    match tx_error {
+        TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(code),
+        )) => {
+            assert_eq!(code, PoolError::InvalidAuthority as u32);
+        }
+        _ => panic!("Wrong error"),
+    }
+
+    let pool_token_balance =
+        get_token_balance(&mut banks_client, &authority_pool_token.pubkey()).await;
+    assert_eq!(pool_token_balance, 1000);
+}
+
+#[tokio::test]
+async fn fail_with_wrong_token_program() {
+    let (mut banks_client, payer, recent_blockhash, pool_accounts, bank_token, pool_token) =
+        setup().await;
+
+    let mut instruction = redeem_instruction(&pool_token, &bank_token, &pool_accounts, 2000);
+    substitute_account(&mut instruction, &spl_token::id(), &system_program::id());
+
+    let tx_error = process_instruction(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        instruction,
+        &[&pool_accounts.sender],
+    )
+    .await
+    .err()
+    .unwrap();
+
+    match tx_error {
+        TransportError::TransactionError(TransactionError::InstructionError(_, error)) => {
+            assert_eq!(error, InstructionError::IncorrectProgramId);
+        }
+        _ => panic!("Wrong error"),
+    }
+}
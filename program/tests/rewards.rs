@@ -0,0 +1,342 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::*;
+
+use simple_token_pool::error::PoolError;
+use solana_program::{hash::Hash, pubkey::Pubkey};
+use solana_program_test::*;
+use solana_sdk::{signature::Keypair, signer::Signer};
+
+const SENDER_MINT_AMOUNT: u64 = 10000;
+
+async fn setup() -> (BanksClient, Keypair, Hash, PoolAccounts, Pubkey) {
+    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+    let pool_accounts = PoolAccounts::new();
+    create_accounts(&mut banks_client, &payer, &recent_blockhash, &pool_accounts).await;
+
+    pool_accounts
+        .initialize(&mut banks_client, &payer, &recent_blockhash)
+        .await
+        .unwrap();
+
+    // Manager funds rewards from its own bank token account
+    let rewards = Keypair::new();
+    create_token_account(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &rewards,
+        &pool_accounts.bank_mint.pubkey(),
+        &pool_accounts.owner.pubkey(),
+    )
+    .await
+    .unwrap();
+
+    mint_tokens(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts.bank_mint.pubkey(),
+        &rewards.pubkey(),
+        &pool_accounts.owner,
+        SENDER_MINT_AMOUNT,
+    )
+    .await
+    .unwrap();
+
+    (
+        banks_client,
+        payer,
+        recent_blockhash,
+        pool_accounts,
+        rewards.pubkey(),
+    )
+}
+
+/// Creates bank and pool token accounts of a user, with bank tokens to swap
+async fn create_user(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: &Hash,
+    pool_accounts: &PoolAccounts,
+) -> (Pubkey, Pubkey) {
+    let (bank_token, pool_token) = create_sender_accounts(
+        banks_client,
+        payer,
+        recent_blockhash,
+        &pool_accounts,
+        SENDER_MINT_AMOUNT,
+    )
+    .await;
+
+    (bank_token, pool_token)
+}
+
+#[tokio::test]
+async fn success() {
+    let (mut banks_client, payer, recent_blockhash, pool_accounts, rewards) = setup().await;
+    let (first_bank_token, first_pool_token) =
+        create_user(&mut banks_client, &payer, &recent_blockhash, &pool_accounts).await;
+    let (second_bank_token, second_pool_token) =
+        create_user(&mut banks_client, &payer, &recent_blockhash, &pool_accounts).await;
+
+    swap(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &first_bank_token,
+        &first_pool_token,
+        &pool_accounts,
+        5000,
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        get_token_balance(&mut banks_client, &first_pool_token).await,
+        5000
+    );
+
+    deposit_rewards(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.owner,
+        &rewards,
+        5000,
+    )
+    .await
+    .unwrap();
+
+    // Rewards are in the bank, but no pool tokens are minted for them
+    let bank_balance = get_token_balance(&mut banks_client, &pool_accounts.bank).await;
+    assert_eq!(bank_balance, 10000);
+
+    // Pool tokens are worth about two bank tokens now: 5000 * 5001 / 10001
+    swap(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &second_bank_token,
+        &second_pool_token,
+        &pool_accounts,
+        5000,
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        get_token_balance(&mut banks_client, &second_pool_token).await,
+        2500
+    );
+
+    // First user takes the rewards: 5000 * 15001 / 7501
+    redeem(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &first_pool_token,
+        &first_bank_token,
+        &pool_accounts,
+        5000,
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        get_token_balance(&mut banks_client, &first_bank_token).await,
+        SENDER_MINT_AMOUNT - 5000 + 9999
+    );
+
+    // Second user is the last holder, the virtual tokens keep their share: 2500 * 5002 / 2501
+    redeem(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &second_pool_token,
+        &second_bank_token,
+        &pool_accounts,
+        2500,
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        get_token_balance(&mut banks_client, &second_bank_token).await,
+        SENDER_MINT_AMOUNT - 5000 + 5000
+    );
+
+    let bank_balance = get_token_balance(&mut banks_client, &pool_accounts.bank).await;
+    assert_eq!(bank_balance, 1);
+}
+
+#[tokio::test]
+async fn inflation_attack_is_unprofitable() {
+    let (mut banks_client, payer, recent_blockhash, pool_accounts, _) = setup().await;
+    let (attacker_bank_token, attacker_pool_token) =
+        create_user(&mut banks_client, &payer, &recent_blockhash, &pool_accounts).await;
+    let (victim_bank_token, victim_pool_token) =
+        create_user(&mut banks_client, &payer, &recent_blockhash, &pool_accounts).await;
+
+    // Attacker takes the first pool token and sends tokens straight to the bank
+    swap(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &attacker_bank_token,
+        &attacker_pool_token,
+        &pool_accounts,
+        1,
+    )
+    .await
+    .unwrap();
+
+    mint_tokens(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts.bank_mint.pubkey(),
+        &pool_accounts.bank,
+        &pool_accounts.owner,
+        1000,
+    )
+    .await
+    .unwrap();
+
+    // Victim still gets a pool token: 1000 * 2 / 1002
+    swap(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &victim_bank_token,
+        &victim_pool_token,
+        &pool_accounts,
+        1000,
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        get_token_balance(&mut banks_client, &victim_pool_token).await,
+        1
+    );
+
+    // Victim redeems first: 1 * 2002 / 3
+    redeem(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &victim_pool_token,
+        &victim_bank_token,
+        &pool_accounts,
+        1,
+    )
+    .await
+    .unwrap();
+    let victim_balance = get_token_balance(&mut banks_client, &victim_bank_token).await;
+    assert_eq!(victim_balance, SENDER_MINT_AMOUNT - 1000 + 667);
+
+    // Attacker holds the last pool token, but the virtual tokens keep their share: 1 * 1335 / 2
+    redeem(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &attacker_pool_token,
+        &attacker_bank_token,
+        &pool_accounts,
+        1,
+    )
+    .await
+    .unwrap();
+    let attacker_balance = get_token_balance(&mut banks_client, &attacker_bank_token).await;
+    let attacker_redeemed = attacker_balance - (SENDER_MINT_AMOUNT - 1);
+    assert_eq!(attacker_redeemed, 667);
+    assert!(attacker_redeemed < 1 + 1000);
+}
+
+#[tokio::test]
+async fn fail_with_zero_amount_out() {
+    let (mut banks_client, payer, recent_blockhash, pool_accounts, _) = setup().await;
+    let (attacker_bank_token, attacker_pool_token) =
+        create_user(&mut banks_client, &payer, &recent_blockhash, &pool_accounts).await;
+    let (victim_bank_token, victim_pool_token) =
+        create_user(&mut banks_client, &payer, &recent_blockhash, &pool_accounts).await;
+
+    swap(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &attacker_bank_token,
+        &attacker_pool_token,
+        &pool_accounts,
+        1,
+    )
+    .await
+    .unwrap();
+
+    mint_tokens(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts.bank_mint.pubkey(),
+        &pool_accounts.bank,
+        &pool_accounts.owner,
+        SENDER_MINT_AMOUNT,
+    )
+    .await
+    .unwrap();
+
+    // Deposit would round down to no pool tokens, so it's rejected instead
+    let tx_error = swap(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &victim_bank_token,
+        &victim_pool_token,
+        &pool_accounts,
+        5000,
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_pool_error(tx_error, PoolError::ZeroAmountOut);
+
+    let victim_balance = get_token_balance(&mut banks_client, &victim_bank_token).await;
+    assert_eq!(victim_balance, SENDER_MINT_AMOUNT);
+}
+
+#[tokio::test]
+async fn fail_with_empty_pool() {
+    let (mut banks_client, payer, recent_blockhash, pool_accounts, rewards) = setup().await;
+
+    let tx_error = deposit_rewards(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.owner,
+        &rewards,
+        5000,
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_pool_error(tx_error, PoolError::PoolEmpty);
+}
+
+#[tokio::test]
+async fn fail_with_wrong_manager() {
+    let (mut banks_client, payer, recent_blockhash, pool_accounts, rewards) = setup().await;
+
+    let tx_error = deposit_rewards(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &Keypair::new(),
+        &rewards,
+        5000,
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_pool_error(tx_error, PoolError::InvalidManager);
+}
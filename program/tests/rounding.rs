@@ -0,0 +1,436 @@
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use simple_token_pool::{
+    amm::{ConstantProduct, LiquidityAmounts, TradeAmounts},
+    state::{ExchangeRate, PoolData, PriceData},
+};
+
+const ITERATIONS: usize = 100_000;
+
+fn pool_data(rate_numerator: u64, rate_denominator: u64, fee_bps: u16) -> PoolData {
+    let mut pool_data = PoolData::try_from_slice_unchecked(&[0; PoolData::LEN]).unwrap();
+    pool_data.version = PoolData::CURRENT_VERSION;
+    pool_data.rate_numerator = rate_numerator;
+    pool_data.rate_denominator = rate_denominator;
+    pool_data.fee_bps = fee_bps;
+    pool_data
+}
+
+fn random_pool_data(rng: &mut StdRng) -> PoolData {
+    // Large fees are rare but must round as well
+    let fee_bps = match rng.gen_range(0, 4) {
+        0 => 0,
+        1 => rng.gen_range(0, PoolData::BPS_DENOMINATOR as u16),
+        _ => rng.gen_range(0, 500),
+    };
+    pool_data(
+        rng.gen_range(1, 1_000_000),
+        rng.gen_range(1, 1_000_000),
+        fee_bps,
+    )
+}
+
+fn sample_amount(rng: &mut StdRng) -> u64 {
+    match rng.gen_range(0, 3) {
+        0 => rng.gen_range(0, 100),
+        1 => rng.gen::<u32>() as u64,
+        _ => rng.gen::<u64>() >> rng.gen_range(0, 64),
+    }
+}
+
+fn random_rate(rng: &mut StdRng, pool_data: &PoolData) -> ExchangeRate {
+    // An empty pool may keep the dust of the last redeem
+    let (pool_supply, bank_balance) = match rng.gen_range(0, 4) {
+        0 => (0, rng.gen_range(0, 3)),
+        _ => (sample_amount(rng).max(1), sample_amount(rng)),
+    };
+    pool_data.exchange_rate(pool_supply, bank_balance).unwrap()
+}
+
+/// Pool state kept by the tests the same way as by the program
+struct Pool {
+    data: PoolData,
+    supply: u64,
+    balance: u64,
+}
+
+impl Pool {
+    fn rate(&self) -> ExchangeRate {
+        self.data.exchange_rate(self.supply, self.balance).unwrap()
+    }
+
+    fn swap(&mut self, amount_in: u64) -> u64 {
+        let swap = self
+            .data
+            .calc_swap_exact_in(&self.rate(), amount_in)
+            .unwrap();
+        self.supply += swap.amount_out;
+        self.balance += swap.deposit;
+        swap.amount_out
+    }
+
+    fn redeem(&mut self, amount_in: u64) -> u64 {
+        let amount_out = self.rate().calc_bank_amount(amount_in).unwrap();
+        assert!(amount_out <= self.balance);
+        self.supply -= amount_in;
+        self.balance -= amount_out;
+        amount_out
+    }
+}
+
+#[test]
+fn exact_in_round_trip_never_creates_value() {
+    let mut rng = StdRng::seed_from_u64(0);
+    for _ in 0..ITERATIONS {
+        let pool_data = random_pool_data(&mut rng);
+        let rate = random_rate(&mut rng, &pool_data);
+        let amount_in = sample_amount(&mut rng);
+
+        let swap = match pool_data.calc_swap_exact_in(&rate, amount_in) {
+            Ok(swap) => swap,
+            Err(_) => continue,
+        };
+        assert_eq!(swap.amount_in, amount_in);
+        assert_eq!(swap.fee + swap.deposit, amount_in);
+
+        let rate = match (
+            rate.pool_supply.checked_add(swap.amount_out),
+            rate.bank_balance.checked_add(swap.deposit),
+        ) {
+            (Some(pool_supply), Some(bank_balance)) => {
+                match pool_data.exchange_rate(pool_supply, bank_balance) {
+                    Ok(rate) => rate,
+                    Err(_) => continue,
+                }
+            }
+            _ => continue,
+        };
+        if let Ok(redeemed) = rate.calc_bank_amount(swap.amount_out) {
+            assert!(redeemed <= swap.deposit, "{:?} {:?}", rate, swap);
+        }
+    }
+}
+
+#[test]
+fn exact_out_round_trip_never_creates_value() {
+    let mut rng = StdRng::seed_from_u64(1);
+    for _ in 0..ITERATIONS {
+        let pool_data = random_pool_data(&mut rng);
+        let rate = random_rate(&mut rng, &pool_data);
+        let amount_out = sample_amount(&mut rng);
+
+        let swap = match pool_data.calc_swap_exact_out(&rate, amount_out) {
+            Ok(swap) => swap,
+            Err(_) => continue,
+        };
+        assert_eq!(swap.amount_out, amount_out);
+        assert_eq!(swap.fee + swap.deposit, swap.amount_in);
+
+        let rate = match (
+            rate.pool_supply.checked_add(swap.amount_out),
+            rate.bank_balance.checked_add(swap.deposit),
+        ) {
+            (Some(pool_supply), Some(bank_balance)) => {
+                match pool_data.exchange_rate(pool_supply, bank_balance) {
+                    Ok(rate) => rate,
+                    Err(_) => continue,
+                }
+            }
+            _ => continue,
+        };
+        if let Ok(redeemed) = rate.calc_bank_amount(amount_out) {
+            assert!(redeemed <= swap.deposit, "{:?} {:?}", rate, swap);
+        }
+    }
+}
+
+#[test]
+fn exact_out_takes_least_amount_in() {
+    let mut rng = StdRng::seed_from_u64(2);
+    for _ in 0..ITERATIONS {
+        let pool_data = random_pool_data(&mut rng);
+        let rate = random_rate(&mut rng, &pool_data);
+        let amount_out = sample_amount(&mut rng);
+
+        let swap = match pool_data.calc_swap_exact_out(&rate, amount_out) {
+            Ok(swap) => swap,
+            Err(_) => continue,
+        };
+
+        // The same input swapped the other way mints at least as much
+        let exact_in = pool_data.calc_swap_exact_in(&rate, swap.amount_in).unwrap();
+        assert!(exact_in.amount_out >= amount_out);
+
+        if swap.amount_in > 0 {
+            let less = pool_data
+                .calc_swap_exact_in(&rate, swap.amount_in - 1)
+                .unwrap();
+            assert!(less.amount_out < amount_out, "{:?} {:?}", rate, swap);
+        }
+    }
+}
+
+#[test]
+fn exact_out_examples() {
+    let pool = pool_data(3, 2, 0);
+    let rate = pool.exchange_rate(0, 0).unwrap();
+
+    // 100 pool tokens at 3/2 need a deposit of 66.67, rounded up
+    let swap = pool.calc_swap_exact_out(&rate, 100).unwrap();
+    assert_eq!((swap.amount_in, swap.fee, swap.deposit), (67, 0, 67));
+
+    // 1% fee on 68 is 0.68, rounded up, leaves the same deposit
+    let pool = pool_data(3, 2, 100);
+    let swap = pool.calc_swap_exact_out(&rate, 100).unwrap();
+    assert_eq!((swap.amount_in, swap.fee, swap.deposit), (68, 1, 67));
+
+    let pool = pool_data(1, 1, 100);
+    let rate = pool.exchange_rate(0, 0).unwrap();
+    let swap = pool.calc_swap_exact_out(&rate, 4950).unwrap();
+    assert_eq!((swap.amount_in, swap.fee, swap.deposit), (5000, 50, 4950));
+
+    // Nothing can be swapped when the fee takes everything
+    let pool = pool_data(1, 1, 10_000);
+    assert!(pool.calc_swap_exact_out(&rate, 1).is_err());
+}
+
+#[test]
+fn shares_examples() {
+    let pool = pool_data(1, 1, 0);
+
+    // Empty pool mints at the configured rate
+    let rate = pool.exchange_rate(0, 0).unwrap();
+    assert_eq!(rate.calc_pool_amount(1000), Ok(1000));
+
+    // Rewards doubled the bank, pool tokens are worth about two bank tokens
+    let rate = pool.exchange_rate(1000, 2000).unwrap();
+    assert_eq!(rate.calc_pool_amount(1000), Ok(500));
+    assert_eq!(rate.calc_bank_amount(500), Ok(999));
+
+    // The last pool tokens leave the share of the virtual tokens in the bank
+    assert_eq!(rate.calc_bank_amount(1000), Ok(1999));
+}
+
+#[test]
+fn shares_stay_solvent() {
+    let mut rng = StdRng::seed_from_u64(3);
+    for _ in 0..1_000 {
+        let mut data = random_pool_data(&mut rng);
+        data.fee_bps = 0;
+        let mut pool = Pool {
+            data,
+            supply: 0,
+            balance: 0,
+        };
+        let mut paid = 0;
+        let mut shares = [0u64; 4];
+        let mut received = 0;
+
+        for _ in 0..50 {
+            let user = rng.gen_range(0, shares.len());
+            if rng.gen() || shares[user] == 0 {
+                let amount_in = rng.gen_range(0, 1_000_000);
+                paid += amount_in;
+                shares[user] += pool.swap(amount_in);
+            } else {
+                let amount_in = rng.gen_range(1, shares[user] + 1);
+                shares[user] -= amount_in;
+                received += pool.redeem(amount_in);
+            }
+        }
+
+        // Every redeem is covered by the bank, the virtual tokens may keep some dust
+        for user in 0..shares.len() {
+            received += pool.redeem(shares[user]);
+        }
+        assert_eq!(pool.supply, 0);
+        assert_eq!(received + pool.balance, paid);
+
+        // Nobody can take the dust of an empty pool without pool tokens
+        pool.balance = 1;
+        assert_eq!(pool.redeem(0), 0);
+    }
+}
+
+#[test]
+fn shares_split_rewards_in_proportion() {
+    let mut rng = StdRng::seed_from_u64(4);
+    for _ in 0..1_000 {
+        let rate_numerator = rng.gen_range(1, 1_000);
+        let rate_denominator = rng.gen_range(1, 1_000);
+        let mut pool = Pool {
+            data: pool_data(rate_numerator, rate_denominator, 0),
+            supply: 0,
+            balance: 0,
+        };
+        let mut deposits = [0u64; 4];
+        let mut shares = [0u64; 4];
+
+        for user in 0..deposits.len() {
+            deposits[user] = rng.gen_range(1_000_000, 1_000_000_000);
+            shares[user] = pool.swap(deposits[user]);
+        }
+
+        let rewards = rng.gen_range(0, 1_000_000_000);
+        let supply = pool.supply;
+        pool.balance += rewards;
+
+        for user in 0..deposits.len() {
+            let received = pool.redeem(shares[user]);
+            let expected =
+                deposits[user] as u128 + rewards as u128 * shares[user] as u128 / supply as u128;
+
+            // The virtual tokens hold a share of the rewards, and each swap may leave up to a pool token worth of dust to the holders
+            let tolerance = rewards as u128 * rate_numerator as u128 / supply as u128
+                + deposits.len() as u128 * (rate_denominator / rate_numerator + 1) as u128
+                + 2;
+            let difference = (received as i128 - expected as i128).abs() as u128;
+            assert!(
+                difference <= tolerance,
+                "user {} received {}, expected {}",
+                user,
+                received,
+                expected
+            );
+        }
+        assert_eq!(pool.supply, 0);
+    }
+}
+
+#[test]
+fn oracle_rate_examples() {
+    let pool = pool_data(1, 1, 0);
+    let price = |price, exponent| PriceData {
+        price,
+        exponent,
+        last_update_slot: 0,
+    };
+
+    // Oracle price replaces the share price of the pool
+    let rate = price(15, -1).exchange_rate(1000, 1000).unwrap();
+    let swap = pool.calc_swap_exact_in(&rate, 1000).unwrap();
+    assert_eq!(swap.amount_out, 1500);
+
+    let rate = price(2, 3).exchange_rate(0, 0).unwrap();
+    assert_eq!(rate.calc_pool_amount(7), Ok(14_000));
+
+    let rate = price(3, -1).exchange_rate(0, 0).unwrap();
+    let swap = pool.calc_swap_exact_out(&rate, 100).unwrap();
+    assert_eq!(swap.amount_in, 334);
+
+    // Prices that can't be represented are rejected
+    assert!(price(0, 0).exchange_rate(0, 0).is_err());
+    assert!(price(1, 20).exchange_rate(0, 0).is_err());
+    assert!(price(1, -20).exchange_rate(0, 0).is_err());
+    assert!(price(u64::MAX, 1).exchange_rate(0, 0).is_err());
+    assert!(price(1, i32::MIN).exchange_rate(0, 0).is_err());
+}
+
+fn random_constant_product(rng: &mut StdRng) -> ConstantProduct {
+    ConstantProduct {
+        pool_supply: rng.gen_range(1, 1 << 40),
+        bank_balance: rng.gen_range(1, 1 << 40),
+        reserve_balance: rng.gen_range(1, 1 << 40),
+    }
+}
+
+#[test]
+fn constant_product_examples() {
+    let pool = ConstantProduct {
+        pool_supply: 1000,
+        bank_balance: 1000,
+        reserve_balance: 4000,
+    };
+
+    // 0.3% of 100 is rounded up to 1, 4000 * 99 / 1099 = 360.33 down to 360
+    assert_eq!(
+        ConstantProduct::calc_trade(1000, 4000, 100, 30),
+        Ok(TradeAmounts {
+            fee: 1,
+            amount_out: 360
+        })
+    );
+
+    // The smaller side sets the pool tokens, the larger one is not taken in full
+    assert_eq!(
+        pool.calc_deposit(500, 3000, 1, 1),
+        Ok(LiquidityAmounts {
+            pool_amount: 500,
+            bank_amount: 500,
+            reserve_amount: 2000,
+        })
+    );
+    assert_eq!(
+        pool.calc_withdraw(333),
+        Ok(LiquidityAmounts {
+            pool_amount: 333,
+            bank_amount: 333,
+            reserve_amount: 1332,
+        })
+    );
+
+    // The first deposit mints at the pool rate
+    let empty = ConstantProduct::default();
+    assert_eq!(
+        empty.calc_deposit(1000, 7, 3, 2),
+        Ok(LiquidityAmounts {
+            pool_amount: 1500,
+            bank_amount: 1000,
+            reserve_amount: 7,
+        })
+    );
+    assert!(empty.calc_deposit(1000, 0, 1, 1).is_err());
+}
+
+#[test]
+fn constant_product_trade_never_shrinks_product() {
+    let mut rng = StdRng::seed_from_u64(0);
+    for _ in 0..ITERATIONS {
+        let pool = random_constant_product(&mut rng);
+        let amount_in = rng.gen_range(0, 1 << 40);
+        let fee_bps = rng.gen_range(0, 1000);
+
+        let TradeAmounts { fee, amount_out } = ConstantProduct::calc_trade(
+            pool.bank_balance,
+            pool.reserve_balance,
+            amount_in,
+            fee_bps,
+        )
+        .unwrap();
+        assert!(amount_out < pool.reserve_balance);
+        assert!(fee as u128 * 10_000 >= amount_in as u128 * fee_bps as u128);
+
+        let before = pool.bank_balance as u128 * pool.reserve_balance as u128;
+        let after = (pool.bank_balance + amount_in - fee) as u128
+            * (pool.reserve_balance - amount_out) as u128;
+        assert!(after >= before, "{:?} {} {}", pool, amount_in, fee_bps);
+    }
+}
+
+#[test]
+fn constant_product_liquidity_round_trip_never_creates_value() {
+    let mut rng = StdRng::seed_from_u64(1);
+    for _ in 0..ITERATIONS {
+        let pool = random_constant_product(&mut rng);
+        let bank_amount = rng.gen_range(0, 1 << 40);
+        let reserve_amount = rng.gen_range(0, 1 << 40);
+
+        let deposit = pool
+            .calc_deposit(bank_amount, reserve_amount, 1, 1)
+            .unwrap();
+        assert!(deposit.bank_amount <= bank_amount);
+        assert!(deposit.reserve_amount <= reserve_amount);
+
+        let pool = ConstantProduct {
+            pool_supply: pool.pool_supply + deposit.pool_amount,
+            bank_balance: pool.bank_balance + deposit.bank_amount,
+            reserve_balance: pool.reserve_balance + deposit.reserve_amount,
+        };
+        let withdraw = pool.calc_withdraw(deposit.pool_amount).unwrap();
+        assert!(withdraw.bank_amount <= deposit.bank_amount, "{:?}", pool);
+        assert!(
+            withdraw.reserve_amount <= deposit.reserve_amount,
+            "{:?}",
+            pool
+        );
+    }
+}
@@ -0,0 +1,767 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::*;
+
+use simple_token_pool::{curve::CurveType, error::PoolError, state::PriceData};
+use solana_program::{clock::UnixTimestamp, hash::Hash, pubkey::Pubkey};
+use solana_program_test::*;
+use solana_sdk::{signature::Keypair, signer::Signer};
+
+const SENDER_MINT_AMOUNT: u64 = 10000;
+
+/// Most pool tokens the sale sells
+const HARD_CAP: u64 = 5000;
+
+/// Seconds the sale runs for
+const SALE_DURATION: UnixTimestamp = 600;
+
+struct Setup {
+    context: ProgramTestContext,
+    pool_accounts: PoolAccounts,
+    bank_token: Pubkey,
+    pool_token: Pubkey,
+}
+
+/// Pool with a sender holding bank tokens
+async fn setup() -> Setup {
+    setup_with(PoolAccounts::new()).await
+}
+
+async fn setup_with(pool_accounts: PoolAccounts) -> Setup {
+    let mut context = program_test().start_with_context().await;
+    let payer = &context.payer;
+    let banks_client = &mut context.banks_client;
+    let recent_blockhash = context.last_blockhash;
+
+    create_accounts(banks_client, payer, &recent_blockhash, &pool_accounts).await;
+    pool_accounts
+        .initialize(banks_client, payer, &recent_blockhash)
+        .await
+        .unwrap();
+
+    let (bank_token, pool_token) = create_sender_accounts(
+        banks_client,
+        payer,
+        &recent_blockhash,
+        &pool_accounts,
+        SENDER_MINT_AMOUNT,
+    )
+    .await;
+
+    Setup {
+        context,
+        pool_accounts,
+        bank_token,
+        pool_token,
+    }
+}
+
+/// Starts a sale running from now on
+async fn start_sale(
+    context: &mut ProgramTestContext,
+    pool_accounts: &PoolAccounts,
+    soft_cap_bps: u16,
+) -> UnixTimestamp {
+    let now = get_clock(&mut context.banks_client).await.unix_timestamp;
+    set_sale(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        pool_accounts,
+        &pool_accounts.owner,
+        now,
+        now + SALE_DURATION,
+        soft_cap_bps,
+        HARD_CAP,
+    )
+    .await
+    .unwrap();
+    now + SALE_DURATION
+}
+
+/// Warps ahead until the clock is past the timestamp, returns a new blockhash
+async fn warp_past(context: &mut ProgramTestContext, timestamp: UnixTimestamp) -> Hash {
+    loop {
+        let clock = get_clock(&mut context.banks_client).await;
+        if clock.unix_timestamp >= timestamp {
+            return context.banks_client.get_recent_blockhash().await.unwrap();
+        }
+        context.warp_to_slot(clock.slot + 10_000).unwrap();
+    }
+}
+
+#[tokio::test]
+async fn success_refund_after_missed_soft_cap() {
+    let Setup {
+        mut context,
+        pool_accounts,
+        bank_token,
+        pool_token,
+    } = setup().await;
+    let end = start_sale(&mut context, &pool_accounts, 5000).await;
+    let recent_blockhash = context.last_blockhash;
+
+    swap(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        &bank_token,
+        &pool_token,
+        &pool_accounts,
+        1000,
+    )
+    .await
+    .unwrap();
+
+    // Depositors can't back out while the sale runs
+    let error = redeem(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        &pool_token,
+        &bank_token,
+        &pool_accounts,
+        1000,
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_pool_error(error, PoolError::SaleNotEnded);
+
+    let recent_blockhash = warp_past(&mut context, end).await;
+
+    let error = withdraw_proceeds(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.owner,
+        &Pubkey::new_unique(),
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_pool_error(error, PoolError::SoftCapMissed);
+
+    redeem(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        &pool_token,
+        &bank_token,
+        &pool_accounts,
+        1000,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(
+        get_token_balance(&mut context.banks_client, &bank_token).await,
+        SENDER_MINT_AMOUNT
+    );
+    assert_eq!(
+        get_token_balance(&mut context.banks_client, &pool_token).await,
+        0
+    );
+}
+
+#[tokio::test]
+async fn success_refunds_in_reverse_order() {
+    let mut pool_accounts = PoolAccounts::new();
+    pool_accounts.rate_numerator = 3;
+    pool_accounts.rate_denominator = 2;
+    pool_accounts.fee_bps = 100;
+    let Setup {
+        mut context,
+        pool_accounts,
+        bank_token,
+        pool_token,
+    } = setup_with(pool_accounts).await;
+    let end = start_sale(&mut context, &pool_accounts, 10_000).await;
+    let recent_blockhash = context.last_blockhash;
+
+    let (second_bank_token, second_pool_token) = create_sender_accounts(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        &pool_accounts,
+        SENDER_MINT_AMOUNT / 2,
+    )
+    .await;
+
+    // Sale swaps pay no fee, 1.5 pool tokens for each bank token
+    for (bank_token, pool_token, amount) in &[
+        (&bank_token, &pool_token, 1000),
+        (&second_bank_token, &second_pool_token, 2000),
+    ] {
+        swap(
+            &mut context.banks_client,
+            &context.payer,
+            &recent_blockhash,
+            bank_token,
+            pool_token,
+            &pool_accounts,
+            *amount,
+        )
+        .await
+        .unwrap();
+    }
+    assert_eq!(
+        get_token_balance(&mut context.banks_client, &pool_token).await,
+        1500
+    );
+    assert_eq!(
+        get_token_balance(&mut context.banks_client, &second_pool_token).await,
+        3000
+    );
+
+    let recent_blockhash = warp_past(&mut context, end).await;
+
+    // Each buyer gets back the whole deposit, the later one first
+    for (bank_token, pool_token, amount) in &[
+        (&second_bank_token, &second_pool_token, 3000),
+        (&bank_token, &pool_token, 1500),
+    ] {
+        redeem(
+            &mut context.banks_client,
+            &context.payer,
+            &recent_blockhash,
+            pool_token,
+            bank_token,
+            &pool_accounts,
+            *amount,
+        )
+        .await
+        .unwrap();
+    }
+    assert_eq!(
+        get_token_balance(&mut context.banks_client, &second_bank_token).await,
+        SENDER_MINT_AMOUNT / 2
+    );
+    assert_eq!(
+        get_token_balance(&mut context.banks_client, &bank_token).await,
+        SENDER_MINT_AMOUNT
+    );
+
+    assert_eq!(
+        get_token_balance(&mut context.banks_client, &pool_accounts.bank).await,
+        0
+    );
+    assert_eq!(
+        get_token_balance(
+            &mut context.banks_client,
+            &pool_accounts.fee_receiver.pubkey()
+        )
+        .await,
+        0
+    );
+}
+
+#[tokio::test]
+async fn success_withdraw_proceeds_after_soft_cap() {
+    let mut pool_accounts = PoolAccounts::new();
+    pool_accounts.fee_bps = 100;
+    let Setup {
+        mut context,
+        pool_accounts,
+        bank_token,
+        pool_token,
+    } = setup_with(pool_accounts).await;
+    let end = start_sale(&mut context, &pool_accounts, 2000).await;
+    let recent_blockhash = context.last_blockhash;
+
+    swap(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        &bank_token,
+        &pool_token,
+        &pool_accounts,
+        1000,
+    )
+    .await
+    .unwrap();
+
+    // The hard cap is the supply cap
+    let error = swap(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        &bank_token,
+        &pool_token,
+        &pool_accounts,
+        HARD_CAP,
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_pool_error(error, PoolError::SupplyCapExceeded);
+
+    let destination = Keypair::new();
+    create_token_account(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        &destination,
+        &pool_accounts.bank_mint.pubkey(),
+        &pool_accounts.owner.pubkey(),
+    )
+    .await
+    .unwrap();
+
+    let error = withdraw_proceeds(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.owner,
+        &destination.pubkey(),
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_pool_error(error, PoolError::SaleNotEnded);
+
+    let recent_blockhash = warp_past(&mut context, end).await;
+
+    let error = redeem(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        &pool_token,
+        &bank_token,
+        &pool_accounts,
+        1000,
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_pool_error(error, PoolError::SoftCapMet);
+
+    withdraw_proceeds(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.owner,
+        &destination.pubkey(),
+    )
+    .await
+    .unwrap();
+
+    // The fee is taken out of the proceeds
+    assert_eq!(
+        get_token_balance(&mut context.banks_client, &destination.pubkey()).await,
+        990
+    );
+    assert_eq!(
+        get_token_balance(
+            &mut context.banks_client,
+            &pool_accounts.fee_receiver.pubkey()
+        )
+        .await,
+        10
+    );
+    assert_eq!(
+        get_token_balance(&mut context.banks_client, &pool_accounts.bank).await,
+        0
+    );
+}
+
+#[tokio::test]
+async fn fail_swap_outside_window() {
+    let Setup {
+        mut context,
+        pool_accounts,
+        bank_token,
+        pool_token,
+    } = setup().await;
+    let recent_blockhash = context.last_blockhash;
+    let now = get_clock(&mut context.banks_client).await.unix_timestamp;
+
+    set_sale(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.owner,
+        now + SALE_DURATION,
+        now + 2 * SALE_DURATION,
+        0,
+        HARD_CAP,
+    )
+    .await
+    .unwrap();
+
+    let error = swap(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        &bank_token,
+        &pool_token,
+        &pool_accounts,
+        1000,
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_pool_error(error, PoolError::SaleNotActive);
+
+    let recent_blockhash = warp_past(&mut context, now + 2 * SALE_DURATION).await;
+
+    let error = swap(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        &bank_token,
+        &pool_token,
+        &pool_accounts,
+        1000,
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_pool_error(error, PoolError::SaleNotActive);
+}
+
+#[tokio::test]
+async fn fail_set_invalid_sale() {
+    let Setup {
+        mut context,
+        pool_accounts,
+        ..
+    } = setup().await;
+    let recent_blockhash = context.last_blockhash;
+    let now = get_clock(&mut context.banks_client).await.unix_timestamp;
+
+    // The sale has to end in the future
+    let error = set_sale(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.owner,
+        now - 2 * SALE_DURATION,
+        now - SALE_DURATION,
+        0,
+        HARD_CAP,
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_pool_error(error, PoolError::InvalidSale);
+
+    // A soft cap needs a hard cap to take its share of
+    let error = set_sale(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.owner,
+        now,
+        now + SALE_DURATION,
+        5000,
+        0,
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_pool_error(error, PoolError::InvalidSale);
+
+    let error = set_sale(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.sender,
+        now,
+        now + SALE_DURATION,
+        5000,
+        HARD_CAP,
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_pool_error(error, PoolError::InvalidManager);
+}
+
+#[tokio::test]
+async fn success_remove_sale_lifts_hard_cap() {
+    let Setup {
+        mut context,
+        pool_accounts,
+        ..
+    } = setup().await;
+    let recent_blockhash = context.last_blockhash;
+    let now = get_clock(&mut context.banks_client).await.unix_timestamp;
+
+    set_sale(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.owner,
+        now + SALE_DURATION,
+        now + 2 * SALE_DURATION,
+        5000,
+        HARD_CAP,
+    )
+    .await
+    .unwrap();
+    let pool_data = get_pool_data(&mut context.banks_client, &pool_accounts).await;
+    assert_eq!(pool_data.supply_cap, HARD_CAP);
+
+    // The pool had no supply cap before the sale
+    set_sale(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.owner,
+        0,
+        0,
+        0,
+        0,
+    )
+    .await
+    .unwrap();
+    let pool_data = get_pool_data(&mut context.banks_client, &pool_accounts).await;
+    assert!(!pool_data.has_sale());
+    assert_eq!(pool_data.supply_cap, 0);
+}
+
+#[tokio::test]
+async fn fail_set_sale_over_supply_cap() {
+    let Setup {
+        mut context,
+        pool_accounts,
+        ..
+    } = setup().await;
+    let recent_blockhash = context.last_blockhash;
+    let now = get_clock(&mut context.banks_client).await.unix_timestamp;
+
+    set_swap_limits(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.owner,
+        HARD_CAP * 2,
+        0,
+        0,
+    )
+    .await
+    .unwrap();
+
+    // Removing the sale couldn't bring the supply cap back
+    let error = set_sale(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.owner,
+        now,
+        now + SALE_DURATION,
+        5000,
+        HARD_CAP,
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_pool_error(error, PoolError::InvalidSale);
+
+    // Nor does removing a sale the pool doesn't run lift the supply cap
+    set_sale(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.owner,
+        0,
+        0,
+        0,
+        0,
+    )
+    .await
+    .unwrap();
+    let pool_data = get_pool_data(&mut context.banks_client, &pool_accounts).await;
+    assert_eq!(pool_data.supply_cap, HARD_CAP * 2);
+}
+
+#[tokio::test]
+async fn fail_change_started_sale() {
+    let Setup {
+        mut context,
+        pool_accounts,
+        ..
+    } = setup().await;
+    let end = start_sale(&mut context, &pool_accounts, 5000).await;
+    let recent_blockhash = context.last_blockhash;
+
+    let pool_data = get_pool_data(&mut context.banks_client, &pool_accounts).await;
+    assert_eq!(pool_data.sale_end, end);
+    assert_eq!(pool_data.soft_cap().unwrap(), HARD_CAP / 2);
+
+    // Removing the sale would take away the refunds
+    let error = set_sale(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.owner,
+        0,
+        0,
+        0,
+        0,
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_pool_error(error, PoolError::InvalidSale);
+
+    // Lowering the hard cap would lower the soft cap
+    let error = set_swap_limits(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.owner,
+        HARD_CAP / 10,
+        0,
+        0,
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_pool_error(error, PoolError::InvalidSale);
+
+    let pool_data = get_pool_data(&mut context.banks_client, &pool_accounts).await;
+    assert_eq!(pool_data.supply_cap, HARD_CAP);
+}
+
+#[tokio::test]
+async fn fail_set_sale_on_curve_pool() {
+    let mut pool_accounts = PoolAccounts::new();
+    pool_accounts.curve = CurveType::Linear {
+        base_price: CurveType::PRICE_SCALE,
+        slope: CurveType::PRICE_SCALE / 100,
+    };
+    let Setup {
+        mut context,
+        pool_accounts,
+        ..
+    } = setup_with(pool_accounts).await;
+    let now = get_clock(&mut context.banks_client).await.unix_timestamp;
+
+    // Burning down the curve would refund less than the early buyers paid
+    let error = set_sale(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &pool_accounts,
+        &pool_accounts.owner,
+        now,
+        now + SALE_DURATION,
+        5000,
+        HARD_CAP,
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_pool_error(error, PoolError::InvalidPoolMode);
+}
+
+#[tokio::test]
+async fn fail_with_oracle() {
+    let Setup {
+        mut context,
+        pool_accounts,
+        ..
+    } = setup().await;
+    let recent_blockhash = context.last_blockhash;
+    let now = get_clock(&mut context.banks_client).await.unix_timestamp;
+
+    let oracle = Keypair::new();
+    create_price_account(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        &oracle,
+        PriceData {
+            price: 2,
+            exponent: 0,
+            last_update_slot: 0,
+        },
+    )
+    .await
+    .unwrap();
+    set_oracle(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.owner,
+        &oracle.pubkey(),
+        100,
+    )
+    .await
+    .unwrap();
+
+    // Refunds at a moving price would not pay back the deposits
+    let error = set_sale(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.owner,
+        now,
+        now + SALE_DURATION,
+        5000,
+        HARD_CAP,
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_pool_error(error, PoolError::InvalidPoolMode);
+
+    set_oracle(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.owner,
+        &Pubkey::default(),
+        0,
+    )
+    .await
+    .unwrap();
+    set_sale(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.owner,
+        now,
+        now + SALE_DURATION,
+        2000,
+        HARD_CAP,
+    )
+    .await
+    .unwrap();
+
+    let error = set_oracle(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.owner,
+        &oracle.pubkey(),
+        200,
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_pool_error(error, PoolError::InvalidPoolMode);
+}
@@ -4,7 +4,8 @@ mod helpers;
 
 use helpers::*;
 
-use solana_program::{hash::Hash, instruction::InstructionError, pubkey::Pubkey};
+use simple_token_pool::{curve::CurveType, error::PoolError, find_authority_bump_seed, id};
+use solana_program::{hash::Hash, instruction::InstructionError, pubkey::Pubkey, system_program};
 use solana_program_test::*;
 use solana_sdk::{
     signature::Keypair, signer::Signer, transaction::TransactionError, transport::TransportError,
@@ -37,7 +38,7 @@ async fn prepare_sender(
         &payer,
         &recent_blockhash,
         &token_recipient,
-        &pool_accounts.pool_mint.pubkey(),
+        &pool_accounts.pool_mint,
         &pool_accounts.sender.pubkey(),
     )
     .await
@@ -59,8 +60,27 @@ async fn prepare_sender(
 }
 
 async fn setup() -> (BanksClient, Keypair, Hash, PoolAccounts) {
+    setup_with(PoolAccounts::new()).await
+}
+
+async fn setup_with_rate(
+    rate_numerator: u64,
+    rate_denominator: u64,
+) -> (BanksClient, Keypair, Hash, PoolAccounts) {
+    let mut pool_accounts = PoolAccounts::new();
+    pool_accounts.rate_numerator = rate_numerator;
+    pool_accounts.rate_denominator = rate_denominator;
+    setup_with(pool_accounts).await
+}
+
+async fn setup_with_fee(fee_bps: u16) -> (BanksClient, Keypair, Hash, PoolAccounts) {
+    let mut pool_accounts = PoolAccounts::new();
+    pool_accounts.fee_bps = fee_bps;
+    setup_with(pool_accounts).await
+}
+
+async fn setup_with(pool_accounts: PoolAccounts) -> (BanksClient, Keypair, Hash, PoolAccounts) {
     let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
-    let pool_accounts = PoolAccounts::new();
     create_accounts(&mut banks_client, &payer, &recent_blockhash, &pool_accounts).await;
 
     pool_accounts
@@ -99,6 +119,441 @@ async fn success() {
     assert_eq!(new_recipient_balance, 5000);
 }
 
+#[tokio::test]
+async fn success_with_rate() {
+    let (mut banks_client, payer, recent_blockhash, pool_accounts) = setup_with_rate(3, 2).await;
+    let (token_sender, token_recipient) =
+        prepare_sender(&mut banks_client, &payer, &recent_blockhash, &pool_accounts).await;
+
+    swap(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &token_sender,
+        &token_recipient,
+        &pool_accounts,
+        5000,
+    )
+    .await
+    .unwrap();
+
+    let new_recipient_balance = get_token_balance(&mut banks_client, &token_recipient).await;
+    assert_eq!(new_recipient_balance, 7500);
+}
+
+#[tokio::test]
+async fn success_with_rate_rounds_down() {
+    let (mut banks_client, payer, recent_blockhash, pool_accounts) = setup_with_rate(3, 2).await;
+    let (token_sender, token_recipient) =
+        prepare_sender(&mut banks_client, &payer, &recent_blockhash, &pool_accounts).await;
+
+    swap(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &token_sender,
+        &token_recipient,
+        &pool_accounts,
+        3,
+    )
+    .await
+    .unwrap();
+
+    // 3 * 3 / 2 = 4.5 is rounded down in favour of the pool
+    let new_recipient_balance = get_token_balance(&mut banks_client, &token_recipient).await;
+    assert_eq!(new_recipient_balance, 4);
+}
+
+#[tokio::test]
+async fn success_with_fee() {
+    let (mut banks_client, payer, recent_blockhash, pool_accounts) = setup_with_fee(100).await;
+    let (token_sender, token_recipient) =
+        prepare_sender(&mut banks_client, &payer, &recent_blockhash, &pool_accounts).await;
+
+    swap(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &token_sender,
+        &token_recipient,
+        &pool_accounts,
+        5000,
+    )
+    .await
+    .unwrap();
+
+    let fee_balance =
+        get_token_balance(&mut banks_client, &pool_accounts.fee_receiver.pubkey()).await;
+    assert_eq!(fee_balance, 50);
+
+    let bank_balance = get_token_balance(&mut banks_client, &pool_accounts.bank).await;
+    assert_eq!(bank_balance, 4950);
+
+    let new_recipient_balance = get_token_balance(&mut banks_client, &token_recipient).await;
+    assert_eq!(new_recipient_balance, 4950);
+}
+
+#[tokio::test]
+async fn success_with_fee_rounds_up() {
+    let (mut banks_client, payer, recent_blockhash, pool_accounts) = setup_with_fee(100).await;
+    let (token_sender, token_recipient) =
+        prepare_sender(&mut banks_client, &payer, &recent_blockhash, &pool_accounts).await;
+
+    // 1% of 150 is 1.5 and is rounded up in favour of the pool
+    swap(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &token_sender,
+        &token_recipient,
+        &pool_accounts,
+        150,
+    )
+    .await
+    .unwrap();
+
+    let fee_balance =
+        get_token_balance(&mut banks_client, &pool_accounts.fee_receiver.pubkey()).await;
+    assert_eq!(fee_balance, 2);
+
+    let new_recipient_balance = get_token_balance(&mut banks_client, &token_recipient).await;
+    assert_eq!(new_recipient_balance, 148);
+
+    // Dust amounts the fee takes whole mint nothing and fail
+    let latest_blockhash = banks_client.get_recent_blockhash().await.unwrap();
+    let error = swap(
+        &mut banks_client,
+        &payer,
+        &latest_blockhash,
+        &token_sender,
+        &token_recipient,
+        &pool_accounts,
+        1,
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_pool_error(error, PoolError::ZeroAmountOut);
+
+    let fee_balance =
+        get_token_balance(&mut banks_client, &pool_accounts.fee_receiver.pubkey()).await;
+    assert_eq!(fee_balance, 2);
+
+    let new_recipient_balance = get_token_balance(&mut banks_client, &token_recipient).await;
+    assert_eq!(new_recipient_balance, 148);
+}
+
+#[tokio::test]
+async fn success_with_minimum_amount_out() {
+    let (mut banks_client, payer, recent_blockhash, pool_accounts) = setup_with_fee(100).await;
+    let (token_sender, token_recipient) =
+        prepare_sender(&mut banks_client, &payer, &recent_blockhash, &pool_accounts).await;
+
+    // Exactly the quoted amount is accepted
+    swap_with_minimum(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &token_sender,
+        &token_recipient,
+        &pool_accounts,
+        5000,
+        4950,
+    )
+    .await
+    .unwrap();
+
+    let new_recipient_balance = get_token_balance(&mut banks_client, &token_recipient).await;
+    assert_eq!(new_recipient_balance, 4950);
+}
+
+#[tokio::test]
+async fn fail_with_exceeded_slippage() {
+    let (mut banks_client, payer, recent_blockhash, pool_accounts) = setup_with_fee(100).await;
+    let (token_sender, token_recipient) =
+        prepare_sender(&mut banks_client, &payer, &recent_blockhash, &pool_accounts).await;
+
+    let tx_error = swap_with_minimum(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &token_sender,
+        &token_recipient,
+        &pool_accounts,
+        5000,
+        4951,
+    )
+    .await
+    .err()
+    .unwrap();
+
+    match tx_error {
+        TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(code),
+        )) => {
+            assert_eq!(code, PoolError::ExceededSlippage as u32);
+        }
+        _ => panic!("Wrong error"),
+    }
+
+    let sender_balance = get_token_balance(&mut banks_client, &token_sender).await;
+    assert_eq!(sender_balance, SENDER_MINT_AMOUNT);
+}
+
+#[tokio::test]
+async fn success_exact_out() {
+    let mut pool_accounts = PoolAccounts::new();
+    pool_accounts.rate_numerator = 3;
+    pool_accounts.rate_denominator = 2;
+    pool_accounts.fee_bps = 100;
+    let (mut banks_client, payer, recent_blockhash, pool_accounts) =
+        setup_with(pool_accounts).await;
+    let (token_sender, token_recipient) =
+        prepare_sender(&mut banks_client, &payer, &recent_blockhash, &pool_accounts).await;
+
+    swap_exact_out(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &token_sender,
+        &token_recipient,
+        &pool_accounts,
+        100,
+        68,
+    )
+    .await
+    .unwrap();
+
+    // 100 * 2 / 3 = 66.67 and 1% fee are both rounded up in favour of the pool
+    let new_recipient_balance = get_token_balance(&mut banks_client, &token_recipient).await;
+    assert_eq!(new_recipient_balance, 100);
+
+    let new_sender_balance = get_token_balance(&mut banks_client, &token_sender).await;
+    assert_eq!(new_sender_balance, SENDER_MINT_AMOUNT - 68);
+
+    let fee_balance =
+        get_token_balance(&mut banks_client, &pool_accounts.fee_receiver.pubkey()).await;
+    assert_eq!(fee_balance, 1);
+
+    let bank_balance = get_token_balance(&mut banks_client, &pool_accounts.bank).await;
+    assert_eq!(bank_balance, 67);
+}
+
+#[tokio::test]
+async fn success_exact_out_round_trip() {
+    let (mut banks_client, payer, recent_blockhash, pool_accounts) = setup_with_rate(3, 2).await;
+    let (token_sender, token_recipient) =
+        prepare_sender(&mut banks_client, &payer, &recent_blockhash, &pool_accounts).await;
+
+    swap_exact_out(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &token_sender,
+        &token_recipient,
+        &pool_accounts,
+        1,
+        1,
+    )
+    .await
+    .unwrap();
+
+    // Redeeming the pool token gives back no more than was paid
+    redeem(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &token_recipient,
+        &token_sender,
+        &pool_accounts,
+        1,
+    )
+    .await
+    .unwrap();
+
+    let new_sender_balance = get_token_balance(&mut banks_client, &token_sender).await;
+    assert!(new_sender_balance <= SENDER_MINT_AMOUNT);
+}
+
+#[tokio::test]
+async fn success_with_linear_curve() {
+    let mut pool_accounts = PoolAccounts::new();
+    pool_accounts.curve = CurveType::Linear {
+        base_price: CurveType::PRICE_SCALE,
+        slope: CurveType::PRICE_SCALE / 100,
+    };
+    let (mut banks_client, payer, recent_blockhash, pool_accounts) =
+        setup_with(pool_accounts).await;
+    let (token_sender, token_recipient) =
+        prepare_sender(&mut banks_client, &payer, &recent_blockhash, &pool_accounts).await;
+
+    // First 100 pool tokens cost 100 + 0.01 * 100^2 / 2
+    swap(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &token_sender,
+        &token_recipient,
+        &pool_accounts,
+        150,
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        get_token_balance(&mut banks_client, &token_recipient).await,
+        100
+    );
+
+    // Next 100 are dearer, 100 + 0.01 * (200^2 - 100^2) / 2
+    swap(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &token_sender,
+        &token_recipient,
+        &pool_accounts,
+        250,
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        get_token_balance(&mut banks_client, &token_recipient).await,
+        200
+    );
+
+    // Redeeming walks back down the curve
+    redeem(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &token_recipient,
+        &token_sender,
+        &pool_accounts,
+        100,
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        get_token_balance(&mut banks_client, &token_sender).await,
+        SENDER_MINT_AMOUNT - 150
+    );
+    assert_eq!(
+        get_token_balance(&mut banks_client, &pool_accounts.bank).await,
+        150
+    );
+}
+
+#[tokio::test]
+async fn success_exact_out_with_step_curve() {
+    let mut pool_accounts = PoolAccounts::new();
+    pool_accounts.curve = CurveType::Step {
+        base_price: CurveType::PRICE_SCALE,
+        step_size: 10,
+        increment: CurveType::PRICE_SCALE,
+    };
+    let (mut banks_client, payer, recent_blockhash, pool_accounts) =
+        setup_with(pool_accounts).await;
+    let (token_sender, token_recipient) =
+        prepare_sender(&mut banks_client, &payer, &recent_blockhash, &pool_accounts).await;
+
+    // 10 pool tokens at 1, 10 at 2 and 5 at 3
+    swap_exact_out(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &token_sender,
+        &token_recipient,
+        &pool_accounts,
+        25,
+        45,
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        get_token_balance(&mut banks_client, &token_recipient).await,
+        25
+    );
+    assert_eq!(
+        get_token_balance(&mut banks_client, &token_sender).await,
+        SENDER_MINT_AMOUNT - 45
+    );
+}
+
+#[tokio::test]
+async fn fail_exact_out_with_exceeded_slippage() {
+    let (mut banks_client, payer, recent_blockhash, pool_accounts) = setup_with_rate(3, 2).await;
+    let (token_sender, token_recipient) =
+        prepare_sender(&mut banks_client, &payer, &recent_blockhash, &pool_accounts).await;
+
+    let tx_error = swap_exact_out(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &token_sender,
+        &token_recipient,
+        &pool_accounts,
+        100,
+        66,
+    )
+    .await
+    .err()
+    .unwrap();
+
+    match tx_error {
+        TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(code),
+        )) => {
+            assert_eq!(code, PoolError::ExceededSlippage as u32);
+        }
+        _ => panic!("Wrong error"),
+    }
+}
+
+#[tokio::test]
+async fn fail_with_wrong_fee_receiver() {
+    let (mut banks_client, payer, recent_blockhash, mut pool_accounts) = setup_with_fee(100).await;
+    let (token_sender, token_recipient) =
+        prepare_sender(&mut banks_client, &payer, &recent_blockhash, &pool_accounts).await;
+
+    let fake_fee_receiver = Keypair::new();
+    create_token_account(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &fake_fee_receiver,
+        &pool_accounts.bank_mint.pubkey(),
+        &payer.pubkey(),
+    )
+    .await
+    .unwrap();
+    pool_accounts.fee_receiver = fake_fee_receiver;
+
+    let tx_error = swap(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &token_sender,
+        &token_recipient,
+        &pool_accounts,
+        5000,
+    )
+    .await
+    .err()
+    .unwrap();
+
+    match tx_error {
+        TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(code),
+        )) => {
+            assert_eq!(code, PoolError::InvalidFeeReceiver as u32);
+        }
+        _ => panic!("Wrong error"),
+    }
+}
+
 #[tokio::test]
 async fn fail_with_insufficient_tokens() {
     let (mut banks_client, payer, recent_blockhash, pool_accounts) = setup().await;
@@ -131,3 +586,223 @@ async fn fail_with_insufficient_tokens() {
         _ => panic!("Wrong error"),
     }
 }
+
+#[tokio::test]
+async fn fail_with_pool_authority_as_transfer_authority() {
+    let (mut banks_client, payer, recent_blockhash, pool_accounts) = setup().await;
+    let (token_sender, token_recipient) =
+        prepare_sender(&mut banks_client, &payer, &recent_blockhash, &pool_accounts).await;
+
+    swap(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &token_sender,
+        &token_recipient,
+        &pool_accounts,
+        5000,
+    )
+    .await
+    .unwrap();
+
+    // Bank tokens moved from the bank back into itself would mint free pool tokens
+    let mut instruction =
+        swap_instruction(&pool_accounts.bank, &token_recipient, &pool_accounts, 1000);
+    substitute_pool_authority(&mut instruction, &pool_accounts);
+
+    let tx_error = process_instruction(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        instruction,
+        &[],
+    )
+    .await
+    .err()
+    .unwrap();
+
+    match tx_error {
+        TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(code),
+        )) => {
+            assert_eq!(code, PoolError::InvalidAuthority as u32);
+        }
+        _ => panic!("Wrong error"),
+    }
+
+    let recipient_balance = get_token_balance(&mut banks_client, &token_recipient).await;
+    assert_eq!(recipient_balance, 5000);
+}
+
+async fn swap_with_substitution(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: &Hash,
+    pool_accounts: &PoolAccounts,
+    from: &Pubkey,
+    to: &Pubkey,
+) -> TransportError {
+    let (token_sender, token_recipient) =
+        prepare_sender(banks_client, payer, recent_blockhash, pool_accounts).await;
+
+    let mut instruction = swap_instruction(&token_sender, &token_recipient, pool_accounts, 5000);
+    substitute_account(&mut instruction, from, to);
+
+    process_instruction(
+        banks_client,
+        payer,
+        recent_blockhash,
+        instruction,
+        &[&pool_accounts.sender],
+    )
+    .await
+    .err()
+    .unwrap()
+}
+
+#[tokio::test]
+async fn fail_with_wrong_pool_owner() {
+    let (mut banks_client, payer, recent_blockhash, pool_accounts) = setup().await;
+
+    // Any account not owned by the program
+    let tx_error = swap_with_substitution(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.pool,
+        &pool_accounts.bank_mint.pubkey(),
+    )
+    .await;
+
+    match tx_error {
+        TransportError::TransactionError(TransactionError::InstructionError(_, error)) => {
+            assert_eq!(error, InstructionError::IncorrectProgramId);
+        }
+        _ => panic!("Wrong error"),
+    }
+}
+
+#[tokio::test]
+async fn fail_with_wrong_authority() {
+    let (mut banks_client, payer, recent_blockhash, pool_accounts) = setup().await;
+    let (pool_authority, _) = find_authority_bump_seed(&id(), &pool_accounts.pool);
+
+    let tx_error = swap_with_substitution(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_authority,
+        &pool_accounts.owner.pubkey(),
+    )
+    .await;
+
+    match tx_error {
+        TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(code),
+        )) => {
+            assert_eq!(code, PoolError::InvalidAuthority as u32);
+        }
+        _ => panic!("Wrong error"),
+    }
+}
+
+#[tokio::test]
+async fn fail_with_wrong_pool_mint() {
+    let (mut banks_client, payer, recent_blockhash, pool_accounts) = setup().await;
+    let (pool_authority, _) = find_authority_bump_seed(&id(), &pool_accounts.pool);
+
+    // Mint controlled by the pool authority but not stored in the pool
+    let fake_pool_mint = Keypair::new();
+    create_mint(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &fake_pool_mint,
+        &pool_authority,
+    )
+    .await
+    .unwrap();
+
+    let tx_error = swap_with_substitution(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.pool_mint,
+        &fake_pool_mint.pubkey(),
+    )
+    .await;
+
+    match tx_error {
+        TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(code),
+        )) => {
+            assert_eq!(code, PoolError::InvalidPoolMint as u32);
+        }
+        _ => panic!("Wrong error"),
+    }
+}
+
+#[tokio::test]
+async fn fail_with_wrong_bank() {
+    let (mut banks_client, payer, recent_blockhash, pool_accounts) = setup().await;
+
+    let fake_bank = Keypair::new();
+    create_token_account(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &fake_bank,
+        &pool_accounts.bank_mint.pubkey(),
+        &payer.pubkey(),
+    )
+    .await
+    .unwrap();
+
+    let tx_error = swap_with_substitution(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.bank,
+        &fake_bank.pubkey(),
+    )
+    .await;
+
+    match tx_error {
+        TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(code),
+        )) => {
+            assert_eq!(code, PoolError::InvalidBank as u32);
+        }
+        _ => panic!("Wrong error"),
+    }
+}
+
+#[tokio::test]
+async fn fail_with_wrong_token_program() {
+    let (mut banks_client, payer, recent_blockhash, pool_accounts) = setup().await;
+
+    let tx_error = swap_with_substitution(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &spl_token::id(),
+        &system_program::id(),
+    )
+    .await;
+
+    match tx_error {
+        TransportError::TransactionError(TransactionError::InstructionError(_, error)) => {
+            assert_eq!(error, InstructionError::IncorrectProgramId);
+        }
+        _ => panic!("Wrong error"),
+    }
+}
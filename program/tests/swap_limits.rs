@@ -0,0 +1,224 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::*;
+
+use simple_token_pool::{error::PoolError, state::PoolData};
+use solana_program::{hash::Hash, pubkey::Pubkey};
+use solana_program_test::*;
+use solana_sdk::signature::Keypair;
+
+const SENDER_MINT_AMOUNT: u64 = 10000;
+
+const SUPPLY_CAP: u64 = 3000;
+const MIN_AMOUNT_IN: u64 = 100;
+const MAX_AMOUNT_IN: u64 = 2000;
+
+/// Pool with swap limits and a sender holding bank tokens
+async fn setup() -> (BanksClient, Keypair, Hash, PoolAccounts, Pubkey, Pubkey) {
+    let (mut banks_client, payer, recent_blockhash, pool_accounts, bank_token, pool_token) =
+        setup_pool_with_sender(SENDER_MINT_AMOUNT).await;
+
+    set_swap_limits(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.owner,
+        SUPPLY_CAP,
+        MIN_AMOUNT_IN,
+        MAX_AMOUNT_IN,
+    )
+    .await
+    .unwrap();
+
+    (
+        banks_client,
+        payer,
+        recent_blockhash,
+        pool_accounts,
+        bank_token,
+        pool_token,
+    )
+}
+
+#[tokio::test]
+async fn success_up_to_supply_cap() {
+    let (mut banks_client, payer, recent_blockhash, pool_accounts, bank_token, pool_token) =
+        setup().await;
+
+    let pool_data = PoolData::try_from_slice_unchecked(
+        &get_account(&mut banks_client, &pool_accounts.pool)
+            .await
+            .data,
+    )
+    .unwrap();
+    assert_eq!(pool_data.supply_cap, SUPPLY_CAP);
+    assert_eq!(pool_data.min_amount_in, MIN_AMOUNT_IN);
+    assert_eq!(pool_data.max_amount_in, MAX_AMOUNT_IN);
+
+    for amount in &[
+        MIN_AMOUNT_IN,
+        MAX_AMOUNT_IN,
+        SUPPLY_CAP - MIN_AMOUNT_IN - MAX_AMOUNT_IN,
+    ] {
+        swap(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &bank_token,
+            &pool_token,
+            &pool_accounts,
+            *amount,
+        )
+        .await
+        .unwrap();
+    }
+    assert_eq!(
+        get_token_balance(&mut banks_client, &pool_token).await,
+        SUPPLY_CAP
+    );
+    assert_eq!(pool_data.remaining_supply(SUPPLY_CAP), Some(0));
+
+    let error = swap(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &bank_token,
+        &pool_token,
+        &pool_accounts,
+        MIN_AMOUNT_IN + 1,
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_pool_error(error, PoolError::SupplyCapExceeded);
+}
+
+#[tokio::test]
+async fn success_without_limits() {
+    let (mut banks_client, payer, recent_blockhash, pool_accounts, bank_token, pool_token) =
+        setup().await;
+
+    set_swap_limits(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.owner,
+        0,
+        0,
+        0,
+    )
+    .await
+    .unwrap();
+
+    swap(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &bank_token,
+        &pool_token,
+        &pool_accounts,
+        SENDER_MINT_AMOUNT,
+    )
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn fail_below_minimum() {
+    let (mut banks_client, payer, recent_blockhash, pool_accounts, bank_token, pool_token) =
+        setup().await;
+
+    let error = swap(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &bank_token,
+        &pool_token,
+        &pool_accounts,
+        MIN_AMOUNT_IN - 1,
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_pool_error(error, PoolError::AmountInBelowMinimum);
+}
+
+#[tokio::test]
+async fn fail_above_maximum() {
+    let (mut banks_client, payer, recent_blockhash, pool_accounts, bank_token, pool_token) =
+        setup().await;
+
+    let error = swap(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &bank_token,
+        &pool_token,
+        &pool_accounts,
+        MAX_AMOUNT_IN + 1,
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_pool_error(error, PoolError::AmountInAboveMaximum);
+
+    // The limit applies to the amount in of exact out swaps as well
+    let error = swap_exact_out(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &bank_token,
+        &pool_token,
+        &pool_accounts,
+        MAX_AMOUNT_IN + 1,
+        SENDER_MINT_AMOUNT,
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_pool_error(error, PoolError::AmountInAboveMaximum);
+}
+
+#[tokio::test]
+async fn fail_set_invalid_limits() {
+    let (mut banks_client, payer, recent_blockhash, pool_accounts, _, _) = setup().await;
+
+    let error = set_swap_limits(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.owner,
+        SUPPLY_CAP,
+        MAX_AMOUNT_IN + 1,
+        MAX_AMOUNT_IN,
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_pool_error(error, PoolError::InvalidSwapLimits);
+}
+
+#[tokio::test]
+async fn fail_set_with_wrong_manager() {
+    let (mut banks_client, payer, recent_blockhash, pool_accounts, _, _) = setup().await;
+
+    let error = set_swap_limits(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts,
+        &pool_accounts.sender,
+        0,
+        0,
+        0,
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_pool_error(error, PoolError::InvalidManager);
+}